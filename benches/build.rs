@@ -0,0 +1,82 @@
+use calcite::Executable;
+use calcite::interlock::builder::InterlockBuilder;
+use calcite::interlock::{Priority, TaskId};
+use std::time::{Duration, Instant};
+
+const TASKS: usize = 200_000;
+const RESOURCES: u32 = 5_000;
+const ACCESSES: usize = 4;
+const DEPENDENCIES: usize = 2;
+const SAMPLES: u32 = 5;
+
+//deterministic graph shape, no need for a proper rng
+struct Lcg(u64);
+
+impl Lcg {
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+}
+
+fn fill(builder: &mut InterlockBuilder<'static, (), u32>) {
+    let mut rng = Lcg(42);
+    let mut ids: Vec<TaskId> = Vec::with_capacity(TASKS);
+
+    for _ in 0..TASKS {
+        let reads: Vec<u32> = (0..ACCESSES / 2).map(|_| rng.next() as u32 % RESOURCES).collect();
+        let writes: Vec<u32> = (0..ACCESSES / 2).map(|_| rng.next() as u32 % RESOURCES).collect();
+        let deps: Vec<TaskId> = match ids.len() {
+            0 => Vec::new(),
+            len => (0..DEPENDENCIES).map(|_| ids[rng.next() as usize % len]).collect()
+        };
+
+        ids.push(builder.add(|_: &()| {}, reads, writes, deps));
+    }
+}
+
+fn measure(name: &str, mut f: impl FnMut()) {
+    let mut best = Duration::from_secs(u64::MAX);
+    let mut total = Duration::from_secs(0);
+
+    for _ in 0..SAMPLES {
+        let start = Instant::now();
+        f();
+
+        let time = start.elapsed();
+        best = best.min(time);
+        total += time;
+    }
+
+    println!("{:<24} best {:>10.3?}   mean {:>10.3?}", name, best, total / SAMPLES);
+}
+
+fn main() {
+    println!("{} tasks, {} resources, {} accesses and {} dependencies per task", TASKS, RESOURCES, ACCESSES, DEPENDENCIES);
+
+    measure("add", || {
+        let mut builder = InterlockBuilder::new();
+        fill(&mut builder);
+    });
+
+    measure("add (with_capacity)", || {
+        let mut builder = InterlockBuilder::with_capacity(TASKS, TASKS * ACCESSES);
+        fill(&mut builder);
+    });
+
+    measure("add + build", || {
+        let mut builder = InterlockBuilder::with_capacity(TASKS, TASKS * ACCESSES);
+        fill(&mut builder);
+        builder.build();
+    });
+
+    let mut builder = InterlockBuilder::with_capacity(TASKS, TASKS * ACCESSES);
+    fill(&mut builder);
+    let mut exec = builder.build();
+
+    measure("run", || exec.run(&()));
+
+    exec.set_priority(Priority::Fanout);
+    measure("run (fanout priority)", || exec.run(&()));
+}
@@ -0,0 +1,108 @@
+//! Incremental build of a small project: every source file is compiled by its own task, then everything is linked.
+//!
+//! Compile tasks are memoized by the hash of their source, so a rebuild only compiles edited files,
+//! and the linker skips itself when no object changed. Run with `cargo run --example build_system`.
+
+use calcite::Executable;
+use calcite::interlock::builder::TaskSpec;
+use calcite::interlock::{builder, InterlockExecutor, MemoCache, TaskContext};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+const FILES: [&str; 4] = ["main", "parser", "lexer", "codegen"];
+
+struct Project {
+    sources: HashMap<&'static str, String>,
+    objects: Mutex<HashMap<&'static str, u64>>,
+    binary: Mutex<u64>,
+    log: Mutex<Vec<String>>
+}
+
+impl Project {
+
+    fn new() -> Self {
+        Self {
+            sources: FILES.iter().map(|&file| (file, format!("fn {}() {{}}", file))).collect(),
+            objects: Mutex::new(HashMap::new()),
+            binary: Mutex::new(0),
+            log: Mutex::new(Vec::new())
+        }
+    }
+
+    fn log(&self, message: String) {
+        self.log.lock().unwrap().push(message);
+    }
+}
+
+fn hash(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn build_graph() -> InterlockExecutor<'static, Project, String> {
+    let mut builder = builder::<Project, String>();
+    builder.hierarchy(|path: &String| path.rfind('/').map(|end| path[..end].to_string()));
+
+    let compile = FILES.iter().map(|&file| {
+        TaskSpec::new(format!("compile {}", file), move |project: &Project| {
+            //stands in for a slow compiler
+            let object = hash(&project.sources[file]);
+            project.objects.lock().unwrap().insert(file, object);
+            project.log(format!("compiled {}", file));
+        })
+        .memoize(move |project: &Project| hash(&project.sources[file]))
+        .writes([format!("obj/{}", file)])
+    });
+
+    let link = TaskSpec::with_context("link", |project: &Project, context: &TaskContext<String>| {
+        if !context.changed(&"obj".to_string()) {
+            context.unchanged();
+            return project.log("link: up to date".to_string());
+        }
+
+        let objects = project.objects.lock().unwrap();
+        let mut objects: Vec<_> = objects.iter().collect();
+        objects.sort();
+
+        *project.binary.lock().unwrap() = hash(objects);
+        project.log("linked".to_string());
+    })
+    .reads(["obj".to_string()])
+    .after(FILES.iter().map(|file| format!("compile {}", file)));
+
+    builder.extend(compile.chain(Some(link))).expect("the build graph is valid");
+    builder.build()
+}
+
+fn build(name: &str, graph: &mut InterlockExecutor<'static, Project, String>, project: &Project) {
+    graph.run(project);
+
+    let mut log = std::mem::take(&mut *project.log.lock().unwrap());
+    log.sort();
+    println!("{}: {}", name, log.join(", "));
+}
+
+fn main() {
+    let mut project = Project::new();
+    let mut graph = build_graph();
+
+    build("clean build", &mut graph, &project);
+    build("no changes", &mut graph, &project);
+
+    project.sources.insert("parser", "fn parser() { todo!() }".to_string());
+    build("edited parser", &mut graph, &project);
+
+    //a new process only knows the hashes it persisted, the objects are still on disk
+    let cache: MemoCache = graph.memo_cache();
+    println!("persisted {} hashes", cache.len());
+
+    //a cache of a graph built differently is refused instead of skipping the wrong tasks
+    let mut restarted = build_graph();
+    restarted.restore_memo_cache(&cache).expect("the graph changed since the cache was persisted");
+
+    project.sources.insert("lexer", "fn lexer() { loop {} }".to_string());
+    build("restarted, edited lexer", &mut restarted, &project);
+}
@@ -0,0 +1,148 @@
+//! Frame graph of a small game engine: input, AI, physics, animation, audio and rendering.
+//!
+//! Systems declare the parts of the world they read and write, the executor runs everything
+//! that doesn't conflict in parallel. Run with `cargo run --example engine_frame`.
+
+use calcite::Executable;
+use calcite::interlock::builder::TaskSpec;
+use calcite::interlock::resource::{path_parent, ConflictPolicy};
+use calcite::interlock::{builder, TaskContext};
+use std::sync::{Mutex, RwLock};
+
+#[derive(Default)]
+struct Input {
+    thrust: f32,
+    frame: u32
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+struct Body {
+    position: f32,
+    velocity: f32
+}
+
+/// Everything the systems share, every part is locked on its own, the graph keeps the locks uncontended.
+#[derive(Default)]
+struct World {
+    events: Mutex<Vec<f32>>,
+    input: RwLock<Input>,
+    bodies: RwLock<Vec<Body>>,
+    targets: RwLock<Vec<f32>>,
+    poses: RwLock<Vec<u32>>,
+    frame: RwLock<Vec<String>>,
+    log: Mutex<Vec<String>>
+}
+
+impl World {
+
+    fn new(bodies: usize) -> Self {
+        Self {
+            bodies: RwLock::new(vec![Body::default(); bodies]),
+            targets: RwLock::new(vec![0.0; bodies]),
+            poses: RwLock::new(vec![0; bodies]),
+            ..Self::default()
+        }
+    }
+
+    fn log(&self, message: String) {
+        self.log.lock().unwrap().push(message);
+    }
+}
+
+fn main() {
+    let mut builder = builder::<World, &'static str>();
+
+    //resources are paths, a task reading "world" conflicts with every task writing below it
+    builder.hierarchy(path_parent);
+    //the log is synchronized on its own, writers don't need to be serialized
+    builder.policy("log", ConflictPolicy::Concurrent);
+
+    builder.extend(vec![
+        TaskSpec::new("input", |world: &World| {
+            let mut input = world.input.write().unwrap();
+            input.thrust = world.events.lock().unwrap().drain(..).sum();
+            input.frame += 1;
+        }).writes(["input"]),
+
+        TaskSpec::new("ai", |world: &World| {
+            let bodies = world.bodies.read().unwrap();
+            let thrust = world.input.read().unwrap().thrust;
+            let mut targets = world.targets.write().unwrap();
+
+            for (target, body) in targets.iter_mut().zip(bodies.iter()) {
+                *target = body.position + thrust;
+            }
+        }).reads(["input", "world/bodies"]).writes(["world/targets"]),
+
+        TaskSpec::with_context("physics", |world: &World, context: &TaskContext<&'static str>| {
+            let targets = world.targets.read().unwrap();
+            let mut bodies = world.bodies.write().unwrap();
+
+            for (body, target) in bodies.iter_mut().zip(targets.iter()) {
+                body.velocity = (target - body.position) * 0.5;
+                body.position += body.velocity;
+            }
+
+            //resting bodies don't need to be drawn again
+            if bodies.iter().all(|body| body.velocity == 0.0) {
+                context.unchanged();
+            }
+
+            world.log(format!("physics: moved {} bodies", bodies.len()));
+        }).reads(["world/targets"]).writes(["world/bodies", "log"]).after(["ai"]),
+
+        //independent of physics, runs in parallel with it
+        TaskSpec::with_context("animation", |world: &World, context: &TaskContext<&'static str>| {
+            let pose = if world.input.read().unwrap().thrust > 0.0 { 1 } else { 0 };
+            let mut poses = world.poses.write().unwrap();
+
+            if poses.iter().all(|&current| current == pose) {
+                return context.unchanged();
+            }
+
+            poses.iter_mut().for_each(|current| *current = pose);
+        }).reads(["input"]).writes(["world/poses"]),
+
+        TaskSpec::new("audio", |world: &World| {
+            let loudest = world.bodies.read().unwrap().iter().map(|body| body.velocity.abs()).fold(0.0, f32::max);
+            world.log(format!("audio: loudest body at {:.2}", loudest));
+        }).reads(["world/bodies"]).writes(["log"]).after(["physics"]),
+
+        //skips drawing when nothing visible changed since the last frame
+        TaskSpec::with_context("render", |world: &World, context: &TaskContext<&'static str>| {
+            if !context.changed(&"world/bodies") && !context.changed(&"world/poses") {
+                context.unchanged();
+                return world.log("render: world unchanged, reusing the last frame".to_string());
+            }
+
+            let bodies = world.bodies.read().unwrap();
+            let poses = world.poses.read().unwrap();
+            *world.frame.write().unwrap() = bodies.iter()
+                .zip(poses.iter())
+                .map(|(body, pose)| format!("{:.2}/{}", body.position, pose))
+                .collect();
+        }).reads(["world"]).writes(["render/frame", "log"]).after(["physics", "animation"]),
+
+        TaskSpec::new("present", |world: &World| {
+            let frame = world.input.read().unwrap().frame;
+            println!("frame {}: {}", frame, world.frame.read().unwrap().join(" "));
+
+            for message in world.log.lock().unwrap().drain(..) {
+                println!("    {}", message);
+            }
+        }).reads(["render/frame", "input", "log"]).after(["render", "audio"]),
+    ]).expect("the frame graph is valid");
+
+    let (mut frame, warnings) = builder.build_with_report();
+    for warning in warnings {
+        println!("warning: {}", warning);
+    }
+
+    println!("{:?}", frame);
+
+    let world = World::new(4);
+    for thrust in [1.0, 2.0, 0.0, 0.0].iter() {
+        world.events.lock().unwrap().push(*thrust);
+        frame.run(&world);
+    }
+}
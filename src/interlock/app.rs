@@ -0,0 +1,370 @@
+use super::InterlockExecutor;
+use super::signal::Signal;
+use super::timestep::{FixedTimestep, Tick};
+#[cfg(feature = "inspector")]
+use super::{ExecutionReport, LockStats};
+use crate::Executable;
+#[cfg(feature = "inspector")]
+use std::fmt::Display;
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// When a stage of an `App` runs.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Schedule {
+    /// Once, before the stages of the first update.
+    Startup,
+    /// Once per update.
+    Frame,
+    /**
+     Once per `step` of elapsed time, so zero or several times per update: elapsed time accumulates
+     and every run takes `step` from it, e.g. a physics simulation independent of the frame rate.
+     Runs at most `max_steps` times per update if it is set, see `FixedTimestep::max_steps`.
+    */
+    Fixed { step: Duration, max_steps: Option<u32> },
+    /**
+     Once every `interval` of wall-clock time, in the first update after it elapsed, e.g. an autosave. The first run
+     comes an interval after the first update; intervals missed by slow updates are skipped rather than made up.
+    */
+    Every(Duration),
+    /// Once a day, in the first update after the wall clock passed `at` since midnight UTC, e.g. daily telemetry.
+    Daily { at: Duration },
+    /// Once, when the app shuts down.
+    Shutdown
+}
+
+/// Runs of a stage of an `App`, measured around the whole run of its executor.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct StageStats {
+    pub runs: u64,
+    pub total: Duration,
+    /// Duration of the last run, including its hooks.
+    pub last: Duration
+}
+
+impl StageStats {
+
+    pub fn mean(&self) -> Duration {
+        match self.runs {
+            0 => Duration::default(),
+            runs => Duration::from_nanos((self.total.as_nanos() / runs as u128) as u64)
+        }
+    }
+}
+
+struct Stage<'task, T, R, N> {
+    name: String,
+    schedule: Schedule,
+    executor: InterlockExecutor<'task, T, R, N>,
+    timestep: Option<FixedTimestep>,
+    //when a timed stage runs next, set by the first update
+    due: Option<SystemTime>,
+    //signals of linked stages waiting for this one, set by its tasks or, for whole runs, after a run
+    signals: Vec<Signal>,
+    completed: Vec<Signal>,
+    stats: StageStats
+}
+
+impl<'task, T: Sync, R: Eq + Hash + Sync, N: Sync> Stage<'task, T, R, N> {
+
+    fn run(&mut self, data: &T, tick: Option<Tick>) {
+        struct Abandon<'a>(&'a [Signal]);
+
+        impl Drop for Abandon<'_> {
+
+            fn drop(&mut self) {
+                self.0.iter().for_each(Signal::abandon);
+            }
+        }
+
+        //a panicking run releases the stages waiting for it as well
+        let abandon = Abandon(&self.signals);
+
+        let start = Instant::now();
+        match tick {
+            Some(tick) => self.executor.run_tick(data, tick),
+            None => self.executor.run(data)
+        }
+
+        self.completed.iter().for_each(Signal::set);
+        drop(abandon);
+
+        let elapsed = start.elapsed();
+        self.stats.runs += 1;
+        self.stats.total += elapsed;
+        self.stats.last = elapsed;
+    }
+}
+
+/// Returns when a timed stage runs next after it was last due at `due`, or for the first time if it is `None`.
+fn next_due(schedule: Schedule, due: Option<SystemTime>, now: SystemTime) -> Option<SystemTime> {
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    match schedule {
+        Schedule::Every(interval) => Some(match due {
+            Some(due) if due + interval > now => due + interval,
+            _ => now + interval
+        }),
+
+        Schedule::Daily { at } => {
+            let days = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / DAY.as_secs();
+            let today = UNIX_EPOCH + DAY * days as u32 + at;
+            Some(if today > now { today } else { today + DAY })
+        },
+
+        _ => None
+    }
+}
+
+/// Wall clock of an app's timed stages, see `App::set_clock`.
+type Clock<'a> = dyn Fn() -> SystemTime + Send + Sync + 'a;
+
+/**
+ Application loop over several graphs sharing the same data, e.g. a game with a startup graph, a fixed-rate
+ simulation, a per-frame render graph and a shutdown graph. Every update runs the stages in the order they were
+ added, each according to its `Schedule`; the data can be changed between updates through `data_mut`.
+*/
+pub struct App<'task, T, R, N = String> {
+    data: T,
+    stages: Vec<Stage<'task, T, R, N>>,
+    links: Vec<(usize, usize)>,
+    clock: Box<Clock<'task>>,
+    started: bool,
+    stopped: bool
+}
+
+impl<'task, T: Sync, R: Eq + Hash + Sync, N: Sync> App<'task, T, R, N> {
+
+    pub fn new(data: T) -> Self {
+        Self { data, stages: Vec::new(), links: Vec::new(), clock: Box::new(SystemTime::now), started: false, stopped: false }
+    }
+
+    /// Replaces the wall clock of timed stages, `SystemTime::now` by default, e.g. with a simulated one in tests.
+    pub fn set_clock(&mut self, clock: impl Fn() -> SystemTime + Send + Sync + 'task) {
+        self.clock = Box::new(clock);
+    }
+
+    /**
+     Adds a stage running `executor`. Panics if a stage with the same name exists, if a fixed step or an interval
+     is zero, if a fixed stage may run no step per update or if a daily stage runs a day or more after midnight.
+    */
+    pub fn add_stage(&mut self, name: impl Into<String>, schedule: Schedule, executor: InterlockExecutor<'task, T, R, N>) {
+        let name = name.into();
+        assert!(self.stage(&name).is_none(), "stage '{}' already exists", name);
+
+        let timestep = match schedule {
+            Schedule::Fixed { step, max_steps } => {
+                let timestep = FixedTimestep::new(step);
+                Some(max_steps.map_or(timestep, |max_steps| timestep.max_steps(max_steps)))
+            },
+
+            Schedule::Every(interval) => {
+                assert!(interval > Duration::default(), "stage '{}' can't run every 0 seconds", name);
+                None
+            },
+
+            Schedule::Daily { at } => {
+                assert!(at < Duration::from_secs(24 * 60 * 60), "stage '{}' can't run a day or more after midnight", name);
+                None
+            },
+
+            _ => None
+        };
+
+        self.stages.push(Stage { name, schedule, executor, timestep, due: None, signals: Vec::new(), completed: Vec::new(), stats: StageStats::default() });
+    }
+
+    /**
+     Makes `task` of `stage` wait in every update until the task labeled `after` of stage `other` completed, or the whole
+     run of `other` if `after` is `None`, e.g. to start rendering as soon as the simulation extracted its state instead of
+     after the whole simulation. Linked stages run at the same time, where the first of them was added; a waiting task
+     executes other work of the pool meanwhile, e.g. the tasks of the awaited stage, so a single thread makes progress too.
+     The waiting task panics if the awaited stage panicked.
+
+     A stage can't both wait and be waited for, a waiting task may execute the waiting tasks of other stages on top
+     of itself, and they must not wait for it in turn. For the same reason, tasks of awaited stages shouldn't suspend.
+
+     Panics if a stage or a label doesn't exist, if both stages are the same, if one of them isn't a frame stage,
+     or if the waiting stage is waited for or the awaited stage waits.
+    */
+    pub fn link<Q: Eq + ?Sized>(&mut self, stage: &str, task: &Q, other: &str, after: Option<&Q>) where N: Borrow<Q> {
+        assert_ne!(stage, other, "stage '{}' can't wait for itself", stage);
+
+        let find = |name: &str| {
+            let idx = self.stages.iter().position(|stage| stage.name == name).unwrap_or_else(|| panic!("no stage '{}'", name));
+            assert_eq!(self.stages[idx].schedule, Schedule::Frame, "stage '{}' isn't a frame stage", name);
+            idx
+        };
+
+        let (waiting, awaited) = (find(stage), find(other));
+        assert!(self.links.iter().all(|&(other, _)| other != awaited), "stage '{}' waits itself, it can't be waited for", other);
+        assert!(self.links.iter().all(|&(_, other)| other != waiting), "stage '{}' is waited for, it can't wait itself", stage);
+        let label = |idx: usize, label: &Q| self.stages[idx].executor.task_by_label(label).unwrap_or_else(|| panic!("no such task in stage '{}'", self.stages[idx].name));
+
+        let task = label(waiting, task);
+        let after = after.map(|after| label(awaited, after));
+
+        let signal = Signal::default();
+        self.stages[waiting].executor.tasks[task.id()].add_wait(signal.clone());
+
+        let source = &mut self.stages[awaited];
+        match after {
+            Some(after) => source.executor.tasks[after.id()].add_signal(signal.clone()),
+            None => source.completed.push(signal.clone())
+        }
+
+        source.signals.push(signal);
+        self.links.push((waiting, awaited));
+    }
+
+    /**
+     Advances the app by `elapsed`, the time since the last update: runs the startup stages if this is the first update,
+     then every frame stage once, every fixed stage once per step accumulated so far and every timed stage that is due.
+     Tasks see their position within the update in `TaskContext::tick`: runs of fixed stages see their step,
+     frame stages see a single run with the `alpha` of the last fixed stage before them, to interpolate its state.
+
+     Panics if the app was shut down.
+    */
+    pub fn update(&mut self, elapsed: Duration) where R: Send, N: Send {
+        assert!(!self.stopped, "update of an app that was shut down");
+
+        self.stages.iter().flat_map(|stage| stage.signals.iter()).for_each(Signal::reset);
+
+        let data = &self.data;
+        let now = (self.clock)();
+
+        if !self.started {
+            self.started = true;
+            self.stages.iter_mut().filter(|stage| stage.schedule == Schedule::Startup).for_each(|stage| stage.run(data, None));
+            self.stages.iter_mut().for_each(|stage| stage.due = next_due(stage.schedule, None, now));
+        }
+
+        let mut alpha = 0.0;
+        let mut done = vec![false; self.stages.len()];
+
+        for idx in 0..self.stages.len() {
+            let group = linked(&self.links, idx);
+            let stage = &mut self.stages[idx];
+
+            match stage.timestep.as_mut() {
+                Some(timestep) => {
+                    let ticks = timestep.advance(elapsed);
+                    alpha = timestep.alpha();
+                    ticks.for_each(|tick| stage.run(data, Some(tick)));
+                },
+
+                None if stage.schedule == Schedule::Frame && !done[idx] => {
+                    let tick = Some(Tick { index: 0, count: 1, step: elapsed, alpha });
+                    if group.len() == 1 {
+                        stage.run(data, tick);
+                        continue;
+                    }
+
+                    group.iter().for_each(|&idx| done[idx] = true);
+
+                    let stages = self.stages.iter_mut().enumerate().filter(|(idx, _)| group.contains(idx)).map(|(_, stage)| stage);
+                    rayon::scope(|scope| stages.for_each(|stage| scope.spawn(move |_| stage.run(data, tick))));
+                },
+
+                None if stage.due.is_some_and(|due| due <= now) => {
+                    stage.run(data, None);
+                    stage.due = next_due(stage.schedule, stage.due, now);
+                },
+
+                None => {}
+            }
+        }
+    }
+
+    /// Runs the shutdown stages, later calls do nothing. Shutting down an app that never updated doesn't start it.
+    pub fn shutdown(&mut self) {
+        if !self.stopped {
+            self.stopped = true;
+
+            let data = &self.data;
+            self.stages.iter_mut().filter(|stage| stage.schedule == Schedule::Shutdown).for_each(|stage| stage.run(data, None));
+        }
+    }
+
+    /// Returns when the next timed stage is due, e.g. for a driver to sleep until then, `None` before the first update.
+    pub fn next_due(&self) -> Option<SystemTime> {
+        self.stages.iter().filter_map(|stage| stage.due).min()
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    pub fn data(&self) -> &T {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+
+    pub fn into_data(self) -> T {
+        self.data
+    }
+
+    pub fn executor(&self, stage: &str) -> Option<&InterlockExecutor<'task, T, R, N>> {
+        self.stage(stage).map(|stage| &stage.executor)
+    }
+
+    pub fn executor_mut(&mut self, stage: &str) -> Option<&mut InterlockExecutor<'task, T, R, N>> {
+        self.stages.iter_mut().find(|other| other.name == stage).map(|stage| &mut stage.executor)
+    }
+
+    pub fn stats(&self, stage: &str) -> Option<StageStats> {
+        self.stage(stage).map(|stage| stage.stats)
+    }
+
+    /// Returns the name, schedule and stats of every stage, in the order they were added.
+    pub fn stages(&self) -> Vec<(&str, Schedule, StageStats)> {
+        self.stages.iter().map(|stage| (stage.name.as_str(), stage.schedule, stage.stats)).collect()
+    }
+
+    fn stage(&self, name: &str) -> Option<&Stage<'task, T, R, N>> {
+        self.stages.iter().find(|stage| stage.name == name)
+    }
+}
+
+/// Returns the stage at `idx` and every stage linked to it, directly or through other stages, in order.
+fn linked(links: &[(usize, usize)], idx: usize) -> Vec<usize> {
+    let mut group = vec![idx];
+    let mut next = 0;
+
+    while let Some(&current) = group.get(next) {
+        for &(a, b) in links.iter() {
+            let other = match (a == current, b == current) {
+                (true, _) => b,
+                (_, true) => a,
+                _ => continue
+            };
+
+            if !group.contains(&other) {
+                group.push(other);
+            }
+        }
+
+        next += 1;
+    }
+
+    group.sort_unstable();
+    group
+}
+
+#[cfg(feature = "inspector")]
+impl<'task, T: Sync, R: Eq + Hash + Sync, N: Sync> App<'task, T, R, N> {
+
+    /// Returns the lock stats of the last run of every stage, see `InterlockExecutor::lock_stats`.
+    pub fn lock_stats(&self) -> Vec<(&str, LockStats)> {
+        self.stages.iter().map(|stage| (stage.name.as_str(), stage.executor.lock_stats())).collect()
+    }
+
+    /// Returns the report of every stage, see `InterlockExecutor::report`.
+    pub fn reports(&self) -> Vec<(&str, ExecutionReport)> where N: Display {
+        self.stages.iter().map(|stage| (stage.name.as_str(), stage.executor.report())).collect()
+    }
+}
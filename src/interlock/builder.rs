@@ -1,142 +1,1034 @@
 use crate::Executable;
+use crate::tasks::Marker;
 use super::InterlockExecutor;
-use super::task::TaskId;
+use super::commands::CommandBuffer;
+use super::error::{BuildError, BuildWarning};
+use super::impact::{self, GraphStats, SplitImpact};
+use super::memo::Memo;
+use super::supervise::{Supervision, Supervisor};
+use super::run::{Boxed, Changes, Configured, ContextExecutable, Extra, Plain};
+use super::task::{Factory, SharedFn, Snapshot, TaskId, TaskOptions};
+use super::resource::{self, Access, Accesses, ConflictPolicy, Fairness, Parent, Policies, Resolve, ResourceTable, Resources};
 use std::borrow::Borrow;
-use multimap::MultiMap;
-use std::hash::Hash;
-use std::collections::HashSet;
+use std::hash::{BuildHasher, Hash};
+use std::collections::hash_map::RandomState;
+use std::marker::PhantomData;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::cmp::Reverse;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-struct TaskBuilder<'task, T, R> {
-    task: Box<dyn Executable<T> + Send + 'task>,
-    dependencies: Vec<TaskId>,
+/// Number of dependencies above which `build_with_report` warns about a task's fan-in.
+pub const MAX_FAN_IN: usize = 32;
+
+/// Dependencies and accesses of a task are ranges into the builder's shared storage.
+struct TaskBuilder<'task, T, R, N> {
+    task: Boxed<'task, T, R>,
+    label: Option<N>,
+    dependencies: Range<usize>,
+    accesses: Range<usize>,
+    all: Option<Access>,
+    resolve: Option<Arc<Resolve<'task, T, R>>>,
+    options: TaskOptions<'task, T, R>
+}
+
+/**
+ Bundles a labeled task with its accesses and dependencies, which refer to other tasks by label.
+ Used to register many generated tasks at once with `InterlockBuilder::extend`.
+*/
+pub struct TaskSpec<'task, T, R, N = String> {
+    task: Boxed<'task, T, R>,
+    options: TaskOptions<'task, T, R>,
+    //resources of the snapshots in the options, checked against the writes when the spec is added
+    snapshotted: Vec<R>,
+    label: N,
     reads: Vec<R>,
-    writes: Vec<R>
+    writes: Vec<R>,
+    all: Option<Access>,
+    dependencies: Vec<N>
+}
+
+impl<'task, T, R, N> TaskSpec<'task, T, R, N> {
+
+    pub fn new(label: impl Into<N>, task: impl Executable<T> + Send + 'task) -> Self where T: 'task, R: 'task {
+        Self::with_body(label, Boxed::new(Plain(task)))
+    }
+
+    pub fn new_box(label: impl Into<N>, task: Box<dyn Executable<T> + Send + 'task>) -> Self where T: 'task, R: 'task {
+        Self::with_body(label, Boxed::Plain(task))
+    }
+
+    /// See `InterlockBuilder::add_with_context`.
+    pub fn with_context(label: impl Into<N>, task: impl ContextExecutable<T, R> + Send + 'task) -> Self {
+        Self::with_body(label, Boxed::new(task))
+    }
+
+    /// See `InterlockBuilder::add_with_extra`.
+    pub fn with_extra<E: 'static>(label: impl Into<N>, task: impl FnMut(&T, &E) + Send + 'task) -> Self where T: 'task, R: 'task {
+        Self::with_body(label, Boxed::new(Extra(task, PhantomData)))
+    }
+
+    /// See `InterlockBuilder::add_with_params`.
+    pub fn with_params<P: Send + Sync + 'static>(label: impl Into<N>, params: P, task: impl FnMut(&T, &P) + Send + 'task) -> Self
+        where T: 'task, R: 'task {
+        let mut spec = Self::with_body(label, Boxed::new(Configured(task, PhantomData)));
+        spec.options.params = Some(Arc::new(params));
+        spec
+    }
+
+    /// See `InterlockBuilder::flush`.
+    pub fn flush<C: Send + 'task>(label: impl Into<N>, commands: &'task CommandBuffer<C>, apply: impl FnMut(&T, C) + Send + 'task) -> Self
+        where T: 'task, R: 'task {
+        let mut spec = Self::new(label, flush(commands, apply));
+        spec.all = Some(Access::Write);
+        spec
+    }
+
+    fn with_body(label: impl Into<N>, task: Boxed<'task, T, R>) -> Self {
+        Self {
+            task,
+            options: TaskOptions::default(),
+            snapshotted: Vec::new(),
+            label: label.into(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            all: None,
+            dependencies: Vec::new()
+        }
+    }
+
+    /// See `InterlockBuilder::add_factory`.
+    pub fn from_factory<E: Executable<T> + Send + 'task>(label: impl Into<N>, factory: impl Fn() -> E + Send + Sync + 'task) -> Self
+        where T: 'task, R: 'task {
+        let factory = boxed(factory);
+        let mut spec = Self::with_body(label, factory());
+        spec.options.factory = Some(factory);
+        spec
+    }
+
+    /// See `InterlockBuilder::add_marker`.
+    pub fn marker(marker: Marker<impl Into<N>>) -> Self where T: 'task, R: 'task {
+        Self::shared(marker.into_name(), |_: &T| {})
+    }
+
+    /// See `InterlockBuilder::add_shared`.
+    pub fn shared(label: impl Into<N>, task: impl Fn(&T) + Send + Sync + 'task) -> Self where T: 'task, R: 'task {
+        let (task, shared, factory) = share(task);
+        let mut spec = Self::with_body(label, task);
+        spec.options.factory = Some(factory);
+        spec.options.shared = Some(shared);
+        spec
+    }
+
+    pub fn reads(mut self, reads: impl IntoIterator<Item=R>) -> Self {
+        self.reads.extend(reads);
+        self
+    }
+
+    pub fn writes(mut self, writes: impl IntoIterator<Item=R>) -> Self {
+        self.writes.extend(writes);
+        self
+    }
+
+    /// See `InterlockBuilder::read_all`.
+    pub fn read_all(mut self) -> Self {
+        self.all.get_or_insert(Access::Read);
+        self
+    }
+
+    /// See `InterlockBuilder::write_all`.
+    pub fn write_all(mut self) -> Self {
+        self.all = Some(Access::Write);
+        self
+    }
+
+    /// See `InterlockBuilder::memoize`.
+    pub fn memoize(mut self, key: impl Fn(&T) -> u64 + Send + Sync + 'task) -> Self {
+        self.options.memo = Some(Memo::new(Arc::new(key)));
+        self
+    }
+
+    /// See `InterlockBuilder::realtime`.
+    pub fn realtime(mut self) -> Self {
+        self.options.realtime = true;
+        self
+    }
+
+    /// See `InterlockBuilder::mark_blocking`.
+    pub fn blocking(mut self) -> Self {
+        self.options.blocking = true;
+        self
+    }
+
+    /// See `InterlockBuilder::remote`.
+    pub fn remote(mut self) -> Self {
+        self.options.remote = true;
+        self
+    }
+
+    /// See `InterlockBuilder::non_idempotent`.
+    pub fn non_idempotent(mut self) -> Self {
+        self.options.idempotent = false;
+        self
+    }
+
+    /// See `InterlockBuilder::compensate`.
+    pub fn compensate(mut self, compensation: impl Executable<T> + Send + 'task) -> Self {
+        self.options.compensation = Some(Arc::new(Mutex::new(Box::new(compensation))));
+        self
+    }
+
+    /// See `InterlockBuilder::supervise`.
+    pub fn supervise(mut self, supervision: Supervision) -> Self {
+        self.options.supervisor = Some(Supervisor::new(supervision.checked()));
+        self
+    }
+
+    /// See `InterlockBuilder::transactional`, `InterlockBuilder::extend` fails unless the task writes `resource`.
+    pub fn transactional<S: 'task>(mut self,
+                                   resource: R,
+                                   snapshot: impl Fn(&T) -> S + Send + Sync + 'task,
+                                   restore: impl Fn(&T, S) + Send + Sync + 'task) -> Self where T: 'task {
+        self.options.snapshots.push(erased(snapshot, restore));
+        self.snapshotted.push(resource);
+        self
+    }
+
+    /// Adds dependencies by label, they may refer to tasks added before or to tasks of the same `extend` call.
+    pub fn after<L: Into<N>>(mut self, labels: impl IntoIterator<Item=L>) -> Self {
+        self.dependencies.extend(labels.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn label(&self) -> &N {
+        &self.label
+    }
 }
 
-pub struct InterlockBuilder<'task, T, R> {
-    tasks: Vec<TaskBuilder<'task, T, R>>
+/**
+ Builder of a graph, `Send` if `R` and `N` are `Send`, so graphs can be built on another thread.
+ Tasks are labeled with `N`, strings by default; see `named` for other label types.
+*/
+pub struct InterlockBuilder<'task, T, R, N = String> {
+    brand: u32,
+    tasks: Vec<TaskBuilder<'task, T, R, N>>,
+    dependencies: Vec<TaskId>,
+    accesses: Vec<(R, Access)>,
+    labels: HashMap<N, TaskId>,
+    parent: Option<Arc<Parent<'task, R>>>,
+    policies: Policies<R>,
+    capacities: Vec<(R, usize)>,
+    fairness: Fairness,
+    duplicates: Vec<BuildWarning<R>>,
+    seen: Seen
+}
+
+/// Dependencies and accesses of the task being added, to find duplicates without scanning the task's lists.
+#[derive(Default)]
+struct Seen {
+    hasher: RandomState,
+    dependencies: HashSet<TaskId>,
+    //hash of a resource to the index of the first access with that hash
+    accesses: HashMap<u64, usize>
+}
+
+impl<'task, T: Sync, R: Eq + Hash, N: Eq + Hash + Clone> Default for InterlockBuilder<'task, T, R, N> {
+
+    fn default() -> Self {
+        Self::named()
+    }
 }
 
 impl<'task, T: Sync, R: Eq + Hash> InterlockBuilder<'task, T, R> {
     pub fn new() -> Self {
-        Self { tasks: Vec::new() }
+        Self::named()
+    }
+
+    /**
+     Creates a builder with room for `tasks` tasks, and for `edges` dependencies as well as `edges` resource accesses.
+     Dependencies and accesses of all tasks share the same storage, so large graphs only reallocate it a few times.
+    */
+    pub fn with_capacity(tasks: usize, edges: usize) -> Self {
+        Self::named_with_capacity(tasks, edges)
+    }
+}
+
+impl<'task, T: Sync, R: Eq + Hash, N: Eq + Hash + Clone> InterlockBuilder<'task, T, R, N> {
+
+    /**
+     Creates a builder labeling tasks with `N` instead of strings, e.g. a fieldless enum of the passes of a frame,
+     so labels are compared and copied without allocating and reach `TaskSpec` dependencies, `InterlockExecutor::label`
+     and `InterlockExecutor::timeline` as they are. Persisted plans and remote tasks need string labels.
+    */
+    pub fn named() -> Self {
+        Self::named_with_capacity(0, 0)
+    }
+
+    /// Creates a builder labeling tasks with `N` like `named`, with room like `with_capacity`.
+    pub fn named_with_capacity(tasks: usize, edges: usize) -> Self {
+        Self {
+            brand: TaskId::next_brand(),
+            tasks: Vec::with_capacity(tasks),
+            dependencies: Vec::with_capacity(edges),
+            accesses: Vec::with_capacity(edges),
+            labels: HashMap::new(),
+            parent: None,
+            policies: Policies::new(),
+            capacities: Vec::new(),
+            fairness: Fairness::Unordered,
+            duplicates: Vec::new(),
+            seen: Seen::default()
+        }
+    }
+
+    /**
+     Makes resources hierarchical: `parent` maps a resource to the resource it is nested in, if any.
+     Writing a resource then conflicts with every access to its descendants, and writing a descendant conflicts
+     with every access to the resource, e.g. using `resource::path_parent` a writer of `"world/chunks"` excludes
+     readers of `"world/chunks/7"` without listing every chunk. Building the graph or starting a run panics if
+     a resource has more than `resource::MAX_DEPTH` ancestors, e.g. because `parent` leads back to a descendant.
+    */
+    pub fn hierarchy(&mut self, parent: impl Fn(&R) -> Option<R> + Send + Sync + 'task) {
+        self.parent = Some(Arc::new(parent));
+    }
+
+    /**
+     Sets the conflict policy of `resource`, resources use `ConflictPolicy::ReadWrite` by default.
+     Resources with a capacity are `ConflictPolicy::Concurrent` whatever their policy, see `capacity`.
+    */
+    pub fn policy(&mut self, resource: R, policy: ConflictPolicy) {
+        self.policies.insert(resource, policy);
+    }
+
+    /// Decides which of several conflicting tasks starts first, tasks start in whatever order they become ready by default.
+    pub fn fairness(&mut self, fairness: Fairness) {
+        self.fairness = fairness;
+    }
+
+    /**
+     Lets at most `permits` tasks accessing `resource` execute at the same time, e.g. the connections of a database pool.
+     Its accesses no longer conflict with each other, whether they read or write it; tasks take a permit instead.
+     A task waiting for a permit is parked without occupying a thread until a permit is released, so IO-bound graphs
+     can have far more tasks than threads, e.g. with `InterlockExecutor::run_spawned`. Only sequential runs block.
+     Only static accesses of the resource itself take permits, not resolved accesses or accesses to nested resources.
+     The resource is `ConflictPolicy::Concurrent` whatever policy `policy` sets for it, before or after this call.
+
+     Panics if `permits` is 0.
+    */
+    pub fn capacity(&mut self, resource: R, permits: usize) {
+        assert!(permits > 0, "a resource needs a capacity of at least one permit");
+
+        match self.capacities.iter_mut().find(|(other, _)| *other == resource) {
+            Some((_, capacity)) => *capacity = permits,
+            None => self.capacities.push((resource, permits))
+        }
     }
 
     pub fn add_box<D: Borrow<TaskId>>(&mut self, task: Box<dyn Executable<T> + Send + 'task>,
                                       reads: impl IntoIterator<Item=R>,
                                       writes: impl IntoIterator<Item=R>,
-                                      deps: impl IntoIterator<Item=D>) -> TaskId {
-        let id = TaskId::new(self.tasks.len());
+                                      deps: impl IntoIterator<Item=D>) -> TaskId where T: 'task, R: 'task {
+        self.add_body(Boxed::Plain(task), reads, writes, deps)
+    }
+
+    pub fn add<D: Borrow<TaskId>>(&mut self,
+                                  task: impl Executable<T> + Send + 'task,
+                                  reads: impl IntoIterator<Item=R>,
+                                  writes: impl IntoIterator<Item=R>,
+                                  deps: impl IntoIterator<Item=D>) -> TaskId where T: 'task, R: 'task {
+        self.add_body(Boxed::new(Plain(task)), reads, writes, deps)
+    }
+
+    /**
+     Adds a task that also receives a `TaskContext` on every run,
+     e.g. to skip work if none of the resources it reads changed since it last ran.
+    */
+    pub fn add_with_context<D: Borrow<TaskId>>(&mut self,
+                                               task: impl ContextExecutable<T, R> + Send + 'task,
+                                               reads: impl IntoIterator<Item=R>,
+                                               writes: impl IntoIterator<Item=R>,
+                                               deps: impl IntoIterator<Item=D>) -> TaskId {
+        self.add_body(Boxed::new(task), reads, writes, deps)
+    }
+
+    /**
+     Adds a task that also receives the extra data of every run, see `InterlockExecutor::run_with_extra`.
+     Panics when it executes in a run without extra data of type `E`.
+    */
+    pub fn add_with_extra<E: 'static, D: Borrow<TaskId>>(&mut self,
+                                                         task: impl FnMut(&T, &E) + Send + 'task,
+                                                         reads: impl IntoIterator<Item=R>,
+                                                         writes: impl IntoIterator<Item=R>,
+                                                         deps: impl IntoIterator<Item=D>) -> TaskId where T: 'task, R: 'task {
+        self.add_body(Boxed::new(Extra(task, PhantomData)), reads, writes, deps)
+    }
+
+    /**
+     Adds a task that receives `params` on every run, e.g. an iteration count to tune while the graph runs.
+     Replace them between runs with `InterlockExecutor::configure` instead of sharing a `Cell` with the task.
+    */
+    pub fn add_with_params<P: Send + Sync + 'static, D: Borrow<TaskId>>(&mut self,
+                                                                      params: P,
+                                                                      task: impl FnMut(&T, &P) + Send + 'task,
+                                                                      reads: impl IntoIterator<Item=R>,
+                                                                      writes: impl IntoIterator<Item=R>,
+                                                                      deps: impl IntoIterator<Item=D>) -> TaskId where T: 'task, R: 'task {
+        let id = self.add_body(Boxed::new(Configured(task, PhantomData)), reads, writes, deps);
+        self.tasks[id.id()].options.params = Some(Arc::new(params));
+        id
+    }
+
+    /**
+     Adds a flush task, which applies the commands pushed to `commands` so far with `apply`, so its dependants observe
+     them in the same run. The flush writes every resource: no task accessing a resource executes at the same time,
+     so `apply` can mutate the data through interior mutability without contention, e.g. a `RwLock` of entities.
+     Commands pushed after the flush started are left for a later flush or `InterlockExecutor::run_deferred`.
+    */
+    pub fn flush<C: Send + 'task, D: Borrow<TaskId>>(&mut self,
+                                                     commands: &'task CommandBuffer<C>,
+                                                     apply: impl FnMut(&T, C) + Send + 'task,
+                                                     deps: impl IntoIterator<Item=D>) -> TaskId where T: 'task, R: 'task {
+        let id = self.add(flush(commands, apply), None, None, deps);
+        self.write_all(id);
+        id
+    }
+
+    fn add_body<D: Borrow<TaskId>>(&mut self, task: Boxed<'task, T, R>,
+                                   reads: impl IntoIterator<Item=R>,
+                                   writes: impl IntoIterator<Item=R>,
+                                   deps: impl IntoIterator<Item=D>) -> TaskId {
+        let id = TaskId::branded(self.brand, self.tasks.len());
+
+        //duplicates are dropped, a dependency listed twice would be waited for twice
+        self.seen.dependencies.clear();
+        self.seen.accesses.clear();
+
+        let start = self.dependencies.len();
+        for dep in deps {
+            let dep = self.check(*dep.borrow());
+            match self.seen.dependencies.insert(dep) {
+                true => self.dependencies.push(dep),
+                false => self.duplicates.push(BuildWarning::DuplicateDependency { task: id, dependency: dep })
+            }
+        }
+
+        let dependencies = start..self.dependencies.len();
+
+        let start = self.accesses.len();
+        for read in reads {
+            self.push_access(id, start, read, Access::Read);
+        }
+
+        for write in writes {
+            self.push_access(id, start, write, Access::Write);
+        }
+
+        let accesses = start..self.accesses.len();
 
         self.tasks.push(TaskBuilder {
             task,
-            dependencies: deps.into_iter().map(|x| *x.borrow()).collect(),
-            reads: reads.into_iter().collect(),
-            writes: writes.into_iter().collect()
+            label: None,
+            dependencies,
+            accesses,
+            all: None,
+            resolve: None,
+            options: TaskOptions::default()
         });
 
         id
     }
 
-    pub fn add<D: Borrow<TaskId>>(&mut self,
-                                  task: impl Executable<T> + Send + 'task,
-                                  reads: impl IntoIterator<Item=R>,
-                                  writes: impl IntoIterator<Item=R>,
-                                  deps: impl IntoIterator<Item=D>) -> TaskId {
-        self.add_box(Box::new(task), reads, writes, deps)
+    /**
+     Adds a task created by `factory`. The factory is kept, so `InterlockExecutor::duplicate` can create
+     an independent instance of the task for every copy of the executor.
+    */
+    pub fn add_factory<E, D>(&mut self,
+                             factory: impl Fn() -> E + Send + Sync + 'task,
+                             reads: impl IntoIterator<Item=R>,
+                             writes: impl IntoIterator<Item=R>,
+                             deps: impl IntoIterator<Item=D>) -> TaskId
+        where E: Executable<T> + Send + 'task, D: Borrow<TaskId>, T: 'task, R: 'task {
+        let factory = boxed(factory);
+        let id = self.add_body(factory(), reads, writes, deps);
+
+        self.tasks[id.id()].options.factory = Some(factory);
+        id
     }
 
-    pub fn build(self) -> InterlockExecutor<'task, T> {
-        struct Task<'task, T> {
-            task: Box<dyn Executable<T> + Send + 'task>,
-            dependants: Vec<TaskId>,
-            resource_locks: HashSet<TaskId>,
-            initial: usize
+    /**
+     Adds a task that only needs shared access to itself. Graphs of shared tasks can be run by several
+     `InterlockExecutor::run_shared` calls at the same time, and duplicated without a factory.
+    */
+    pub fn add_shared<D: Borrow<TaskId>>(&mut self,
+                                         task: impl Fn(&T) + Send + Sync + 'task,
+                                         reads: impl IntoIterator<Item=R>,
+                                         writes: impl IntoIterator<Item=R>,
+                                         deps: impl IntoIterator<Item=D>) -> TaskId where T: 'task, R: 'task {
+        let (task, shared, factory) = share(task);
+        let id = self.add_body(task, reads, writes, deps);
+
+        self.tasks[id.id()].options.factory = Some(factory);
+        self.tasks[id.id()].options.shared = Some(shared);
+        id
+    }
+
+    /**
+     Adds a marker labeled by its name, see `tasks::Marker`. Markers access no resources and run no code,
+     so they can be duplicated and run by `InterlockExecutor::run_shared` like shared tasks.
+    */
+    pub fn add_marker<D: Borrow<TaskId>>(&mut self, marker: Marker<impl Into<N>>, deps: impl IntoIterator<Item=D>) -> Result<TaskId, BuildError<N>>
+        where T: 'task, R: 'task {
+        let label = marker.into_name().into();
+        if self.labels.contains_key(&label) {
+            return Err(BuildError::DuplicateLabel(label));
         }
 
-        impl<'task, T> Task<'task, T> {
+        let id = self.add_shared(|_: &T| {}, None, None, deps);
+        self.label(id, label)?;
+        Ok(id)
+    }
+
+    /// Labels `task`, so it can be referred to by `TaskSpec` dependencies and shows up in diagnostics.
+    pub fn label(&mut self, task: TaskId, label: impl Into<N>) -> Result<(), BuildError<N>> {
+        let label = label.into();
+        self.check(task);
+
+        match self.labels.get(&label) {
+            Some(&owner) if owner == task => return Ok(()),
+            Some(_) => return Err(BuildError::DuplicateLabel(label)),
+            None => {}
+        }
+
+        if let Some(old) = self.tasks[task.id()].label.replace(label.clone()) {
+            self.labels.remove(&old);
+        }
+
+        self.labels.insert(label, task);
+        Ok(())
+    }
 
-            fn new(task: Box<dyn Executable<T> + Send + 'task>, initial: usize) -> Self {
-                Self { task, initial, dependants: Vec::new(), resource_locks: HashSet::new() }
+    /**
+     Adds all `specs` and returns their ids by label.
+     Dependencies may refer to specs declared later in the same call, specs are added in dependency order
+     and otherwise in declaration order. Nothing is added if any spec is invalid, e.g. snapshots a resource it doesn't write.
+    */
+    pub fn extend(&mut self, specs: impl IntoIterator<Item=TaskSpec<'task, T, R, N>>) -> Result<HashMap<N, TaskId>, BuildError<N>> {
+        let mut specs: Vec<_> = specs.into_iter().map(Some).collect();
+        let mut index = HashMap::with_capacity(specs.len());
+
+        for (idx, spec) in specs.iter().flatten().enumerate() {
+            if self.labels.contains_key(&spec.label) || index.insert(&spec.label, idx).is_some() {
+                return Err(BuildError::DuplicateLabel(spec.label.clone()));
             }
 
-            fn add_resource_lock(&mut self, id: TaskId) {
-                self.resource_locks.insert(id);
+            if spec.all != Some(Access::Write) && spec.snapshotted.iter().any(|resource| !spec.writes.contains(resource)) {
+                return Err(BuildError::UnwrittenSnapshot(spec.label.clone()));
             }
+        }
 
-            fn add_dependant(&mut self, id: TaskId) {
-                self.dependants.push(id);
+        //dependencies within the batch, dependencies on tasks added before are already satisfied
+        let mut pending = vec![0usize; specs.len()];
+        let mut dependants = vec![Vec::new(); specs.len()];
+
+        for (idx, spec) in specs.iter().flatten().enumerate() {
+            for dep in spec.dependencies.iter() {
+                match index.get(dep) {
+                    Some(&dep) => {
+                        pending[idx] += 1;
+                        dependants[dep].push(idx);
+                    },
+
+                    None if self.labels.contains_key(dep) => {},
+                    None => return Err(BuildError::UnknownLabel { task: spec.label.clone(), dependency: dep.clone() })
+                }
+            }
+        }
+
+        let mut ready: BinaryHeap<_> = (0..specs.len()).filter(|idx| pending[*idx] == 0).map(Reverse).collect();
+        let mut order = Vec::with_capacity(specs.len());
+
+        while let Some(Reverse(idx)) = ready.pop() {
+            order.push(idx);
+
+            for next in dependants[idx].iter() {
+                pending[*next] -= 1;
+                if pending[*next] == 0 {
+                    ready.push(Reverse(*next));
+                }
             }
+        }
 
-            fn build(self, id: TaskId) -> super::Task<'task, T> {
-                let mut lock = Vec::with_capacity(self.resource_locks.len());
-                let mut unlock = self.dependants; //why allocate new vec when i can do this??
+        if order.len() < specs.len() {
+            let cycle = find_cycle(&pending, &index, |idx| specs[idx].as_ref().map(|s| s.dependencies.as_slice()).unwrap_or_default());
+            return Err(BuildError::Cycle(cycle.into_iter().map(|idx| specs[idx].as_ref().unwrap().label.clone()).collect()));
+        }
+
+        drop(index);
+        self.tasks.reserve(order.len());
+        self.dependencies.reserve(specs.iter().flatten().map(|spec| spec.dependencies.len()).sum());
+        self.accesses.reserve(specs.iter().flatten().map(|spec| spec.reads.len() + spec.writes.len()).sum());
+
+        let mut ids = HashMap::with_capacity(order.len());
+        for idx in order {
+            let spec = specs[idx].take().expect("spec was added twice");
+            let deps: Vec<_> = spec.dependencies.iter().map(|dep| self.labels[dep]).collect();
+
+            let id = self.add_body(spec.task, spec.reads, spec.writes, deps);
+            let task = &mut self.tasks[id.id()];
+            task.all = spec.all;
+            task.options = spec.options;
+            task.label = Some(spec.label.clone());
+
+            self.labels.insert(spec.label.clone(), id);
+            ids.insert(spec.label, id);
+        }
+
+        Ok(ids)
+    }
+
+    /**
+     Declares that `task` reads every resource, e.g. for a snapshot or a debug dump.
+     It conflicts with every writer, including tasks writing everything, but not with other readers.
+    */
+    pub fn read_all(&mut self, task: TaskId) {
+        self.task_mut(task).all.get_or_insert(Access::Read);
+    }
+
+    /**
+     Declares that `task` writes every resource, so it never runs at the same time as any task accessing one,
+     whatever the conflict policy of the resource, including resources with a capacity.
+    */
+    pub fn write_all(&mut self, task: TaskId) {
+        self.task_mut(task).all = Some(Access::Write);
+    }
+
+    /**
+     Lets `task` access resources that are only known at run start, e.g. a variable set of chunks.
+     Before every run `resolve` is called with the run data and declares the accesses for that run in addition
+     to the static ones; the executor only recomputes conflicts of resolved resources when they change.
+    */
+    pub fn resolve(&mut self, task: TaskId, resolve: impl Fn(&T, &mut Accesses<R>) + Send + Sync + 'task) {
+        self.task_mut(task).resolve = Some(Arc::new(resolve));
+    }
+
+    /**
+     Memoizes `task`: before every run `key` hashes the inputs of the task, and if the hash matches the one of
+     its last execution the task is skipped and its outputs count as unchanged. Hashes of labeled tasks can be
+     persisted with `InterlockExecutor::memo_cache`.
+    */
+    pub fn memoize(&mut self, task: TaskId, key: impl Fn(&T) -> u64 + Send + Sync + 'task) {
+        self.task_mut(task).options.memo = Some(Memo::new(Arc::new(key)));
+    }
+
+    /**
+     Marks `task` as latency critical, e.g. audio mixing. Whenever several tasks are ready, realtime tasks are
+     started before bulk tasks; with `InterlockExecutor::reserve_realtime` they also execute on reserved threads.
+     Dependencies and conflicts still apply, a realtime task waiting for a bulk task waits for it to finish.
+    */
+    pub fn realtime(&mut self, task: TaskId) {
+        self.task_mut(task).options.realtime = true;
+    }
+
+    /**
+     Marks `task` as spending most of its time blocked, e.g. reading a file. It executes on an extra thread while the
+     worker that started it keeps executing ready tasks, so the pool doesn't lose a core while it waits. Extra threads
+     are started the first time every blocking task runs at once and sleep otherwise, see `PoolInfo::blocking`.
+     Sequential runs execute it on the calling thread, and `InterlockExecutor::dedicate` overrides it.
+    */
+    pub fn mark_blocking(&mut self, task: TaskId) {
+        self.task_mut(task).options.blocking = true;
+    }
+
+    /**
+     Marks `task` as remote: once the executor has a transport, see `InterlockExecutor::set_transport`,
+     the task is sent to a worker process by label instead of executing its body, and is ordered and excluded
+     like any other task meanwhile. Workers execute it with `remote::RemoteExecutor`. The task needs a label.
+    */
+    pub fn remote(&mut self, task: TaskId) {
+        self.task_mut(task).options.remote = true;
+    }
+
+    /**
+     Marks `task` as unsafe to execute again after it was interrupted, e.g. a payment or an append to a log.
+     Tasks are idempotent by default. A run resumed with `InterlockExecutor::resume_from_checkpoint` doesn't
+     execute a non-idempotent task that started but didn't complete again, and stops its branch instead:
+     the task and its dependants are skipped.
+    */
+    pub fn non_idempotent(&mut self, task: TaskId) {
+        self.task_mut(task).options.idempotent = false;
+    }
+
+    /**
+     Registers `compensation` to undo the effects of `task`, e.g. to delete an uploaded file. When a task panics,
+     the run compensates every task that completed in it, dependants before their dependencies, and then panics.
+     Compensations execute on the calling thread; a panicking compensation doesn't stop the others.
+    */
+    pub fn compensate(&mut self, task: TaskId, compensation: impl Executable<T> + Send + 'task) {
+        self.task_mut(task).options.compensation = Some(Arc::new(Mutex::new(Box::new(compensation))));
+    }
+
+    /**
+     Supervises `task` for drivers running the graph over and over: instead of failing the run, a panic of the task
+     is caught and handled by `supervision`, see `InterlockExecutor::supervision` for the counters. Dependants of a
+     failed task execute as if it had completed. Panics if the policy gives up before the first failure.
+    */
+    pub fn supervise(&mut self, task: TaskId, supervision: Supervision) {
+        self.task_mut(task).options.supervisor = Some(Supervisor::new(supervision.checked()));
+    }
 
-                lock.extend(self.resource_locks.iter().map(|t| *t)); //cloning da iterator
-                unlock.extend(self.resource_locks);
+    /**
+     Makes `task` transactional over `resource`, e.g. a script mutating the world: `snapshot` copies the resource
+     before every execution and `restore` puts the copy back if the task panics, before the panic goes on to fail
+     the run or to the supervision of the task. Panics unless the task writes `resource` or everything, so no other
+     task accesses the resource between the snapshot and the restore. Resources restore in reverse order.
+    */
+    pub fn transactional<S: 'task>(&mut self,
+                                   task: TaskId,
+                                   resource: R,
+                                   snapshot: impl Fn(&T) -> S + Send + Sync + 'task,
+                                   restore: impl Fn(&T, S) + Send + Sync + 'task) where T: 'task {
+        let task = self.check(task);
+        self.add_snapshot(task, &resource, erased(snapshot, restore));
+    }
+
+    fn add_snapshot(&mut self, task: TaskId, resource: &R, snapshot: Arc<Snapshot<'task, T>>) {
+        let builder = &self.tasks[task.id()];
+        let writes = builder.all == Some(Access::Write)
+            || self.accesses[builder.accesses.clone()].iter().any(|(other, access)| other == resource && *access == Access::Write);
 
-                super::Task::new(id, self.task, lock, unlock, self.initial)
+        assert!(writes, "can't snapshot a resource the task doesn't write");
+        self.tasks[task.id()].options.snapshots.push(snapshot);
+    }
+
+    /**
+     Simulates a run of the graph on `threads` threads without building it, `cost` estimates the execution time of a task.
+     Only static accesses are taken into account, resolvers are ignored and accesses of resources with a capacity
+     don't conflict, whatever their number of permits. Panics if `threads` is 0.
+    */
+    pub fn graph_stats(&self, threads: usize, cost: impl Fn(TaskId) -> Duration) -> GraphStats {
+        self.stats_with(|_, _| None, threads, cost)
+    }
+
+    /**
+     What-if analysis of splitting `resource`, e.g. a world lock into one lock per region: every task of `parts[i]`
+     accesses only the i-th part of the resource instead, so tasks of different parts no longer conflict over it.
+     Accessors of the resource that are in no part still access all of it and conflict with every part.
+     Returns the stats of `graph_stats` before and after the split, the builder is left unchanged.
+
+     Panics if a task belongs to a different builder or to several parts.
+    */
+    pub fn split_impact(&self, resource: &R, parts: &[Vec<TaskId>], threads: usize, cost: impl Fn(TaskId) -> Duration) -> SplitImpact {
+        let mut part_of = vec![None; self.tasks.len()];
+
+        for (idx, part) in parts.iter().enumerate() {
+            for task in part.iter().map(|task| self.check(*task)) {
+                match part_of[task.id()] {
+                    Some(other) if other != idx => panic!("task {:?} is in parts {} and {}", task, other, idx),
+                    _ => part_of[task.id()] = Some(idx)
+                }
             }
         }
 
-        let mut tasks: Vec<Task<'task, T>> = Vec::with_capacity(self.tasks.len());
+        SplitImpact {
+            before: self.graph_stats(threads, &cost),
+            after: self.stats_with(|task, accessed| part_of[task.id()].filter(|_| accessed == resource), threads, &cost)
+        }
+    }
 
-        let mut read_map = MultiMap::new();
-        let mut write_map = MultiMap::new();
+    /// Stats of the graph where `part` picks the part of a resource accessed by a task, `None` for the whole resource.
+    fn stats_with(&self, part: impl Fn(TaskId, &R) -> Option<usize>, threads: usize, cost: impl Fn(TaskId) -> Duration) -> GraphStats {
+        let parent = self.parent.as_deref();
+        let mut table = ResourceTable::new();
+        let mut dependencies = Vec::with_capacity(self.tasks.len());
+        let mut costs = Vec::with_capacity(self.tasks.len());
 
-        for (id, task) in self.tasks.into_iter().enumerate().map(|(id, task)| (TaskId::new(id), task)) {
-            tasks.push(Task::new(task.task, task.dependencies.len()));
+        for (id, task) in self.tasks.iter().enumerate() {
+            let id = TaskId::branded(self.brand, id);
 
-            for read in task.reads {
-                read_map.insert(read, id);
+            for (resource, access) in self.accesses[task.accesses.clone()].iter() {
+                table.insert_part(parent, resource, part(id, resource), *access, id);
             }
 
-            for write in task.writes {
-                write_map.insert(write, id);
+            if let Some(access) = task.all {
+                table.insert_all(access, id);
             }
 
-            for dep in task.dependencies { //guaranteed to be added before that
-                tasks[dep.id()].add_dependant(id); //add as a dependant
+            let mut deps = self.dependencies[task.dependencies.clone()].to_vec();
+            deps.sort_by_key(TaskId::id);
+            deps.dedup();
+
+            dependencies.push(deps);
+            costs.push(cost(id));
+        }
+
+        let capacities = self.capacities.iter().map(|(resource, _)| (resource, ConflictPolicy::Concurrent));
+        let policies: Policies<&R> = self.policies.iter().map(|(resource, policy)| (resource, *policy)).chain(capacities).collect();
+
+        let locks = table.conflicts::<_, R>(self.tasks.len(), &policies);
+        impact::stats(dependencies, locks, costs, self.fairness, threads)
+    }
+
+    /// Adds an access of the task whose accesses start at `start`, a resource that is both read and written is only written.
+    fn push_access(&mut self, task: TaskId, start: usize, resource: R, access: Access) {
+        let hash = self.seen.hasher.hash_one(&resource);
+        let existing = match self.seen.accesses.get(&hash) {
+            Some(&idx) if self.accesses[idx].0 == resource => Some(idx),
+            //colliding hashes are rare enough to scan for
+            Some(_) => self.accesses[start..].iter().position(|(other, _)| *other == resource).map(|idx| start + idx),
+            None => None
+        };
+
+        match existing {
+            Some(idx) => {
+                if access == Access::Write {
+                    self.accesses[idx].1 = Access::Write;
+                }
+
+                self.duplicates.push(BuildWarning::DuplicateAccess { task, resource, access });
+            },
+
+            None => {
+                self.seen.accesses.entry(hash).or_insert(self.accesses.len());
+                self.accesses.push((resource, access))
             }
         }
+    }
 
-        //every WRITE locks every WRITE and every READ
-        for (res, writes) in write_map.iter_all() {
-            let reads = read_map.get_vec(res);
+    fn check(&self, task: TaskId) -> TaskId {
+        if task.brand() != self.brand {
+            panic!("task {:?} belongs to a different builder", task);
+        }
 
-            for current in writes {
-                for next in writes {
-                    if current != next {
-                        tasks[next.id()].add_resource_lock(*current);
-                    }
+        task
+    }
+
+    fn task_mut(&mut self, task: TaskId) -> &mut TaskBuilder<'task, T, R, N> {
+        let id = self.check(task).id();
+        &mut self.tasks[id]
+    }
+
+    /**
+     Builds the executor like `build` and additionally reports suspicious patterns of the graph,
+     see `BuildWarning`. Warnings are ordered by kind and then by task or declaration order.
+    */
+    pub fn build_with_report(mut self) -> (InterlockExecutor<'task, T, R, N>, Vec<BuildWarning<R>>) where R: Clone {
+        let mut warnings = Vec::new();
+        let mut connectable = Vec::with_capacity(self.tasks.len());
+
+        for (id, task) in self.tasks.iter().enumerate() {
+            let id = TaskId::branded(self.brand, id);
+            let accesses = !task.accesses.is_empty() || task.all.is_some() || task.resolve.is_some();
+
+            if !accesses && task.dependencies.is_empty() {
+                warnings.push(BuildWarning::Unconstrained(id));
+            }
+
+            //resolved accesses may connect the task at run time
+            connectable.push(accesses && task.resolve.is_none());
+
+            if task.dependencies.len() > MAX_FAN_IN {
+                warnings.push(BuildWarning::FanIn { task: id, dependencies: task.dependencies.len() });
+            }
+        }
+
+        warnings.append(&mut self.duplicates);
+
+        //a resource is read if it, one of its ancestors or one of its descendants is read
+        if self.tasks.iter().all(|task| task.all != Some(Access::Read)) {
+            let parent = self.parent.as_deref();
+            let mut reads = HashSet::new();
+            let mut nested = HashSet::new();
+
+            for (resource, _) in self.accesses.iter().filter(|(_, access)| *access == Access::Read) {
+                resource::ancestors(parent, resource, |ancestor| { nested.insert(ancestor); });
+                reads.insert(resource);
+            }
+
+            let mut written = HashSet::new();
+            for (resource, _) in self.accesses.iter().filter(|(_, access)| *access == Access::Write) {
+                if !written.insert(resource) || reads.contains(resource) || nested.contains(resource) {
+                    continue;
                 }
 
-                if let Some(reads) = reads {
-                    for next in reads {
-                        if current != next {
-                            tasks[next.id()].add_resource_lock(*current);
-                        }
-                    }
+                let mut read = false;
+                resource::ancestors(parent, resource, |ancestor| read |= reads.contains(&ancestor));
+
+                if !read {
+                    warnings.push(BuildWarning::WriteOnly(resource.clone()));
                 }
             }
         }
 
-        //every READ locks every WRITE
-        for (res, reads) in read_map.iter_all() {
-            if let Some(writes) = write_map.get_vec(res) {
-                for current in reads {
-                    for next in writes {
-                        if current != next {
-                            tasks[next.id()].add_resource_lock(*current);
-                        }
+        let executor = self.build();
+
+        for (task, connectable) in executor.tasks.iter().zip(connectable) {
+            if connectable && task.initial_count() == 0 && task.unlockable_deps().is_empty() {
+                warnings.push(BuildWarning::Disconnected(task.id()));
+            }
+        }
+
+        (executor, warnings)
+    }
+
+    pub fn build(self) -> InterlockExecutor<'task, T, R, N> {
+        struct Task<'task, T, R> {
+            task: Boxed<'task, T, R>,
+            options: TaskOptions<'task, T, R>,
+            permits: Vec<usize>,
+            dependants: Vec<TaskId>,
+            initial: usize
+        }
+
+        impl<'task, T, R> Task<'task, T, R> {
+
+            fn new(task: Boxed<'task, T, R>, options: TaskOptions<'task, T, R>, permits: Vec<usize>, initial: usize) -> Self {
+                Self { task, options, permits, initial, dependants: Vec::new() }
+            }
+
+            fn add_dependant(&mut self, id: TaskId) {
+                self.dependants.push(id);
+            }
+
+            fn build<N>(self, id: TaskId, label: Option<N>, resource_locks: Vec<TaskId>) -> super::Task<'task, T, R, N> {
+                let mut unlock = self.dependants; //why allocate new vec when i can do this??
+                unlock.extend(resource_locks.iter().copied());
+
+                let mut task = super::Task::new(id, label, self.task, self.options, resource_locks, unlock, self.initial);
+                task.set_permits(self.permits);
+                task
+            }
+        }
+
+        let mut tasks: Vec<Task<'task, T, R>> = Vec::with_capacity(self.tasks.len());
+        let mut dependencies = self.dependencies.into_iter();
+        let mut accesses = self.accesses.into_iter();
+
+        let parent = self.parent;
+        let mut table = ResourceTable::new();
+        let mut resolvers = Vec::new();
+        let mut labels = Vec::with_capacity(self.tasks.len());
+
+        let (semaphores, capacities): (HashMap<_, _>, Vec<_>) = self.capacities.into_iter()
+            .enumerate()
+            .map(|(idx, (resource, permits))| ((resource, idx), permits))
+            .unzip();
+
+        let brand = self.brand;
+
+        for (id, task) in self.tasks.into_iter().enumerate().map(|(id, task)| (TaskId::branded(brand, id), task)) {
+            //ranges are consecutive, so each task takes the next accesses and dependencies
+            let mut permits = Vec::new();
+            for (resource, access) in accesses.by_ref().take(task.accesses.len()) {
+                permits.extend(semaphores.get(&resource).copied());
+                table.insert(parent.as_deref(), resource, access, id);
+            }
+
+            tasks.push(Task::new(task.task, task.options, permits, task.dependencies.len()));
+            labels.push(task.label);
+
+            if let Some(access) = task.all {
+                table.insert_all(access, id);
+            }
+
+            if let Some(resolve) = task.resolve {
+                resolvers.push((id, resolve));
+            }
+
+            for dep in dependencies.by_ref().take(task.dependencies.len()) { //guaranteed to be added before that
+                tasks[dep.id()].add_dependant(id); //add as a dependant
+            }
+        }
+
+        //accesses of resources with a capacity take permits instead of conflicting
+        let mut policies = self.policies;
+        for (resource, _) in semaphores {
+            policies.insert(resource, ConflictPolicy::Concurrent);
+        }
+
+        //conflicting tasks lock each other
+        let mut locks = table.conflicts(tasks.len(), &policies);
+
+        //or wait for each other in order of addition
+        if self.fairness == Fairness::Fifo {
+            for (id, locks) in locks.iter_mut().enumerate() {
+                let current = TaskId::branded(brand, id);
+
+                for earlier in locks.drain(..).filter(|lock| lock.id() < id) {
+                    if !tasks[earlier.id()].dependants.contains(&current) {
+                        tasks[earlier.id()].add_dependant(current);
+                        tasks[id].initial += 1;
                     }
                 }
             }
+
+            tasks.iter_mut().for_each(|task| task.dependants.sort_by_key(TaskId::id));
+        }
+        let changes = Changes::new(tasks.len(), &table);
+
+        let mut resources = Resources::new(parent, policies, table);
+        for (id, resolve) in resolvers {
+            resources.add_resolver(id, resolve);
         }
 
-        tasks.into_iter()
+        let tasks = tasks.into_iter()
+            .zip(labels)
+            .zip(locks)
             .enumerate()
-            .map(|(id, t)| t.build(TaskId::new(id)))
-            .collect()
+            .map(|(id, ((t, label), locks))| t.build(TaskId::branded(brand, id), label, locks))
+            .collect();
+
+        let mut executor = InterlockExecutor::new(tasks, resources, changes, &capacities);
+        executor.set_fairness(self.fairness);
+        executor
     }
-}
\ No newline at end of file
+}
+
+/// Erases the type of a snapshot, the restore is shared by the snapshots of every execution.
+fn erased<'task, T, S: 'task>(snapshot: impl Fn(&T) -> S + Send + Sync + 'task, restore: impl Fn(&T, S) + Send + Sync + 'task) -> Arc<Snapshot<'task, T>> {
+    let restore = Arc::new(restore);
+    Arc::new(move |data: &T| {
+        let copy = snapshot(data);
+        let restore = restore.clone();
+        Box::new(move |data: &T| restore(data, copy)) as Box<dyn FnOnce(&T) + 'task>
+    })
+}
+
+fn boxed<'task, T: 'task, R: 'task, E: Executable<T> + Send + 'task>(factory: impl Fn() -> E + Send + Sync + 'task) -> Arc<Factory<'task, T, R>> {
+    Arc::new(move || Boxed::new(Plain(factory())))
+}
+
+/// Returns the body of a shared task for plain runs, for shared runs and a factory handing out the same body.
+#[allow(clippy::type_complexity)]
+fn share<'task, T: 'task, R: 'task>(task: impl Fn(&T) + Send + Sync + 'task)
+    -> (Boxed<'task, T, R>, Arc<SharedFn<'task, T>>, Arc<Factory<'task, T, R>>) {
+    let shared: Arc<SharedFn<'task, T>> = Arc::new(task);
+    let body = |shared: Arc<SharedFn<'task, T>>| Boxed::new(Plain(move |data: &T| shared(data)));
+
+    let cloned = shared.clone();
+    (body(shared.clone()), shared, Arc::new(move || body(cloned.clone())))
+}
+
+/// Finds a cycle among specs with unresolved dependencies, every one of them depends on another one.
+fn find_cycle<'a, N: Eq + Hash + 'a>(pending: &[usize], index: &HashMap<&N, usize>, dependencies: impl Fn(usize) -> &'a [N]) -> Vec<usize> {
+    let mut path = Vec::new();
+    let mut visited = HashSet::new();
+    let mut current = pending.iter().position(|count| *count > 0).expect("no unresolved spec");
+
+    while visited.insert(current) {
+        path.push(current);
+        current = dependencies(current).iter()
+            .filter_map(|dep| index.get(dep).copied())
+            .find(|dep| pending[*dep] > 0)
+            .expect("unresolved spec without unresolved dependency");
+    }
+
+    //the path may start with specs leading into the cycle
+    let start = path.iter().position(|idx| *idx == current).unwrap();
+    path.split_off(start)
+}
+
+/// Body of a flush task, applies the commands pushed so far.
+fn flush<'task, T, C: Send>(commands: &'task CommandBuffer<C>, mut apply: impl FnMut(&T, C) + Send + 'task) -> impl Executable<T> + Send + 'task {
+    move |data: &T| commands.take().into_iter().for_each(|command| apply(data, command))
+}
@@ -1,79 +1,335 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::cell::UnsafeCell;
+use self::order::{ACQUIRE, ACQ_REL, RELAXED, RELEASE};
+use self::storage::{Borrowed, Storage};
+#[cfg(not(loom))]
+use std::sync::atomic::AtomicUsize;
+#[cfg(loom)]
+use loom::sync::atomic::AtomicUsize;
+use std::fmt::{self, Debug, Formatter};
 use std::ops::{DerefMut, Deref};
 
 /**
-This is a very specialized cell that has following semantics:
-- it can be locked by calling lock()
-- it can be unlocked by calling unlock()
-- if the counter is fully unlocked (has lock count of 0), the reference to the data can be taken by calling take()
-- when the reference goes out of scope, the counter goes into the 'completed' state
-- when in 'completed' state, the counter can be reset
-
-This type behavior can be described as the following state machine:
-**/
+ Counter guarding a value that can be borrowed mutably once the counter drops to zero, e.g. the body of a task
+ that may start once all tasks it waits for finished. The counter is shared between threads, every thread
+ finishing a dependency unlocks it and the one unlocking it last takes the value.
+
+ Every run goes through the states of `CellState`:
+
+ ```text
+            reset(0)
+  Completed ---------------------------> Ready ---take()---> Taken
+     ^    |                               ^  |                 |
+     |    | reset(n > 0)        unlock()  |  | lock()          |
+     |    +--------------------------> Locked(n)               |
+     |                                                         |
+     +------------------ CountRef dropped ---------------------+
+ ```
+
+ `lock` and `unlock` move between `Locked(n)` and `Ready`, `take` only succeeds when `Ready` and fails otherwise.
+ `Taken` and `Completed` cells count locks and unlocks too, e.g. a lock racing with the thread taking the value,
+ the locks carry over from `Taken` to `Completed` and have to be released before the next `reset`.
+ Resetting a cell that isn't `Completed` without locks and unlocking a cell without locks panic.
+ A run that fails leaves cells behind in any state, `complete` brings them back to `Completed` without locks.
+*/
 pub struct CountCell<T: ?Sized> {
     borrow: AtomicUsize,
-    value: UnsafeCell<T>
+    value: Storage<T>
 }
 
+/// Mutable borrow of the value of a `CountCell`, completes the cell when dropped.
 pub struct CountRef<'a, T: ?Sized> {
-    value: &'a mut T,
+    value: Borrowed<'a, T>,
     borrow: &'a AtomicUsize
 }
 
-const LOCK_BIT: usize = !(::core::usize::MAX >> 1); //currently locked
-const COMP_BIT: usize = !(::core::usize::MAX >> 2) & !LOCK_BIT; //completed
+/// State of a `CountCell`, see its documentation for the transitions.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum CellState {
+    /// The value was borrowed and released, the cell can be reset once it holds no locks.
+    Completed { locks: usize },
+    /// The value can't be taken until the cell is unlocked this many times.
+    Locked(usize),
+    /// The value can be taken.
+    Ready,
+    /// The value is borrowed by a `CountRef`, locks taken meanwhile carry over to `Completed`.
+    Taken { locks: usize }
+}
+
+/**
+ Orderings of the counter. Every transition is a read-modify-write of the same atomic, so all threads agree on
+ the order of the transitions whatever the orderings are: exactly one `unlock` sees the last lock go, and exactly
+ one `take` succeeds per run. The orderings only decide which memory writes are visible to whom:
+
+ - `unlock` releases everything the unlocking thread wrote before, e.g. the resources a dependency produced.
+ - `take` acquires when it succeeds. The unlocks before it form a release sequence, since each of them is
+   a read-modify-write, so the taker sees the writes of every unlocking thread and not only of the last one.
+ - dropping a `CountRef` releases the writes of the borrow, and `reset` continues the release sequence,
+   so the next `take` sees them too, even on another thread.
+ - `lock` acquires, which is stronger than needed: a lock only delays the `take`, the memory it waits for is
+   released by the matching `unlock`.
+
+ The `seqcst` feature makes every access `SeqCst`, to rule the orderings out when chasing a bug.
+ The loom models of `interleavings` check the first three points.
+*/
+#[cfg(not(feature = "seqcst"))]
+mod order {
+    use std::sync::atomic::Ordering;
+
+    pub const ACQUIRE: Ordering = Ordering::Acquire;
+    pub const RELEASE: Ordering = Ordering::Release;
+    pub const ACQ_REL: Ordering = Ordering::AcqRel;
+    pub const RELAXED: Ordering = Ordering::Relaxed;
+}
+
+#[cfg(feature = "seqcst")]
+mod order {
+    use std::sync::atomic::Ordering;
+
+    pub const ACQUIRE: Ordering = Ordering::SeqCst;
+    pub const RELEASE: Ordering = Ordering::SeqCst;
+    pub const ACQ_REL: Ordering = Ordering::SeqCst;
+    pub const RELAXED: Ordering = Ordering::SeqCst;
+}
+
+/**
+ Storage of the value. The counter hands out a single borrow at a time, so the value lives in an `UnsafeCell`.
+ The `safe-cell` feature moves the value in and out of a `Mutex` instead, which is slower but has no unsafe
+ code, e.g. for crates running their tests under Miri.
+*/
+#[cfg(not(any(feature = "safe-cell", loom)))]
+mod storage {
+    use std::cell::UnsafeCell;
+
+    pub struct Storage<T: ?Sized>(UnsafeCell<T>);
+
+    pub struct Borrowed<'a, T: ?Sized>(&'a mut T);
+
+    impl<T> Storage<T> {
+
+        pub fn new(value: T) -> Self {
+            Self(UnsafeCell::new(value))
+        }
+
+        pub fn into_inner(self) -> T {
+            self.0.into_inner()
+        }
+    }
+
+    impl<T: ?Sized> Storage<T> {
+
+        /// Safety: the value must not be borrowed already.
+        pub unsafe fn borrow(&self) -> Borrowed<'_, T> {
+            Borrowed(&mut *self.0.get())
+        }
+    }
+
+    impl<'a, T: ?Sized> Borrowed<'a, T> {
+
+        pub fn get(&self) -> &T {
+            self.0
+        }
+
+        pub fn get_mut(&mut self) -> &mut T {
+            self.0
+        }
+
+        pub fn release(&mut self) {}
+    }
+
+    //SAFETY: the value is only reachable through a CountRef, and take() hands out at most one at a time,
+    //so sharing the cell only ever moves the mutable borrow to another thread, which needs T: Send.
+    //Send is derived automatically from UnsafeCell<T>, CountRef is Send/Sync like the &mut T it wraps.
+    unsafe impl<T: ?Sized + Send> Sync for Storage<T> {}
+}
+
+#[cfg(all(feature = "safe-cell", not(loom)))]
+mod storage {
+    use std::sync::{Mutex, PoisonError};
+
+    pub struct Storage<T: ?Sized>(Mutex<Option<Box<T>>>);
+
+    pub struct Borrowed<'a, T: ?Sized> {
+        storage: &'a Storage<T>,
+        value: Option<Box<T>>
+    }
+
+    impl<T> Storage<T> {
+
+        pub fn new(value: T) -> Self {
+            Self(Mutex::new(Some(Box::new(value))))
+        }
+
+        pub fn into_inner(self) -> T {
+            *self.0.into_inner().unwrap_or_else(PoisonError::into_inner).expect("value returned by every borrow")
+        }
+    }
+
+    impl<T: ?Sized> Storage<T> {
+
+        /// Safety: none, borrowing the value twice panics.
+        pub unsafe fn borrow(&self) -> Borrowed<'_, T> {
+            let value = self.0.lock().unwrap_or_else(PoisonError::into_inner).take().expect("value borrowed twice");
+            Borrowed { storage: self, value: Some(value) }
+        }
+    }
+
+    impl<'a, T: ?Sized> Borrowed<'a, T> {
+
+        pub fn get(&self) -> &T {
+            self.value.as_deref().expect("value of a released borrow")
+        }
+
+        pub fn get_mut(&mut self) -> &mut T {
+            self.value.as_deref_mut().expect("value of a released borrow")
+        }
+
+        /// Moves the value back, before the counter lets the next borrow start.
+        pub fn release(&mut self) {
+            *self.storage.0.lock().unwrap_or_else(PoisonError::into_inner) = self.value.take();
+        }
+    }
+}
+
+/// Storage checked by loom, which reports borrows of the value that the counter doesn't order, see `interleavings`.
+#[cfg(loom)]
+mod storage {
+    use loom::cell::{MutPtr, UnsafeCell};
+    use std::marker::PhantomData;
+
+    pub struct Storage<T: ?Sized>(UnsafeCell<T>);
+
+    pub struct Borrowed<'a, T: ?Sized>(MutPtr<T>, PhantomData<&'a mut T>);
+
+    impl<T> Storage<T> {
+
+        pub fn new(value: T) -> Self {
+            Self(UnsafeCell::new(value))
+        }
+
+        pub fn into_inner(self) -> T {
+            self.0.into_inner()
+        }
+    }
+
+    impl<T: ?Sized> Storage<T> {
+
+        /// Safety: the value must not be borrowed already.
+        pub unsafe fn borrow(&self) -> Borrowed<'_, T> {
+            Borrowed(self.0.get_mut(), PhantomData)
+        }
+    }
+
+    impl<'a, T: ?Sized> Borrowed<'a, T> {
+
+        pub fn get(&self) -> &T {
+            //SAFETY: the pointer is the only borrow of the value while it lives
+            unsafe { self.0.deref() }
+        }
+
+        pub fn get_mut(&mut self) -> &mut T {
+            //SAFETY: see get
+            unsafe { self.0.deref() }
+        }
+
+        pub fn release(&mut self) {}
+    }
+
+    //SAFETY: as for the UnsafeCell storage, the borrow behaves like the &mut T it hands out
+    unsafe impl<T: ?Sized + Send> Sync for Storage<T> {}
+    unsafe impl<T: ?Sized + Send> Send for Borrowed<'_, T> {}
+    unsafe impl<T: ?Sized + Sync> Sync for Borrowed<'_, T> {}
+}
+
+const LOCK_BIT: usize = !(usize::MAX >> 1); //currently locked
+const COMP_BIT: usize = !(usize::MAX >> 2) & !LOCK_BIT; //completed
 const CNT_MASK: usize = !(LOCK_BIT | COMP_BIT);
 impl<T: ?Sized> CountCell<T> {
 
+    /// Starts a new run locked `value` times. Panics unless the cell is `Completed`.
     pub fn reset(&self, value: usize) {
-        match self.borrow.compare_exchange(COMP_BIT, value, Ordering::Release, Ordering::Relaxed) {
-            Err(v) => panic!("attempt to reset non completed counter: {}", v),
-            _ => {}
+        if let Err(v) = self.borrow.compare_exchange(COMP_BIT, value, RELEASE, RELAXED) {
+            panic!("attempt to reset non completed counter: {}", v)
         }
     }
 
-    pub fn lock(&self) { //locks the counter so the task cannot be started w/o unlocking it first
-        let new = self.borrow.fetch_add(1, Ordering::Acquire) + 1;
+    /**
+     Locks the cell once more, so the value can't be taken without unlocking it first.
+     A `Taken` or `Completed` cell keeps the lock until it is unlocked, which has to happen before the next `reset`.
+    */
+    pub fn lock(&self) {
+        let new = self.borrow.fetch_add(1, ACQUIRE) + 1;
 
         if new == COMP_BIT {
-            self.borrow.fetch_sub(1, Ordering::AcqRel);
+            self.borrow.fetch_sub(1, ACQ_REL);
             panic!("failed to acquire lock: too many locks");
         }
     }
 
-    pub fn unlock(&self) -> bool { //unlocks the counter and returns true if task is fully unlocked
-        let old = self.borrow.fetch_sub(1, Ordering::Release);
+    /// Unlocks the cell once, returns whether the value can be taken now. Panics if the cell isn't locked.
+    pub fn unlock(&self) -> bool {
+        let old = self.borrow.fetch_sub(1, RELEASE);
 
         if old & CNT_MASK == 0 {
-            self.borrow.fetch_add(1, Ordering::AcqRel);
+            self.borrow.fetch_add(1, ACQ_REL);
             panic!("failed to release the lock: lock underflow")
         }
 
         old == 1
     }
 
-    // locks task forever so that it cannot be unlocked (only works if we have no locks atm)
-    pub fn take(&self) -> Option<CountRef<T>> {
+    /**
+     Ends the run of a cell a failed run left behind, e.g. `Locked` because the task it waited for panicked,
+     so it can be reset again. Drops its locks, a `Taken` cell is left as it is until its borrow is dropped.
+    */
+    pub fn complete(&self) {
+        let _ = self.borrow.fetch_update(RELEASE, RELAXED, |value| (value & LOCK_BIT == 0).then_some(COMP_BIT));
+    }
+
+    /// Borrows the value if the cell is `Ready`, the cell is `Taken` until the borrow is dropped.
+    pub fn take(&self) -> Option<CountRef<'_, T>> {
         match self.borrow.compare_exchange(
             0,
             LOCK_BIT,
-            Ordering::AcqRel,
-            Ordering::Relaxed) {
+            ACQ_REL,
+            RELAXED) {
+                //SAFETY: the counter is taken now, so this is the only borrow until the CountRef drops
                 Ok(_) => Some(CountRef {
                     borrow: &self.borrow,
-                    value: unsafe { &mut *self.value.get() }
+                    value: unsafe { self.value.borrow() }
                 }),
                 Err(_) => None
         }
     }
+
+    /// Returns the current state, which other threads may change right away.
+    pub fn state(&self) -> CellState {
+        let value = self.borrow.load(ACQUIRE);
+        let locks = value & CNT_MASK;
+
+        match value & !CNT_MASK {
+            LOCK_BIT => CellState::Taken { locks },
+            COMP_BIT => CellState::Completed { locks },
+            _ if locks == 0 => CellState::Ready,
+            _ => CellState::Locked(locks)
+        }
+    }
 }
 
 impl<T> CountCell<T> {
 
+    /// Creates a `Completed` cell, it has to be reset before its value can be taken.
     pub fn new(value: T) -> Self {
-        Self { value: UnsafeCell::new(value), borrow: AtomicUsize::new(COMP_BIT) }
+        Self { value: Storage::new(value), borrow: AtomicUsize::new(COMP_BIT) }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> Debug for CountCell<T> {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CountCell").field("state", &self.state()).finish_non_exhaustive()
     }
 }
 
@@ -82,7 +338,7 @@ impl<'a, T: ?Sized> Deref for CountRef<'a, T> {
 
     #[inline]
     fn deref(&self) -> &Self::Target {
-        self.value
+        self.value.get()
     }
 }
 
@@ -90,7 +346,7 @@ impl<'a, T: ?Sized> DerefMut for CountRef<'a, T> {
 
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
-        self.value
+        self.value.get_mut()
     }
 }
 
@@ -98,15 +354,262 @@ impl<'a, T: ?Sized> Drop for CountRef<'a, T> {
 
     #[inline]
     fn drop(&mut self) {
-        self.borrow.fetch_xor(LOCK_BIT | COMP_BIT, Ordering::AcqRel);
+        self.value.release();
+        self.borrow.fetch_xor(LOCK_BIT | COMP_BIT, ACQ_REL);
     }
 }
 
-//SAFETY: this cell is mutable borrow only
-unsafe impl<T: ?Sized> Sync for CountCell<T> {}
+/**
+ Models of the counter run by loom with every interleaving and every value the orderings let the atomics read,
+ checking the borrows of the value for data races. Only built with loom, run them in release mode:
+ `RUSTFLAGS="--cfg loom" cargo test --release --lib interleavings`.
+*/
+#[cfg(all(test, loom))]
+mod interleavings {
+    use super::*;
+    use loom::cell::UnsafeCell;
+    use loom::sync::Arc;
+    use loom::thread;
+
+    #[test]
+    fn unlock_take() {
+        loom::model(|| {
+            let cell = Arc::new(CountCell::new(0));
+            let written = Arc::new([UnsafeCell::new(false), UnsafeCell::new(false)]);
+            cell.reset(2);
+
+            let threads: Vec<_> = (0..2).map(|idx| {
+                let (cell, written) = (cell.clone(), written.clone());
+
+                thread::spawn(move || {
+                    //SAFETY: every thread writes its own flag, loom reports reads not ordered after the write
+                    written[idx].with_mut(|written| unsafe { *written = true });
+
+                    if cell.unlock() {
+                        let mut runs = cell.take().expect("the last unlock takes the value");
+                        assert!(written.iter().all(|written| written.with(|written| unsafe { *written })), "the taker sees the writes of every unlock");
+                        *runs += 1;
+                    }
+                })
+            }).collect();
+
+            threads.into_iter().for_each(|thread| thread.join().unwrap());
+            assert_eq!(cell.state(), CellState::Completed { locks: 0 });
+        });
+    }
+
+    #[test]
+    fn reset_take() {
+        loom::model(|| {
+            let cell = Arc::new(CountCell::new(0));
+            cell.reset(0);
+
+            let first = {
+                let cell = cell.clone();
+                thread::spawn(move || *cell.take().unwrap() = 1)
+            };
+
+            //threads act only in the state they wait for instead of spinning, loom also runs them in that order
+            let reset = {
+                let cell = cell.clone();
+                thread::spawn(move || if cell.state() == (CellState::Completed { locks: 0 }) {
+                    cell.reset(1);
+                })
+            };
+
+            //the reset continues the release sequence of the first borrow, so the next one sees its write
+            let next = {
+                let cell = cell.clone();
+                thread::spawn(move || if cell.state() == CellState::Locked(1) {
+                    assert!(cell.unlock());
+                    assert_eq!(*cell.take().unwrap(), 1);
+                })
+            };
+
+            vec![first, reset, next].into_iter().for_each(|thread| thread.join().unwrap());
+        });
+    }
+}
 
+#[cfg(test)]
 mod tests {
     use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[derive(Clone, Copy, Debug)]
+    enum Op {
+        Reset(usize),
+        Lock,
+        Unlock,
+        Take,
+        Release
+    }
+
+    //reference model of the documented state machine, None if the operation panics
+    fn model(state: CellState, op: Op) -> Option<CellState> {
+        use self::CellState::*;
+
+        match (op, state) {
+            (Op::Reset(0), Completed { locks: 0 }) => Some(Ready),
+            (Op::Reset(count), Completed { locks: 0 }) => Some(Locked(count)),
+            (Op::Reset(_), _) => None,
+            (Op::Lock, Ready) => Some(Locked(1)),
+            (Op::Lock, Locked(count)) => Some(Locked(count + 1)),
+            (Op::Lock, Taken { locks }) => Some(Taken { locks: locks + 1 }),
+            (Op::Lock, Completed { locks }) => Some(Completed { locks: locks + 1 }),
+            (Op::Unlock, Locked(1)) => Some(Ready),
+            (Op::Unlock, Locked(count)) => Some(Locked(count - 1)),
+            (Op::Unlock, Ready | Taken { locks: 0 } | Completed { locks: 0 }) => None,
+            (Op::Unlock, Taken { locks }) => Some(Taken { locks: locks - 1 }),
+            (Op::Unlock, Completed { locks }) => Some(Completed { locks: locks - 1 }),
+            (Op::Take, Ready) => Some(Taken { locks: 0 }),
+            (Op::Take, state) => Some(state),
+            (Op::Release, Taken { locks }) => Some(Completed { locks }),
+            (Op::Release, state) => unreachable!("nothing to release in {:?}", state)
+        }
+    }
+
+    fn apply<'a>(cell: &'a CountCell<()>, taken: &mut Option<CountRef<'a, ()>>, op: Op) {
+        match op {
+            Op::Reset(count) => cell.reset(count),
+            Op::Lock => cell.lock(),
+            Op::Unlock => assert_eq!(cell.unlock(), cell.state() == CellState::Ready),
+            Op::Take => if let Some(borrow) = cell.take() {
+                assert!(taken.replace(borrow).is_none(), "value taken twice");
+            },
+            Op::Release => *taken = None
+        }
+    }
+
+    fn locks(state: CellState) -> usize {
+        match state {
+            CellState::Locked(locks) | CellState::Taken { locks } | CellState::Completed { locks } => locks,
+            CellState::Ready => 0
+        }
+    }
+
+    #[test]
+    fn transitions() {
+        const OPS: [Op; 6] = [Op::Reset(0), Op::Reset(2), Op::Lock, Op::Unlock, Op::Take, Op::Release];
+
+        //every state with up to 2 locks, reached by replaying the path to it on a new cell
+        let mut pending = vec![(CellState::Completed { locks: 0 }, Vec::new())];
+        let mut visited = vec![CellState::Completed { locks: 0 }];
+
+        while let Some((state, path)) = pending.pop() {
+            for op in OPS {
+                if matches!(op, Op::Release) && !matches!(state, CellState::Taken { .. }) {
+                    continue;
+                }
+
+                let cell = CountCell::new(());
+                let mut taken = None;
+                path.iter().for_each(|op| apply(&cell, &mut taken, *op));
+                assert_eq!(cell.state(), state);
+
+                let expected = model(state, op);
+                let result = panic::catch_unwind(AssertUnwindSafe(|| apply(&cell, &mut taken, op)));
+                assert_eq!(result.is_ok(), expected.is_some(), "{:?} in {:?}", op, state);
+
+                let next = expected.unwrap_or(state);
+                assert_eq!(cell.state(), next, "{:?} in {:?}, a panic leaves the cell unchanged", op, state);
+
+                if locks(next) <= 2 && !visited.contains(&next) {
+                    visited.push(next);
+                    pending.push((next, path.iter().copied().chain([op]).collect()));
+                }
+            }
+        }
+
+        assert_eq!(visited.len(), 9);
+    }
+
+    #[test]
+    fn publication() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::thread;
+
+        const THREADS: usize = 4;
+        const RUNS: usize = 200;
+
+        let cell = CountCell::new(0);
+        let written: [AtomicBool; THREADS] = Default::default();
+
+        for _ in 0..RUNS {
+            cell.reset(THREADS);
+
+            thread::scope(|scope| for idx in 0..THREADS {
+                let (cell, written) = (&cell, &written);
+
+                scope.spawn(move || {
+                    //relaxed, so only the orderings of the cell make the writes visible
+                    written[idx].store(true, Ordering::Relaxed);
+
+                    if cell.unlock() {
+                        let mut runs = cell.take().expect("the last unlock takes the value");
+                        assert!(written.iter().all(|written| written.load(Ordering::Relaxed)), "the taker sees the writes of every unlock");
+                        *runs += 1;
+                    }
+                });
+            });
+
+            written.iter().for_each(|written| written.store(false, Ordering::Relaxed));
+        }
+
+        assert_eq!(cell.into_inner(), RUNS);
+    }
+
+    #[test]
+    fn take_while_completed() {
+        let cell = CountCell::new(5);
+
+        assert!(cell.take().is_none(), "a new cell has to be reset first");
+        assert_eq!(cell.state(), CellState::Completed { locks: 0 });
+
+        cell.reset(0);
+        *cell.take().unwrap() += 1;
+        assert!(cell.take().is_none(), "the value is taken once per run");
+        assert_eq!(cell.into_inner(), 6);
+    }
+
+    #[test]
+    fn lock_after_take() {
+        let cell = CountCell::new(());
+
+        cell.reset(0);
+        let borrow = cell.take().unwrap();
+        cell.lock();
+        assert_eq!(cell.state(), CellState::Taken { locks: 1 });
+
+        drop(borrow);
+        assert_eq!(cell.state(), CellState::Completed { locks: 1 });
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| cell.reset(0))).is_err(), "the lock has to be released first");
+
+        assert!(!cell.unlock(), "the value isn't available until the next reset");
+        cell.reset(0);
+        assert_eq!(cell.state(), CellState::Ready);
+        assert_eq!(format!("{:?}", cell), "CountCell { state: Ready, .. }");
+    }
+
+    #[test]
+    fn complete() {
+        let cell = CountCell::new(());
+
+        cell.reset(2);
+        cell.complete();
+        assert_eq!(cell.state(), CellState::Completed { locks: 0 });
+
+        cell.reset(0);
+        let borrow = cell.take().unwrap();
+        cell.lock();
+        cell.complete();
+        assert_eq!(cell.state(), CellState::Taken { locks: 1 }, "the borrow keeps the value");
+
+        drop(borrow);
+        cell.complete();
+        cell.reset(0);
+        assert_eq!(cell.state(), CellState::Ready);
+    }
 
     #[test]
     #[should_panic(expected = "attempt to reset non completed counter: 2")]
@@ -137,6 +640,14 @@ mod tests {
         assert!(cell.take().is_some(), "cell does not want to give up the lock >/<");
     }
 
+    #[test]
+    fn auto_traits() {
+        fn send_sync<T: Send + Sync>() {}
+
+        send_sync::<CountCell<Vec<u32>>>();
+        send_sync::<CountRef<'static, Vec<u32>>>();
+    }
+
     #[test]
     fn unlock() {
         let cell = CountCell::new(());
@@ -0,0 +1,183 @@
+use super::task::TaskId;
+use super::version::GraphVersion;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex, PoisonError, TryLockError};
+
+/**
+ Tasks completed by a run so far and the snapshots they saved with `TaskContext::save`, by task index.
+ Handed to the sink of `InterlockExecutor::checkpoint` after every completed task; after a crash, a graph built
+ the same way continues the run with `InterlockExecutor::resume_from_checkpoint`. Serializable with the `serde` feature.
+*/
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint {
+    completed: BTreeSet<usize>,
+    snapshots: BTreeMap<usize, Vec<u8>>,
+    /// Non-idempotent tasks that started, see `InterlockBuilder::non_idempotent`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    started: BTreeSet<usize>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    version: Option<GraphVersion>
+}
+
+impl Checkpoint {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_completed(&self, task: TaskId) -> bool {
+        self.completed.contains(&task.id())
+    }
+
+    /// Returns the indices of the completed tasks, see `TaskId::id`.
+    pub fn completed(&self) -> impl Iterator<Item=usize> + '_ {
+        self.completed.iter().copied()
+    }
+
+    /// Returns the snapshot `task` saved in its last execution, e.g. to restore its state before resuming.
+    pub fn snapshot(&self, task: TaskId) -> Option<&[u8]> {
+        self.snapshots.get(&task.id()).map(Vec::as_slice)
+    }
+
+    /// Returns whether `task` is non-idempotent and started but didn't complete, so a resumed run stops its branch.
+    pub fn is_interrupted(&self, task: TaskId) -> bool {
+        self.is_interrupted_at(task.id())
+    }
+
+    /// Returns the indices of the interrupted tasks, see `is_interrupted`.
+    pub fn interrupted(&self) -> impl Iterator<Item=usize> + '_ {
+        self.started.difference(&self.completed).copied()
+    }
+
+    /// Returns the structure of the graph the checkpoint was taken from, `None` for checkpoints created with `new`.
+    pub fn version(&self) -> Option<&GraphVersion> {
+        self.version.as_ref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.completed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.completed.is_empty()
+    }
+
+    pub(crate) fn contains(&self, task: usize) -> bool {
+        self.completed.contains(&task)
+    }
+
+    pub(crate) fn is_interrupted_at(&self, task: usize) -> bool {
+        self.started.contains(&task) && !self.completed.contains(&task)
+    }
+}
+
+/// Persists a checkpoint, e.g. by writing it to disk.
+pub(crate) type Sink<'a> = dyn Fn(&Checkpoint) + Send + Sync + 'a;
+
+/**
+ Checkpoint of the current run and where it is persisted to. Sinks execute without holding the checkpoint,
+ on copies numbered in the order they were recorded: only the latest copy is persisted, older ones are dropped.
+*/
+pub(crate) struct Checkpoints<'a> {
+    sink: Arc<Sink<'a>>,
+    version: GraphVersion,
+    current: Mutex<(u64, Checkpoint)>,
+    //latest copy no sink has seen yet
+    pending: Mutex<Option<(u64, Checkpoint)>>,
+    //number of the last persisted copy, held while a sink executes
+    persisted: Mutex<u64>
+}
+
+impl<'a> Checkpoints<'a> {
+
+    pub fn new(sink: Arc<Sink<'a>>, version: GraphVersion) -> Self {
+        Self { sink, version, current: Mutex::new((0, Checkpoint::new())), pending: Mutex::new(None), persisted: Mutex::new(0) }
+    }
+
+    /// Returns the same persistence without a recorded run.
+    pub fn duplicate(&self) -> Self {
+        Self::new(self.sink.clone(), self.version.clone())
+    }
+
+    /// Starts recording a run, from scratch or from a restored checkpoint.
+    pub fn reset(&self, mut checkpoint: Checkpoint) {
+        checkpoint.version = Some(self.version.clone());
+        self.current.lock().unwrap_or_else(PoisonError::into_inner).1 = checkpoint;
+        *self.pending.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// Records that the non-idempotent `task` started and persists the checkpoint before it executes.
+    pub fn start(&self, task: usize) {
+        self.record(|current| { current.started.insert(task); });
+        self.persist(true);
+    }
+
+    /**
+     Records that `task` completed and persists the checkpoint, sinks see one checkpoint at a time.
+     While another task's checkpoint is being persisted, the copy is left to that task instead of waiting for it.
+    */
+    pub fn complete(&self, task: usize, snapshot: Option<Vec<u8>>) {
+        self.record(|current| {
+            current.completed.insert(task);
+
+            if let Some(snapshot) = snapshot {
+                current.snapshots.insert(task, snapshot);
+            }
+        });
+
+        self.persist(false);
+    }
+
+    /// Updates the checkpoint and leaves a copy for the next sink.
+    fn record(&self, update: impl FnOnce(&mut Checkpoint)) {
+        let copy = {
+            let mut current = self.current.lock().unwrap_or_else(PoisonError::into_inner);
+            update(&mut current.1);
+            current.0 += 1;
+            (current.0, current.1.clone())
+        };
+
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        if pending.as_ref().is_none_or(|(number, _)| *number < copy.0) {
+            *pending = Some(copy);
+        }
+    }
+
+    /// Persists copies until none is left, `wait` waits for a sink that is executing instead of leaving the copies to it.
+    fn persist(&self, mut wait: bool) {
+        loop {
+            let persisted = match wait {
+                true => Ok(self.persisted.lock().unwrap_or_else(PoisonError::into_inner)),
+                false => self.persisted.try_lock()
+            };
+
+            let mut persisted = match persisted {
+                Ok(persisted) => persisted,
+                Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+                Err(TryLockError::WouldBlock) => return
+            };
+
+            while let Some((number, checkpoint)) = self.take() {
+                if number > *persisted {
+                    (self.sink)(&checkpoint);
+                    *persisted = number;
+                }
+            }
+
+            drop(persisted);
+
+            //a copy left while the sink held the lock would otherwise wait for the next task
+            if self.pending.lock().unwrap_or_else(PoisonError::into_inner).is_none() {
+                return;
+            }
+
+            wait = false;
+        }
+    }
+
+    /// Takes the latest copy, the sink executes without holding it.
+    fn take(&self) -> Option<(u64, Checkpoint)> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner).take()
+    }
+}
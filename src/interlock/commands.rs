@@ -0,0 +1,51 @@
+use std::sync::{Mutex, PoisonError};
+
+/**
+ Mutations of the run data deferred by tasks, which only see `&T` during a run.
+ Tasks push commands while they execute and `InterlockExecutor::run_deferred` applies them with `&mut T`
+ after the last task finished, e.g. to spawn and despawn entities of a world.
+*/
+pub struct CommandBuffer<C> {
+    commands: Mutex<Vec<C>>
+}
+
+impl<C> CommandBuffer<C> {
+
+    pub fn new() -> Self {
+        Self { commands: Mutex::new(Vec::new()) }
+    }
+
+    /// Defers `command`, commands of a task are applied in the order it pushed them.
+    pub fn push(&self, command: C) {
+        self.lock().push(command);
+    }
+
+    pub fn extend(&self, commands: impl IntoIterator<Item=C>) {
+        self.lock().extend(commands);
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Removes all pending commands in the order they were pushed.
+    pub fn take(&self) -> Vec<C> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<C>> {
+        //a task panicking while pushing leaves the commands intact
+        self.commands.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<C> Default for CommandBuffer<C> {
+
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,142 @@
+use super::task::TaskId;
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// How a task execution ended.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum TaskStatus {
+    /// The task ran and changed what it writes.
+    Finished,
+    /// The task ran and declared its outputs unchanged, see `TaskContext::unchanged`.
+    Unchanged,
+    /// The task was memoized and its inputs didn't change, so it didn't run.
+    Skipped
+}
+
+/// Event of a task finishing during a run, see `InterlockExecutor::completions`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct TaskCompleted {
+    pub task: TaskId,
+    /// Time from the start to the end of the task, including time it was suspended.
+    pub duration: Duration,
+    pub status: TaskStatus
+}
+
+#[derive(Default)]
+struct State {
+    events: VecDeque<TaskCompleted>,
+    waker: Option<Waker>,
+    closed: bool
+}
+
+#[derive(Default)]
+struct Channel {
+    state: Mutex<State>
+}
+
+/// Executor side of a completion stream, closes it when the executor is dropped.
+pub struct Sender {
+    channel: Arc<Channel>
+}
+
+impl Sender {
+
+    pub fn is_subscribed(&self) -> bool {
+        Arc::strong_count(&self.channel) > 1
+    }
+
+    pub fn send(&self, event: TaskCompleted) {
+        let waker = {
+            let mut state = self.channel.state.lock().unwrap();
+            state.events.push_back(event);
+            state.waker.take()
+        };
+
+        //woken without the lock, a waker polling right away would wait for it otherwise
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for Sender {
+
+    fn drop(&mut self) {
+        let waker = {
+            let mut state = self.channel.state.lock().unwrap();
+            state.closed = true;
+            state.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/**
+ Asynchronous stream of the tasks finishing in the runs of an executor, in the order they finish.
+ It ends once the executor is dropped and all events were taken. Implements `futures_core::Stream`,
+ so it works with `StreamExt`, `select!` and the other combinators of any async runtime.
+*/
+pub struct Completions {
+    channel: Arc<Channel>
+}
+
+pub(crate) fn channel() -> (Sender, Completions) {
+    let channel = Arc::new(Channel::default());
+    (Sender { channel: channel.clone() }, Completions { channel })
+}
+
+impl Completions {
+
+    fn poll(&self, cx: &mut Context<'_>) -> Poll<Option<TaskCompleted>> {
+        let mut state = self.channel.state.lock().unwrap();
+
+        match state.events.pop_front() {
+            Some(event) => Poll::Ready(Some(event)),
+            None if state.closed => Poll::Ready(None),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Returns the next event without waiting, if there is one.
+    pub fn try_next(&mut self) -> Option<TaskCompleted> {
+        self.channel.state.lock().unwrap().events.pop_front()
+    }
+
+    /// Waits for the next event, returns `None` once the stream ended, like `StreamExt::next` without the import.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Next<'_> {
+        Next { completions: self }
+    }
+}
+
+/// Future returned by `Completions::next`.
+pub struct Next<'a> {
+    completions: &'a mut Completions
+}
+
+impl<'a> Future for Next<'a> {
+    type Output = Option<TaskCompleted>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.completions.poll(cx)
+    }
+}
+
+impl Stream for Completions {
+    type Item = TaskCompleted;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll(cx)
+    }
+}
@@ -1,55 +1,451 @@
-use super::task::{TaskRef, Task};
-use rayon::join;
+use super::checkpoint::Checkpoints;
+use super::resource::{Parent, ResourceTable};
+use super::run::{Changes, TaskContext};
+use super::task::Task;
+use super::timestep::Tick;
+use super::pool::{Pools, Seeding};
+use super::remote::{Remote, RemoteTask};
+use super::scratch::Scratch;
+use super::semaphore::{Exhausted, Permits, Semaphore};
+use super::shutdown::Drain;
+use super::signal::Signal;
+use super::spawn::{Job, Latch, Pending, Spawn};
+use rayon::{join, ScopeFifo};
+use std::any::Any;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
 
-pub struct Context<'r, 'task, T> {
+/**
+ Per-run state of a task: its counter and access to its body.
+ A plain run uses the counters and bodies stored in the tasks, shared runs use their own counters.
+*/
+pub trait Slot<'r, T, R>: Sync {
+    type Borrow: Send;
+
+    fn reset(&self, count: usize);
+    /// Completes the slot after a failed run, see `CountCell::complete`.
+    fn complete(&self);
+    fn lock(&self);
+    fn unlock(&self) -> bool;
+    fn take(&'r self) -> Option<Self::Borrow>;
+    fn execute(borrow: &mut Self::Borrow, data: &T, context: &TaskContext<'_, R>);
+}
+
+/// Executor state tasks can query during a run.
+pub struct Env<'r, R> {
+    pub changes: &'r Changes,
+    pub table: &'r ResourceTable<R>,
+    pub parent: Option<&'r Parent<'r, R>>,
+    pub pools: &'r Pools,
+    /// Time slice of a task execution, see `TaskContext::should_yield`.
+    pub slice: Option<Duration>,
+    pub seeding: Seeding,
+    /// Ids of all tasks, realtime tasks first.
+    pub order: &'r [usize],
+    /// Semaphores of resources with a capacity, see `Task::permits`.
+    pub semaphores: &'r [Semaphore],
+    /// Transport of remote tasks, they execute their local body without one.
+    pub remote: Option<&'r Remote<'r>>,
+    /// Number of the run, counting from 1.
+    pub run: u64,
+    pub checkpoints: Option<&'r Checkpoints<'r>>,
+    /**
+     Tasks the run resumed from a checkpoint skips by id, empty if the run starts from scratch:
+     completed tasks and the branches of interrupted non-idempotent tasks.
+    */
+    pub resumed: &'r [bool],
+    /// Tasks with a compensation that completed in this run, in order, see `InterlockBuilder::compensate`.
+    pub compensable: Mutex<Vec<usize>>,
+    /// Position of the run within a fixed timestep update, see `TaskContext::tick`.
+    pub tick: Option<Tick>,
+    /// Shutdown state, tasks don't start once a shutdown was requested, see `ShutdownHandle`.
+    pub drain: Option<&'r Drain>,
+    /// Temporary memory of the tasks, see `TaskContext::scratch`.
+    pub scratch: &'r Scratch,
+    /// Whether tasks are only scheduled, not executed, see `InterlockExecutor::warm_up`.
+    pub dry: bool,
+    /// Data of this run only, see `InterlockExecutor::run_with_extra`.
+    pub extra: Option<&'r (dyn Any + Sync)>,
+    #[cfg(feature = "inspector")]
+    pub stats: &'r super::stats::Stats,
+    /// Counter of bytes allocated by the current thread, see `InterlockExecutor::set_allocation_counter`.
+    #[cfg(feature = "inspector")]
+    pub allocations: Option<&'r (dyn Fn() -> u64 + Send + Sync)>,
+    #[cfg(feature = "async")]
+    pub subscribers: &'r [super::completions::Sender]
+}
+
+/**
+ Storage of the tasks or slots of a run, indexed by task id. Keeps the scheduling logic independent of the layout,
+ e.g. a slice, a fixed-size array or separately allocated chunks of a large graph.
+*/
+pub trait TaskStore<I>: Sync {
+    fn len(&self) -> usize;
+    fn get(&self, id: usize) -> &I;
+}
+
+impl<I: Sync> TaskStore<I> for [I] {
+
+    fn len(&self) -> usize {
+        <[I]>::len(self)
+    }
+
+    fn get(&self, id: usize) -> &I {
+        &self[id]
+    }
+}
+
+impl<I: Sync> TaskStore<I> for Vec<I> {
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn get(&self, id: usize) -> &I {
+        &self[id]
+    }
+}
+
+impl<I: Sync, const C: usize> TaskStore<I> for [I; C] {
+
+    fn len(&self) -> usize {
+        C
+    }
+
+    fn get(&self, id: usize) -> &I {
+        &self[id]
+    }
+}
+
+/// Chunks of the same length, except for the last one, which may be shorter. Runs panic on any other layout.
+impl<I: Sync> TaskStore<I> for [Vec<I>] {
+
+    //runs take the length first, which keeps `get` a division
+    fn len(&self) -> usize {
+        let chunk = self.first().map_or(0, Vec::len);
+        let uniform = self.split_last().is_none_or(|(last, rest)| rest.iter().all(|c| c.len() == chunk) && last.len() <= chunk);
+        assert!(uniform && (chunk > 0 || self.len() <= 1), "can't store tasks in chunks of different lengths, only the last one may be shorter");
+
+        self.iter().map(Vec::len).sum()
+    }
+
+    fn get(&self, id: usize) -> &I {
+        let chunk = self[0].len();
+        &self[id / chunk][id % chunk]
+    }
+}
+
+pub struct Context<'r, 'task, T, R, S, N, TS: ?Sized = [Task<'task, T, R, N>], SS: ?Sized = [S]> {
     data: &'r T,
-    tasks: &'r Vec<Task<'task, T>>
+    tasks: &'r TS,
+    slots: &'r SS,
+    env: Env<'r, R>,
+    /// Tasks of a run on a pool waiting for a permit, created when the first one is parked, see `Context::park`.
+    parked: OnceLock<Arc<Latch>>,
+    types: PhantomData<(&'r S, &'r Task<'task, T, R, N>)>
 }
 
-impl<'r, 'task, T: Sync> Context<'r, 'task, T> {
-    pub fn new(data: &'r T, tasks: &'r Vec<Task<'task, T>>) -> Self {
-        tasks.iter().for_each(|task| task.init());
-        Self { data, tasks }
+/**
+ Panics if the initial count of a task differs from the number of unlocks it receives in a run, which would make
+ the run hang or execute the task twice. Every task locking another one also unlocks it, so the expected count
+ is the number of unlocks minus the number of locks.
+*/
+#[cfg(debug_assertions)]
+pub fn check_initial_counts<'task, T, R, N>(tasks: &(impl TaskStore<Task<'task, T, R, N>> + ?Sized)) {
+    let mut expected = vec![0isize; tasks.len()];
+    let tasks = (0..tasks.len()).map(|id| tasks.get(id));
+
+    for task in tasks.clone() {
+        task.unlockable_deps().iter().for_each(|other| expected[other.id()] += 1);
+        task.lockable_deps().iter().for_each(|other| expected[other.id()] -= 1);
+    }
+
+    let mismatches: Vec<_> = tasks
+        .zip(expected)
+        .filter(|(task, expected)| task.initial_count() as isize != *expected)
+        .map(|(task, expected)| {
+            let outcome = if task.initial_count() as isize > expected { "is stuck" } else { "runs twice" };
+            format!("{} starts at {} instead of {} and {}", task.id().id(), task.initial_count(), expected, outcome)
+        })
+        .collect();
+
+    assert!(mismatches.is_empty(), "initial counts out of sync with the graph: {}", mismatches.join(", "));
+}
+
+impl<'r, 'task, T: Sync, R: Eq + Hash + Sync, S: Slot<'r, T, R>, N: Sync, TS, SS> Context<'r, 'task, T, R, S, N, TS, SS>
+    where TS: TaskStore<Task<'task, T, R, N>> + ?Sized, SS: TaskStore<S> + ?Sized {
+    pub fn new(data: &'r T, tasks: &'r TS, slots: &'r SS, env: Env<'r, R>) -> Self {
+        assert_eq!(tasks.len(), slots.len(), "a slot for every task");
+        (0..tasks.len()).for_each(|id| slots.get(id).reset(tasks.get(id).initial_count()));
+        Self { data, tasks, slots, env, parked: OnceLock::new(), types: PhantomData }
+    }
+
+    /// Takes the tasks with a compensation that completed so far, in order of completion.
+    pub fn take_compensable(&self) -> Vec<usize> {
+        std::mem::take(&mut *self.env.compensable.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn lock(&self, id: usize) {
+        let locks = self.tasks.get(id).lockable_deps();
+
+        #[cfg(feature = "inspector")]
+        self.env.stats.count_locks(locks.len());
+
+        locks.iter().for_each(|task| self.slots.get(task.id()).lock());
+    }
+
+    fn permits(&self, id: usize) -> Permits<'r> {
+        Permits::new(self.env.semaphores, self.tasks.get(id).permits())
+    }
+
+    /**
+     Takes the remaining permits of a task on a pool and executes it, returns the borrow unless the task was parked.
+     A task waiting for a permit mustn't block its worker: the task holding the permit may be lower on the stack
+     of the same worker, e.g. when it yields or joins, and would never resume. It is parked instead, see `park`.
+    */
+    fn execute(&self, id: usize, mut borrow: S::Borrow, mut permits: Permits<'r>) -> Option<S::Borrow> {
+        while let Some(semaphore) = permits.next() {
+            match semaphore.try_acquire() {
+                Ok(()) => permits.taken(),
+                Err(exhausted) => {
+                    self.park(id, borrow, permits, exhausted);
+                    return None;
+                }
+            }
+        }
+
+        self.execute_placed(id, &mut borrow);
+        Some(borrow)
+    }
+
+    /**
+     Parks a task of a run on a pool until a permit is released, with the permits it already holds. The task releasing
+     the permit resumes it on its own thread, with the tasks it unlocks, which may start in another order than
+     `Seeding::Ordered` would. The run waits for every parked task, the permit may be held by another shared run.
+    */
+    fn park(&self, id: usize, borrow: S::Borrow, mut permits: Permits<'r>, exhausted: Exhausted<'_>) {
+        let pending = self.parked.get_or_init(Arc::default).start();
+
+        exhausted.park(erase(move || {
+            permits.taken();
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                if let Some(_borrow) = self.execute(id, borrow, permits) {
+                    self.run_iterator(self.unlock(id));
+                }
+            }));
+
+            if let Err(payload) = result {
+                pending.panic(payload);
+            }
+        }));
+    }
+
+    /// Executes a task on the pool it is placed on.
+    fn execute_placed(&self, id: usize, borrow: &mut S::Borrow) {
+        let task = self.tasks.get(id);
+        if task.is_blocking() && !self.env.pools.is_dedicated(id) {
+            return self.env.pools.expand(|| self.execute_task(id, borrow, self.env.slice));
+        }
+
+        match self.env.pools.get(id, task.is_realtime()) {
+            Some(pool) => pool.install(|| self.execute_task(id, borrow, self.env.slice)),
+            None => self.execute_task(id, borrow, self.env.slice)
+        }
+    }
+
+    /**
+     Executes a task once the tasks of other graphs it waits for completed, then signals the tasks waiting for it.
+     Skips it after a shutdown, its dependants are unlocked as usual and skip themselves.
+     Supervised tasks that fail or are disabled count as completed, see `InterlockBuilder::supervise`.
+    */
+    fn execute_task(&self, id: usize, borrow: &mut S::Borrow, slice: Option<Duration>) {
+        if self.env.dry {
+            return;
+        }
+
+        let task = self.tasks.get(id);
+
+        let _running = match self.env.drain.map(|drain| drain.start(id)) {
+            Some(None) => return task.signals().iter().for_each(Signal::abandon),
+            running => running
+        };
+
+        task.waits().iter().for_each(Signal::wait);
+
+        match task.supervisor() {
+            Some(supervisor) if supervisor.is_disabled() => (),
+            Some(supervisor) => match panic::catch_unwind(AssertUnwindSafe(|| self.execute_restoring(id, borrow, slice))) {
+                Ok(()) => supervisor.complete(),
+                Err(payload) => supervisor.fail(payload).unwrap_or_else(|payload| panic::resume_unwind(payload))
+            },
+            None => self.execute_restoring(id, borrow, slice)
+        }
+
+        task.signals().iter().for_each(Signal::set);
+    }
+
+    /// Executes a task, restoring the resources it snapshotted if it panics, see `InterlockBuilder::transactional`.
+    fn execute_restoring(&self, id: usize, borrow: &mut S::Borrow, slice: Option<Duration>) {
+        let snapshots = self.tasks.get(id).snapshots();
+        if snapshots.is_empty() {
+            return self.execute_body(id, borrow, slice);
+        }
+
+        let restores: Vec<_> = snapshots.iter().map(|snapshot| snapshot(self.data)).collect();
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| self.execute_body(id, borrow, slice))) {
+            restores.into_iter().rev().for_each(|restore| restore(self.data));
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Executes a task until it finishes, suspended tasks get a new `slice` after the worker executed other pending work.
+    fn execute_body(&self, id: usize, borrow: &mut S::Borrow, slice: Option<Duration>) {
+        let env = &self.env;
+        let memo = self.tasks.get(id).memo().map(|memo| (memo, memo.hash(self.data)));
+
+        //clean tasks are skipped entirely, they neither start nor change anything
+        let clean = memo.is_some_and(|(memo, hash)| memo.is_clean(hash));
+        if clean || env.resumed.get(id).copied().unwrap_or_default() {
+            #[cfg(feature = "async")]
+            self.complete(id, Duration::default(), super::TaskStatus::Skipped);
+
+            if clean {
+                self.checkpoint(id, None);
+            }
+
+            return;
+        }
+
+        if !self.tasks.get(id).is_idempotent() {
+            if let Some(checkpoints) = env.checkpoints {
+                checkpoints.start(id);
+            }
+        }
+
+        let since = env.changes.start(id);
+
+        #[cfg(any(feature = "inspector", feature = "async"))]
+        let start = Instant::now();
+
+        //only unchanged if every execution said so
+        let mut unchanged = true;
+        let mut snapshot = None;
+
+        #[cfg(feature = "inspector")]
+        let mut allocated = None;
+
+        loop {
+            let deadline = slice.map(|slice| Instant::now() + slice);
+            let context = TaskContext::new(self.tasks.get(id).id(), since, env.changes, env.table, env.parent, deadline, env.tick, env.scratch)
+                .with_extra(env.extra)
+                .with_params(self.tasks.get(id).params());
+
+            //measured per slice, other tasks may execute on this thread while the task yields
+            #[cfg(feature = "inspector")]
+            let before = env.allocations.filter(|_| env.stats.is_sampled()).map(|counter| counter());
+
+            match env.remote.filter(|_| self.tasks.get(id).is_remote()) {
+                Some(remote) => self.dispatch(id, remote),
+                None => S::execute(borrow, self.data, &context)
+            }
+
+            #[cfg(feature = "inspector")]
+            {
+                let measured = before.zip(env.allocations).map(|(before, counter)| counter().saturating_sub(before));
+                allocated = add(add(allocated, measured), context.take_allocated());
+            }
+
+            unchanged &= context.is_unchanged();
+            snapshot = context.take_snapshot().or(snapshot);
+
+            if !context.is_suspended() {
+                break;
+            }
+
+            rayon::yield_now();
+        }
+
+        #[cfg(feature = "inspector")]
+        env.stats.record(id, start, Instant::now(), allocated);
+
+        #[cfg(feature = "async")]
+        self.complete(id, start.elapsed(), if unchanged { super::TaskStatus::Unchanged } else { super::TaskStatus::Finished });
+
+        if !unchanged {
+            env.changes.finish(id);
+        }
+
+        if let Some((memo, hash)) = memo {
+            memo.record(hash);
+        }
+
+        self.checkpoint(id, snapshot);
+
+        if self.tasks.get(id).compensation().is_some() {
+            env.compensable.lock().unwrap_or_else(PoisonError::into_inner).push(id);
+        }
     }
 
-    fn lock(&self, borrow: &TaskRef<'r, 'task, T>) {
-        borrow.task()
-            .lockable_deps()
-            .iter()
-            .for_each(|task| self.tasks[task.id()].lock());
+    fn checkpoint(&self, id: usize, snapshot: Option<Vec<u8>>) {
+        if let Some(checkpoints) = self.env.checkpoints {
+            checkpoints.complete(id, snapshot);
+        }
     }
 
-    fn execute(&self, borrow: &mut TaskRef<'r, 'task, T>) {
-        borrow.execute(self.data)
+    /// Executes a remote task on a worker, a failed remote execution panics like a failed local one.
+    fn dispatch(&self, id: usize, remote: &Remote<'_>) {
+        let task = RemoteTask { label: remote.labels[id].clone().expect("remote task without label"), run: self.env.run };
+
+        if let Err(err) = remote.transport.execute(&task) {
+            panic!("{}", err);
+        }
     }
 
-    fn unlock<'a>(&self, borrow: &'a TaskRef<'r, 'task, T>) -> impl Iterator<Item=TaskRef<'r, 'task, T>> + Send + 'a {
-        let tasks = self.tasks;
-        
-        borrow.task()
-            .unlockable_deps()
-            .iter()
-            .filter(move |task| tasks[task.id()].unlock())
-            .map(move |task| tasks[task.id()].take())
-            .filter(|task| task.is_some())
-            .map(|task| task.unwrap())
+    #[cfg(feature = "async")]
+    fn complete(&self, id: usize, duration: Duration, status: super::TaskStatus) {
+        let event = super::TaskCompleted { task: self.tasks.get(id).id(), duration, status };
+        self.env.subscribers.iter().for_each(|subscriber| subscriber.send(event));
     }
 
-    fn take_unlocked(&self) -> impl Iterator<Item=TaskRef<'r, 'task, T>> + Send + 'r {
-        self.tasks.iter()
-            .map(|task| task.take())
-            .filter(|task| task.is_some())
-            .map(|task| task.unwrap())
+    fn unlock(&self, id: usize) -> impl Iterator<Item=(usize, S::Borrow)> + Send + 'r {
+        let unlocks = self.tasks.get(id).unlockable_deps();
+
+        #[cfg(feature = "inspector")]
+        self.env.stats.count_unlocks(unlocks.len());
+
+        let slots = self.slots;
+        let taker = self.taker();
+
+        unlocks.iter()
+            .filter(move |task| slots.get(task.id()).unlock())
+            .filter_map(move |task| taker.take(task.id()).map(|borrow| (task.id(), borrow)))
     }
 
-    fn run_iterator(&self, mut iter: impl Iterator<Item=TaskRef<'r, 'task, T>> + Send) {
-        if let Some(mut task) = iter.next() {
-            self.lock(&task);
+    /// Returns the tasks that are ready at run start, realtime tasks first.
+    fn take_unlocked(&self) -> impl Iterator<Item=(usize, S::Borrow)> + Send + 'r {
+        let taker = self.taker();
+        self.env.order.iter().filter_map(move |&id| taker.take(id).map(|borrow| (id, borrow)))
+    }
+
+    fn taker(&self) -> Taker<'r, SS> {
+        Taker {
+            slots: self.slots,
+            #[cfg(feature = "inspector")]
+            stats: self.env.stats
+        }
+    }
+
+    fn run_iterator(&self, mut iter: impl Iterator<Item=(usize, S::Borrow)> + Send) {
+        if let Some((id, borrow)) = iter.next() {
+            self.lock(id);
 
             let tail = move || self.run_iterator(iter);
-            let head = move || {
-                self.execute(&mut task);
-                self.run_iterator(self.unlock(&task));
+            let head = move || if let Some(_borrow) = self.execute(id, borrow, self.permits(id)) {
+                self.run_iterator(self.unlock(id));
             };
 
             join(head, tail);
@@ -57,6 +453,202 @@ impl<'r, 'task, T: Sync> Context<'r, 'task, T> {
     }
 
     pub fn run(&self) {
-        self.run_iterator(self.take_unlocked())
+        let parked = Parked(&self.parked);
+
+        match self.env.seeding {
+            Seeding::Lazy => self.run_iterator(self.take_unlocked()),
+            Seeding::Ordered => self.run_ordered()
+        }
+
+        drop(parked);
+        if let Some(latch) = self.parked.get() {
+            latch.resume();
+        }
+    }
+
+    /// Runs every task as a job of a FIFO scope, the ready tasks are all taken before the first job starts.
+    fn run_ordered(&self) {
+        let ready: Vec<_> = self.take_unlocked()
+            .map(|(id, borrow)| {
+                self.lock(id);
+                (id, borrow)
+            })
+            .collect();
+
+        rayon::scope_fifo(|scope| ready.into_iter().for_each(|(id, borrow)| self.spawn_ordered(scope, id, borrow)));
+    }
+
+    fn spawn_ordered<'s>(&'s self, scope: &ScopeFifo<'s>, id: usize, borrow: S::Borrow) where 'r: 's {
+        scope.spawn_fifo(move |scope| if let Some(_borrow) = self.execute(id, borrow, self.permits(id)) {
+            for (id, borrow) in self.unlock(id) {
+                self.lock(id);
+                self.spawn_ordered(scope, id, borrow);
+            }
+        });
+    }
+
+    fn run_sequential_iterator(&self, iter: impl Iterator<Item=(usize, S::Borrow)>) {
+        for (id, mut borrow) in iter {
+            self.lock(id);
+
+            let mut permits = self.permits(id);
+            permits.acquire();
+            self.execute_task(id, &mut borrow, None);
+            drop(permits);
+
+            self.run_sequential_iterator(self.unlock(id));
+        }
+    }
+
+    /// Runs every task on the calling thread, in the order a single worker would, including realtime and distributed tasks.
+    pub fn run_sequential(&self) {
+        self.run_sequential_iterator(self.take_unlocked())
+    }
+
+    fn spawn(&'r self, id: usize, borrow: S::Borrow, spawner: &'r (dyn Spawn + Sync), latch: &Arc<Latch>) {
+        let task = Spawned { borrow: Some(borrow), permits: self.permits(id), pending: latch.start() };
+        self.acquire(id, task, spawner, latch.clone());
+    }
+
+    /**
+     Takes the remaining permits of a spawned task and then spawns it. A task waiting for a permit is parked
+     with the permits it already holds instead of blocking a thread, the task releasing the permit resumes it.
+    */
+    fn acquire(&'r self, id: usize, mut task: Spawned<'r, S::Borrow>, spawner: &'r (dyn Spawn + Sync), latch: Arc<Latch>) {
+        while let Some(semaphore) = task.permits.next() {
+            match semaphore.try_acquire() {
+                Ok(()) => task.permits.taken(),
+                Err(exhausted) => {
+                    exhausted.park(erase(move || {
+                        task.permits.taken();
+                        self.acquire(id, task, spawner, latch);
+                    }));
+
+                    return;
+                }
+            }
+        }
+
+        let job = move || {
+            let borrow = task.borrow.as_mut().unwrap();
+            let result = panic::catch_unwind(AssertUnwindSafe(|| self.execute_task(id, borrow, None)));
+            task.permits.release();
+
+            match result {
+                Ok(()) if !latch.is_panicking() => {
+                    for (id, borrow) in self.unlock(id) {
+                        self.lock(id);
+                        self.spawn(id, borrow, spawner, &latch);
+                    }
+                },
+
+                Ok(()) => {},
+                Err(payload) => latch.panic(payload)
+            }
+
+            task.borrow = None;
+        };
+
+        spawner.spawn(erase(job));
+    }
+
+    /**
+     Runs every task as a job of `spawner` and blocks until all of them finished, then resumes the first panic of a task.
+     Tasks don't yield and ignore the reserved and distributed pools.
+    */
+    pub fn run_spawned(&'r self, spawner: &'r (dyn Spawn + Sync)) {
+        struct Wait<'a>(&'a Latch);
+
+        impl Drop for Wait<'_> {
+
+            fn drop(&mut self) {
+                self.0.wait();
+            }
+        }
+
+        let latch = Arc::new(Latch::default());
+        let wait = Wait(&latch);
+
+        for (id, borrow) in self.take_unlocked() {
+            self.lock(id);
+            self.spawn(id, borrow, spawner, &latch);
+        }
+
+        drop(wait);
+        latch.resume();
+    }
+}
+
+/// Waits for the tasks parked during a run on a pool when dropped, also if the run panics.
+struct Parked<'a>(&'a OnceLock<Arc<Latch>>);
+
+impl Drop for Parked<'_> {
+
+    fn drop(&mut self) {
+        if let Some(latch) = self.0.get() {
+            latch.wait();
+        }
+    }
+}
+
+/// Takes slots, detached from the context so iterators of ready tasks can outlive the borrow of it.
+struct Taker<'r, SS: ?Sized> {
+    slots: &'r SS,
+    #[cfg(feature = "inspector")]
+    stats: &'r super::stats::Stats
+}
+
+impl<'r, SS: ?Sized> Clone for Taker<'r, SS> {
+
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'r, SS: ?Sized> Copy for Taker<'r, SS> {}
+
+impl<'r, SS: ?Sized> Taker<'r, SS> {
+
+    fn take<T, R, S: Slot<'r, T, R> + 'r>(self, id: usize) -> Option<S::Borrow> where SS: TaskStore<S> {
+        let borrow = self.slots.get(id).take();
+
+        #[cfg(feature = "inspector")]
+        if borrow.is_none() {
+            self.stats.count_failed_take();
+        }
+
+        borrow
     }
-}
\ No newline at end of file
+}
+
+/// Spawned task, the borrow is dropped and the permits are released before the job finishes, whether the job runs or not.
+struct Spawned<'r, B> {
+    borrow: Option<B>,
+    permits: Permits<'r>,
+    pending: Pending
+}
+
+impl<B> Drop for Spawned<'_, B> {
+
+    fn drop(&mut self) {
+        if self.borrow.take().is_some() {
+            self.pending.abandon();
+        }
+    }
+}
+
+#[cfg(feature = "inspector")]
+fn add(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b)
+    }
+}
+
+fn erase<'a>(job: impl FnOnce() + Send + 'a) -> Job {
+    let job: Box<dyn FnOnce() + Send + 'a> = Box::new(job);
+
+    //SAFETY: run_spawned and run don't return before every job finished or was dropped, and jobs release everything
+    //they borrow before they finish, so nothing borrowed is used after the borrows end
+    unsafe { std::mem::transmute(job) }
+}
@@ -0,0 +1,156 @@
+use super::InterlockExecutor;
+use super::resource::Access;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display, Formatter};
+use std::fmt;
+use std::hash::Hash;
+
+/**
+ Differences between two built graphs, see `InterlockExecutor::diff`.
+ Tasks are matched by label, unlabeled tasks and dependencies on them are not compared.
+*/
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct GraphDiff<'a, R, N = String> {
+    /// Labels of tasks only the new graph has, in its task order.
+    pub added: Vec<&'a N>,
+    /// Labels of tasks only the old graph has, in its task order.
+    pub removed: Vec<&'a N>,
+    /// Tasks of both graphs whose dependencies or accesses differ, in task order of the new graph.
+    pub changed: Vec<TaskDiff<'a, R, N>>
+}
+
+/// Differences of a task present in both graphs.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TaskDiff<'a, R, N = String> {
+    pub label: &'a N,
+    pub added_dependencies: Vec<&'a N>,
+    pub removed_dependencies: Vec<&'a N>,
+    pub added_accesses: Vec<(&'a R, Access)>,
+    pub removed_accesses: Vec<(&'a R, Access)>,
+    /// Old and new access to all resources, if it changed.
+    pub all: Option<(Option<Access>, Option<Access>)>
+}
+
+impl<'a, R, N> GraphDiff<'a, R, N> {
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Labeled task with everything the diff compares.
+struct Node<'a, R, N> {
+    label: &'a N,
+    dependencies: Vec<&'a N>,
+    accesses: Vec<(&'a R, Access)>,
+    all: Option<Access>
+}
+
+fn nodes<'a, T, R: Eq + Hash, N>(executor: &'a InterlockExecutor<'_, T, R, N>) -> Vec<Node<'a, R, N>> {
+    let table = executor.resources.table();
+    let mut accesses = table.accesses(executor.tasks.len());
+    let mut dependencies = vec![Vec::new(); executor.tasks.len()];
+
+    for task in executor.tasks.iter() {
+        if let Some(label) = task.label() {
+            for dependant in task.dependants() {
+                dependencies[dependant.id()].push(label);
+            }
+        }
+    }
+
+    executor.tasks.iter()
+        .zip(dependencies)
+        .zip(accesses.iter_mut().map(std::mem::take))
+        .filter_map(|((task, dependencies), accesses)| task.label().map(|label| Node {
+            label,
+            dependencies,
+            accesses,
+            all: table.all(task.id())
+        }))
+        .collect()
+}
+
+/// Returns the items of `new` missing in `old` and the items of `old` missing in `new`.
+fn difference<I: Copy + Eq + Hash>(old: &[I], new: &[I]) -> (Vec<I>, Vec<I>) {
+    let (old_set, new_set): (HashSet<_>, HashSet<_>) = (old.iter().collect(), new.iter().collect());
+
+    (new.iter().filter(|item| !old_set.contains(item)).copied().collect(),
+     old.iter().filter(|item| !new_set.contains(item)).copied().collect())
+}
+
+pub(crate) fn diff<'a, T, R: Eq + Hash, N: Eq + Hash>(old: &'a InterlockExecutor<'_, T, R, N>, new: &'a InterlockExecutor<'_, T, R, N>) -> GraphDiff<'a, R, N> {
+    let old = nodes(old);
+    let new = nodes(new);
+
+    let old_index: HashMap<_, _> = old.iter().map(|node| (node.label, node)).collect();
+    let new_index: HashMap<_, _> = new.iter().map(|node| (node.label, node)).collect();
+
+    let mut result = GraphDiff {
+        added: new.iter().filter(|node| !old_index.contains_key(node.label)).map(|node| node.label).collect(),
+        removed: old.iter().filter(|node| !new_index.contains_key(node.label)).map(|node| node.label).collect(),
+        changed: Vec::new()
+    };
+
+    for node in new.iter() {
+        if let Some(previous) = old_index.get(node.label) {
+            let (added_dependencies, removed_dependencies) = difference(&previous.dependencies, &node.dependencies);
+            let (added_accesses, removed_accesses) = difference(&previous.accesses, &node.accesses);
+            let all = Some((previous.all, node.all)).filter(|(old, new)| old != new);
+
+            let task = TaskDiff { label: node.label, added_dependencies, removed_dependencies, added_accesses, removed_accesses, all };
+            if !task.is_empty() {
+                result.changed.push(task);
+            }
+        }
+    }
+
+    result
+}
+
+impl<'a, R, N> TaskDiff<'a, R, N> {
+
+    pub fn is_empty(&self) -> bool {
+        self.added_dependencies.is_empty() && self.removed_dependencies.is_empty()
+            && self.added_accesses.is_empty() && self.removed_accesses.is_empty() && self.all.is_none()
+    }
+}
+
+impl<'a, R: Debug, N: Display> Display for GraphDiff<'a, R, N> {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for label in self.added.iter() {
+            writeln!(f, "+ '{}'", label)?;
+        }
+
+        for label in self.removed.iter() {
+            writeln!(f, "- '{}'", label)?;
+        }
+
+        for task in self.changed.iter() {
+            writeln!(f, "~ '{}'", task.label)?;
+
+            for dep in task.added_dependencies.iter() {
+                writeln!(f, "    + after '{}'", dep)?;
+            }
+
+            for dep in task.removed_dependencies.iter() {
+                writeln!(f, "    - after '{}'", dep)?;
+            }
+
+            for (resource, access) in task.added_accesses.iter() {
+                writeln!(f, "    + {:?} {:?}", access, resource)?;
+            }
+
+            for (resource, access) in task.removed_accesses.iter() {
+                writeln!(f, "    - {:?} {:?}", access, resource)?;
+            }
+
+            if let Some((old, new)) = task.all {
+                writeln!(f, "    ~ all: {:?} -> {:?}", old, new)?;
+            }
+        }
+
+        Ok(())
+    }
+}
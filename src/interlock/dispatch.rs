@@ -0,0 +1,65 @@
+use crate::Executable;
+use super::cell::{CountCell, CountRef};
+use super::context::Slot;
+use super::run::TaskContext;
+
+/**
+ Task bodies of a single type for `InterlockExecutor::run_dispatched`, the body of a task at the index of its id,
+ e.g. an enum with a variant per system. Bodies are called directly instead of through a `Box<dyn>`, so the compiler
+ can inline small ones, which matters in graphs of many tiny tasks. Created once and reused for every run.
+*/
+pub struct Dispatch<E> {
+    bodies: Vec<CountCell<E>>
+}
+
+impl<E> Dispatch<E> {
+
+    pub fn new(bodies: impl IntoIterator<Item=E>) -> Self {
+        Self { bodies: bodies.into_iter().map(CountCell::new).collect() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bodies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bodies.is_empty()
+    }
+
+    pub(crate) fn slots(&self) -> &[CountCell<E>] {
+        &self.bodies
+    }
+
+    pub fn into_inner(self) -> Vec<E> {
+        self.bodies.into_iter().map(CountCell::into_inner).collect()
+    }
+}
+
+impl<'r, T, R, E: Executable<T> + Send + 'r> Slot<'r, T, R> for CountCell<E> {
+    type Borrow = CountRef<'r, E>;
+
+    fn reset(&self, count: usize) {
+        CountCell::reset(self, count);
+    }
+
+    fn complete(&self) {
+        CountCell::complete(self);
+    }
+
+    fn lock(&self) {
+        CountCell::lock(self)
+    }
+
+    fn unlock(&self) -> bool {
+        CountCell::unlock(self)
+    }
+
+    fn take(&'r self) -> Option<Self::Borrow> {
+        CountCell::take(self)
+    }
+
+    #[inline]
+    fn execute(borrow: &mut Self::Borrow, data: &T, _: &TaskContext<'_, R>) {
+        borrow.run(data);
+    }
+}
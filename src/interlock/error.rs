@@ -0,0 +1,227 @@
+use super::InterlockExecutor;
+use super::resource::Access;
+use super::task::{Task, TaskId};
+use super::version::VersionMismatch;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::fmt;
+use std::hash::Hash;
+
+/// Error of an invalid graph definition, `N` is the type of the labels.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum BuildError<N = String> {
+    /// Two tasks use the same label.
+    DuplicateLabel(N),
+    /// A task depends on a label no task uses.
+    UnknownLabel { task: N, dependency: N },
+    /// Tasks depend on each other in a cycle, each task depends on the next one and the last one on the first.
+    Cycle(Vec<N>),
+    /// A transactional task snapshots a resource it neither writes nor covers by writing everything.
+    UnwrittenSnapshot(N),
+    /// A task without a label can't be matched when persisting a plan.
+    UnlabeledTask(TaskId),
+    /// A plan contains a task no body was registered for.
+    MissingTask(String),
+    /// A task of a plan refers to a task or semaphore the plan doesn't have, or its initial count doesn't match
+    /// the lock and unlock lists, e.g. because the serialized plan was edited.
+    CorruptPlan(String),
+    /// A persisted artifact was taken from a graph with a different structure.
+    VersionMismatch(VersionMismatch)
+}
+
+impl<N: Display> Display for BuildError<N> {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::DuplicateLabel(label) => write!(f, "label '{}' is used by multiple tasks", label),
+            BuildError::UnknownLabel { task, dependency } => {
+                writeln!(f, "task '{}' depends on unknown task '{}'", task, dependency)?;
+                writeln!(f, "  '{}'", task)?;
+                writeln!(f, "   | depends on")?;
+                write!(f, "  '{}'  <- no task has this label", dependency)
+            },
+
+            BuildError::Cycle(labels) => {
+                write!(f, "dependency cycle detected: ")?;
+                for label in labels.iter() {
+                    write!(f, "'{}' -> ", label)?;
+                }
+
+                match labels.first() {
+                    Some(label) => write!(f, "'{}'", label)?,
+                    None => return Ok(())
+                }
+
+                //every task depends on the next one, the arrow leads back from the last one to the first
+                for (idx, label) in labels.iter().enumerate() {
+                    match idx {
+                        0 => write!(f, "\n  +-> '{}'", label)?,
+                        _ => write!(f, "\n  |   '{}'", label)?
+                    }
+
+                    write!(f, "\n  |    | depends on")?;
+                }
+
+                write!(f, "\n  +----+")
+            },
+
+            BuildError::UnwrittenSnapshot(label) => write!(f, "task '{}' snapshots a resource it doesn't write", label),
+            BuildError::UnlabeledTask(task) => write!(f, "{:?} has no label", task),
+            BuildError::MissingTask(label) => write!(f, "no task is registered for '{}'", label),
+            BuildError::CorruptPlan(label) => write!(f, "plan of task '{}' refers to missing tasks or semaphores or is out of sync with the graph", label),
+            BuildError::VersionMismatch(mismatch) => write!(f, "{}", mismatch)
+        }
+    }
+}
+
+impl<N: Display + Debug> Error for BuildError<N> {}
+
+/**
+ Suspicious but valid pattern in a graph definition, reported by `InterlockBuilder::build_with_report`.
+ Resolved accesses are only known at run start, so they are not taken into account.
+*/
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum BuildWarning<R> {
+    /// A task accesses no resources and depends on no task, so nothing orders it against the rest of the graph.
+    Unconstrained(TaskId),
+    /**
+     A task accesses resources but none of them are shared and no task depends on it or is depended on by it.
+     Every task of a graph runs, so this is what an unreachable task amounts to: one nothing orders against the rest.
+    */
+    Disconnected(TaskId),
+    /// A task listed the same dependency more than once.
+    DuplicateDependency { task: TaskId, dependency: TaskId },
+    /// A task listed the same resource more than once, `access` is how it was listed again. A write wins over a read.
+    DuplicateAccess { task: TaskId, resource: R, access: Access },
+    /// A resource is written but no task reads it, directly or through the hierarchy.
+    WriteOnly(R),
+    /// A task depends on more than `builder::MAX_FAN_IN` tasks.
+    FanIn { task: TaskId, dependencies: usize }
+}
+
+impl<R: Debug> Display for BuildWarning<R> {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildWarning::Unconstrained(task) => write!(f, "{:?} has no accesses and no dependencies", task),
+            BuildWarning::Disconnected(task) => write!(f, "{:?} is not connected to any other task", task),
+            BuildWarning::DuplicateDependency { task, dependency } => write!(f, "{:?} depends on {:?} more than once", task, dependency),
+            BuildWarning::DuplicateAccess { task, resource, access } => write!(f, "{:?} lists resource {:?} more than once, again as {:?}", task, resource, access),
+            BuildWarning::WriteOnly(resource) => write!(f, "resource {:?} is written but never read", resource),
+            BuildWarning::FanIn { task, dependencies } => write!(f, "{:?} depends on {} tasks", task, dependencies)
+        }
+    }
+}
+
+/**
+ Warning or error displayed with the labels of the tasks it refers to and the tasks next to them in the graph,
+ instead of bare ids. Created by `InterlockExecutor::describe`.
+*/
+pub struct Described<'a, 'task, D, T, R, N> {
+    diagnostic: &'a D,
+    executor: &'a InterlockExecutor<'task, T, R, N>
+}
+
+/// Task shown by label, or by id if it has none.
+struct Name<'a, N>(TaskId, Option<&'a N>);
+
+//tasks listed for a fan-in, the rest are counted
+const LISTED: usize = 8;
+
+impl<'task, T: Sync, R: Eq + Hash, N: Display> InterlockExecutor<'task, T, R, N> {
+
+    /**
+     Returns `diagnostic` for display with the labels of the tasks it refers to, e.g. for the warnings of
+     `InterlockBuilder::build_with_report` or an error of `plan`. Tasks without a label show as their id,
+     an unlabeled task with its dependencies and dependants, and a resource written only with its writers.
+    */
+    pub fn describe<'a, D>(&'a self, diagnostic: &'a D) -> Described<'a, 'task, D, T, R, N> {
+        Described { diagnostic, executor: self }
+    }
+}
+
+impl<'a, 'task, D, T, R, N> Described<'a, 'task, D, T, R, N> {
+
+    fn name(&self, task: TaskId) -> Name<'a, N> {
+        Name(task, self.executor.tasks[task.id()].label())
+    }
+
+    fn dependencies(&self, task: TaskId) -> Vec<TaskId> {
+        self.executor.tasks[..task.id()].iter().filter(|other| other.dependants().contains(&task)).map(Task::id).collect()
+    }
+
+    fn list(&self, f: &mut Formatter<'_>, tasks: &[TaskId]) -> fmt::Result where N: Display {
+        for (idx, task) in tasks.iter().take(LISTED).enumerate() {
+            match idx {
+                0 => write!(f, "{}", self.name(*task))?,
+                _ => write!(f, ", {}", self.name(*task))?
+            }
+        }
+
+        match tasks.len().checked_sub(LISTED) {
+            Some(rest) if rest > 0 => write!(f, " and {} more", rest),
+            _ => Ok(())
+        }
+    }
+}
+
+impl<'a, 'task, T, R: Eq + Hash + Debug, N: Display> Display for Described<'a, 'task, BuildWarning<R>, T, R, N> {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.diagnostic {
+            BuildWarning::Unconstrained(task) => write!(f, "{} has no accesses and no dependencies", self.name(*task)),
+            BuildWarning::Disconnected(task) => write!(f, "{} is not connected to any other task", self.name(*task)),
+            BuildWarning::DuplicateDependency { task, dependency } => write!(f, "{} depends on {} more than once", self.name(*task), self.name(*dependency)),
+            BuildWarning::DuplicateAccess { task, resource, access } => write!(f, "{} lists resource {:?} more than once, again as {:?}", self.name(*task), resource, access),
+            BuildWarning::WriteOnly(resource) => {
+                write!(f, "resource {:?} is written but never read, written by ", resource)?;
+
+                let accesses = self.executor.resources.table().accesses(self.executor.tasks.len());
+                let writers = accesses.iter().enumerate()
+                    .filter(|(_, accesses)| accesses.iter().any(|(other, access)| *other == resource && *access == Access::Write))
+                    .map(|(id, _)| self.executor.tasks[id].id())
+                    .collect::<Vec<_>>();
+
+                self.list(f, &writers)
+            },
+
+            BuildWarning::FanIn { task, dependencies } => {
+                write!(f, "{} depends on {} tasks: ", self.name(*task), dependencies)?;
+                self.list(f, &self.dependencies(*task))
+            }
+        }
+    }
+}
+
+impl<'a, 'task, E: Display, T, R, N: Display> Display for Described<'a, 'task, BuildError<E>, T, R, N> {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.diagnostic {
+            BuildError::UnlabeledTask(task) => {
+                write!(f, "{} has no label", self.name(*task))?;
+                for dependency in self.dependencies(*task) {
+                    write!(f, "\n  {}\n   | depends on\n  {}", self.name(*task), self.name(dependency))?;
+                }
+
+                for dependant in self.executor.tasks[task.id()].dependants() {
+                    write!(f, "\n  {}\n   | depends on\n  {}", self.name(*dependant), self.name(*task))?;
+                }
+
+                Ok(())
+            },
+
+            //the others name their tasks already
+            other => write!(f, "{}", other)
+        }
+    }
+}
+
+impl<'a, N: Display> Display for Name<'a, N> {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.1 {
+            Some(label) => write!(f, "'{}'", label),
+            None => write!(f, "{:?}", self.0)
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use crate::Executable;
+
+/**
+ Graph of `N` tasks with a fixed size, for targets that can't allocate, e.g. a real-time loop on a microcontroller.
+ Built by const fns, so it can be evaluated at compile time into a `const`:
+
+ `const GRAPH: StaticGraph<3> = StaticGraph::new().writes(0, SENSORS).reads(1, SENSORS).after(2, 1);`
+
+ Tasks are referred to by index and only depend on tasks declared before them, which keeps the graph acyclic.
+ Resources are the bits of a `u64` mask: tasks writing a resource conflict with every task accessing it.
+ Only uses `core`, nothing allocates.
+*/
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct StaticGraph<const N: usize> {
+    //dependencies[task][dependency]
+    dependencies: [[bool; N]; N],
+    reads: [u64; N],
+    writes: [u64; N]
+}
+
+impl<const N: usize> StaticGraph<N> {
+
+    pub const fn new() -> Self {
+        Self { dependencies: [[false; N]; N], reads: [0; N], writes: [0; N] }
+    }
+
+    /// Makes `task` wait for `dependency`. Panics, at compile time in a const, unless `dependency` comes before `task`.
+    pub const fn after(mut self, task: usize, dependency: usize) -> Self {
+        assert!(task < N, "task out of range");
+        assert!(dependency < task, "tasks can only depend on tasks declared before them");
+        self.dependencies[task][dependency] = true;
+        self
+    }
+
+    /// Adds the resources of the `resources` mask to the ones `task` reads.
+    pub const fn reads(mut self, task: usize, resources: u64) -> Self {
+        assert!(task < N, "task out of range");
+        self.reads[task] |= resources;
+        self
+    }
+
+    /// Adds the resources of the `resources` mask to the ones `task` writes.
+    pub const fn writes(mut self, task: usize, resources: u64) -> Self {
+        assert!(task < N, "task out of range");
+        self.writes[task] |= resources;
+        self
+    }
+
+    pub const fn depends_on(&self, task: usize, dependency: usize) -> bool {
+        self.dependencies[task][dependency]
+    }
+
+    /// Returns whether `a` and `b` access a resource that at least one of them writes.
+    pub const fn conflicts(&self, a: usize, b: usize) -> bool {
+        self.writes[a] & (self.reads[b] | self.writes[b]) != 0 || self.writes[b] & self.reads[a] != 0
+    }
+
+    /// Starts a run of the graph for a custom backend, see `StaticSchedule`.
+    pub fn schedule(&self) -> StaticSchedule<'_, N> {
+        StaticSchedule::new(self)
+    }
+}
+
+impl<const N: usize> Default for StaticGraph<N> {
+
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+enum State {
+    Waiting,
+    Running,
+    Done
+}
+
+/**
+ State of a single run of a `StaticGraph`, for backends executing the tasks themselves, e.g. on the second core
+ of a microcontroller or from interrupts: `start` hands out a task that may start, the backend executes it and
+ reports it back with `complete`. Tasks handed out at the same time never conflict.
+*/
+#[derive(Clone, Debug)]
+pub struct StaticSchedule<'g, const N: usize> {
+    graph: &'g StaticGraph<N>,
+    pending: [usize; N],
+    states: [State; N],
+    done: usize
+}
+
+impl<'g, const N: usize> StaticSchedule<'g, N> {
+
+    fn new(graph: &'g StaticGraph<N>) -> Self {
+        let mut pending = [0; N];
+        for (task, dependencies) in graph.dependencies.iter().enumerate() {
+            pending[task] = dependencies.iter().filter(|dependency| **dependency).count();
+        }
+
+        Self { graph, pending, states: [State::Waiting; N], done: 0 }
+    }
+
+    /// Returns the first task in declaration order that may start and marks it as running, `None` if no task may start now.
+    pub fn start(&mut self) -> Option<usize> {
+        let graph = self.graph;
+        let states = &self.states;
+
+        let task = (0..N).find(|&task| states[task] == State::Waiting && self.pending[task] == 0
+            && (0..N).all(|other| states[other] != State::Running || !graph.conflicts(task, other)))?;
+
+        self.states[task] = State::Running;
+        Some(task)
+    }
+
+    /// Marks `task` as finished, so its dependants and the tasks conflicting with it may start. Panics if it isn't running.
+    pub fn complete(&mut self, task: usize) {
+        assert_eq!(self.states[task], State::Running, "task {} isn't running", task);
+        self.states[task] = State::Done;
+        self.done += 1;
+
+        //dependants always come after their dependencies
+        for dependant in task + 1..N {
+            if self.graph.dependencies[dependant][task] {
+                self.pending[dependant] -= 1;
+            }
+        }
+    }
+
+    /// Returns whether every task completed.
+    pub fn is_done(&self) -> bool {
+        self.done == N
+    }
+}
+
+/**
+ Fixed-size graph together with its task bodies, executing on the calling thread without allocating.
+ Runs tasks in declaration order, which always satisfies the dependencies; use `StaticGraph::schedule`
+ to execute the tasks on another backend.
+*/
+pub struct StaticInterlock<'a, T, const N: usize> {
+    graph: StaticGraph<N>,
+    tasks: [&'a mut (dyn Executable<T> + 'a); N]
+}
+
+impl<'a, T, const N: usize> StaticInterlock<'a, T, N> {
+
+    pub fn new(graph: StaticGraph<N>, tasks: [&'a mut (dyn Executable<T> + 'a); N]) -> Self {
+        Self { graph, tasks }
+    }
+
+    pub fn graph(&self) -> &StaticGraph<N> {
+        &self.graph
+    }
+}
+
+impl<'a, T, const N: usize> Executable<T> for StaticInterlock<'a, T, N> {
+
+    fn run(&mut self, data: &T) {
+        self.tasks.iter_mut().for_each(|task| task.run(data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::cell::RefCell;
+    use std::vec::Vec;
+
+    const SENSORS: u64 = 1;
+    const OUTPUT: u64 = 1 << 1;
+
+    const GRAPH: StaticGraph<4> = StaticGraph::new()
+        .writes(0, SENSORS)
+        .reads(1, SENSORS)
+        .writes(1, OUTPUT)
+        .reads(2, SENSORS)
+        .after(3, 1)
+        .after(3, 2);
+
+    #[test]
+    fn run() {
+        let log = RefCell::new(Vec::new());
+        let task = |id: usize| {
+            let log = &log;
+            move |_: &()| log.borrow_mut().push(id)
+        };
+
+        let (mut a, mut b, mut c, mut d) = (task(0), task(1), task(2), task(3));
+
+        let mut interlock = StaticInterlock::new(GRAPH, [&mut a, &mut b, &mut c, &mut d]);
+        interlock.run(&());
+        assert_eq!(*log.borrow(), [0, 1, 2, 3]);
+        assert!(interlock.graph().depends_on(3, 2));
+    }
+
+    #[test]
+    fn schedule() {
+        let mut schedule = GRAPH.schedule();
+
+        assert_eq!(schedule.start(), Some(0));
+        assert_eq!(schedule.start(), None, "readers of the sensors wait for the writer");
+
+        schedule.complete(0);
+        assert_eq!(schedule.start(), Some(1));
+        assert_eq!(schedule.start(), Some(2), "readers don't conflict");
+        assert_eq!(schedule.start(), None);
+
+        schedule.complete(2);
+        assert_eq!(schedule.start(), None, "3 still waits for 1");
+        schedule.complete(1);
+        assert_eq!(schedule.start(), Some(3));
+        schedule.complete(3);
+        assert!(schedule.is_done());
+    }
+}
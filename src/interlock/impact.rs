@@ -0,0 +1,99 @@
+use super::resource::Fairness;
+use super::task::TaskId;
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+/// Shape and simulated run time of a graph that wasn't built, see `InterlockBuilder::graph_stats`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct GraphStats {
+    /// Dependencies between tasks, including conflicts turned into dependencies by `Fairness::Fifo`.
+    pub dependencies: usize,
+    /// Pairs of tasks that can't run at the same time.
+    pub conflicts: usize,
+    /// Cost of the costliest chain of dependencies, the run time with unlimited threads and no conflicts.
+    pub critical_path: Duration,
+    /**
+     Simulated run time: whenever a thread is idle, it starts the first task in declaration order
+     whose dependencies finished and which conflicts with no running task.
+    */
+    pub makespan: Duration
+}
+
+/// Graph stats before and after splitting a resource, see `InterlockBuilder::split_impact`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct SplitImpact {
+    pub before: GraphStats,
+    pub after: GraphStats
+}
+
+impl SplitImpact {
+
+    /// Returns how much shorter the simulated run gets, zero if it doesn't.
+    pub fn saved(&self) -> Duration {
+        self.before.makespan.saturating_sub(self.after.makespan)
+    }
+}
+
+/// Derives the stats of a graph from the dependencies, the conflicts and the cost of every task, lists sorted by id.
+pub(crate) fn stats(mut dependencies: Vec<Vec<TaskId>>, mut locks: Vec<Vec<TaskId>>, costs: Vec<Duration>, fairness: Fairness, threads: usize) -> GraphStats {
+    assert!(threads > 0, "a simulation needs at least one thread");
+    let tasks = dependencies.len();
+
+    //like the builder, conflicts become dependencies of the later task
+    if fairness == Fairness::Fifo {
+        for (id, locks) in locks.iter_mut().enumerate() {
+            for earlier in locks.drain(..).filter(|lock| lock.id() < id) {
+                if let Err(idx) = dependencies[id].binary_search_by_key(&earlier.id(), TaskId::id) {
+                    dependencies[id].insert(idx, earlier);
+                }
+            }
+        }
+    }
+
+    let mut dependants = vec![Vec::new(); tasks];
+    let mut finish = vec![Duration::default(); tasks];
+
+    for (id, dependencies) in dependencies.iter().enumerate() {
+        dependencies.iter().for_each(|dependency| dependants[dependency.id()].push(id));
+        finish[id] = costs[id] + dependencies.iter().map(|dependency| finish[dependency.id()]).max().unwrap_or_default();
+    }
+
+    let mut pending: Vec<usize> = dependencies.iter().map(Vec::len).collect();
+    let mut ready: BTreeSet<usize> = (0..tasks).filter(|&id| pending[id] == 0).collect();
+    let mut running: Vec<(Duration, usize)> = Vec::new();
+    let mut now = Duration::default();
+
+    loop {
+        let startable: Vec<usize> = ready.iter().copied().collect();
+        for id in startable {
+            let conflicting = running.iter().any(|(_, other)| locks[id].binary_search_by_key(other, TaskId::id).is_ok());
+
+            if running.len() < threads && !conflicting {
+                ready.remove(&id);
+                running.push((now + costs[id], id));
+            }
+        }
+
+        //ends are compared before ids, so simultaneous ends finish in declaration order
+        let Some((idx, _)) = running.iter().enumerate().min_by_key(|(_, task)| **task) else {
+            break;
+        };
+
+        let (end, id) = running.swap_remove(idx);
+        now = end;
+
+        for &dependant in dependants[id].iter() {
+            pending[dependant] -= 1;
+            if pending[dependant] == 0 {
+                ready.insert(dependant);
+            }
+        }
+    }
+
+    GraphStats {
+        dependencies: dependencies.iter().map(Vec::len).sum(),
+        conflicts: locks.iter().map(Vec::len).sum::<usize>() / 2,
+        critical_path: finish.into_iter().max().unwrap_or_default(),
+        makespan: now
+    }
+}
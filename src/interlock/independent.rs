@@ -0,0 +1,90 @@
+use super::InterlockExecutor;
+use super::task::{Task, TaskId};
+
+/// Tasks reachable from every task through dependants, one bit per task.
+struct Reach {
+    words: usize,
+    bits: Vec<u64>
+}
+
+impl Reach {
+
+    fn new<T, R, N>(tasks: &[Task<'_, T, R, N>]) -> Self {
+        let words = tasks.len().div_ceil(64);
+        let mut bits = vec![0u64; words * tasks.len()];
+
+        //dependants come after their dependencies, so they are complete when their dependencies are visited
+        for task in tasks.iter().rev() {
+            let id = task.id().id();
+
+            for dependant in task.dependants().iter().map(TaskId::id) {
+                let (head, tail) = bits.split_at_mut(dependant * words);
+                let (reach, dependant_reach) = (&mut head[id * words..][..words], &tail[..words]);
+
+                reach.iter_mut().zip(dependant_reach).for_each(|(word, other)| *word |= other);
+                reach[dependant / 64] |= 1 << (dependant % 64);
+            }
+        }
+
+        Self { words, bits }
+    }
+
+    fn reaches(&self, from: usize, to: usize) -> bool {
+        self.bits[from * self.words + to / 64] & (1 << (to % 64)) != 0
+    }
+}
+
+fn conflict<T, R, N>(tasks: &[Task<'_, T, R, N>], a: usize, b: usize) -> bool {
+    tasks[a].static_locks().iter().any(|task| task.id() == b) || tasks[b].static_locks().iter().any(|task| task.id() == a)
+}
+
+pub(crate) fn independent_sets<T, R, N>(executor: &InterlockExecutor<'_, T, R, N>) -> Vec<Vec<TaskId>> {
+    let tasks = &executor.tasks;
+    let reach = Reach::new(tasks);
+    let mut sets: Vec<Vec<TaskId>> = Vec::new();
+
+    //first fit in declaration order, earlier tasks can only reach later ones
+    for task in tasks.iter() {
+        let id = task.id().id();
+        let independent = |set: &&mut Vec<TaskId>| set.iter().all(|other| !reach.reaches(other.id(), id) && !conflict(tasks, other.id(), id));
+
+        match sets.iter_mut().find(independent) {
+            Some(set) => set.push(task.id()),
+            None => sets.push(vec![task.id()])
+        }
+    }
+
+    sets
+}
+
+pub(crate) fn are_independent<T, R, N>(executor: &InterlockExecutor<'_, T, R, N>, set: &[TaskId]) -> bool {
+    let tasks = &executor.tasks;
+    let mut member = vec![false; tasks.len()];
+    set.iter().for_each(|task| member[task.id()] = true);
+
+    //a walk from every member, a path between two members is found from the earlier one
+    let mut visited = vec![false; tasks.len()];
+    let mut pending = Vec::new();
+
+    for (idx, task) in set.iter().enumerate() {
+        if set[idx + 1..].iter().any(|other| other.id() == task.id() || conflict(tasks, task.id(), other.id())) {
+            return false;
+        }
+
+        visited.iter_mut().for_each(|visited| *visited = false);
+        pending.extend_from_slice(tasks[task.id()].dependants());
+
+        while let Some(next) = pending.pop() {
+            if member[next.id()] {
+                return false;
+            }
+
+            if !visited[next.id()] {
+                visited[next.id()] = true;
+                pending.extend_from_slice(tasks[next.id()].dependants());
+            }
+        }
+    }
+
+    true
+}
@@ -0,0 +1,145 @@
+use super::InterlockExecutor;
+use super::resource::Access;
+use super::task::{Task, TaskId};
+use std::hash::Hash;
+use std::iter::FusedIterator;
+use std::slice;
+use std::vec;
+
+/**
+ View of a task of a built graph, e.g. for tooling walking the graph. Dependencies are the ones of the built graph,
+ which include conflicts turned into dependencies by `Fairness::Fifo`. Created by `InterlockExecutor::iter`
+ and `InterlockExecutor::task`.
+*/
+#[derive(Clone, Debug)]
+pub struct TaskInfo<'a, R, N = String> {
+    id: TaskId,
+    label: Option<&'a N>,
+    accesses: Vec<(&'a R, Access)>,
+    all: Option<Access>,
+    dependencies: Vec<TaskId>,
+    dependants: &'a [TaskId],
+    realtime: bool,
+    blocking: bool,
+    remote: bool
+}
+
+impl<'a, R, N> TaskInfo<'a, R, N> {
+
+    fn new<T>(task: &'a Task<'_, T, R, N>, accesses: Vec<(&'a R, Access)>, all: Option<Access>, dependencies: Vec<TaskId>) -> Self {
+        Self {
+            id: task.id(),
+            label: task.label(),
+            accesses,
+            all,
+            dependencies,
+            dependants: task.dependants(),
+            realtime: task.is_realtime(),
+            blocking: task.is_blocking(),
+            remote: task.is_remote()
+        }
+    }
+
+    /// Returns the view of `task` of `executor`.
+    pub(crate) fn of<T>(executor: &'a InterlockExecutor<'_, T, R, N>, task: TaskId) -> Self where R: Eq + Hash {
+        let table = executor.resources.table();
+        let id = task.id();
+
+        let dependencies = executor.tasks[..id].iter()
+            .filter(|other| other.dependants().contains(&task))
+            .map(Task::id)
+            .collect();
+
+        Self::new(&executor.tasks[id], table.accesses(executor.tasks.len()).swap_remove(id), table.all(task), dependencies)
+    }
+
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    pub fn label(&self) -> Option<&'a N> {
+        self.label
+    }
+
+    /// Returns the static accesses of the task in declaration order, without resolved ones.
+    pub fn accesses(&self) -> &[(&'a R, Access)] {
+        &self.accesses
+    }
+
+    /// Returns the access to all resources, see `InterlockBuilder::read_all`.
+    pub fn all(&self) -> Option<Access> {
+        self.all
+    }
+
+    /// Returns the tasks this task waits for, in declaration order.
+    pub fn dependencies(&self) -> &[TaskId] {
+        &self.dependencies
+    }
+
+    /// Returns the tasks waiting for this task.
+    pub fn dependants(&self) -> &'a [TaskId] {
+        self.dependants
+    }
+
+    pub fn is_realtime(&self) -> bool {
+        self.realtime
+    }
+
+    /// See `InterlockBuilder::mark_blocking`.
+    pub fn is_blocking(&self) -> bool {
+        self.blocking
+    }
+
+    pub fn is_remote(&self) -> bool {
+        self.remote
+    }
+}
+
+/// Iterator over the tasks of an executor in declaration order, see `InterlockExecutor::iter`.
+pub struct TaskIter<'a, 'task, T, R, N = String> {
+    tasks: slice::Iter<'a, Task<'task, T, R, N>>,
+    accesses: vec::IntoIter<Vec<(&'a R, Access)>>,
+    dependencies: vec::IntoIter<Vec<TaskId>>,
+    all: Vec<Option<Access>>
+}
+
+impl<'a, 'task, T, R: Eq + Hash, N> TaskIter<'a, 'task, T, R, N> {
+
+    pub(crate) fn new(executor: &'a InterlockExecutor<'task, T, R, N>) -> Self {
+        let table = executor.resources.table();
+        let tasks = &executor.tasks;
+
+        //dependants come after their dependencies, so dependencies are collected in declaration order
+        let mut dependencies = vec![Vec::new(); tasks.len()];
+        for task in tasks.iter() {
+            task.dependants().iter().for_each(|dependant| dependencies[dependant.id()].push(task.id()));
+        }
+
+        Self {
+            tasks: tasks.iter(),
+            accesses: table.accesses(tasks.len()).into_iter(),
+            dependencies: dependencies.into_iter(),
+            all: tasks.iter().map(|task| table.all(task.id())).collect()
+        }
+    }
+}
+
+impl<'a, 'task, T, R, N> Iterator for TaskIter<'a, 'task, T, R, N> {
+    type Item = TaskInfo<'a, R, N>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let task = self.tasks.next()?;
+        let accesses = self.accesses.next().expect("accesses of every task");
+        let dependencies = self.dependencies.next().expect("dependencies of every task");
+
+        Some(TaskInfo::new(task, accesses, self.all[task.id().id()], dependencies))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.tasks.size_hint()
+    }
+}
+
+impl<'a, 'task, T, R, N> ExactSizeIterator for TaskIter<'a, 'task, T, R, N> {}
+
+impl<'a, 'task, T, R, N> FusedIterator for TaskIter<'a, 'task, T, R, N> {}
@@ -0,0 +1,1261 @@
+use super::{InterlockExecutor, PoolInfo, TaskInfo};
+use super::stats::{LockStats, Sampling, TaskStats};
+use super::task::TaskId;
+use crate::test::analysis::{TimelineAnalyzer, TimelineTask};
+use std::fmt::{Display, Write};
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const NODE_WIDTH: usize = 160;
+const NODE_HEIGHT: usize = 28;
+const COLUMN: usize = 200;
+const ROW: usize = 44;
+const TIMELINE_WIDTH: f64 = 960.0;
+const LANE: usize = 24;
+
+/// Separates the levels of hierarchical task labels, e.g. `physics/solve/islands`, which exports group by prefix.
+pub const LABEL_SEPARATOR: char = '/';
+
+const STYLE: &str = "body{font-family:sans-serif;margin:24px;color:#222}\
+    h2{margin-top:32px}\
+    svg text{font-size:12px;dominant-baseline:middle}\
+    .node rect,.node ellipse,.node polygon{fill:#e8f0fe;stroke:#4a6fa5}\
+    .lane{font-weight:bold}\
+    .dep{stroke:#4a6fa5;fill:none;marker-end:url(#arrow)}\
+    .conflict{stroke:#c0392b;stroke-dasharray:4 3;fill:none}\
+    .span{fill:#7aa6da;stroke:#4a6fa5}\
+    .external{fill:#a5d6a7;stroke:#2e7d32}\
+    table{border-collapse:collapse}\
+    td,th{border:1px solid #ccc;padding:4px 10px;text-align:right}\
+    td:first-child,th:first-child{text-align:left}\
+    pre{background:#f5f5f5;padding:8px}";
+
+/**
+ Snapshot of a graph and the timings of its tasks, owning everything so it can outlive the executor,
+ e.g. to archive it after a run. Created with `InterlockExecutor::report`.
+*/
+#[derive(Clone, Debug)]
+pub struct ExecutionReport {
+    tasks: Vec<ReportTask>,
+    spans: Vec<Span>,
+    started: Option<Instant>
+}
+
+/// Run that took longer than the threshold of `InterlockExecutor::on_slow_run`, with everything recorded about it.
+#[derive(Clone, Debug)]
+pub struct SlowRun {
+    pub duration: Duration,
+    /// Report of the run, e.g. to write `to_chrome_trace` to a file.
+    pub report: ExecutionReport,
+    pub locks: LockStats,
+    /// Threads the executor ran on, e.g. to tell a hitch caused by oversubscription.
+    pub pools: PoolInfo
+}
+
+/// Span measured outside of the executor, e.g. a GPU pass.
+#[derive(Clone, Debug)]
+struct Span {
+    lane: String,
+    name: String,
+    start: Duration,
+    end: Duration
+}
+
+/**
+ Appearance of a task in the exported graphs, returned by the callback of `InterlockExecutor::styled_report`,
+ e.g. to color tasks by the subsystem owning them. Unset properties keep the default appearance.
+*/
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct Style {
+    color: Option<String>,
+    shape: Option<Shape>,
+    lane: Option<String>
+}
+
+impl Style {
+
+    /// Fills the task with `color`, a color name or `#rrggbb`.
+    pub fn color(mut self, color: impl Into<String>) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+
+    pub fn shape(mut self, shape: Shape) -> Self {
+        self.shape = Some(shape);
+        self
+    }
+
+    /**
+     Draws the task in the group `lane` instead of the one of its label prefix, its label is then shown in full.
+     Lanes can be hierarchical like labels, see `LABEL_SEPARATOR`.
+    */
+    pub fn lane(mut self, lane: impl Into<String>) -> Self {
+        self.lane = Some(lane.into());
+        self
+    }
+}
+
+/// Shape of a task in the exported graphs, see `Style`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Shape {
+    Box,
+    Rounded,
+    Ellipse,
+    Diamond,
+    Hexagon
+}
+
+/// Item of the outline of hierarchical labels, see `ExecutionReport::outline`.
+enum Outline<'a> {
+    /// Start of a group with its index and the last part of its prefix.
+    Open(usize, &'a str),
+    Close,
+    Task(usize)
+}
+
+#[derive(Clone, Debug)]
+struct ReportTask {
+    label: Option<String>,
+    dependants: Vec<usize>,
+    conflicts: Vec<usize>,
+    stats: TaskStats,
+    style: Style
+}
+
+impl<'task, T: Sync, R: Eq + Hash, N> InterlockExecutor<'task, T, R, N> {
+
+    /// Returns the timings of `task`, recorded over all runs of this executor.
+    pub fn stats(&self, task: TaskId) -> TaskStats {
+        self.stats.get(task.id())
+    }
+
+    /// Returns how many counter operations the last run took, see `LockStats`.
+    pub fn lock_stats(&self) -> LockStats {
+        self.stats.locks()
+    }
+
+    /**
+     Measures the bytes each task allocates with `counter`, which returns the bytes allocated so far by the calling
+     thread, e.g. a thread local counter of a counting global allocator. Reported in `TaskStats::allocated`.
+    */
+    pub fn set_allocation_counter(&mut self, counter: Option<Arc<dyn Fn() -> u64 + Send + Sync + 'task>>) {
+        self.allocations = counter;
+    }
+
+    /**
+     Chooses which runs count towards `stats` and the timeline of `report`, e.g. every 60th frame or only the frames
+     over budget, so release builds can keep the inspector enabled. Other runs skip the timing and allocation counting
+     of their tasks. The next run is always recorded with `Sampling::Every`.
+    */
+    pub fn set_sampling(&mut self, sampling: Sampling) {
+        self.stats.set_sampling(sampling);
+    }
+
+    /**
+     Calls `dump` after every run that took at least `threshold`, with the report of that run, like a flight recorder
+     catching rare hitches. Runs that aren't recorded by the sampling of the executor are never dumped, with
+     `Sampling::Slower` of the same threshold only the dumped runs are recorded at all.
+    */
+    pub fn on_slow_run(&mut self, threshold: Duration, dump: impl Fn(&SlowRun) + Send + Sync + 'task) where N: Display {
+        self.after_run(move |_, executor| {
+            if let Some(duration) = executor.stats.recorded_run().filter(|&duration| duration >= threshold) {
+                dump(&SlowRun { duration, report: executor.report(), locks: executor.lock_stats(), pools: executor.pool_info() });
+            }
+        });
+    }
+
+    pub fn report(&self) -> ExecutionReport where N: Display {
+        self.styled_report(|_| Style::default())
+    }
+
+    /**
+     Takes a report like `report`, with every task drawn in the style `style` returns for it in the graphs
+     exported to DOT, mermaid and HTML, e.g. to color tasks by subsystem or to set realtime tasks apart.
+    */
+    pub fn styled_report(&self, mut style: impl FnMut(TaskInfo<'_, R, N>) -> Style) -> ExecutionReport where N: Display {
+        let ids = |ids: &[TaskId]| ids.iter().map(TaskId::id).collect();
+
+        let tasks = self.tasks.iter().zip(self.iter()).enumerate().map(|(id, (task, info))| ReportTask {
+            label: task.label().map(N::to_string),
+            dependants: ids(task.dependants()),
+            conflicts: ids(task.lockable_deps()),
+            stats: self.stats.get(id),
+            style: style(info)
+        }).collect();
+
+        ExecutionReport { tasks, spans: Vec::new(), started: self.stats.started() }
+    }
+
+    /// Shorthand for `report().to_html()`.
+    pub fn inspect(&self) -> String where N: Display {
+        self.report().to_html()
+    }
+
+    /**
+     Returns the last run as a timeline of labeled tasks, named by their labels as they are, e.g. to assert on the
+     order of enum labeled passes without rendering them. Unlabeled tasks and tasks that didn't execute are left out.
+     Tasks executed on the pool keep the index of their thread, see `TimelineAnalyzer::recorded_threads`.
+    */
+    pub fn timeline(&self) -> TimelineAnalyzer<N> where N: Clone {
+        self.tasks.iter()
+            .enumerate()
+            .filter_map(|(id, task)| task.label().map(|label| (label, self.stats.get(id))))
+            .filter_map(|(label, stats)| {
+                let (start, end) = stats.last?;
+                let task = TimelineTask::new(label.clone(), start, end - start);
+                Some(match stats.thread {
+                    Some(thread) => task.with_thread(thread),
+                    None => task
+                })
+            })
+            .collect()
+    }
+}
+
+impl ExecutionReport {
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /**
+     Adds a span measured outside of the executor to the timeline of the last run, e.g. the duration of a GPU pass
+     from timestamp queries. `lane` names the timeline it is drawn on, e.g. the GPU queue, and `start` and `end`
+     are relative to the start of the run, like the timings of tasks.
+    */
+    pub fn add_span(&mut self, lane: impl Into<String>, name: impl Into<String>, start: Duration, end: Duration) {
+        self.spans.push(Span { lane: lane.into(), name: name.into(), start, end: end.max(start) });
+    }
+
+    /**
+     Adds a span like `add_span`, given by instants of the CPU clock, e.g. GPU timestamps already calibrated against it.
+
+     Panics if the graph hasn't run yet.
+    */
+    pub fn add_span_at(&mut self, lane: impl Into<String>, name: impl Into<String>, start: Instant, end: Instant) {
+        let started = self.started.expect("spans can only be added to a report of a graph that ran");
+        self.add_span(lane, name, start.saturating_duration_since(started), end.saturating_duration_since(started));
+    }
+
+    /// Returns the last run with the added spans as a timeline, tasks are named by label or `#index`.
+    pub fn to_timeline(&self) -> TimelineAnalyzer<String> {
+        let tasks = self.tasks.iter()
+            .enumerate()
+            .filter_map(|(id, task)| task.stats.last.map(|(start, end)| {
+                let name = task.label.clone().unwrap_or_else(|| format!("#{}", id));
+                TimelineTask::new(name, start, end - start)
+            }));
+
+        let spans = self.spans.iter().map(|span| TimelineTask::new(span.name.clone(), span.start, span.end - span.start));
+        tasks.chain(spans).collect()
+    }
+
+    /**
+     Returns the timings of `task` when the report was taken. Filtered and collapsed reports number their tasks
+     anew, so look their tasks up with `stats_by_label` instead of with ids of the executor.
+    */
+    pub fn stats(&self, task: TaskId) -> TaskStats {
+        self.tasks[task.id()].stats
+    }
+
+    /// Returns the timings of the first task labeled `label`, e.g. of a task merged by `collapse`.
+    pub fn stats_by_label(&self, label: &str) -> Option<TaskStats> {
+        self.tasks.iter().find(|task| task.label.as_deref() == Some(label)).map(|task| task.stats)
+    }
+
+    /// Returns the duration of the last run, from its start to the end of its last task.
+    pub fn duration(&self) -> Option<Duration> {
+        self.tasks.iter().filter_map(|task| task.stats.last).map(|(_, end)| end).max()
+    }
+
+    /**
+     Returns the graph in the DOT format, dependencies are drawn as arrows and conflicts as dashed lines.
+     Tasks with hierarchical labels are drawn in a cluster per prefix, see `LABEL_SEPARATOR`.
+    */
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph interlock {\n    node [shape=box];\n");
+
+        for (depth, item) in self.outline() {
+            let indent = "    ".repeat(depth);
+            match item {
+                Outline::Open(idx, name) => writeln!(dot, "{}subgraph cluster_{} {{\n{}    label=\"{}\";", indent, idx, indent, dot_escape(name)).unwrap(),
+                Outline::Close => writeln!(dot, "{}}}", indent).unwrap(),
+                Outline::Task(id) => {
+                    let label = self.short_label(id).map_or(format!("#{}", id), dot_escape);
+                    writeln!(dot, "{}t{} [label=\"{}\"{}];", indent, id, label, self.dot_style(id)).unwrap();
+                }
+            }
+        }
+
+        for (id, task) in self.tasks.iter().enumerate() {
+            task.dependants.iter().for_each(|dependant| writeln!(dot, "    t{} -> t{};", id, dependant).unwrap());
+            self.conflicts(id).for_each(|other| writeln!(dot, "    t{} -> t{} [style=dashed, dir=none];", id, other).unwrap());
+        }
+
+        dot.push('}');
+        dot
+    }
+
+    /**
+     Returns the graph as a mermaid flowchart, dependencies are drawn as arrows and conflicts as dotted lines.
+     Tasks with hierarchical labels are drawn in a subgraph per prefix, see `LABEL_SEPARATOR`.
+    */
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("flowchart LR\n");
+
+        for (depth, item) in self.outline() {
+            let indent = "    ".repeat(depth);
+            match item {
+                Outline::Open(idx, name) => writeln!(mermaid, "{}subgraph g{}[\"{}\"]", indent, idx, mermaid_escape(name)).unwrap(),
+                Outline::Close => writeln!(mermaid, "{}end", indent).unwrap(),
+                Outline::Task(id) => {
+                    let name = self.short_label(id).map_or(format!("#{}", id), mermaid_escape);
+                    let (open, close) = match self.tasks[id].style.shape {
+                        None | Some(Shape::Box) => ("[", "]"),
+                        Some(Shape::Rounded) => ("(", ")"),
+                        Some(Shape::Ellipse) => ("([", "])"),
+                        Some(Shape::Diamond) => ("{", "}"),
+                        Some(Shape::Hexagon) => ("{{", "}}")
+                    };
+
+                    writeln!(mermaid, "{}t{}{}\"{}\"{}", indent, id, open, name, close).unwrap();
+                }
+            }
+        }
+
+        for (id, task) in self.tasks.iter().enumerate() {
+            if let Some(color) = &task.style.color {
+                writeln!(mermaid, "    style t{} fill:{}", id, color.replace([',', ';', '\n'], "")).unwrap();
+            }
+        }
+
+        for (id, task) in self.tasks.iter().enumerate() {
+            task.dependants.iter().for_each(|dependant| writeln!(mermaid, "    t{} --> t{}", id, dependant).unwrap());
+        }
+
+        for id in 0..self.tasks.len() {
+            self.conflicts(id).for_each(|other| writeln!(mermaid, "    t{} -.- t{}", id, other).unwrap());
+        }
+
+        mermaid
+    }
+
+    /**
+     Returns the last run in the trace event format of chrome://tracing and Perfetto, a complete event per task
+     that executed in the last run on the thread it executed on, and a thread per lane of the added spans.
+     Tasks are categorized by the prefix of their label, see `LABEL_SEPARATOR`.
+    */
+    pub fn to_chrome_trace(&self) -> String {
+        let mut events = Vec::new();
+        let micros = |duration: Duration| duration.as_secs_f64() * 1_000_000.0;
+
+        for (id, task) in self.tasks.iter().enumerate() {
+            if let Some((start, end)) = task.stats.last {
+                let name = task.label.clone().unwrap_or_else(|| format!("#{}", id));
+                let category = task.label.as_deref().and_then(prefix).unwrap_or("task");
+                let thread = task.stats.thread.map_or(0, |thread| thread + 1);
+
+                events.push(format!("{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":0,\"tid\":{}}}",
+                                    json_escape(&name), json_escape(category), micros(start), micros(end - start), thread));
+            }
+        }
+
+        for (lane, (name, spans)) in self.lanes().into_iter().enumerate() {
+            events.push(format!("{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}", lane, json_escape(name)));
+
+            for (_, span) in spans {
+                events.push(format!("{{\"name\":\"{}\",\"cat\":\"span\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":1,\"tid\":{}}}",
+                                    json_escape(&span.name), micros(span.start), micros(span.end - span.start), lane));
+            }
+        }
+
+        format!("{{\"traceEvents\":[{}]}}", events.join(","))
+    }
+
+    /**
+     Returns the report of the tasks labeled `prefix` or below it, e.g. `physics` keeps `physics/solve/islands`
+     but not `physics2`. Dependencies and conflicts with other tasks are left out, spans are kept.
+    */
+    pub fn filter(&self, prefix: &str) -> ExecutionReport {
+        let targets: Vec<_> = self.tasks.iter().map(|task| task.label.as_deref().is_some_and(|label| is_below(label, prefix))).collect();
+
+        let mut next = 0;
+        let targets = targets.into_iter().map(|keep| keep.then(|| { next += 1; next - 1 })).collect();
+        self.remap(targets, |id| self.tasks[id].label.clone())
+    }
+
+    /**
+     Returns the report with the tasks labeled `prefix` or below it merged into a single task labeled `prefix`,
+     e.g. to show `physics` as one node in a graph of 800 tasks. The merged task takes the place of the first one,
+     its total time adds up the ones of the merged tasks and its last run spans all of them. It keeps the style of the first one.
+    */
+    pub fn collapse(&self, prefix: &str) -> ExecutionReport {
+        let mut merged = None;
+        let mut next = 0;
+
+        let targets = self.tasks.iter().map(|task| {
+            let below = task.label.as_deref().is_some_and(|label| is_below(label, prefix));
+
+            Some(match (below, merged) {
+                (true, Some(merged)) => merged,
+                (below, _) => {
+                    merged = merged.or(below.then_some(next));
+                    next += 1;
+                    next - 1
+                }
+            })
+        }).collect();
+
+        self.remap(targets, |id| match self.tasks[id].label.as_deref().is_some_and(|label| is_below(label, prefix)) {
+            true => Some(prefix.to_string()),
+            false => self.tasks[id].label.clone()
+        })
+    }
+
+    /// Moves every task to the index `targets` maps it to, or drops it, merging the tasks moved to the same index.
+    fn remap(&self, targets: Vec<Option<usize>>, label: impl Fn(usize) -> Option<String>) -> ExecutionReport {
+        let mut tasks: Vec<ReportTask> = Vec::new();
+
+        for (id, task) in self.tasks.iter().enumerate() {
+            let target = match targets[id] {
+                Some(target) => target,
+                None => continue
+            };
+
+            let map = |ids: &[usize]| ids.iter().filter_map(|&other| targets[other]).filter(|&other| other != target).collect::<Vec<_>>();
+            let (dependants, conflicts) = (map(&task.dependants), map(&task.conflicts));
+
+            match tasks.get_mut(target) {
+                Some(merged) => {
+                    merged.dependants.extend(dependants);
+                    merged.conflicts.extend(conflicts);
+                    merged.stats = merge(merged.stats, task.stats);
+                },
+
+                None => tasks.push(ReportTask { label: label(id), dependants, conflicts, stats: task.stats, style: task.style.clone() })
+            }
+        }
+
+        for task in tasks.iter_mut() {
+            task.dependants.sort_unstable();
+            task.dependants.dedup();
+            task.conflicts.sort_unstable();
+            task.conflicts.dedup();
+        }
+
+        ExecutionReport { tasks, spans: self.spans.clone(), started: self.started }
+    }
+
+    /// Returns every group of a task and its prefixes, each after its own prefix.
+    fn groups(&self) -> Vec<&str> {
+        let mut groups: Vec<&str> = (0..self.tasks.len())
+            .filter_map(|id| self.group(id))
+            .flat_map(|group| group.match_indices(LABEL_SEPARATOR).map(move |(idx, _)| &group[..idx]).chain(Some(group)))
+            .collect();
+
+        groups.sort_unstable();
+        groups.dedup();
+        groups
+    }
+
+    /// Returns the tasks nested in the groups of their prefixes, with their depth, tasks without a prefix at the top level.
+    fn outline(&self) -> Vec<(usize, Outline<'_>)> {
+        let groups = self.groups();
+        let mut outline = Vec::with_capacity(self.tasks.len() + groups.len() * 2);
+        self.push_group(&groups, None, 1, &mut outline);
+        outline
+    }
+
+    fn push_group<'a>(&'a self, groups: &[&'a str], group: Option<&str>, depth: usize, outline: &mut Vec<(usize, Outline<'a>)>) {
+        for (idx, nested) in groups.iter().enumerate().filter(|(_, nested)| prefix(nested) == group) {
+            outline.push((depth, Outline::Open(idx, leaf(nested))));
+            self.push_group(groups, Some(nested), depth + 1, outline);
+            outline.push((depth, Outline::Close));
+        }
+
+        for id in 0..self.tasks.len() {
+            if self.group(id) == group {
+                outline.push((depth, Outline::Task(id)));
+            }
+        }
+    }
+
+    /// Returns the group `id` is drawn in: its lane, or else the prefix of its label.
+    fn group(&self, id: usize) -> Option<&str> {
+        let task = &self.tasks[id];
+        task.style.lane.as_deref().or_else(|| task.label.as_deref().and_then(prefix))
+    }
+
+    /// Returns the label of `id` without the prefix of the group it is drawn in.
+    fn short_label(&self, id: usize) -> Option<&str> {
+        let task = &self.tasks[id];
+        task.label.as_deref().map(|label| if task.style.lane.is_some() { label } else { leaf(label) })
+    }
+
+    /// Returns the DOT attributes of the style of `id`, to append to its label.
+    fn dot_style(&self, id: usize) -> String {
+        let style = &self.tasks[id].style;
+        let mut attributes = String::new();
+
+        let shape = match style.shape {
+            None | Some(Shape::Box) | Some(Shape::Rounded) => None,
+            Some(Shape::Ellipse) => Some("ellipse"),
+            Some(Shape::Diamond) => Some("diamond"),
+            Some(Shape::Hexagon) => Some("hexagon")
+        };
+
+        //rounded corners are a style of boxes, like the fill
+        let styles: Vec<_> = (style.shape == Some(Shape::Rounded)).then_some("rounded")
+            .into_iter()
+            .chain(style.color.as_ref().map(|_| "filled"))
+            .collect();
+
+        if let Some(shape) = shape {
+            write!(attributes, ", shape={}", shape).unwrap();
+        }
+
+        if !styles.is_empty() {
+            write!(attributes, ", style=\"{}\"", styles.join(",")).unwrap();
+        }
+
+        if let Some(color) = &style.color {
+            write!(attributes, ", fillcolor=\"{}\"", dot_escape(color)).unwrap();
+        }
+
+        attributes
+    }
+
+    /**
+     Returns the last run as a mermaid gantt chart, tasks that didn't execute in the last run are left out.
+     Added spans follow in a section per lane.
+    */
+    pub fn to_gantt(&self) -> String {
+        let mut gantt = String::from("gantt\n    dateFormat x\n    axisFormat %L ms\n");
+
+        //mermaid has millisecond resolution, every task is at least one long to remain visible
+        let millis = |start: Duration, end: Duration| (start.as_millis(), end.as_millis().max(start.as_millis() + 1));
+
+        if !self.spans.is_empty() {
+            gantt.push_str("    section tasks\n");
+        }
+
+        for (id, task) in self.tasks.iter().enumerate() {
+            if let Some((start, end)) = task.stats.last {
+                let (start, end) = millis(start, end);
+                writeln!(gantt, "    {} : t{}, {}, {}", self.mermaid_name(id), id, start, end).unwrap();
+            }
+        }
+
+        for (lane, spans) in self.lanes() {
+            writeln!(gantt, "    section {}", mermaid_escape(lane)).unwrap();
+            for (idx, span) in spans {
+                let (start, end) = millis(span.start, span.end);
+                writeln!(gantt, "    {} : s{}, {}, {}", mermaid_escape(&span.name), idx, start, end).unwrap();
+            }
+        }
+
+        gantt
+    }
+
+    /// Returns the added spans grouped by lane, lanes in the order they were first used.
+    fn lanes(&self) -> Vec<(&str, Vec<(usize, &Span)>)> {
+        let mut lanes: Vec<(&str, Vec<_>)> = Vec::new();
+        for (idx, span) in self.spans.iter().enumerate() {
+            match lanes.iter_mut().find(|(lane, _)| *lane == span.lane) {
+                Some((_, spans)) => spans.push((idx, span)),
+                None => lanes.push((&span.lane, vec![(idx, span)]))
+            }
+        }
+
+        lanes
+    }
+
+    /**
+     Renders a self-contained HTML page with the graph, the timeline of the last run and the timings of every task,
+     e.g. to write it to a file and open it in a browser. The DOT and mermaid sources are included to render them elsewhere.
+    */
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        write!(html, "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Interlock graph</title><style>{}</style></head><body>", STYLE).unwrap();
+        write!(html, "<h1>Interlock graph</h1><p>{} tasks</p>", self.tasks.len()).unwrap();
+
+        html.push_str("<h2>Graph</h2>");
+        self.write_graph(&mut html);
+        write!(html, "<details><summary>DOT</summary><pre>{}</pre></details>", escape(&self.to_dot())).unwrap();
+        write!(html, "<details><summary>Mermaid</summary><pre>{}</pre></details>", escape(&self.to_mermaid())).unwrap();
+
+        html.push_str("<h2>Last run</h2>");
+        self.write_timeline(&mut html);
+        write!(html, "<details><summary>Mermaid</summary><pre>{}</pre></details>", escape(&self.to_gantt())).unwrap();
+
+        html.push_str("<h2>Tasks</h2>");
+        self.write_stats(&mut html);
+
+        html.push_str("</body></html>");
+        html
+    }
+
+    /// Returns the tasks conflicting with `id` that come after it, so every conflict is only visited once.
+    fn conflicts(&self, id: usize) -> impl Iterator<Item=usize> + '_ {
+        self.tasks[id].conflicts.iter().copied().filter(move |&other| other > id)
+    }
+
+    fn name(&self, id: usize) -> String {
+        match &self.tasks[id].label {
+            Some(label) => escape(label),
+            None => format!("#{}", id)
+        }
+    }
+
+    fn mermaid_name(&self, id: usize) -> String {
+        self.tasks[id].label.as_deref().map_or(format!("#{}", id), mermaid_escape)
+    }
+
+    /// Returns the column of every task: the length of the longest dependency chain leading to it.
+    fn depths(&self) -> Vec<usize> {
+        let mut depths = vec![0; self.tasks.len()];
+        let mut counts = vec![0; self.tasks.len()];
+        self.tasks.iter().flat_map(|task| task.dependants.iter()).for_each(|&dependant| counts[dependant] += 1);
+
+        let mut ready: Vec<_> = (0..self.tasks.len()).filter(|&id| counts[id] == 0).collect();
+        while let Some(id) = ready.pop() {
+            for &dependant in self.tasks[id].dependants.iter() {
+                depths[dependant] = depths[dependant].max(depths[id] + 1);
+                counts[dependant] -= 1;
+                if counts[dependant] == 0 {
+                    ready.push(dependant);
+                }
+            }
+        }
+
+        depths
+    }
+
+    fn write_graph(&self, html: &mut String) {
+        let depths = self.depths();
+        let columns = depths.iter().max().map_or(0, |depth| depth + 1);
+
+        //tasks without a lane first, then a band per lane in the order lanes are first used
+        let mut lanes: Vec<Option<&str>> = vec![None];
+        for task in self.tasks.iter() {
+            if !lanes.contains(&task.style.lane.as_deref()) {
+                lanes.push(task.style.lane.as_deref());
+            }
+        }
+
+        let mut rows = vec![vec![0; columns]; lanes.len()];
+        let slots: Vec<_> = depths.iter().zip(self.tasks.iter()).map(|(&depth, task)| {
+            let lane = lanes.iter().position(|&lane| lane == task.style.lane.as_deref()).unwrap();
+            rows[lane][depth] += 1;
+            (lane, depth, rows[lane][depth] - 1)
+        }).collect();
+
+        let mut tops = Vec::with_capacity(lanes.len());
+        let mut height = 10;
+        for (lane, rows) in lanes.iter().zip(rows.iter()) {
+            let header = lane.map_or(0, |_| LANE);
+            tops.push(height + header);
+            height += header + rows.iter().max().unwrap_or(&0) * ROW;
+        }
+
+        let positions: Vec<_> = slots.iter().map(|&(lane, depth, row)| (depth * COLUMN + 10, tops[lane] + row * ROW)).collect();
+
+        let width = columns * COLUMN;
+        write!(html, "<svg width=\"{}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\">", width, height).unwrap();
+        html.push_str("<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto\"><path d=\"M0,0L10,5L0,10z\" fill=\"#4a6fa5\"/></marker></defs>");
+
+        for (id, task) in self.tasks.iter().enumerate() {
+            let (x, y) = positions[id];
+
+            for &dependant in task.dependants.iter() {
+                let (dx, dy) = positions[dependant];
+                write!(html, "<line class=\"dep\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"/>", x + NODE_WIDTH, y + NODE_HEIGHT / 2, dx, dy + NODE_HEIGHT / 2).unwrap();
+            }
+
+            for other in self.conflicts(id) {
+                let (ox, oy) = positions[other];
+                write!(html, "<line class=\"conflict\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"/>", x + NODE_WIDTH / 2, y + NODE_HEIGHT, ox + NODE_WIDTH / 2, oy).unwrap();
+            }
+        }
+
+        for (lane, top) in lanes.iter().zip(tops.iter()) {
+            if let Some(lane) = lane {
+                write!(html, "<text class=\"lane\" x=\"10\" y=\"{}\">{}</text>", top - LANE / 2, escape(lane)).unwrap();
+            }
+        }
+
+        for (id, &(x, y)) in positions.iter().enumerate() {
+            let style = &self.tasks[id].style;
+            let fill = style.color.as_ref().map_or(String::new(), |color| format!(" style=\"fill:{}\"", escape(color)));
+            let (w, h, m) = (NODE_WIDTH, NODE_HEIGHT, NODE_HEIGHT / 2);
+
+            write!(html, "<g class=\"node\"><title>{}</title>", self.name(id)).unwrap();
+            match style.shape {
+                None => write!(html, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"4\"{}/>", x, y, w, h, fill),
+                Some(Shape::Box) => write!(html, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"{}/>", x, y, w, h, fill),
+                Some(Shape::Rounded) => write!(html, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\"{}/>", x, y, w, h, m, fill),
+                Some(Shape::Ellipse) => write!(html, "<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\"{}/>", x + w / 2, y + m, w / 2, m, fill),
+                Some(Shape::Diamond) => write!(html, "<polygon points=\"{},{} {},{} {},{} {},{}\"{}/>",
+                                               x, y + m, x + w / 2, y, x + w, y + m, x + w / 2, y + h, fill),
+                Some(Shape::Hexagon) => write!(html, "<polygon points=\"{},{} {},{} {},{} {},{} {},{} {},{}\"{}/>",
+                                               x, y + m, x + m, y, x + w - m, y, x + w, y + m, x + w - m, y + h, x + m, y + h, fill)
+            }.unwrap();
+
+            write!(html, "<text x=\"{}\" y=\"{}\">{}</text></g>", x + 8, y + m, self.name(id)).unwrap();
+        }
+
+        html.push_str("</svg>");
+    }
+
+    fn write_timeline(&self, html: &mut String) {
+        let end = match self.duration() {
+            Some(end) => end.max(self.spans.iter().map(|span| span.end).max().unwrap_or_default()),
+            None => return html.push_str("<p>The graph hasn't run yet.</p>")
+        };
+
+        let spans: Vec<_> = self.tasks.iter()
+            .enumerate()
+            .filter_map(|(id, task)| task.stats.last.map(|(start, end)| (id, start, end, task.stats.thread)))
+            .collect();
+
+        let mut lanes: Vec<_> = spans.iter().map(|&(_, _, _, thread)| thread).collect();
+        lanes.sort_unstable();
+        lanes.dedup();
+
+        let external = self.lanes();
+        let scale = TIMELINE_WIDTH / end.as_secs_f64().max(f64::EPSILON);
+        write!(html, "<p>{}</p>", format_duration(end)).unwrap();
+        write!(html, "<svg width=\"{}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\">", TIMELINE_WIDTH as usize + 90, (lanes.len() + external.len()) * LANE + 10).unwrap();
+
+        for (lane, thread) in lanes.iter().enumerate() {
+            let name = thread.map_or("caller".to_string(), |thread| format!("thread {}", thread));
+            write!(html, "<text x=\"0\" y=\"{}\">{}</text>", lane * LANE + LANE / 2, name).unwrap();
+        }
+
+        for &(id, start, end, thread) in spans.iter() {
+            let lane = lanes.binary_search(&thread).unwrap();
+            let x = 80.0 + start.as_secs_f64() * scale;
+            let width = ((end - start).as_secs_f64() * scale).max(1.0);
+
+            write!(html, "<rect class=\"span\" x=\"{:.1}\" y=\"{}\" width=\"{:.1}\" height=\"{}\"><title>{}: {}</title></rect>",
+                   x, lane * LANE + 2, width, LANE - 4, self.name(id), format_duration(end - start)).unwrap();
+        }
+
+        //external lanes below the threads
+        for (lane, (name, spans)) in external.iter().enumerate() {
+            let y = (lanes.len() + lane) * LANE;
+            write!(html, "<text x=\"0\" y=\"{}\">{}</text>", y + LANE / 2, escape(name)).unwrap();
+
+            for (_, span) in spans {
+                let x = 80.0 + span.start.as_secs_f64() * scale;
+                let width = ((span.end - span.start).as_secs_f64() * scale).max(1.0);
+
+                write!(html, "<rect class=\"external\" x=\"{:.1}\" y=\"{}\" width=\"{:.1}\" height=\"{}\"><title>{}: {}</title></rect>",
+                       x, y + 2, width, LANE - 4, escape(&span.name), format_duration(span.end - span.start)).unwrap();
+            }
+        }
+
+        html.push_str("</svg>");
+    }
+
+    fn write_stats(&self, html: &mut String) {
+        html.push_str("<table><tr><th>Task</th><th>Runs</th><th>Total</th><th>Mean</th><th>Max</th><th>Last</th><th>Allocated</th><th>Max allocated</th></tr>");
+
+        for (id, task) in self.tasks.iter().enumerate() {
+            let stats = task.stats;
+            let last = stats.last.map_or("-".to_string(), |(start, end)| format_duration(end - start));
+            let allocated = stats.allocated.map_or("-".to_string(), format_bytes);
+
+            write!(html, "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                   self.name(id), stats.runs, format_duration(stats.total), format_duration(stats.mean()), format_duration(stats.max), last,
+                   allocated, format_bytes(stats.max_allocated)).unwrap();
+        }
+
+        html.push_str("</table>");
+    }
+}
+
+/// Returns the prefix of a hierarchical label, e.g. `physics/solve` for `physics/solve/islands`.
+fn prefix(label: &str) -> Option<&str> {
+    label.rfind(LABEL_SEPARATOR).map(|idx| &label[..idx])
+}
+
+/// Returns the last part of a hierarchical label, e.g. `islands` for `physics/solve/islands`.
+fn leaf(label: &str) -> &str {
+    label.rfind(LABEL_SEPARATOR).map_or(label, |idx| &label[idx + 1..])
+}
+
+fn is_below(label: &str, prefix: &str) -> bool {
+    label.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with(LABEL_SEPARATOR))
+}
+
+/// Timings of tasks merged into one, see `ExecutionReport::collapse`.
+fn merge(a: TaskStats, b: TaskStats) -> TaskStats {
+    let last = match (a.last, b.last) {
+        (Some((a_start, a_end)), Some((b_start, b_end))) => Some((a_start.min(b_start), a_end.max(b_end))),
+        (a, b) => a.or(b)
+    };
+
+    TaskStats {
+        runs: a.runs.max(b.runs),
+        total: a.total + b.total,
+        max: a.max.max(b.max),
+        last,
+        thread: a.thread.filter(|_| a.thread == b.thread),
+        allocated: a.allocated.zip(b.allocated).map(|(a, b)| a + b).or(a.allocated).or(b.allocated),
+        max_allocated: a.max_allocated.max(b.max_allocated)
+    }
+}
+
+fn dot_escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c)
+        }
+    }
+
+    escaped
+}
+
+fn mermaid_escape(name: &str) -> String {
+    //mermaid has no escape for quotes, colons end gantt task names
+    name.replace('"', "'").replace(':', "#58;")
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3} ms", duration.as_secs_f64() * 1000.0)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1048575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0)
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c)
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Executable;
+    use crate::interlock::builder;
+    use crate::interlock::builder::TaskSpec;
+    use crate::interlock::{Shape, Style};
+
+    #[test]
+    fn inspect() {
+        let closure = |_: &()| {};
+
+        let mut builder = builder::<(), &str>();
+        let ids = builder.extend(vec![
+            TaskSpec::new("a", closure).writes(["x"]),
+            TaskSpec::new("b", closure).writes(["x"]),
+            TaskSpec::new("<c>", closure).after(["a"]),
+        ]).unwrap();
+
+        let mut exec = builder.build();
+        assert!(exec.inspect().contains("hasn't run yet"));
+
+        exec.run(&());
+        exec.run(&());
+
+        let stats = exec.stats(ids["<c>"]);
+        assert_eq!(stats.runs, 2);
+        assert!(stats.last.is_some());
+        assert!(stats.max <= stats.total);
+
+        let html = exec.inspect();
+        assert!(html.contains("&lt;c&gt;"));
+        assert!(!html.contains("<c>"));
+        assert_eq!(html.matches("class=\"dep\"").count(), 1);
+        assert_eq!(html.matches("class=\"conflict\"").count(), 1);
+        assert_eq!(html.matches("class=\"span\"").count(), 3);
+    }
+
+    #[test]
+    fn report() {
+        let closure = |_: &()| {};
+
+        let mut builder = builder::<(), &str>();
+        let ids = builder.extend(vec![
+            TaskSpec::new("a", closure).writes(["x"]),
+            TaskSpec::new("b", closure).writes(["x"]),
+            TaskSpec::new("c: \"last\"", closure).after(["a"]),
+        ]).unwrap();
+
+        let mut exec = builder.build();
+        exec.run(&());
+
+        let report = exec.report();
+        drop(exec);
+
+        assert_eq!(report.len(), 3);
+        assert_eq!(report.stats(ids["a"]).runs, 1);
+        assert!(report.duration().is_some());
+
+        let dot = report.to_dot();
+        assert!(dot.contains("t0 -> t2;"));
+        assert!(dot.contains("t0 -> t1 [style=dashed, dir=none];"));
+        assert!(dot.contains("[label=\"c: \\\"last\\\"\"]"));
+
+        let mermaid = report.to_mermaid();
+        assert!(mermaid.contains("t0 --> t2"));
+        assert!(mermaid.contains("t0 -.- t1"));
+
+        let gantt = report.to_gantt();
+        assert_eq!(gantt.lines().filter(|line| line.contains(" : t")).count(), 3);
+        assert!(gantt.contains("c#58; 'last'"));
+
+        let html = report.to_html();
+        assert!(html.contains("digraph interlock"));
+        assert!(html.contains("flowchart LR"));
+        assert!(html.contains("gantt"));
+    }
+
+    #[test]
+    fn hierarchy() {
+        let closure = |_: &()| {};
+
+        let mut builder = builder::<(), &str>();
+        builder.extend(vec![
+            TaskSpec::new("input", closure),
+            TaskSpec::new("physics/solve/islands", closure).after(["input"]),
+            TaskSpec::new("physics/solve/contacts", closure).after(["physics/solve/islands"]),
+            TaskSpec::new("physics/integrate", closure).after(["physics/solve/contacts"]),
+            TaskSpec::new("physics2", closure),
+            TaskSpec::new("render", closure).after(["physics/integrate"])
+        ]).unwrap();
+
+        let mut exec = builder.build();
+        exec.run(&());
+        let report = exec.report();
+
+        let dot = report.to_dot();
+        assert!(dot.contains("    subgraph cluster_0 {\n        label=\"physics\";\n        subgraph cluster_1 {\n            label=\"solve\";\n            t1 [label=\"islands\"];"));
+        assert!(dot.contains("        }\n        t3 [label=\"integrate\"];\n    }\n    t0"));
+
+        let mermaid = report.to_mermaid();
+        assert!(mermaid.contains("    subgraph g0[\"physics\"]\n        subgraph g1[\"solve\"]"));
+        assert_eq!(mermaid.matches("end\n").count(), 2);
+
+        let trace = report.to_chrome_trace();
+        assert!(trace.starts_with("{\"traceEvents\":[{\"name\":\"input\",\"cat\":\"task\""));
+        assert!(trace.contains("\"name\":\"physics/solve/islands\",\"cat\":\"physics/solve\""));
+        assert_eq!(trace.matches("\"ph\":\"X\"").count(), 6);
+
+        let physics = report.filter("physics");
+        assert_eq!(physics.len(), 3, "physics2 isn't below physics");
+        assert!(physics.to_dot().contains("t0 -> t1;\n    t1 -> t2;\n}"));
+
+        let collapsed = report.collapse("physics");
+        assert_eq!(collapsed.len(), 4);
+        assert!(collapsed.to_dot().contains("t1 [label=\"physics\"]"));
+        assert!(collapsed.to_dot().contains("t0 -> t1;\n    t1 -> t3;\n}"));
+        assert_eq!(collapsed.stats_by_label("physics").map(|stats| stats.runs), Some(1));
+        assert_eq!(collapsed.stats_by_label("render").map(|stats| stats.runs), Some(1));
+        assert!(collapsed.stats_by_label("physics/integrate").is_none());
+    }
+
+    #[test]
+    fn styles() {
+        let closure = |_: &()| {};
+
+        let mut builder = builder::<(), &str>();
+        builder.extend(vec![
+            TaskSpec::new("physics/solve", closure),
+            TaskSpec::new("physics/integrate", closure).after(["physics/solve"]),
+            TaskSpec::new("upload", closure).after(["physics/integrate"]),
+            TaskSpec::new("draw", closure).after(["upload"])
+        ]).unwrap();
+
+        let exec = builder.build();
+        let report = exec.styled_report(|task| match task.label().map(String::as_str) {
+            Some("physics/solve") => Style::default().color("#ffcc80").shape(Shape::Rounded),
+            Some("physics/integrate") => Style::default().color("red"),
+            Some("upload") => Style::default().shape(Shape::Diamond).lane("gpu"),
+            _ => Style::default().shape(Shape::Hexagon).lane("gpu")
+        });
+
+        let dot = report.to_dot();
+        assert!(dot.contains("t0 [label=\"solve\", style=\"rounded,filled\", fillcolor=\"#ffcc80\"];"));
+        assert!(dot.contains("t1 [label=\"integrate\", style=\"filled\", fillcolor=\"red\"];"));
+        assert!(dot.contains("    subgraph cluster_0 {\n        label=\"gpu\";\n        t2 [label=\"upload\", shape=diamond];\n        t3 [label=\"draw\", shape=hexagon];"));
+
+        let mermaid = report.to_mermaid();
+        assert!(mermaid.contains("t0(\"solve\")"));
+        assert!(mermaid.contains("t2{\"upload\"}"));
+        assert!(mermaid.contains("t3{{\"draw\"}}"));
+        assert!(mermaid.contains("style t0 fill:#ffcc80\n    style t1 fill:red\n"));
+
+        let html = report.to_html();
+        assert!(html.contains("<text class=\"lane\" x=\"10\" y=\"66\">gpu</text>"));
+        assert!(html.contains("rx=\"14\" style=\"fill:#ffcc80\"/>"));
+        assert_eq!(html.matches("<polygon").count(), 2);
+
+        let collapsed = report.collapse("physics");
+        assert!(collapsed.to_dot().contains("t0 [label=\"physics\", style=\"rounded,filled\", fillcolor=\"#ffcc80\"];"));
+        assert_eq!(exec.report().to_dot().matches("fillcolor").count(), 0);
+    }
+
+    #[test]
+    fn spans() {
+        use std::time::{Duration, Instant};
+
+        let closure = crate::tasks::sleep(Duration::from_millis(2));
+
+        let mut builder = builder::<(), &str>();
+        builder.extend(vec![TaskSpec::new("record", closure)]).unwrap();
+
+        let mut exec = builder.build();
+        let late = std::panic::AssertUnwindSafe(|| exec.report().add_span_at("gpu", "late", Instant::now(), Instant::now()));
+        assert!(std::panic::catch_unwind(late).is_err());
+
+        exec.run(&());
+        let mut report = exec.report();
+        report.add_span("gpu graphics", "shadows", Duration::from_millis(1), Duration::from_millis(4));
+        report.add_span("gpu compute", "culling", Duration::from_millis(0), Duration::from_millis(1));
+        report.add_span_at("gpu graphics", "gbuffer", Instant::now(), Instant::now() + Duration::from_millis(3));
+
+        let timeline = report.to_timeline();
+        assert_eq!(timeline.iter().count(), 4);
+        assert_eq!(timeline.first(&"shadows".to_string()).unwrap().len(), Duration::from_millis(3));
+        assert!(timeline.first(&"gbuffer".to_string()).unwrap().start() >= timeline.first(&"record".to_string()).unwrap().end());
+
+        let gantt = report.to_gantt();
+        assert!(gantt.contains("section tasks\n    record : t0"));
+        assert!(gantt.contains("section gpu graphics\n    shadows : s0, 1, 4\n    gbuffer : s2"));
+        assert!(gantt.contains("section gpu compute\n    culling : s1, 0, 1"));
+
+        let html = report.to_html();
+        assert_eq!(html.matches("class=\"external\"").count(), 3);
+        assert!(html.contains(">gpu compute</text>"));
+    }
+
+    #[test]
+    fn allocations() {
+        use std::cell::Cell;
+        use std::sync::Arc;
+
+        thread_local! {
+            static ALLOCATED: Cell<u64> = const { Cell::new(0) };
+        }
+
+        let allocate = |bytes: u64| move |_: &()| ALLOCATED.with(|allocated| allocated.set(allocated.get() + bytes));
+
+        let mut builder = builder::<(), &str>();
+        let ids = builder.extend(vec![
+            TaskSpec::new("small", allocate(16)),
+            TaskSpec::new("large", allocate(4096)),
+            TaskSpec::with_context("arena", |_: &(), context: &crate::interlock::TaskContext<'_, &str>| context.allocated(100)),
+            TaskSpec::new("none", |_: &()| {})
+        ]).unwrap();
+
+        let mut exec = builder.build();
+        exec.run(&());
+        assert_eq!(exec.stats(ids["arena"]).allocated, Some(100));
+        assert_eq!(exec.stats(ids["small"]).allocated, None, "without a counter only reported bytes are known");
+
+        exec.set_allocation_counter(Some(Arc::new(|| ALLOCATED.with(Cell::get))));
+        exec.run(&());
+        exec.run(&());
+
+        let report = exec.report();
+        let allocated: Vec<_> = ["small", "large", "arena", "none"].iter().map(|label| report.stats(ids[*label]).allocated).collect();
+        assert_eq!(allocated, [Some(16), Some(4096), Some(100), Some(0)]);
+        assert_eq!(report.stats(ids["large"]).max_allocated, 4096);
+        assert!(report.to_html().contains("<td>4.0 KiB</td>"));
+    }
+
+    #[test]
+    fn after_run_report() {
+        use std::sync::Mutex;
+
+        let mut builder = builder::<(), &str>();
+        builder.extend(vec![TaskSpec::new("a", |_: &()| {})]).unwrap();
+
+        let durations = Mutex::new(Vec::new());
+        let mut exec = builder.build();
+        exec.after_run(|_: &(), exec: &crate::interlock::InterlockExecutor<'_, (), &str>| durations.lock().unwrap().push(exec.report().duration()));
+        exec.run(&());
+        drop(exec);
+
+        assert!(durations.into_inner().unwrap()[0].is_some(), "after run hooks see the report of the run");
+    }
+
+    #[test]
+    fn timeline() {
+        use crate::interlock::named_builder;
+
+        #[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+        enum Pass { Shadows, Lighting }
+
+        let closure = |_: &()| {};
+
+        let mut builder = named_builder::<(), &str, Pass>();
+        builder.add(closure, [], [], &[]);
+        builder.extend(vec![
+            TaskSpec::new(Pass::Lighting, closure).after([Pass::Shadows]),
+            TaskSpec::new(Pass::Shadows, closure),
+        ]).unwrap();
+
+        let mut exec = builder.build();
+        assert_eq!(exec.timeline().iter().count(), 0);
+
+        exec.run(&());
+        let timeline = exec.timeline();
+        assert_eq!(timeline.iter().count(), 2, "unlabeled tasks are left out");
+        let (shadows, lighting) = (timeline.single(&Pass::Shadows).unwrap(), timeline.single(&Pass::Lighting).unwrap());
+        assert!(shadows.end() <= lighting.start(), "labels reach the timeline as they are");
+        assert_eq!(shadows.thread(), exec.stats(exec.task_by_label(&Pass::Shadows).unwrap()).thread);
+    }
+
+    #[test]
+    fn lock_stats() {
+        use crate::interlock::LockStats;
+
+        let closure = |_: &()| {};
+
+        let mut chained = builder::<(), &str>();
+        let first = chained.add(closure, [], [], &[]);
+        let second = chained.add(closure, [], [], [first]);
+        chained.add(closure, [], [], [second]);
+
+        let mut exec = chained.build();
+        assert_eq!(exec.lock_stats(), LockStats::default());
+
+        exec.run(&());
+        let chain = exec.lock_stats();
+        assert_eq!(chain, LockStats { locks: 0, unlocks: 2, failed_takes: 2 }, "waiting tasks fail to be taken at run start");
+
+        exec.run(&());
+        assert_eq!(exec.lock_stats(), chain, "counts are per run");
+
+        let mut conflicting = builder::<(), &str>();
+        conflicting.add(closure, [], ["x"], &[]);
+        conflicting.add(closure, [], ["x"], &[]);
+
+        let mut exec = conflicting.build();
+        exec.run_with_max_threads(1, &());
+        let stats = exec.lock_stats();
+        assert!(stats.locks > 0 && stats.locks == stats.unlocks, "conflicting tasks lock each other and unlock what they locked");
+    }
+
+    #[test]
+    fn sampling() {
+        use crate::interlock::Sampling;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Duration;
+
+        let slow = AtomicBool::new(false);
+        let mut graph = builder::<(), &str>();
+        let task = graph.add(|_: &()| if slow.load(Ordering::Relaxed) { std::thread::sleep(Duration::from_millis(30)) }, [], [], &[]);
+
+        let mut exec = graph.build();
+        exec.set_sampling(Sampling::Every(3));
+        (0..7).for_each(|_| exec.run(&()));
+        assert_eq!(exec.stats(task).runs, 3, "the first run and every 3rd after it");
+
+        exec.set_sampling(Sampling::Slower(Duration::from_millis(20)));
+        exec.run(&());
+        assert_eq!(exec.stats(task).runs, 3);
+
+        slow.store(true, Ordering::Relaxed);
+        exec.run(&());
+        slow.store(false, Ordering::Relaxed);
+        exec.run(&());
+
+        let stats = exec.stats(task);
+        assert_eq!(stats.runs, 4);
+        assert!(stats.max >= Duration::from_millis(30));
+        assert!(stats.last.is_some_and(|(start, end)| end - start >= Duration::from_millis(30)), "the timeline is the one of the slow run");
+
+        let zero = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| exec.set_sampling(Sampling::Every(0))));
+        assert!(zero.is_err());
+    }
+
+    #[test]
+    fn on_slow_run() {
+        use crate::interlock::Sampling;
+        use std::sync::Mutex;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Duration;
+
+        let slow = AtomicBool::new(false);
+        let traces = Mutex::new(Vec::new());
+
+        let mut graph = builder::<(), &str>();
+        graph.extend(vec![
+            TaskSpec::new("load", |_: &()| if slow.load(Ordering::Relaxed) { std::thread::sleep(Duration::from_millis(30)) }),
+            TaskSpec::new("draw", |_: &()| {}).after(["load"])
+        ]).unwrap();
+
+        let mut exec = graph.build();
+        exec.set_sampling(Sampling::Slower(Duration::from_millis(20)));
+        exec.on_slow_run(Duration::from_millis(20), |run| {
+            assert!(run.duration >= Duration::from_millis(30));
+            assert!(run.report.duration().is_some_and(|duration| duration >= Duration::from_millis(30)), "the report is the one of the slow run");
+            traces.lock().unwrap().push(run.report.to_chrome_trace());
+        });
+
+        for hitch in [false, true, false] {
+            slow.store(hitch, Ordering::Relaxed);
+            exec.run(&());
+        }
+
+        drop(exec);
+        let traces = traces.into_inner().unwrap();
+        assert_eq!(traces.len(), 1);
+        assert!(traces[0].contains("\"load\"") && traces[0].contains("\"draw\""));
+    }
+
+    #[test]
+    fn spawned_slow_run() {
+        use crate::interlock::{Job, Sampling};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+
+        let dumped = AtomicUsize::new(0);
+        let mut graph = builder::<(), &str>();
+        let task = graph.add(|_: &()| std::thread::sleep(Duration::from_millis(5)), [], [], &[]);
+
+        let mut exec = graph.build();
+        exec.set_sampling(Sampling::Slower(Duration::from_millis(1)));
+        exec.on_slow_run(Duration::from_millis(1), |run| {
+            assert!(run.duration >= Duration::from_millis(5));
+            dumped.fetch_add(1, Ordering::Relaxed);
+        });
+
+        exec.run_spawned(&(), &|job: Job| { std::thread::spawn(job); });
+        assert_eq!(exec.stats(task).runs, 1, "spawned runs are recorded like every other run");
+
+        drop(exec);
+        assert_eq!(dumped.load(Ordering::Relaxed), 1);
+    }
+}
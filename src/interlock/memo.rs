@@ -0,0 +1,86 @@
+use super::version::GraphVersion;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Hashes the inputs of a memoized task.
+pub(crate) type Key<'a, T> = dyn Fn(&T) -> u64 + Send + Sync + 'a;
+
+/// Input hash function of a memoized task and the input hash of its last execution.
+pub(crate) struct Memo<'a, T> {
+    key: Arc<Key<'a, T>>,
+    last: Mutex<Option<u64>>
+}
+
+impl<'a, T> Memo<'a, T> {
+
+    pub fn new(key: Arc<Key<'a, T>>) -> Self {
+        Self { key, last: Mutex::new(None) }
+    }
+
+    /// Returns the same memoization without a recorded execution.
+    pub fn duplicate(&self) -> Self {
+        Self::new(self.key.clone())
+    }
+
+    pub fn hash(&self, data: &T) -> u64 {
+        (self.key)(data)
+    }
+
+    /// Returns true if the task last executed with the same input hash.
+    pub fn is_clean(&self, hash: u64) -> bool {
+        *self.last.lock().unwrap() == Some(hash)
+    }
+
+    pub fn record(&self, hash: u64) {
+        *self.last.lock().unwrap() = Some(hash);
+    }
+
+    pub fn last(&self) -> Option<u64> {
+        *self.last.lock().unwrap()
+    }
+}
+
+/**
+ Input hashes of the last execution of memoized tasks, by label.
+ Take it from an executor with `InterlockExecutor::memo_cache` and restore it with `restore_memo_cache`,
+ e.g. to skip clean tasks after a restart. Serializable with the `serde` feature.
+*/
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoCache {
+    hashes: HashMap<String, u64>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    version: Option<GraphVersion>
+}
+
+impl MemoCache {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn with_version(version: GraphVersion) -> Self {
+        Self { hashes: HashMap::new(), version: Some(version) }
+    }
+
+    /// Returns the structure of the graph the cache was taken from, `None` for caches created with `new`.
+    pub fn version(&self) -> Option<&GraphVersion> {
+        self.version.as_ref()
+    }
+
+    pub fn get(&self, label: &str) -> Option<u64> {
+        self.hashes.get(label).copied()
+    }
+
+    pub fn insert(&mut self, label: impl Into<String>, hash: u64) {
+        self.hashes.insert(label.into(), hash);
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+}
@@ -1,45 +1,943 @@
 pub mod builder;
+pub mod resource;
+pub mod diff;
+pub mod plan;
+pub mod remote;
+mod app;
 mod cell;
+mod checkpoint;
+mod commands;
+mod error;
 mod context;
+mod dispatch;
+mod fixed;
+mod impact;
+mod independent;
+mod info;
+mod memo;
+mod pipeline;
+mod pool;
+mod run;
+mod scratch;
+mod semaphore;
+mod shutdown;
+mod signal;
+mod spawn;
+mod supervise;
 mod task;
+mod timestep;
+mod verify;
+mod version;
+#[cfg(feature = "inspector")]
+mod stats;
+#[cfg(feature = "inspector")]
+mod inspector;
+#[cfg(feature = "inspector")]
+mod soak;
+#[cfg(feature = "async")]
+mod completions;
+
+pub use self::app::{App, Schedule, StageStats};
+pub use self::cell::{CellState, CountCell, CountRef};
+pub use self::checkpoint::Checkpoint;
+pub use self::commands::CommandBuffer;
+pub use self::dispatch::Dispatch;
+pub use self::error::{BuildError, BuildWarning, Described};
+pub use self::fixed::{StaticGraph, StaticInterlock, StaticSchedule};
+pub use self::impact::{GraphStats, SplitImpact};
+pub use self::info::{TaskInfo, TaskIter};
+pub use self::memo::MemoCache;
+pub use self::pipeline::{Backpressure, Pipeline, StreamStats};
+pub use self::pool::{Parallelism, PoolInfo, Seeding};
+#[cfg(feature = "affinity")]
+pub use self::pool::pinned_pool;
+pub use self::run::{ContextExecutable, TaskContext};
+pub use self::scratch::{Scratch, ScratchStats};
+pub use self::shutdown::{ShutdownHandle, ShutdownReport};
+pub use self::spawn::{Job, Spawn};
+pub use self::supervise::{Supervision, SupervisionStats};
+pub use self::task::{Priority, TaskId};
+pub use self::timestep::{FixedTimestep, Tick, Ticks};
+pub use self::verify::Violation;
+pub use self::version::{GraphVersion, VersionMismatch};
+#[cfg(feature = "inspector")]
+pub use self::stats::{LockStats, Sampling, TaskStats};
+#[cfg(feature = "inspector")]
+pub use self::inspector::{ExecutionReport, LABEL_SEPARATOR, Shape, SlowRun, Style};
+#[cfg(feature = "inspector")]
+pub use self::soak::{Drift, Metric, Soak, SoakReport, SoakWindow, WindowStats};
+#[cfg(feature = "async")]
+pub use self::completions::{Completions, Next, TaskCompleted, TaskStatus};
 
 use crate::Executable;
 use self::builder::InterlockBuilder;
-use self::context::Context;
-use self::task::Task;
+use self::checkpoint::Checkpoints;
+use self::context::{Context, Env, Slot, TaskStore};
+use self::diff::GraphDiff;
+use self::plan::Plan;
+use self::pool::Pools;
+use self::remote::{Remote, Transport};
+use self::resource::{Fairness, Resources};
+use self::run::Changes;
+use self::semaphore::Semaphore;
+use self::shutdown::Drain;
+use self::supervise::Supervisor;
+use self::task::{SharedSlot, Task};
+use rayon::ThreadPool;
+use std::any::Any;
+use std::borrow::Borrow;
+use std::cmp::Reverse;
 use std::hash::Hash;
-use std::fmt::{Debug, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 use std::fmt;
-use std::iter::FromIterator;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Time a task may run before `TaskContext::should_yield` returns true, unless changed with `InterlockExecutor::set_time_slice`.
+pub const DEFAULT_TIME_SLICE: Duration = Duration::from_millis(1);
 
 pub fn builder<'task, T: Sync, R: Eq + Hash>() -> InterlockBuilder<'task, T, R> {
     InterlockBuilder::new()
 }
 
-pub struct InterlockExecutor<'task, T> {
-    tasks: Vec<Task<'task, T>>
+/// Builder only accepting tasks that own everything they use, see `StaticExecutor`.
+pub type StaticBuilder<T, R> = InterlockBuilder<'static, T, R>;
+
+/**
+ Executor without a task lifetime, e.g. to keep a graph in a global or a long-lived engine struct.
+ Wrap it in a `Mutex` to run it from a static, or use `run_shared` on graphs of shared tasks.
+*/
+pub type StaticExecutor<T, R> = InterlockExecutor<'static, T, R>;
+
+pub fn static_builder<T: Sync, R: Eq + Hash>() -> StaticBuilder<T, R> {
+    InterlockBuilder::new()
+}
+
+/// Creates a builder labeling tasks with `N`, see `InterlockBuilder::named`.
+pub fn named_builder<'task, T: Sync, R: Eq + Hash, N: Eq + Hash + Clone>() -> InterlockBuilder<'task, T, R, N> {
+    InterlockBuilder::named()
+}
+
+/// Called on the calling thread before or after a run, see `InterlockExecutor::before_run`.
+type Hook<'a, T, R, N> = dyn Fn(&T, &InterlockExecutor<'a, T, R, N>) + Send + Sync + 'a;
+
+/**
+ Executor of a built graph.
+
+ It is `Send` if `R` is `Send`, since tasks, parent functions and resolvers all have to be `Send + Sync`
+ already, so it can be moved into another thread or stored in a struct that is. It is `Sync` if `R` is `Sync`:
+ `run` needs exclusive access, and `run_shared` only executes tasks that are `Sync` themselves.
+*/
+pub struct InterlockExecutor<'task, T, R, N = String> {
+    tasks: Vec<Task<'task, T, R, N>>,
+    resources: Resources<'task, T, R>,
+    changes: Changes,
+    pools: Pools,
+    parallelism: Parallelism,
+    seeding: Seeding,
+    priority: Priority,
+    fairness: Fairness,
+    slice: Option<Duration>,
+    order: Vec<usize>,
+    semaphores: Vec<Semaphore>,
+    remote: Option<Remote<'task>>,
+    runs: AtomicU64,
+    checkpoints: Option<Checkpoints<'task>>,
+    resumed: Vec<bool>,
+    before_run: Vec<Arc<Hook<'task, T, R, N>>>,
+    after_run: Vec<Arc<Hook<'task, T, R, N>>>,
+    tick: Option<Tick>,
+    drain: Option<Arc<Drain>>,
+    scratch: Scratch,
+    /// Whether the initial counts were checked since the locks last changed, see `context::check_initial_counts`.
+    #[cfg(debug_assertions)]
+    checked: AtomicBool,
+    #[cfg(feature = "inspector")]
+    stats: stats::Stats,
+    #[cfg(feature = "inspector")]
+    allocations: Option<Arc<dyn Fn() -> u64 + Send + Sync + 'task>>,
+    #[cfg(feature = "async")]
+    subscribers: Vec<completions::Sender>
+}
+
+impl<'task, T: Sync, R: Eq + Hash, N> InterlockExecutor<'task, T, R, N> {
+
+    /// Creates the executor of `tasks`, with a semaphore of `capacities[idx]` permits for every index tasks take permits of.
+    pub(crate) fn new(tasks: Vec<Task<'task, T, R, N>>, resources: Resources<'task, T, R>, changes: Changes, capacities: &[usize]) -> Self {
+        let realtime = tasks.iter().any(Task::is_realtime);
+
+        let mut executor = Self {
+            #[cfg(feature = "inspector")]
+            stats: stats::Stats::new(tasks.len()),
+            #[cfg(feature = "inspector")]
+            allocations: None,
+            #[cfg(feature = "async")]
+            subscribers: Vec::new(),
+            semaphores: capacities.iter().map(|&permits| Semaphore::new(permits)).collect(),
+            remote: None,
+            runs: AtomicU64::new(0),
+            checkpoints: None,
+            resumed: Vec::new(),
+            before_run: Vec::new(),
+            after_run: Vec::new(),
+            tick: None,
+            drain: None,
+            scratch: Scratch::new(0),
+            #[cfg(debug_assertions)]
+            checked: AtomicBool::new(false),
+            order: (0..tasks.len()).collect(),
+            tasks, resources, changes,
+            pools: Pools::default(),
+            parallelism: Parallelism::Full,
+            seeding: Seeding::Lazy,
+            priority: Priority::Declaration,
+            fairness: Fairness::Unordered,
+            slice: Some(DEFAULT_TIME_SLICE)
+        };
+
+        //lists of a fresh graph are in declaration order already
+        if realtime {
+            executor.prioritize();
+        }
+
+        executor
+    }
+
+    /// Orders tasks that are ready at the same time: realtime tasks first, then writers if they have priority, then by priority and declaration.
+    fn prioritize(&mut self) {
+        let weights = match self.priority {
+            Priority::Declaration => vec![0; self.tasks.len()],
+            Priority::Fanout => fanout(&self.tasks)
+        };
+
+        let readers = match self.fairness {
+            Fairness::WriterPriority => {
+                let (writes, write_all) = self.resources.table().writes(self.tasks.len());
+                writes.iter().zip(write_all).map(|(writes, all)| writes.is_empty() && !all).collect()
+            },
+
+            _ => vec![false; self.tasks.len()]
+        };
+
+        let tasks = &self.tasks;
+        self.order.sort_by_key(|&id| (!tasks[id].is_realtime(), readers[id], Reverse(weights[id]), id));
+
+        let mut rank = vec![0; self.order.len()];
+        self.order.iter().enumerate().for_each(|(idx, &id)| rank[id] = idx);
+        self.tasks.iter_mut().for_each(|task| task.prioritize(&rank));
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Returns the label of `task`, if it has one.
+    pub fn label(&self, task: TaskId) -> Option<&N> {
+        self.tasks[task.id()].label()
+    }
+
+    /**
+     Returns the task labeled `label`, e.g. to look up a task named in a config file or typed into a console.
+     Labels are compared one by one, so look tasks up once rather than on every frame.
+    */
+    pub fn task_by_label<Q: Eq + ?Sized>(&self, label: &Q) -> Option<TaskId> where N: Borrow<Q> {
+        self.tasks.iter().find(|task| task.label().is_some_and(|other| other.borrow() == label)).map(Task::id)
+    }
+
+    /// Returns the label, accesses and dependencies of `task`, see `iter`.
+    pub fn task(&self, task: TaskId) -> TaskInfo<'_, R, N> {
+        TaskInfo::of(self, task)
+    }
+
+    /**
+     Returns the tasks in declaration order with their labels, static accesses and dependencies,
+     e.g. to export the graph to another tool. Also available by iterating over `&executor`.
+    */
+    pub fn iter(&self) -> TaskIter<'_, 'task, T, R, N> {
+        TaskIter::new(self)
+    }
+
+    /**
+     Reserves `pool` for realtime tasks, see `InterlockBuilder::realtime`. Realtime tasks then execute on its threads,
+     so they never wait for a busy bulk thread to pick them up, while the graph still orders them with all other tasks.
+     Parallel work spawned by realtime tasks runs on the reserved pool as well.
+    */
+    pub fn reserve_realtime(&mut self, pool: Arc<ThreadPool>) {
+        self.pools.reserve_realtime(pool);
+    }
+
+    /**
+     Distributes tasks over `nodes`, e.g. one pinned pool per socket created with `pinned_pool`. Tasks accessing
+     the same resources execute on the same pool, so the data they share stays in the caches of one socket.
+     Tasks without resources execute on the pool the run started on, realtime tasks on the reserved pool, if any.
+
+     Panics if `nodes` is empty.
+    */
+    pub fn distribute(&mut self, nodes: Vec<Arc<ThreadPool>>) {
+        assert!(!nodes.is_empty(), "tasks have to be distributed over at least one pool");
+
+        let groups = self.resources.table().groups(self.tasks.len());
+        let mut sizes = vec![0usize; self.tasks.len()];
+        groups.iter().flatten().for_each(|&group| sizes[group] += 1);
+
+        //largest groups first, each onto the least loaded node
+        let mut order: Vec<_> = (0..sizes.len()).filter(|&group| sizes[group] > 0).collect();
+        order.sort_by_key(|&group| Reverse(sizes[group]));
+
+        let mut loads = vec![0usize; nodes.len()];
+        let mut assigned = vec![0; sizes.len()];
+        for group in order {
+            let node = (0..nodes.len()).min_by_key(|&node| loads[node]).unwrap();
+            loads[node] += sizes[group];
+            assigned[group] = node;
+        }
+
+        let placement = groups.into_iter().map(|group| group.map(|group| assigned[group])).collect();
+        self.pools.distribute(nodes, placement);
+    }
+
+    /**
+     Executes `task` on a thread of its own outside every pool, e.g. a stage reading from disk or the network, so its
+     blocking calls don't hold a worker other tasks could run on. The worker that started it keeps executing other
+     tasks until it completes, and the graph orders it with all other tasks as usual. Parallel work spawned by the task
+     runs on its thread as well. Overrides `reserve_realtime` and `distribute` for the task.
+
+     Panics if the task is dedicated already.
+    */
+    pub fn dedicate(&mut self, task: TaskId) {
+        assert!(!self.pools.is_dedicated(task.id()), "can't dedicate a thread to a task twice");
+        self.pools.dedicate(task.id(), self.tasks.len());
+    }
+
+    /**
+     Changes how many threads the following runs use without rebuilding the graph, e.g. to fall back to a single
+     thread while on battery. `run` and `run_shared` both follow it, `run_with_max_threads` overrides it.
+
+     Panics on `Parallelism::Threads(0)`.
+    */
+    pub fn set_parallelism(&mut self, parallelism: Parallelism) {
+        assert_ne!(parallelism, Parallelism::Threads(0), "a run needs at least one thread");
+        self.parallelism = parallelism;
+    }
+
+    pub fn parallelism(&self) -> Parallelism {
+        self.parallelism
+    }
+
+    /**
+     Returns a handle to shut the executor down from another thread, see `ShutdownHandle::shutdown`.
+     Runs track their tasks for it from the first handle on.
+    */
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        let tasks = &self.tasks;
+        let drain = self.drain.get_or_insert_with(|| Arc::new(Drain::new(tasks.iter().map(Task::id).collect())));
+        ShutdownHandle::new(drain.clone())
+    }
+
+    /// Returns whether the executor was shut down, its runs then skip every task.
+    pub fn is_shut_down(&self) -> bool {
+        self.drain.as_ref().is_some_and(|drain| drain.is_requested())
+    }
+
+    /**
+     Replaces the parameters of `task` from the next run on, e.g. to tune an iteration count from a console.
+     Panics unless the task was added with parameters of type `P`, see `InterlockBuilder::add_with_params`.
+    */
+    pub fn configure<P: Send + Sync + 'static>(&mut self, task: TaskId, params: P) {
+        let task = &mut self.tasks[task.id()];
+        assert!(task.params().is_some_and(|params| params.is::<P>()), "can't configure a task with parameters of another type");
+        task.set_params(Some(Arc::new(params)));
+    }
+
+    /// Returns the parameters of `task` if they are a `P`, see `configure`.
+    pub fn params<P: 'static>(&self, task: TaskId) -> Option<&P> {
+        self.tasks[task.id()].params()?.downcast_ref()
+    }
+
+    /// Returns the failure counters of `task`, or `None` if it isn't supervised, see `InterlockBuilder::supervise`.
+    pub fn supervision(&self, task: TaskId) -> Option<SupervisionStats> {
+        self.tasks[task.id()].supervisor().map(Supervisor::stats)
+    }
+
+    /// Returns the tasks their supervision disabled, in declaration order, e.g. to warn about them after a run.
+    pub fn disabled(&self) -> Vec<TaskId> {
+        self.tasks.iter().filter(|task| task.supervisor().is_some_and(Supervisor::is_disabled)).map(Task::id).collect()
+    }
+
+    /// Executes a task its supervision disabled again from the next run on and forgets its consecutive failures.
+    pub fn enable(&mut self, task: TaskId) {
+        self.tasks[task.id()].supervisor().expect("can't enable a task that isn't supervised").enable();
+    }
+
+    /**
+     Returns the scratch memory the tasks of the last run allocated, see `TaskContext::scratch`. Shared runs don't
+     free it, their allocations add up until the next run.
+    */
+    pub fn scratch_stats(&self) -> ScratchStats {
+        self.scratch.stats()
+    }
+
+    /// Grows the scratch memory to at least `bytes`, so runs don't fall back to the global allocator until it grew on its own.
+    pub fn reserve_scratch(&mut self, bytes: usize) {
+        self.scratch.reserve(bytes);
+    }
+
+    /// Changes how runs on a pool start the tasks that are ready at run start, see `Seeding`.
+    pub fn set_seeding(&mut self, seeding: Seeding) {
+        self.seeding = seeding;
+    }
+
+    pub fn seeding(&self) -> Seeding {
+        self.seeding
+    }
+
+    /// Changes the order in which tasks that are ready at the same time start, see `Priority`.
+    pub fn set_priority(&mut self, priority: Priority) {
+        self.priority = priority;
+        self.prioritize();
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    /// Applies the fairness the graph was built with, see `InterlockBuilder::fairness`.
+    pub(crate) fn set_fairness(&mut self, fairness: Fairness) {
+        self.fairness = fairness;
+        if fairness == Fairness::WriterPriority {
+            self.prioritize();
+        }
+    }
+
+    /**
+     Returns a stream of the tasks finishing in the following runs, e.g. for an async supervisor streaming
+     partial results while a run is still in progress. Events of the whole run are kept until they are taken.
+    */
+    #[cfg(feature = "async")]
+    pub fn completions(&mut self) -> Completions {
+        let (sender, completions) = completions::channel();
+        self.subscribers.push(sender);
+        completions
+    }
+
+    /**
+     Sets how long a task may run before `TaskContext::should_yield` tells it to suspend,
+     `None` never asks tasks to yield.
+    */
+    pub fn set_time_slice(&mut self, slice: Option<Duration>) {
+        self.slice = slice;
+    }
+
+    /// Sets the tick tasks of the following runs see in `TaskContext::tick`, set by `FixedTimestep::run` for each of its runs.
+    pub fn set_tick(&mut self, tick: Option<Tick>) {
+        self.tick = tick;
+    }
+
+    pub fn tick(&self) -> Option<Tick> {
+        self.tick
+    }
+
+    /**
+     Sends remote tasks to workers through `transport` in the following runs, see `InterlockBuilder::remote`.
+     Remote tasks execute their local body again after the transport was removed with `None`.
+     Workers receive the labels as strings.
+
+     Panics if a remote task has no label.
+    */
+    pub fn set_transport(&mut self, transport: Option<Arc<dyn Transport + 'task>>) where N: Display {
+        if let Some(task) = self.tasks.iter().find(|task| task.is_remote() && task.label().is_none()) {
+            panic!("remote task {:?} has no label", task.id());
+        }
+
+        //labels are rendered once, runs only clone them
+        let labels = |tasks: &[Task<'task, T, R, N>]| tasks.iter()
+            .map(|task| task.label().filter(|_| task.is_remote()).map(N::to_string))
+            .collect();
+
+        self.remote = transport.map(|transport| Remote { transport, labels: labels(&self.tasks) });
+    }
+
+    /**
+     Checkpoints the following runs: after every completed task, `sink` receives the tasks the run completed so far
+     together with the snapshots they saved with `TaskContext::save`, e.g. to write them to disk. Tasks skipped
+     as clean count as completed, non-idempotent tasks are also recorded when they start. A crashed process then
+     continues with `resume_from_checkpoint`. Sinks execute one at a time without blocking the tasks completing
+     meanwhile; the checkpoints of those tasks are merged, so a sink only receives the latest one.
+    */
+    pub fn checkpoint(&mut self, sink: impl Fn(&Checkpoint) + Send + Sync + 'task) where N: Display {
+        self.checkpoints = Some(Checkpoints::new(Arc::new(sink), self.version()));
+    }
+
+    /**
+     Calls `hook` on the calling thread before every following run, strictly before the first task starts,
+     e.g. to acquire a frame fence. Hooks are called in the order they were added.
+    */
+    pub fn before_run(&mut self, hook: impl Fn(&T, &Self) + Send + Sync + 'task) {
+        self.before_run.push(Arc::new(hook));
+    }
+
+    /**
+     Calls `hook` on the calling thread after every following run that didn't panic, strictly after the last task
+     finished, e.g. to submit command buffers. With the `inspector` feature, `report` already includes the run.
+    */
+    pub fn after_run(&mut self, hook: impl Fn(&T, &Self) + Send + Sync + 'task) {
+        self.after_run.push(Arc::new(hook));
+    }
+
+    /// Returns how many threads a run started from the calling thread uses, see `PoolInfo::is_oversubscribed`.
+    pub fn pool_info(&self) -> PoolInfo {
+        self.pools.info()
+    }
+
+    /// Returns the structure of the graph that persisted plans, memo caches and checkpoints are checked against.
+    pub fn version(&self) -> GraphVersion where N: Display {
+        GraphVersion::new(self)
+    }
+
+    /**
+     Returns a hash of the structure of the graph: labels, order of the tasks, dependencies and static accesses.
+     It is the same in every process and on every platform running the same build, e.g. for tests asserting that
+     the frame graph didn't change or to key caches on the graph. Bodies and settings of the executor aren't part
+     of it. It isn't stable across releases, see `GraphVersion`.
+    */
+    pub fn fingerprint(&self) -> u64 where N: Display {
+        self.version().fingerprint()
+    }
+
+    /// Returns the input hashes of the last execution of all labeled memoized tasks.
+    pub fn memo_cache(&self) -> MemoCache where N: Display {
+        let mut cache = MemoCache::with_version(self.version());
+        for task in self.tasks.iter() {
+            if let (Some(label), Some(hash)) = (task.label(), task.memo().and_then(|memo| memo.last())) {
+                cache.insert(label.to_string(), hash);
+            }
+        }
+
+        cache
+    }
+
+    /**
+     Restores input hashes of memoized tasks by label, tasks with a matching hash are skipped in the next run.
+     Returns an error and restores nothing if the cache was taken from a graph with a different structure.
+    */
+    pub fn restore_memo_cache(&mut self, cache: &MemoCache) -> Result<(), VersionMismatch> where N: Display {
+        if let Some(version) = cache.version() {
+            version.check(&self.version())?;
+        }
+
+        for task in self.tasks.iter() {
+            if let (Some(hash), Some(memo)) = (task.label().and_then(|label| cache.get(&label.to_string())), task.memo()) {
+                memo.record(hash);
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     Runs the graph without exclusive access, so several runs over different data can happen at the same time.
+     Every run keeps its own counters, tasks are shared between runs and may execute concurrently for different runs.
+
+     Panics if any task wasn't added with `InterlockBuilder::add_shared`, or if the graph resolves resources at run start.
+    */
+    pub fn run_shared(&self, data: &T) where R: Sync, N: Sync {
+        if self.resources.has_resolvers() {
+            panic!("graphs resolving resources at run start can't be run shared");
+        }
+
+        let slots: Vec<_> = self.tasks.iter()
+            .map(|task| match task.shared() {
+                Some(shared) => SharedSlot::new(shared),
+                None => panic!("task {:?} isn't shared", task.id())
+            })
+            .collect();
+
+        self.run_slots(data, &slots, self.parallelism, None)
+    }
+
+    /**
+     Runs the graph with the bodies of `bodies` instead of the ones its tasks were built with, which don't execute,
+     e.g. add them as `|_: &T| {}`. Everything else is as in a plain run. See `Dispatch`.
+
+     Panics unless there is a body for every task.
+    */
+    pub fn run_dispatched<E: Executable<T> + Send>(&mut self, data: &T, bodies: &Dispatch<E>) where R: Sync, N: Sync {
+        assert_eq!(bodies.len(), self.tasks.len(), "a dispatched run needs a body for every task");
+
+        self.prepare(data);
+        self.run_slots(data, bodies.slots(), self.parallelism, None)
+    }
+
+    /**
+     Creates an independent copy of the graph with fresh task instances, e.g. to run the same graph
+     on several threads over different data. Returns `None` if any task wasn't added with a factory.
+    */
+    pub fn duplicate(&self) -> Option<Self> where R: Clone, N: Clone {
+        let tasks = self.tasks.iter().map(Task::duplicate).collect::<Option<_>>()?;
+        let capacities: Vec<_> = self.semaphores.iter().map(Semaphore::permits).collect();
+        let mut duplicate = Self::new(tasks, self.resources.duplicate(), self.changes.duplicate(), &capacities);
+        duplicate.pools = self.pools.duplicate();
+        duplicate.parallelism = self.parallelism;
+        duplicate.fairness = self.fairness;
+        duplicate.set_priority(self.priority);
+        duplicate.slice = self.slice;
+        duplicate.remote = self.remote.clone();
+        duplicate.before_run = self.before_run.clone();
+        duplicate.after_run = self.after_run.clone();
+        duplicate.tick = self.tick;
+        duplicate.scratch.reserve(self.scratch.stats().capacity);
+        #[cfg(feature = "inspector")]
+        {
+            duplicate.allocations = self.allocations.clone();
+            duplicate.stats.set_sampling(self.stats.sampling());
+        }
+        duplicate.checkpoints = self.checkpoints.as_ref().map(Checkpoints::duplicate);
+        Some(duplicate)
+    }
+
+    /**
+     Compares this graph with `other`, e.g. to show what a reload changed. Tasks are matched by label,
+     tasks only `other` has are reported as added and tasks only this graph has as removed.
+    */
+    pub fn diff<'a>(&'a self, other: &'a Self) -> GraphDiff<'a, R, N> where N: Eq + Hash {
+        diff::diff(self, other)
+    }
+
+    /**
+     Partitions the tasks into sets of mutually independent tasks: no task of a set waits for another one, directly or
+     through other tasks, and none conflicts with another one, e.g. to assign the tasks of a set to different processes.
+     Tasks are placed in the first set they fit in, in declaration order, so the first set can't take any other task.
+     Conflicts of resources resolved at run start aren't known and ignored. Takes memory quadratic in the number of tasks.
+    */
+    pub fn independent_sets(&self) -> Vec<Vec<TaskId>> {
+        independent::independent_sets(self)
+    }
+
+    /// Returns whether no task of `tasks` waits for or conflicts with another one, see `independent_sets`.
+    pub fn are_independent(&self, tasks: &[TaskId]) -> bool {
+        independent::are_independent(self, tasks)
+    }
+
+    /// Returns the state of a run, dry runs don't get a number of their own.
+    fn env(&self, dry: bool) -> Env<'_, R> {
+        Env {
+            changes: &self.changes,
+            table: self.resources.table(),
+            parent: self.resources.parent(),
+            pools: &self.pools,
+            slice: self.slice,
+            seeding: self.seeding,
+            order: &self.order,
+            semaphores: &self.semaphores,
+            remote: self.remote.as_ref(),
+            run: match dry {
+                true => self.runs.load(Ordering::Relaxed),
+                false => self.runs.fetch_add(1, Ordering::Relaxed) + 1
+            },
+            checkpoints: self.checkpoints.as_ref(),
+            resumed: &self.resumed,
+            compensable: Mutex::new(Vec::new()),
+            tick: self.tick,
+            drain: self.drain.as_deref(),
+            scratch: &self.scratch,
+            dry,
+            extra: None,
+            #[cfg(feature = "inspector")]
+            stats: &self.stats,
+            #[cfg(feature = "inspector")]
+            allocations: self.allocations.as_deref(),
+            #[cfg(feature = "async")]
+            subscribers: &self.subscribers
+        }
+    }
+
+    fn resolve(&mut self, data: &T) {
+        if self.resources.update(data) {
+            let tasks = &mut self.tasks;
+
+            tasks.iter_mut().for_each(|task| task.clear_dynamic_locks());
+            self.resources.conflicts(|current, next| tasks[next.id()].add_dynamic_lock(current));
+
+            #[cfg(debug_assertions)]
+            self.checked.store(false, Ordering::Relaxed);
+        }
+    }
+
+}
+
+impl<'task, T: Sync, R: Eq + Hash> InterlockExecutor<'task, T, R> {
+
+    /**
+     Returns the structure of the graph to persist it and skip building it at the next start, see `Plan::hydrate`.
+     Every task needs a label to be matched with its body again.
+    */
+    pub fn plan(&self) -> Result<Plan<R>, BuildError> where R: Clone {
+        Plan::new(self)
+    }
 }
 
-impl<'task, T: Sync> FromIterator<Task<'task, T>> for InterlockExecutor<'task, T> {
+impl<'task, T: Sync, R: Eq + Hash + Sync, N: Sync> InterlockExecutor<'task, T, R, N> {
+
+    /**
+     Runs the graph on at most `threads` threads, including parallel work spawned by its tasks, e.g. to leave cores
+     to other work. Runs on the current pool if it isn't larger, otherwise on a pool the executor keeps for the next
+     capped runs. Realtime tasks still execute on the reserved pool, if any.
+
+     Panics if `threads` is 0.
+    */
+    pub fn run_with_max_threads(&mut self, threads: usize, data: &T) {
+        assert!(threads > 0, "a run needs at least one thread");
+        self.run_with(Parallelism::Threads(threads), data);
+    }
+
+    /**
+     Runs every task as a job of `spawner` instead of on a rayon pool, e.g. on the blocking pool of an async runtime
+     that is already there: `exec.run_spawned(&data, &|job| { tokio::task::spawn_blocking(job); })`.
+     Tasks are ordered and excluded like in every other run. Blocks until all tasks finished, so async callers
+     should call it from a blocking context themselves. Tasks don't yield, and realtime or distributed tasks
+     execute on the spawner as well. Tasks waiting for a permit of a resource with a capacity don't occupy a job,
+     see `InterlockBuilder::capacity`.
+
+     Panics if a task panics, after all other started tasks finished. A spawner dropping a job without running it
+     counts as a panic.
+    */
+    pub fn run_spawned(&mut self, data: &T, spawner: &(impl Spawn + Sync)) {
+        self.prepare(data);
+        self.execute(data, &self.tasks, None, |context| context.run_spawned(spawner));
+    }
+
+    /// Runs the graph once with `tick`, see `set_tick`. The tick is cleared afterwards, even if the run panics.
+    pub(crate) fn run_tick(&mut self, data: &T, tick: Tick) {
+        struct Clear<'e, 'task, T: Sync, R: Eq + Hash, N>(&'e mut InterlockExecutor<'task, T, R, N>);
+
+        impl<T: Sync, R: Eq + Hash, N> Drop for Clear<'_, '_, T, R, N> {
+
+            fn drop(&mut self) {
+                self.0.set_tick(None);
+            }
+        }
+
+        self.set_tick(Some(tick));
+        Clear(self).0.run(data);
+    }
+
+    /**
+     Continues the run `checkpoint` was taken from: completed tasks are skipped and count as finished for their
+     dependants, all others execute as usual. The graph has to be built the same way as the checkpointed one,
+     tasks are matched by index. Checkpoints of the resumed run include the tasks completed before.
+
+     Non-idempotent tasks that were interrupted don't execute again, their branch stops instead: returns those tasks
+     and their dependants, which are skipped as well. Start a fresh run to execute them again.
+
+     Returns an error without running anything if the checkpoint was taken from a graph with a different structure.
+    */
+    pub fn resume_from_checkpoint(&mut self, checkpoint: &Checkpoint, data: &T) -> Result<Vec<TaskId>, VersionMismatch> where N: Display {
+        if let Some(version) = checkpoint.version() {
+            version.check(&self.version())?;
+        }
+
+        self.prepare(data);
+
+        //dependants always come after their dependencies
+        let mut stopped: Vec<_> = (0..self.tasks.len()).map(|id| checkpoint.is_interrupted_at(id)).collect();
+        for id in 0..self.tasks.len() {
+            if stopped[id] {
+                self.tasks[id].dependants().iter().for_each(|dependant| stopped[dependant.id()] = true);
+            }
+        }
+
+        self.resumed = stopped.iter().enumerate().map(|(id, &stopped)| stopped || checkpoint.contains(id)).collect();
+
+        if let Some(checkpoints) = &self.checkpoints {
+            checkpoints.reset(checkpoint.clone());
+        }
+
+        //shared runs don't prepare, so the skipped tasks must not outlive this run, even if it panics
+        let result = panic::catch_unwind(AssertUnwindSafe(|| self.run_slots(data, &self.tasks, self.parallelism, None)));
+        self.resumed.clear();
+        if let Err(payload) = result {
+            panic::resume_unwind(payload);
+        }
+
+        Ok(self.tasks.iter()
+            .filter(|task| stopped[task.id().id()] && !checkpoint.contains(task.id().id()))
+            .map(Task::id)
+            .collect())
+    }
+
+    fn prepare(&mut self, data: &T) {
+        self.resolve(data);
+        self.tasks.iter_mut().for_each(Task::restart);
+        self.scratch.reset();
+        self.resumed.clear();
+
+        if let Some(checkpoints) = &self.checkpoints {
+            checkpoints.reset(Checkpoint::new());
+        }
+
+        #[cfg(feature = "async")]
+        self.subscribers.retain(completions::Sender::is_subscribed);
+
+        #[cfg(feature = "inspector")]
+        self.stats.begin();
+    }
+
+    /**
+     Runs the graph and then applies the commands its tasks pushed to `commands` with `apply`, in the order they were
+     pushed, so tasks can mutate the data without breaking shared access during the run. Commands pushed before
+     the run are applied as well; if a task panics, the commands stay in the buffer.
+    */
+    pub fn run_deferred<C>(&mut self, data: &mut T, commands: &CommandBuffer<C>, mut apply: impl FnMut(&mut T, C)) {
+        self.run_with(self.parallelism, data);
+        commands.take().into_iter().for_each(|command| apply(data, command));
+    }
+
+    fn run_with(&mut self, parallelism: Parallelism, data: &T) {
+        self.prepare(data);
+        self.run_slots(data, &self.tasks, parallelism, None)
+    }
+
+    /**
+     Runs the graph with `extra` data for this run only, e.g. the input events and the delta time of a frame, which
+     then don't have to live in the shared data. Tasks receive it through `TaskContext::extra`, or as a second
+     parameter if they were added with `InterlockBuilder::add_with_extra`.
+    */
+    pub fn run_with_extra<E: Sync + 'static>(&mut self, data: &T, extra: &E) {
+        self.prepare(data);
+        self.run_slots(data, &self.tasks, self.parallelism, Some(extra))
+    }
+
+    /// Checks the initial counts once per change of the locks rather than every run, which keeps runs free of allocations.
+    #[cfg(debug_assertions)]
+    fn check(&self) {
+        if !self.checked.swap(true, Ordering::Relaxed) {
+            context::check_initial_counts(&self.tasks);
+        }
+    }
+
+    fn run_slots<'r, S: Slot<'r, T, R>>(&'r self, data: &'r T, slots: &'r [S], parallelism: Parallelism, extra: Option<&'r (dyn Any + Sync)>) {
+        self.execute(data, slots, extra, |context| self.start(context, parallelism))
+    }
+
+    /// Runs the hooks around `run`, which executes the tasks of `context`, times the run and compensates it if it failed.
+    fn execute<'r, S: Slot<'r, T, R>>(&'r self, data: &'r T, slots: &'r [S], extra: Option<&'r (dyn Any + Sync)>,
+                                      run: impl FnOnce(&Context<'r, 'task, T, R, S, N, Vec<Task<'task, T, R, N>>>)) {
+        self.before_run.iter().for_each(|hook| hook(data, self));
+
+        #[cfg(debug_assertions)]
+        self.check();
+
+        let _run = self.drain.as_deref().map(Drain::begin);
+        let context = Context::new(data, &self.tasks, slots, Env { extra, ..self.env(false) });
+
+        #[cfg(feature = "inspector")]
+        let started = std::time::Instant::now();
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| run(&context)));
+
+        #[cfg(feature = "inspector")]
+        self.stats.finish(started);
+
+        //tasks that didn't execute are left locked, the next run resets them
+        if result.is_err() {
+            slots.iter().for_each(Slot::complete);
+        }
+
+        self.compensate(data, context.take_compensable(), result);
+
+        self.after_run.iter().for_each(|hook| hook(data, self));
+    }
+
+    fn start<'r, S, TS, SS>(&self, context: &Context<'r, 'task, T, R, S, N, TS, SS>, parallelism: Parallelism)
+        where S: Slot<'r, T, R>, TS: TaskStore<Task<'task, T, R, N>> + ?Sized, SS: TaskStore<S> + ?Sized {
+        match parallelism {
+            Parallelism::Threads(threads) if threads < rayon::current_num_threads() => {
+                self.pools.capped(threads).install(|| context.run())
+            },
+
+            Parallelism::Sequential => context.run_sequential(),
+            _ => context.run()
+        }
+    }
+
+    /**
+     Prepares the executor for a first run as fast as the ones after it, e.g. on level load: starts the threads of
+     the pools its runs use, resolves the accesses for `data` and schedules a dry run, which takes and releases every
+     task without executing it, so the queues of the workers and the structures created on first use already exist.
+     Executes no task and no hook, and doesn't count as a run. Warm up again after changing the parallelism or pools.
+    */
+    pub fn warm_up(&mut self, data: &T) {
+        self.pools.warm_up(self.parallelism);
+        self.resolve(data);
+
+        #[cfg(debug_assertions)]
+        self.check();
+
+        let context = Context::new(data, &self.tasks, &self.tasks, self.env(true));
+        self.start(&context, self.parallelism);
+    }
+
+    /// Compensates the tasks that completed in a failed run, the last completed first, then resumes the panic of the run.
+    fn compensate(&self, data: &T, compensable: Vec<usize>, result: thread::Result<()>) {
+        if let Err(payload) = result {
+            for id in compensable.into_iter().rev() {
+                let compensation = self.tasks[id].compensation().expect("only tasks with a compensation are recorded");
+
+                //the other compensations still execute if one of them panics
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| compensation.lock().unwrap_or_else(PoisonError::into_inner).run(data)));
+            }
+
+            panic::resume_unwind(payload);
+        }
+    }
+}
 
-    fn from_iter<I: IntoIterator<Item=Task<'task, T>>>(iter: I) -> Self {
-        let tasks: Vec<_> = iter.into_iter().collect();
-        Self { tasks }
+/**
+ Counts the tasks waiting for each task, transitively. Tasks reached over several paths count once per path,
+ which keeps it linear in the number of dependencies. Dependants always come after their dependencies.
+*/
+fn fanout<T, R, N>(tasks: &[Task<'_, T, R, N>]) -> Vec<usize> {
+    let mut weights = vec![0usize; tasks.len()];
+    for id in (0..tasks.len()).rev() {
+        weights[id] = tasks[id].dependants().iter().fold(0, |sum, dep| sum.saturating_add(weights[dep.id()]).saturating_add(1));
     }
+
+    weights
 }
 
-impl<'task, T: Sync> Executable<T> for InterlockExecutor<'task, T> {
+impl<'task, T: Sync, R: Eq + Hash + Sync, N: Sync> Executable<T> for InterlockExecutor<'task, T, R, N> {
 
     fn run(&mut self, data: &T) {
-        Context::new(data, &self.tasks).run()
+        self.run_with(self.parallelism, data)
     }
 }
 
-impl<'task, T> Debug for InterlockExecutor<'task, T> {
+impl<'a, 'task, T: Sync, R: Eq + Hash, N> IntoIterator for &'a InterlockExecutor<'task, T, R, N> {
+    type Item = TaskInfo<'a, R, N>;
+    type IntoIter = TaskIter<'a, 'task, T, R, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'task, T, R, N: Display> Debug for InterlockExecutor<'task, T, R, N> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         writeln!(f, "Interlock [")?;
         for task in self.tasks.iter() {
-            writeln!(f, "    Task #{}: (init={:?}, lock={:?}, unlock={:?})", task.id().id(), task.initial_count(), task.lockable_deps(), task.unlockable_deps())?;
+            write!(f, "    Task #{}", task.id().id())?;
+            if let Some(label) = task.label() {
+                write!(f, " '{}'", label)?;
+            }
+
+            writeln!(f, ": (init={:?}, lock={:?}, unlock={:?})", task.initial_count(), task.lockable_deps(), task.unlockable_deps())?;
         }
         write!(f, "]")?;
 
@@ -50,25 +948,29 @@ impl<'task, T> Debug for InterlockExecutor<'task, T> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test::analysis::{TimelineAnalyzer, TimelineOrder};
+    use crate::test::analysis::{TimelineAnalyzer, TimelineOrder, TimelineTask};
     use crate::test::TimelineReader;
+    use self::builder::TaskSpec;
+    use self::resource::Access;
+    use rayon::prelude::*;
 
-    fn order(n: &TimelineAnalyzer<&str>, a: &str, b: &str) -> TimelineOrder {
-        let task_a = n.first(&a).unwrap_or_else(|| panic!("task '{}' was not executed", a));
-        let task_b = n.first(&b).unwrap_or_else(|| panic!("task '{}' was not executed", b));
-
+    fn single<'a, 'n>(n: &'a TimelineAnalyzer<&'n str>, a: &str) -> &'a TimelineTask<&'n str> {
+        let task = n.iter().find(|task| *task.name() == a).unwrap_or_else(|| panic!("task '{}' was not executed", a));
         assert_eq!(n.count(&a), 1, "task '{}' was executed multiple times", a);
-        assert_eq!(n.count(&b), 1, "task '{}' was executed multiple times", b);
+        task
+    }
 
-        task_a.order_to(task_b)
+    fn order(n: &TimelineAnalyzer<&str>, a: &str, b: &str) -> TimelineOrder {
+        single(n, a).order_to(single(n, b))
     }
 
     fn mutex(n: &TimelineAnalyzer<&str>, a: &str, b: &str) {
-        assert_ne!(order(n, a, b), TimelineOrder::Parallel, "tasks '{}' and '{}' were executed in parallel when they should not", a, b)
+        let (task_a, task_b) = (single(n, a), single(n, b));
+        assert!(task_a.ends_before(task_b) || task_a.starts_after(task_b), "tasks '{}' and '{}' were executed in parallel when they should not", a, b)
     }
 
     fn dep(n: &TimelineAnalyzer<&str>, a: &str, b: &str) {
-        assert_eq!(order(n, a, b), TimelineOrder::After, "task '{}' depends on '{}' but they were executed out of order", b, a)
+        assert!(single(n, a).ends_before(single(n, b)), "task '{}' depends on '{}' but they were executed out of order", b, a)
     }
 
     #[test]
@@ -108,4 +1010,2447 @@ mod tests {
         dep(&analyzer, "c", "g");
         dep(&analyzer, "c", "h");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn hierarchy() {
+        let closure = |_: &()| {};
+
+        let reader = TimelineReader::new();
+        let mut builder = builder();
+        builder.hierarchy(resource::path_parent);
+
+        builder.add(reader.wrap("world", closure), [], ["world/chunks"], &[]);
+        builder.add(reader.wrap("chunk_7", closure), ["world/chunks/7"], [], &[]);
+        builder.add(reader.wrap("chunk_8", closure), [], ["world/chunks/8"], &[]);
+        builder.add(reader.wrap("entities", closure), [], ["world/entities"], &[]);
+
+        let mut exec = builder.build();
+        exec.run(&());
+
+        let analyzer = reader.analyze();
+
+        mutex(&analyzer, "world", "chunk_7");
+        mutex(&analyzer, "world", "chunk_8");
+        order(&analyzer, "world", "entities");
+        order(&analyzer, "chunk_7", "chunk_8");
+
+        assert_eq!(exec.tasks[0].lockable_deps().len(), 2, "parent writer must lock both chunk tasks");
+        assert_eq!(exec.tasks[3].lockable_deps().len(), 0, "unrelated subtree must not be locked");
+    }
+
+    #[test]
+    #[should_panic(expected = "hierarchy is cyclic")]
+    fn hierarchy_cyclic() {
+        let mut graph = builder::<(), u32>();
+        graph.hierarchy(|resource| Some((resource + 1) % 3));
+        graph.add(|_: &()| {}, [], [0], &[]);
+        graph.build();
+    }
+
+    #[test]
+    fn all() {
+        let closure = |_: &()| {};
+
+        let reader = TimelineReader::new();
+        let mut builder = builder();
+
+        builder.add(reader.wrap("a", closure), [0u32], [1u32], &[]);
+        builder.add(reader.wrap("b", closure), [2u32], [], &[]);
+        let snapshot = builder.add(reader.wrap("snapshot", closure), [], [], &[]);
+        let dump = builder.add(reader.wrap("dump", closure), [], [], &[]);
+
+        builder.read_all(snapshot);
+        builder.write_all(dump);
+
+        let mut exec = builder.build();
+        exec.run(&());
+
+        let analyzer = reader.analyze();
+
+        mutex(&analyzer, "a", "snapshot");
+        mutex(&analyzer, "a", "dump");
+        mutex(&analyzer, "b", "dump");
+        mutex(&analyzer, "snapshot", "dump");
+
+        assert_eq!(exec.tasks[1].lockable_deps().len(), 1, "reader must only be locked by the writer of everything");
+    }
+
+    #[test]
+    fn resolve() {
+        let closure = |_: &Vec<u32>| {};
+
+        let reader = TimelineReader::new();
+        let mut builder = builder();
+
+        let writer = builder.add(reader.wrap("writer", closure), [], [1u32], &[]);
+        let chunks = builder.add(reader.wrap("chunks", closure), [], [], &[]);
+        builder.resolve(chunks, |data: &Vec<u32>, accesses| data.iter().for_each(|chunk| accesses.write(*chunk)));
+
+        let mut exec = builder.build();
+
+        exec.run(&vec![1, 2]);
+        assert_eq!(exec.tasks[1].lockable_deps(), &[writer], "resolved resource must lock static writer");
+
+        exec.run(&vec![2, 3]);
+        assert!(exec.tasks[1].lockable_deps().is_empty(), "stale resolved lock was not removed");
+        assert!(exec.tasks[0].unlockable_deps().is_empty(), "stale resolved unlock was not removed");
+
+        let analyzer = reader.analyze();
+        assert_eq!(analyzer.count(&"writer"), 2);
+        assert_eq!(analyzer.count(&"chunks"), 2);
+    }
+
+    #[test]
+    fn policy() {
+        let closure = |_: &()| {};
+
+        let reader = TimelineReader::new();
+        let mut builder = builder();
+        builder.policy("counter", resource::ConflictPolicy::Concurrent);
+        builder.policy("device", resource::ConflictPolicy::Exclusive);
+
+        builder.add(reader.wrap("increment", closure), [], ["counter"], &[]);
+        builder.add(reader.wrap("sample", closure), ["counter"], [], &[]);
+        builder.add(reader.wrap("query_a", closure), ["device"], [], &[]);
+        builder.add(reader.wrap("query_b", closure), ["device"], [], &[]);
+
+        let mut exec = builder.build();
+        exec.run(&());
+
+        let analyzer = reader.analyze();
+        mutex(&analyzer, "query_a", "query_b");
+
+        assert!(exec.tasks[0].lockable_deps().is_empty(), "concurrent resource must not lock");
+        assert!(exec.tasks[1].lockable_deps().is_empty(), "concurrent resource must not lock");
+    }
+
+    #[test]
+    #[should_panic(expected = "task TaskId(0) belongs to a different builder")]
+    fn foreign_task() {
+        let mut a = builder::<(), u32>();
+        let mut b = builder::<(), u32>();
+
+        let task = a.add(|_: &()| {}, [], [], &[]);
+        b.add(|_: &()| {}, [], [], &[]);
+        b.add(|_: &()| {}, [], [], &[task]);
+    }
+
+    #[test]
+    fn extend() {
+        let closure = |_: &()| {};
+
+        let reader = TimelineReader::new();
+        let mut builder = builder();
+
+        let input = builder.add(reader.wrap("input", closure), [], [0u32], &[]);
+        builder.label(input, "input").unwrap();
+
+        let ids = builder.extend(vec![
+            TaskSpec::new("render", reader.wrap("render", closure)).reads([1u32]).after(["physics", "ai"]),
+            TaskSpec::new("physics", reader.wrap("physics", closure)).reads([0u32]).writes([1u32]).after(["input"]),
+            TaskSpec::new("ai", reader.wrap("ai", closure)).reads([0u32, 1u32]).after(["input"]),
+        ]).unwrap();
+
+        assert_eq!(ids.len(), 3);
+        assert!(ids["physics"].id() < ids["render"].id(), "dependency must be added before its dependant");
+
+        let mut exec = builder.build();
+        exec.run(&());
+
+        let analyzer = reader.analyze();
+
+        dep(&analyzer, "input", "physics");
+        dep(&analyzer, "input", "ai");
+        dep(&analyzer, "physics", "render");
+        dep(&analyzer, "ai", "render");
+        mutex(&analyzer, "physics", "ai");
+    }
+
+    #[test]
+    fn boxed() {
+        let closure = |_: &()| {};
+        let reader = TimelineReader::new();
+        let mut builder = builder();
+
+        let first = builder.add_box(Box::new(reader.wrap("first", closure)), [], [0u32], &[]);
+        builder.label(first, "first").unwrap();
+        builder.extend(vec![TaskSpec::new_box("second", Box::new(reader.wrap("second", closure))).reads([0u32]).after(["first"])]).unwrap();
+
+        builder.build().run(&());
+        dep(&reader.analyze(), "first", "second");
+    }
+
+    #[test]
+    fn extend_errors() {
+        let closure = |_: &()| {};
+        let mut builder = builder::<(), u32>();
+
+        let task = builder.add(closure, [], [], &[]);
+        builder.label(task, "a").unwrap();
+
+        assert_eq!(builder.label(task, "a"), Ok(()), "relabeling a task with its own label is a no-op");
+
+        assert_eq!(builder.extend(vec![TaskSpec::new("a", closure)]), Err(BuildError::DuplicateLabel("a".to_string())));
+
+        let unknown = builder.extend(vec![TaskSpec::new("b", closure).after(["x"])]);
+        assert_eq!(unknown, Err(BuildError::UnknownLabel { task: "b".to_string(), dependency: "x".to_string() }));
+        assert_eq!(unknown.unwrap_err().to_string(), "task 'b' depends on unknown task 'x'\n  'b'\n   | depends on\n  'x'  <- no task has this label");
+
+        let cycle = builder.extend(vec![
+            TaskSpec::new("b", closure).after(["a"]),
+            TaskSpec::new("c", closure).after(["b", "e"]),
+            TaskSpec::new("d", closure).after(["c"]),
+            TaskSpec::new("e", closure).after(["d"]),
+        ]);
+
+        assert_eq!(cycle, Err(BuildError::Cycle(vec!["c".to_string(), "e".to_string(), "d".to_string()])));
+        assert_eq!(cycle.unwrap_err().to_string(), "dependency cycle detected: 'c' -> 'e' -> 'd' -> 'c'\n  \
+            +-> 'c'\n  |    | depends on\n  |   'e'\n  |    | depends on\n  |   'd'\n  |    | depends on\n  +----+");
+
+        let unwritten = builder.extend(vec![
+            TaskSpec::new("b", closure).writes([0]).transactional(0, |_| (), |_, _| ()),
+            TaskSpec::new("c", closure).reads([1]).transactional(1, |_| (), |_, _| ()).after(["b"])
+        ]);
+
+        assert_eq!(unwritten, Err(BuildError::UnwrittenSnapshot("c".to_string())));
+        assert_eq!(unwritten.unwrap_err().to_string(), "task 'c' snapshots a resource it doesn't write");
+
+        let other = builder.add(closure, [], [], &[]);
+        assert_eq!(builder.label(other, "a"), Err(BuildError::DuplicateLabel("a".to_string())));
+
+        assert_eq!(builder.build().tasks.len(), 2, "failed extend must not add tasks");
+    }
+
+    #[test]
+    fn duplicates() {
+        let closure = |_: &()| {};
+        let mut builder = builder::<(), u32>();
+
+        let a = builder.add(closure, [2], [], &[]);
+        let b = builder.add(closure, [0, 1, 0], [1, 0], &[a, a]);
+        let c = builder.add(closure, [0], [], &[]);
+
+        let mut exec = builder.build();
+
+        assert_eq!(exec.tasks[b.id()].initial_count(), 1, "duplicate dependency must be counted once");
+        assert_eq!(exec.tasks[a.id()].unlockable_deps(), &[b], "duplicate dependency must unlock once");
+        assert_eq!(exec.tasks[c.id()].lockable_deps(), &[b], "read and write of the same resource must be a write");
+
+        exec.run(&());
+    }
+
+    #[test]
+    fn report() {
+        let closure = |_: &()| {};
+        let mut builder = builder::<(), &str>();
+        builder.hierarchy(resource::path_parent);
+
+        let a = builder.add(closure, [], [], &[]);
+        let b = builder.add(closure, ["world/chunks/0"], ["log", "world"], &[a, a]);
+        let c = builder.add(closure, ["world/chunks"], ["world/chunks"], &[a]);
+        let d = builder.add(closure, ["config"], [], &[]);
+        builder.label(d, "config").unwrap();
+
+        let fan: Vec<_> = (0..builder::MAX_FAN_IN).map(|_| builder.add(closure, [], [], &[a])).collect();
+        let e = builder.add(closure, [], [], fan.iter().chain([b].iter()));
+
+        let (exec, warnings) = builder.build_with_report();
+
+        assert_eq!(warnings, vec![
+            BuildWarning::Unconstrained(a),
+            BuildWarning::FanIn { task: e, dependencies: builder::MAX_FAN_IN + 1 },
+            BuildWarning::DuplicateDependency { task: b, dependency: a },
+            BuildWarning::DuplicateAccess { task: c, resource: "world/chunks", access: Access::Write },
+            BuildWarning::WriteOnly("log"),
+            BuildWarning::Disconnected(d),
+        ]);
+
+        assert_eq!(warnings[0].to_string(), "TaskId(0) has no accesses and no dependencies");
+        assert_eq!(warnings[3].to_string(), "TaskId(2) lists resource \"world/chunks\" more than once, again as Write");
+        assert_eq!(exec.describe(&warnings[1]).to_string(), format!("TaskId({}) depends on 33 tasks: TaskId(1), TaskId(4), TaskId(5), TaskId(6), \
+            TaskId(7), TaskId(8), TaskId(9), TaskId(10) and 25 more", e.id()));
+        assert_eq!(exec.describe(&warnings[5]).to_string(), "'config' is not connected to any other task");
+        assert_eq!(exec.describe(&warnings[4]).to_string(), "resource \"log\" is written but never read, written by TaskId(1)");
+        assert_eq!(exec.len(), builder::MAX_FAN_IN + 5);
+    }
+
+    #[test]
+    fn diff() {
+        let closure = |_: &()| {};
+
+        let graph = |version: u32| {
+            let mut builder = builder::<(), &str>();
+
+            let mut specs = vec![
+                TaskSpec::new("input", closure).writes(["input"]),
+                TaskSpec::new("physics", closure).reads(["input"]).writes(["world"]).after(["input"]),
+            ];
+
+            specs.push(match version {
+                0 => TaskSpec::new("render", closure).reads(["world"]).after(["physics"]),
+                _ => TaskSpec::new("render", closure).reads(["world", "ui"]).after(["ui"]).read_all()
+            });
+
+            specs.push(match version {
+                0 => TaskSpec::new("audio", closure),
+                _ => TaskSpec::new("ui", closure).writes(["ui"])
+            });
+
+            builder.extend(specs).unwrap();
+            builder.build()
+        };
+
+        let (old, new) = (graph(0), graph(1));
+        let diff = old.diff(&new);
+
+        assert_eq!(diff.added, vec!["ui"]);
+        assert_eq!(diff.removed, vec!["audio"]);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].added_dependencies, vec!["ui"]);
+        assert_eq!(diff.changed[0].removed_dependencies, vec!["physics"]);
+        assert_eq!(diff.changed[0].added_accesses, vec![(&"ui", Access::Read)]);
+        assert!(diff.changed[0].removed_accesses.is_empty());
+        assert_eq!(diff.changed[0].all, Some((None, Some(Access::Read))));
+
+        assert_eq!(diff.to_string(), "+ 'ui'\n- 'audio'\n~ 'render'\n    + after 'ui'\n    - after 'physics'\n    + Read \"ui\"\n    ~ all: None -> Some(Read)\n");
+        assert!(new.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn duplicate() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let created = AtomicUsize::new(0);
+        let factory = || {
+            created.fetch_add(1, Ordering::Relaxed);
+            |sum: &AtomicUsize| { sum.fetch_add(1, Ordering::Relaxed); }
+        };
+
+        let mut builder = builder::<AtomicUsize, u32>();
+        let a = builder.add_factory(factory, [], [0], &[]);
+        builder.add_factory(factory, [0], [], &[a]);
+        builder.extend(vec![TaskSpec::from_factory("c", factory).reads([0])]).unwrap();
+
+        let exec = builder.build();
+        let copies: Vec<_> = (0..4).map(|_| exec.duplicate().unwrap()).collect();
+        assert_eq!(created.load(Ordering::Relaxed), 15);
+
+        let sums: Vec<_> = copies.into_par_iter().map(|mut copy| {
+            let sum = AtomicUsize::new(0);
+            copy.run(&sum);
+            sum.into_inner()
+        }).collect();
+
+        assert_eq!(sums, vec![3; 4]);
+
+        let mut partial = InterlockBuilder::<AtomicUsize, u32>::new();
+        partial.add_factory(factory, [], [], &[]);
+        partial.add(|_: &AtomicUsize| {}, [], [], &[]);
+
+        assert!(partial.build().duplicate().is_none(), "task without factory can't be duplicated");
+    }
+
+    #[test]
+    fn run_shared() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
+
+        let closure = |_: &Mutex<Vec<u32>>| {};
+        let push = |value| move |data: &Mutex<Vec<u32>>| data.lock().unwrap().push(value);
+
+        let mut builder = builder();
+        let a = builder.add_shared(push(0), [], [0u32], &[]);
+        let b = builder.add_shared(push(1), [0], [], &[a]);
+        builder.extend(vec![TaskSpec::shared("c", push(2)).reads([0])]).unwrap();
+        builder.add_shared(push(3), [], [], &[b]);
+
+        let mut exec = builder.build();
+        exec.run(&Mutex::new(Vec::new()));
+
+        let runs = AtomicUsize::new(0);
+        (0..16).into_par_iter().for_each(|_| {
+            let data = Mutex::new(Vec::new());
+            exec.run_shared(&data);
+
+            let data = data.into_inner().unwrap();
+            let position = |value| data.iter().position(|v| *v == value).unwrap();
+
+            assert_eq!(data.len(), 4);
+            assert!(position(0) < position(1) && position(1) < position(3), "dependencies must run first");
+
+            runs.fetch_add(1, Ordering::Relaxed);
+        });
+
+        assert_eq!(runs.load(Ordering::Relaxed), 16);
+
+        let mut partial = InterlockBuilder::new();
+        partial.add_shared(closure, [], [0u32], &[]);
+        partial.add(closure, [], [], &[]);
+
+        let exec = partial.build();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| exec.run_shared(&Mutex::new(Vec::new()))));
+        assert!(result.is_err(), "graph with a task that isn't shared must not run shared");
+    }
+
+    #[test]
+    fn shutdown() {
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use std::sync::mpsc;
+
+        let (started, start) = mpsc::channel();
+        let started = Mutex::new(started);
+        let release = AtomicBool::new(false);
+        let executed = AtomicUsize::new(0);
+
+        let mut builder = builder();
+        let a = builder.add(|_: &()| {
+            started.lock().unwrap().send(()).unwrap();
+            while !release.load(Ordering::Acquire) {
+                thread::yield_now();
+            }
+
+            executed.fetch_add(1, Ordering::Relaxed);
+        }, [], [0u32], &[]);
+
+        let b = builder.add(|_: &()| { executed.fetch_add(1, Ordering::Relaxed); }, [0u32], [], &[a]);
+        let c = builder.add(|_: &()| { executed.fetch_add(1, Ordering::Relaxed); }, [], [1u32], &[b]);
+
+        let mut exec = builder.build();
+        let handle = exec.shutdown_handle();
+        assert!(!exec.is_shut_down());
+
+        thread::scope(|scope| {
+            let run = scope.spawn(|| exec.run(&()));
+            start.recv().unwrap();
+
+            let report = handle.shutdown(Duration::from_millis(10));
+            assert!(!report.drained, "a is still running");
+            assert_eq!(report.running, [a]);
+            assert!(report.skipped.is_empty());
+
+            release.store(true, Ordering::Release);
+            run.join().unwrap();
+        });
+
+        let report = handle.shutdown(Duration::from_secs(10));
+        assert!(report.drained);
+        assert!(report.running.is_empty());
+        assert_eq!(report.skipped, [b, c]);
+        assert_eq!(report.abandoned(), [b, c]);
+        assert_eq!(executed.load(Ordering::Relaxed), 1, "a finished, b and c didn't start");
+
+        assert!(exec.is_shut_down());
+        exec.run(&());
+        assert_eq!(executed.load(Ordering::Relaxed), 1, "runs after a shutdown skip every task");
+        assert!(handle.shutdown(Duration::default()).skipped.contains(&a));
+    }
+
+    #[test]
+    fn supervise() {
+        use std::panic::{self, AssertUnwindSafe};
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+        let created = AtomicUsize::new(0);
+        let executed = AtomicUsize::new(0);
+
+        let mut graph = builder::<AtomicBool, u32>();
+        let flaky = graph.add_factory(|| {
+            created.fetch_add(1, Ordering::Relaxed);
+            |fail: &AtomicBool| assert!(!fail.load(Ordering::Relaxed), "flaky failed")
+        }, [], [0], &[]);
+
+        let after = graph.add(|_: &AtomicBool| { executed.fetch_add(1, Ordering::Relaxed); }, [0], [], &[flaky]);
+        let broken = graph.add(|_: &AtomicBool| panic!("broken"), [], [1], &[]);
+        graph.supervise(flaky, Supervision::Restart);
+        graph.supervise(broken, Supervision::Disable(2));
+
+        let mut exec = graph.build();
+        assert_eq!(exec.supervision(after), None);
+
+        let fail = AtomicBool::new(true);
+        exec.run(&fail);
+        exec.set_parallelism(Parallelism::Sequential);
+        exec.run(&fail);
+
+        assert_eq!(executed.load(Ordering::Relaxed), 2, "dependants of a failed task execute");
+        assert_eq!(exec.supervision(flaky), Some(SupervisionStats { failures: 2, consecutive: 2, restarts: 2, disabled: false }));
+        assert_eq!(exec.supervision(broken), Some(SupervisionStats { failures: 2, consecutive: 2, restarts: 1, disabled: true }));
+        assert_eq!(exec.disabled(), [broken]);
+        assert_eq!(created.load(Ordering::Relaxed), 2, "restarted before the second run");
+
+        fail.store(false, Ordering::Relaxed);
+        exec.set_parallelism(Parallelism::Full);
+        exec.run(&fail);
+        exec.run(&fail);
+
+        assert_eq!(created.load(Ordering::Relaxed), 3, "completed tasks aren't restarted");
+        assert_eq!(exec.supervision(flaky).unwrap().consecutive, 0);
+        assert_eq!(exec.supervision(broken).unwrap().failures, 2, "disabled tasks are skipped");
+
+        exec.enable(broken);
+        exec.run(&fail);
+        assert_eq!(exec.supervision(broken), Some(SupervisionStats { failures: 3, consecutive: 1, restarts: 2, disabled: false }));
+
+        let mut graph = builder::<(), u32>();
+        let failing = graph.add(|_: &()| panic!("failing"), [], [0], &[]);
+        graph.add(|_: &()| {}, [0], [], &[failing]);
+        graph.supervise(failing, Supervision::Escalate(2));
+
+        let mut exec = graph.build();
+        exec.run(&());
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| exec.run(&()))).is_err(), "the second failure in a row fails the run");
+        assert_eq!(exec.supervision(failing).unwrap().failures, 2);
+
+        let escalated = panic::catch_unwind(AssertUnwindSafe(|| exec.run(&()))).unwrap_err();
+        assert_eq!(escalated.downcast_ref::<&str>(), Some(&"failing"), "the executor runs again after an escalated failure");
+        assert_eq!(exec.supervision(failing).unwrap().failures, 3);
+    }
+
+    #[test]
+    fn scratch() {
+        let sums = Mutex::new(Vec::new());
+        let mut graph = builder::<usize, u32>();
+
+        for idx in 0..8 {
+            let sums = &sums;
+            graph.add_with_context(move |len: &usize, context: &TaskContext<u32>| {
+                let values = context.scratch().alloc_slice_with(*len, |value| value as u64 * idx);
+                let sum = context.scratch().alloc(values.iter().sum::<u64>());
+
+                assert_eq!(values.as_ptr() as usize % std::mem::align_of::<u64>(), 0);
+                sums.lock().unwrap().push(*sum);
+            }, [], [idx as u32], &[]);
+        }
+
+        let mut exec = graph.build();
+        assert_eq!(exec.scratch_stats(), ScratchStats::default());
+
+        exec.run(&100);
+        let first = exec.scratch_stats();
+        assert!(first.used >= 8 * 101 * 8, "{:?}", first);
+        assert_eq!((first.capacity, first.overflowed), (0, 16), "nothing fits before the first run");
+
+        exec.run(&100);
+        let second = exec.scratch_stats();
+        assert!(second.capacity >= first.used && second.used <= first.used, "{:?}", second);
+        assert_eq!(second.overflowed, 0, "grown to the peak of the first run");
+
+        exec.run(&10);
+        let third = exec.scratch_stats();
+        assert!(third.used < second.used && third.peak == first.used, "{:?}", third);
+
+        drop(exec);
+        let mut sums = sums.into_inner().unwrap();
+        let mut expected: Vec<_> = (0..8).flat_map(|idx| [45 * idx, 4950 * idx, 4950 * idx]).collect();
+        sums.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(sums, expected, "allocations of parallel tasks don't overlap");
+    }
+
+    #[test]
+    fn transactional() {
+        use std::panic::{self, AssertUnwindSafe};
+        use std::sync::Mutex;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        struct World {
+            entities: Mutex<Vec<u32>>,
+            fail: AtomicBool
+        }
+
+        let script = |world: &World| {
+            world.entities.lock().unwrap().push(3);
+            assert!(!world.fail.load(Ordering::Relaxed), "script failed");
+        };
+
+        let snapshot = |world: &World| world.entities.lock().unwrap().clone();
+        let restore = |world: &World, entities| *world.entities.lock().unwrap() = entities;
+
+        let build = || {
+            let mut graph = builder::<World, u32>();
+            let run = graph.add(script, [], [0], &[]);
+            let count = graph.add(|world: &World| assert!(world.entities.lock().unwrap().len() <= 3), [0], [], &[run]);
+            graph.transactional(run, 0, snapshot, restore);
+
+            let read = panic::catch_unwind(AssertUnwindSafe(|| graph.transactional(count, 0, snapshot, restore)));
+            assert!(read.is_err(), "reading tasks can't snapshot");
+            graph.build()
+        };
+
+        let world = World { entities: Mutex::new(vec![1, 2]), fail: AtomicBool::new(true) };
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| build().run(&world))).is_err());
+        assert_eq!(*world.entities.lock().unwrap(), [1, 2], "restored before the run failed");
+
+        world.fail.store(false, Ordering::Relaxed);
+        build().run(&world);
+        assert_eq!(*world.entities.lock().unwrap(), [1, 2, 3]);
+
+        //the restore comes before the supervision, so the dependant sees the restored resource
+        let mut graph = builder::<World, u32>();
+        graph.extend(vec![
+            TaskSpec::new("script", script).writes([0]).transactional(0, snapshot, restore).supervise(Supervision::Restart),
+            TaskSpec::new("count", |world: &World| assert_eq!(world.entities.lock().unwrap().len(), 2)).reads([0]).after(["script"])
+        ]).unwrap();
+
+        let world = World { entities: Mutex::new(vec![1, 2]), fail: AtomicBool::new(true) };
+        let mut exec = graph.build();
+        exec.run(&world);
+        assert_eq!(*world.entities.lock().unwrap(), [1, 2]);
+        assert_eq!(exec.supervision(exec.task_by_label("script").unwrap()).unwrap().failures, 1);
+    }
+
+    #[test]
+    fn seeding() {
+        use std::sync::Mutex;
+
+        let log = Mutex::new(Vec::new());
+        let task = |name: &'static str| {
+            let log = &log;
+            move |_: &()| log.lock().unwrap().push(name)
+        };
+
+        let mut builder = builder();
+        let a = builder.add(task("a"), [], [0u32], &[]);
+        let b = builder.add(task("b"), [], [1u32], &[]);
+        builder.add(task("a2"), [], [0u32], &[a]);
+        builder.add(task("b2"), [], [1u32], &[b]);
+        builder.add(task("c"), [], [2u32], &[]);
+
+        let mut exec = builder.build();
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let mut run = |seeding| {
+            exec.set_seeding(seeding);
+            pool.install(|| exec.run(&()));
+            std::mem::take(&mut *log.lock().unwrap())
+        };
+
+        //dependants start as soon as they are unlocked, before the later ready tasks
+        assert_eq!(run(Seeding::Lazy), ["a", "a2", "b", "b2", "c"]);
+
+        //all ready tasks first, then the tasks they unlocked in the order they were unlocked
+        for _ in 0..4 {
+            assert_eq!(run(Seeding::Ordered), ["a", "b", "c", "a2", "b2"]);
+        }
+
+        let closure = |_: &()| {};
+        let reader = TimelineReader::new();
+
+        let mut graph = InterlockBuilder::new();
+        let a = graph.add(reader.wrap("a", closure), [], [0u32], &[]);
+        graph.add(reader.wrap("b", closure), [], [0u32], &[]);
+        let c = graph.add(reader.wrap("c", closure), [0u32], [1u32], &[a]);
+        graph.add(reader.wrap("d", closure), [1u32], [], &[c]);
+
+        let mut exec = graph.build();
+        exec.set_seeding(Seeding::Ordered);
+        exec.run(&());
+
+        let analyzer = reader.analyze();
+        mutex(&analyzer, "a", "b");
+        mutex(&analyzer, "b", "c");
+        dep(&analyzer, "a", "c");
+        dep(&analyzer, "c", "d");
+    }
+
+    #[test]
+    fn run_dispatched() {
+        use std::sync::Mutex;
+
+        enum System {
+            Push(u32),
+            Double
+        }
+
+        impl Executable<Mutex<Vec<u32>>> for System {
+
+            fn run(&mut self, data: &Mutex<Vec<u32>>) {
+                let mut data = data.lock().unwrap();
+                match self {
+                    System::Push(value) => data.push(*value),
+                    System::Double => data.iter_mut().for_each(|value| *value *= 2)
+                }
+            }
+        }
+
+        let closure = |_: &Mutex<Vec<u32>>| {};
+
+        let mut builder = builder();
+        let a = builder.add(closure, [], [0u32], &[]);
+        let b = builder.add(closure, [], [0u32], &[a]);
+        builder.add(closure, [0], [], &[b]);
+
+        let mut exec = builder.build();
+        let bodies = Dispatch::new(vec![System::Push(1), System::Double, System::Push(3)]);
+
+        for parallelism in [Parallelism::Full, Parallelism::Sequential] {
+            exec.set_parallelism(parallelism);
+
+            let data = Mutex::new(Vec::new());
+            exec.run_dispatched(&data, &bodies);
+            exec.run_dispatched(&data, &bodies);
+            assert_eq!(data.into_inner().unwrap(), vec![4, 6, 2, 3]);
+        }
+
+        let two = Dispatch::new(vec![System::Double, System::Double]);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| exec.run_dispatched(&Mutex::new(Vec::new()), &two)));
+        assert!(result.is_err(), "a dispatched run needs a body for every task");
+        assert_eq!(bodies.into_inner().len(), 3);
+    }
+
+    #[test]
+    fn auto_traits() {
+        fn send<T: Send>() {}
+        fn send_sync<T: Send + Sync>() {}
+
+        send_sync::<InterlockExecutor<'static, (), u32>>();
+        send_sync::<InterlockExecutor<'static, std::cell::Cell<u32>, &str>>();
+        send::<InterlockBuilder<'static, (), u32>>();
+
+        let mut exec = {
+            let mut builder = builder::<(), u32>();
+            builder.add(|_: &()| {}, [], [0], &[]);
+            builder.build()
+        };
+
+        std::thread::spawn(move || exec.run(&())).join().unwrap();
+    }
+
+    #[test]
+    fn static_executor() {
+        use std::sync::{Mutex, OnceLock};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static GRAPH: OnceLock<Mutex<StaticExecutor<AtomicUsize, String>>> = OnceLock::new();
+
+        let graph = GRAPH.get_or_init(|| {
+            let name = "counter".to_string();
+            let mut builder = static_builder();
+
+            builder.add(move |count: &AtomicUsize| { count.fetch_add(name.len(), Ordering::Relaxed); }, [], ["count".to_string()], &[]);
+            Mutex::new(builder.build())
+        });
+
+        let count = AtomicUsize::new(0);
+        graph.lock().unwrap().run(&count);
+        GRAPH.get().unwrap().lock().unwrap().run(&count);
+
+        assert_eq!(count.into_inner(), 14);
+    }
+
+    #[test]
+    fn changes() {
+        use std::sync::Mutex;
+
+        let log = Mutex::new(Vec::new());
+        let mut builder = builder::<bool, &str>();
+        builder.hierarchy(resource::path_parent);
+
+        let physics = builder.add_with_context(|step: &bool, context: &TaskContext<&str>| {
+            if !*step {
+                context.unchanged();
+            }
+        }, [], ["world/bodies"], &[]);
+
+        let ui = builder.add_with_context(|_: &bool, context: &TaskContext<&str>| context.unchanged(), [], ["ui"], &[]);
+        builder.add_with_context(|_: &bool, context: &TaskContext<&str>| {
+            log.lock().unwrap().push((context.changed(&"world"), context.changed(&"world/bodies/7"), context.changed(&"world/static"), context.changed(&"ui")));
+        }, ["world", "ui"], [], &[physics, ui]);
+
+        let mut exec = builder.build();
+        exec.run(&true);
+        exec.run(&true);
+        exec.run(&false);
+        drop(exec);
+
+        assert_eq!(log.into_inner().unwrap(), vec![
+            (true, true, true, true),
+            (true, true, false, false),
+            (false, false, false, false)
+        ]);
+    }
+
+    #[test]
+    fn memoize() {
+        use std::sync::Mutex;
+
+        let compiled = Mutex::new(Vec::new());
+        let compiled = &compiled;
+        let closure = |source: &'static str| move |_: &Vec<u64>| compiled.lock().unwrap().push(source);
+
+        let graph = || {
+            let mut builder = builder::<Vec<u64>, &str>();
+            builder.extend(vec![
+                TaskSpec::new("a", closure("a")).writes(["a.o"]).memoize(|sources: &Vec<u64>| sources[0]),
+                TaskSpec::new("b", closure("b")).writes(["b.o"]).memoize(|sources: &Vec<u64>| sources[1]),
+                TaskSpec::new("link", closure("link")).reads(["a.o", "b.o"]).after(["a", "b"]),
+            ]).unwrap();
+
+            builder.build()
+        };
+
+        let ran = || {
+            let mut ran = std::mem::take(&mut *compiled.lock().unwrap());
+            ran.sort_unstable();
+            ran
+        };
+
+        let mut exec = graph();
+        exec.run(&vec![1, 2]);
+        assert_eq!(ran(), vec!["a", "b", "link"]);
+
+        exec.run(&vec![1, 3]);
+        assert_eq!(ran(), vec!["b", "link"], "clean task must be skipped");
+
+        let cache = exec.memo_cache();
+        assert_eq!((cache.get("a"), cache.get("b"), cache.get("link")), (Some(1), Some(3), None));
+
+        let mut restarted = graph();
+        restarted.restore_memo_cache(&cache).unwrap();
+        restarted.run(&vec![4, 3]);
+        assert_eq!(ran(), vec!["a", "link"], "restored hashes must be used");
+    }
+
+    #[test]
+    fn plan() {
+        use self::plan::TaskRegistry;
+
+        let closure = |_: &()| {};
+        let reader = TimelineReader::new();
+
+        let mut builder = builder::<(), &str>();
+        builder.hierarchy(resource::path_parent);
+        builder.policy("log", resource::ConflictPolicy::Concurrent);
+        builder.extend(vec![
+            TaskSpec::new("physics", closure).writes(["world/bodies"]).reads(["log"]),
+            TaskSpec::new("render", closure).reads(["world"]).writes(["log"]).after(["physics"]),
+            TaskSpec::new("audio", closure).writes(["log"]),
+            TaskSpec::new("save", closure).read_all(),
+        ]).unwrap();
+
+        let exec = builder.build();
+        let plan = exec.plan().unwrap();
+        assert_eq!(plan.len(), 4);
+
+        let mut registry = TaskRegistry::new();
+        registry.hierarchy(resource::path_parent);
+        for &label in ["physics", "render", "audio", "save"].iter() {
+            registry.insert(label, reader.wrap(label, closure));
+        }
+
+        let mut hydrated = plan.clone().hydrate(registry).unwrap();
+        assert_eq!(format!("{:?}", hydrated), format!("{:?}", exec));
+        assert!(exec.diff(&hydrated).is_empty());
+
+        hydrated.run(&());
+        let analyzer = reader.analyze();
+        dep(&analyzer, "physics", "render");
+        mutex(&analyzer, "save", "render");
+
+        assert_eq!(plan.hydrate(TaskRegistry::<(), _>::new()).unwrap_err(), BuildError::MissingTask("physics".to_string()));
+
+        let mut unlabeled = InterlockBuilder::<(), &str>::new();
+        let task = unlabeled.add(closure, [], [], &[]);
+        let dependant = unlabeled.add(closure, [], [], &[task]);
+        unlabeled.label(dependant, "render").unwrap();
+        let exec = unlabeled.build();
+        let error = exec.plan().unwrap_err();
+        assert_eq!(error, BuildError::UnlabeledTask(task));
+        assert_eq!(exec.describe(&error).to_string(), "TaskId(0) has no label\n  'render'\n   | depends on\n  TaskId(0)");
+    }
+
+    #[test]
+    fn realtime() {
+        use std::sync::Mutex;
+
+        let log = Mutex::new(Vec::new());
+        let task = |name: &'static str| {
+            let log = &log;
+            move |_: &()| log.lock().unwrap().push((name, rayon::current_num_threads()))
+        };
+
+        let mut builder = builder::<(), u32>();
+        let a = builder.add(task("a"), [], [], &[]);
+        builder.add(task("b"), [], [], &[]);
+        let c = builder.add(task("c"), [], [], &[]);
+        builder.add(task("d"), [], [], &[a]);
+        let e = builder.add(task("e"), [], [], &[a]);
+        builder.realtime(c);
+        builder.realtime(e);
+
+        let mut exec = builder.build();
+        let single = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+
+        //a single thread executes ready tasks in order, realtime ones first
+        single.install(|| exec.run(&()));
+        let order: Vec<_> = log.lock().unwrap().drain(..).map(|(name, _)| name).collect();
+        assert_eq!(order, vec!["c", "a", "e", "d", "b"]);
+
+        exec.reserve_realtime(Arc::new(rayon::ThreadPoolBuilder::new().num_threads(3).build().unwrap()));
+        single.install(|| exec.run(&()));
+
+        let mut threads = log.lock().unwrap().drain(..).collect::<Vec<_>>();
+        threads.sort_unstable();
+        assert_eq!(threads, vec![("a", 1), ("b", 1), ("c", 3), ("d", 1), ("e", 3)]);
+    }
+
+    #[test]
+    fn dedicate() {
+        use std::sync::Mutex;
+        use std::sync::atomic::AtomicBool;
+
+        let log = Mutex::new(Vec::new());
+        let compute = AtomicBool::new(false);
+
+        let mut graph = builder::<(), u32>();
+        let reader = graph.add(|_: &()| {
+            //blocks like a syscall until a task on the pool ran
+            while !compute.load(Ordering::Acquire) {
+                thread::yield_now();
+            }
+
+            log.lock().unwrap().push(("reader", thread::current().name().map(String::from)));
+        }, [], [], &[]);
+
+        graph.add(|_: &()| compute.store(true, Ordering::Release), [], [], &[]);
+        graph.add(|_: &()| log.lock().unwrap().push(("parser", thread::current().name().map(String::from))), [], [], &[reader]);
+
+        let mut exec = graph.build();
+        exec.dedicate(reader);
+        assert_eq!(exec.pool_info().dedicated, 1);
+
+        //the only worker keeps executing tasks while the reader blocks
+        let single = rayon::ThreadPoolBuilder::new().num_threads(1).thread_name(|_| "worker".into()).build().unwrap();
+        single.install(|| exec.run(&()));
+
+        let dedicated = Some(format!("interlock-dedicated-{}", reader.id()));
+        assert_eq!(*log.lock().unwrap(), [("reader", dedicated), ("parser", Some("worker".into()))]);
+
+        let twice = panic::catch_unwind(AssertUnwindSafe(|| exec.dedicate(reader)));
+        assert!(twice.is_err());
+    }
+
+    #[test]
+    fn mark_blocking() {
+        use std::sync::Mutex;
+        use std::sync::atomic::AtomicBool;
+
+        let log = Mutex::new(Vec::new());
+        let compute = AtomicBool::new(false);
+        let read = |name: &'static str| {
+            let (log, compute) = (&log, &compute);
+            move |_: &()| {
+                while !compute.load(Ordering::Acquire) {
+                    thread::yield_now();
+                }
+
+                log.lock().unwrap().push((name, thread::current().name().unwrap().starts_with("interlock-blocking")));
+            }
+        };
+
+        let mut graph = builder::<(), u32>();
+        let a = graph.add(read("a"), [], [], &[]);
+        let b = graph.add(read("b"), [], [], &[]);
+        graph.add(|_: &()| compute.store(true, Ordering::Release), [], [], &[]);
+        graph.add(|_: &()| log.lock().unwrap().push(("c", thread::current().name() == Some("worker"))), [], [], &[a, b]);
+        graph.mark_blocking(a);
+        graph.mark_blocking(b);
+
+        let mut exec = graph.build();
+        assert!(exec.task(a).is_blocking() && !exec.iter().nth(2).unwrap().is_blocking());
+        let single = rayon::ThreadPoolBuilder::new().num_threads(1).thread_name(|_| "worker".into()).build().unwrap();
+
+        //both readers block at the same time, each on an extra thread, while the only worker computes
+        for _ in 0..2 {
+            single.install(|| exec.run(&()));
+            compute.store(false, Ordering::Relaxed);
+
+            let mut log = log.lock().unwrap().drain(..).collect::<Vec<_>>();
+            log[..2].sort_unstable();
+            assert_eq!(log, [("a", true), ("b", true), ("c", true)]);
+            assert_eq!(exec.pool_info().blocking, 2, "extra threads are kept for later runs");
+        }
+    }
+
+    #[test]
+    fn max_threads() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let threads = AtomicUsize::new(0);
+        let task = |_: &()| { threads.fetch_max(rayon::current_num_threads(), Ordering::Relaxed); };
+
+        let mut builder = builder::<(), u32>();
+        builder.add(task, [], [], &[]);
+        let mut exec = builder.build();
+
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        pool.install(|| {
+            assert_eq!(exec.pool_info().threads, 4);
+            assert_eq!(exec.pool_info().realtime, 0);
+
+            exec.run_with_max_threads(2, &());
+            assert_eq!(threads.swap(0, Ordering::Relaxed), 2);
+
+            exec.run_with_max_threads(8, &());
+            assert_eq!(threads.swap(0, Ordering::Relaxed), 4);
+        });
+
+        let realtime = rayon::ThreadPoolBuilder::new().num_threads(exec.pool_info().available).build().unwrap();
+        exec.reserve_realtime(Arc::new(realtime));
+        assert!(exec.pool_info().is_oversubscribed());
+    }
+
+    #[test]
+    fn distribute() {
+        use std::sync::Mutex;
+
+        let log = Mutex::new(Vec::new());
+        let task = |name: &'static str| {
+            let log = &log;
+            move |_: &()| log.lock().unwrap().push((name, rayon::current_num_threads()))
+        };
+
+        let mut builder = builder::<(), &str>();
+        builder.add(task("a"), [], ["x"], &[]);
+        builder.add(task("b"), ["z"], ["x"], &[]);
+        builder.add(task("c"), ["z"], [], &[]);
+        builder.add(task("d"), [], ["y"], &[]);
+        builder.add(task("e"), [], [], &[]);
+
+        let mut exec = builder.build();
+        let pool = |threads| Arc::new(rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap());
+        exec.distribute(vec![pool(2), pool(3)]);
+        assert_eq!(exec.pool_info().nodes, 5);
+
+        pool(1).install(|| exec.run(&()));
+        drop(exec);
+
+        let mut threads = log.into_inner().unwrap();
+        threads.sort_unstable();
+        assert_eq!(threads, vec![("a", 2), ("b", 2), ("c", 2), ("d", 3), ("e", 1)]);
+    }
+
+    #[cfg(feature = "affinity")]
+    #[test]
+    fn pinned() {
+        use std::io;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let runs = AtomicUsize::new(0);
+        let mut builder = builder::<(), u32>();
+        builder.add(|_: &()| { runs.fetch_add(1, Ordering::Relaxed); }, [], [0], &[]);
+
+        let mut exec = builder.build();
+        exec.distribute(vec![Arc::new(pinned_pool(&[0]).unwrap())]);
+        exec.run(&());
+
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+
+        let empty = pinned_pool(&[]).unwrap_err();
+        assert_eq!((empty.kind(), empty.to_string()), (io::ErrorKind::InvalidInput, "a pinned pool needs at least one core".to_string()));
+
+        #[cfg(target_os = "linux")]
+        assert_eq!(pinned_pool(&[0, 4096]).unwrap_err().to_string(), "core 4096 isn't available to the process");
+    }
+
+    #[test]
+    fn parallelism() {
+        use std::sync::Mutex;
+        use std::thread;
+
+        let log = Mutex::new(Vec::new());
+        let task = |name: &'static str| {
+            let log = &log;
+            move |_: &()| log.lock().unwrap().push((name, thread::current().id(), rayon::current_num_threads()))
+        };
+
+        let mut builder = builder::<(), u32>();
+        let a = builder.add_shared(task("a"), [], [0], &[]);
+        builder.add_shared(task("b"), [0], [], &[a]);
+        builder.add_shared(task("c"), [], [1], &[]);
+        let realtime = builder.add_shared(task("d"), [], [1], &[]);
+        builder.realtime(realtime);
+
+        let mut exec = builder.build();
+        exec.reserve_realtime(Arc::new(rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap()));
+        exec.set_parallelism(Parallelism::Sequential);
+        assert_eq!(exec.parallelism(), Parallelism::Sequential);
+
+        //everything runs on the calling thread in the order a single worker would pick
+        exec.run(&());
+        exec.run_shared(&());
+
+        let runs = log.lock().unwrap().drain(..).collect::<Vec<_>>();
+        assert!(runs.iter().all(|(_, thread, _)| *thread == thread::current().id()));
+        assert_eq!(runs.iter().map(|(name, _, _)| *name).collect::<Vec<_>>(), vec!["d", "c", "a", "b", "d", "c", "a", "b"]);
+
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        exec.set_parallelism(Parallelism::Threads(1));
+        pool.install(|| exec.run(&()));
+
+        let mut threads = log.lock().unwrap().drain(..).map(|(name, _, threads)| (name, threads)).collect::<Vec<_>>();
+        threads.sort_unstable();
+        assert_eq!(threads, vec![("a", 1), ("b", 1), ("c", 1), ("d", 2)]);
+
+        exec.set_parallelism(Parallelism::Full);
+        pool.install(|| exec.run_shared(&()));
+        assert!(log.lock().unwrap().iter().any(|(_, _, threads)| *threads == 4));
+    }
+
+    #[test]
+    fn yielding() {
+        use std::sync::Mutex;
+
+        let log = Mutex::new(Vec::new());
+        let push = |entry: String| log.lock().unwrap().push(entry);
+
+        let mut builder = builder::<(), u32>();
+        let mut progress = 0;
+        let long = builder.add_with_context(move |_: &(), context: &TaskContext<u32>| {
+            while progress < 3 {
+                progress += 1;
+                push(format!("long {}", progress));
+
+                if progress < 3 && context.should_yield() {
+                    return context.suspend();
+                }
+            }
+
+            progress = 0;
+        }, [], [0], &[]);
+
+        builder.add(|_: &()| push("other".to_string()), [], [], &[]);
+        builder.add(|_: &()| push("conflicting".to_string()), [], [0], &[]);
+        builder.add(|_: &()| push("dependant".to_string()), [], [], &[long]);
+
+        let mut exec = builder.build();
+        let single = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+
+        //the long task lets the other ready task run, but keeps its conflicts and dependants waiting
+        exec.set_time_slice(Some(Duration::from_secs(0)));
+        single.install(|| exec.run(&()));
+        assert_eq!(log.lock().unwrap().drain(..).collect::<Vec<_>>(), vec!["long 1", "other", "long 2", "long 3", "dependant", "conflicting"]);
+
+        exec.set_time_slice(None);
+        single.install(|| exec.run(&()));
+        assert_eq!(log.lock().unwrap().drain(..).take(3).collect::<Vec<_>>(), vec!["long 1", "long 2", "long 3"]);
+
+        //sequential runs never ask tasks to yield
+        exec.set_time_slice(Some(Duration::from_secs(0)));
+        exec.set_parallelism(Parallelism::Sequential);
+        exec.run(&());
+        assert_eq!(log.lock().unwrap().drain(..).collect::<Vec<_>>(), vec!["long 1", "long 2", "long 3", "dependant", "conflicting", "other"]);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn completions() {
+        use futures_core::Stream;
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+        use std::thread::{self, Thread};
+
+        struct Unpark(Thread);
+
+        impl Wake for Unpark {
+
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        fn block_on<F: Future>(future: F) -> F::Output {
+            let waker = Waker::from(Arc::new(Unpark(thread::current())));
+            let mut context = Context::from_waker(&waker);
+            let mut future = Box::pin(future);
+
+            loop {
+                match future.as_mut().poll(&mut context) {
+                    Poll::Ready(output) => return output,
+                    Poll::Pending => thread::park()
+                }
+            }
+        }
+
+        let mut builder = static_builder::<u64, u32>();
+        let a = builder.add_with_context(|_: &u64, context: &TaskContext<u32>| context.unchanged(), [], [0], &[]);
+        let b = builder.add(|_: &u64| {}, [0], [], &[a]);
+        builder.memoize(b, |data| *data);
+
+        let mut exec = builder.build();
+        let mut completions = exec.completions();
+        assert_eq!(completions.try_next(), None);
+
+        let runner = thread::spawn(move || {
+            exec.run(&1);
+            exec.run(&1);
+        });
+
+        let mut events = Vec::new();
+        while let Some(event) = block_on(std::future::poll_fn(|cx| Pin::new(&mut completions).poll_next(cx))) {
+            events.push((event.task, event.status));
+        }
+
+        runner.join().unwrap();
+        assert_eq!(block_on(completions.next()), None, "the stream ends with the executor");
+        assert_eq!(events, vec![
+            (a, TaskStatus::Unchanged), (b, TaskStatus::Finished),
+            (a, TaskStatus::Unchanged), (b, TaskStatus::Skipped)
+        ]);
+    }
+
+    #[test]
+    fn spawned() {
+        use std::panic::{self, AssertUnwindSafe};
+        use std::thread;
+
+        let closure = |_: &()| thread::sleep(std::time::Duration::from_millis(1));
+        let reader = TimelineReader::new();
+
+        let mut builder = builder::<(), &str>();
+        builder.extend(vec![
+            TaskSpec::new("a", reader.wrap("a", closure)).writes(["x"]),
+            TaskSpec::new("b", reader.wrap("b", closure)).writes(["x"]),
+            TaskSpec::new("c", reader.wrap("c", closure)).reads(["x"]).after(["a"]),
+            TaskSpec::new("d", reader.wrap("d", closure)).after(["c"]),
+            TaskSpec::new("e", reader.wrap("e", closure)),
+        ]).unwrap();
+
+        let mut exec = builder.build();
+        let spawner = |job: Job| { thread::spawn(job); };
+
+        exec.run_spawned(&(), &spawner);
+        let analyzer = reader.analyze();
+        dep(&analyzer, "a", "c");
+        dep(&analyzer, "c", "d");
+        mutex(&analyzer, "a", "b");
+        mutex(&analyzer, "b", "c");
+        assert_eq!(analyzer.count(&"e"), 1);
+
+        let mut failing = InterlockBuilder::<(), u32>::new();
+        failing.add(|_: &()| panic!("task failed"), [], [], &[]);
+        let mut failing = failing.build();
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| failing.run_spawned(&(), &spawner)));
+        assert_eq!(result.unwrap_err().downcast_ref::<&str>(), Some(&"task failed"));
+
+        let mut dropped = InterlockBuilder::<(), u32>::new();
+        dropped.add(|_: &()| {}, [], [], &[]);
+        let mut dropped = dropped.build();
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| dropped.run_spawned(&(), &drop::<Job>)));
+        assert!(result.is_err(), "dropped jobs must fail the run");
+    }
+
+    #[test]
+    fn capacity() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::thread;
+
+        let active = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let runs = AtomicUsize::new(0);
+
+        let task = |_: &()| {
+            let current = active.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(current, Ordering::SeqCst);
+
+            thread::sleep(Duration::from_millis(2));
+            active.fetch_sub(1, Ordering::SeqCst);
+            runs.fetch_add(1, Ordering::SeqCst);
+        };
+
+        let mut builder = builder::<(), &str>();
+        builder.capacity("db", 2);
+        for _ in 0..16 {
+            builder.add(task, ["cache"], ["db"], &[]);
+        }
+
+        let mut exec = builder.build();
+        let spawner = |job: Job| { thread::spawn(job); };
+
+        exec.run_spawned(&(), &spawner);
+        assert_eq!(runs.load(Ordering::SeqCst), 16);
+        assert_eq!(peak.load(Ordering::SeqCst), 2, "writers of a resource with a capacity must share its permits");
+
+        peak.store(0, Ordering::SeqCst);
+        exec.run(&());
+        assert_eq!(runs.load(Ordering::SeqCst), 32);
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn capacity_policy() {
+        use self::resource::ConflictPolicy;
+
+        let closure = |_: &()| {};
+        let mut builder = builder::<(), &str>();
+        builder.capacity("db", 2);
+        builder.policy("db", ConflictPolicy::Exclusive);
+        builder.policy("cache", ConflictPolicy::Exclusive);
+        builder.capacity("cache", 1);
+
+        builder.add(closure, ["db"], ["cache"], &[]);
+        builder.add(closure, [], ["db", "cache"], &[]);
+
+        assert_eq!(builder.graph_stats(2, |_| Duration::from_millis(1)).conflicts, 0, "resources with a capacity don't conflict in the stats either");
+
+        let exec = builder.build();
+        assert!(exec.tasks.iter().all(|task| task.lockable_deps().is_empty() && task.permits().len() == 2), "policies must not override capacities");
+        assert_eq!(exec.resources.policies()["db"], ConflictPolicy::Concurrent);
+        assert_eq!(exec.resources.policies()["cache"], ConflictPolicy::Concurrent);
+    }
+
+    #[test]
+    fn capacity_single_thread() {
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+        let suspended = AtomicBool::new(false);
+        let executed = AtomicUsize::new(0);
+
+        let mut builder = builder::<(), &str>();
+        builder.capacity("db", 1);
+        builder.add_with_context(|_: &(), context: &TaskContext<&str>| {
+            //the other task starts on this worker while this one holds the permit
+            if !suspended.swap(true, Ordering::SeqCst) {
+                return context.suspend();
+            }
+
+            executed.fetch_add(1, Ordering::SeqCst);
+        }, [], ["db"], &[]);
+        builder.add(|_: &()| { executed.fetch_add(1, Ordering::SeqCst); }, [], ["db"], &[]);
+
+        let mut exec = builder.build();
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+
+        for seeding in [Seeding::Lazy, Seeding::Ordered] {
+            suspended.store(false, Ordering::SeqCst);
+            executed.store(0, Ordering::SeqCst);
+
+            exec.set_seeding(seeding);
+            pool.install(|| exec.run(&()));
+            assert_eq!(executed.load(Ordering::SeqCst), 2, "a task waiting for a permit must not block the worker of its holder");
+        }
+    }
+
+    #[test]
+    fn checkpoint() {
+        use std::panic::{self, AssertUnwindSafe};
+        use std::sync::Mutex;
+
+        let log = Mutex::new(Vec::new());
+        let saved = Mutex::new(None);
+
+        let build = |fail: bool| {
+            let (log, saved) = (&log, &saved);
+            let mut builder = builder::<(), u32>();
+            let a = builder.add_with_context(move |_: &(), context: &TaskContext<'_, u32>| {
+                log.lock().unwrap().push("a");
+                context.save(vec![7]);
+            }, [], [0], &[]);
+
+            let b = builder.add(move |_: &()| {
+                assert!(!fail, "crashed");
+                log.lock().unwrap().push("b");
+            }, [0], [1], &[a]);
+
+            builder.add(move |_: &()| log.lock().unwrap().push("c"), [1], [], &[b]);
+
+            let mut exec = builder.build();
+            exec.checkpoint(move |checkpoint| *saved.lock().unwrap() = Some(checkpoint.clone()));
+            (exec, a)
+        };
+
+        let (mut crashing, a) = build(true);
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| crashing.run(&()))).is_err());
+
+        let checkpoint = saved.lock().unwrap().take().unwrap();
+        assert_eq!(checkpoint.completed().collect::<Vec<_>>(), [0]);
+        assert_eq!(checkpoint.snapshot(a), Some(&[7][..]));
+
+        let (mut restarted, _) = build(false);
+        restarted.resume_from_checkpoint(&checkpoint, &()).unwrap();
+        assert_eq!(*log.lock().unwrap(), ["a", "b", "c"], "completed tasks must not execute again");
+
+        let resumed = saved.lock().unwrap().take().unwrap();
+        assert_eq!(resumed.len(), 3);
+        assert_eq!(resumed.snapshot(a), Some(&[7][..]));
+
+        restarted.run(&());
+        assert_eq!(log.lock().unwrap().len(), 6, "plain runs start from scratch");
+    }
+
+    #[test]
+    fn checkpoint_slow_sink() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Instant;
+
+        let (saved, stalled) = (Mutex::new(None), AtomicBool::new(false));
+        let sinking = AtomicBool::new(false);
+        let finished = AtomicBool::new(false);
+        let wait = |flag: &AtomicBool| {
+            let start = Instant::now();
+            while !flag.load(Ordering::SeqCst) && start.elapsed() < Duration::from_secs(10) {
+                thread::yield_now();
+            }
+
+            flag.load(Ordering::SeqCst)
+        };
+
+        let mut builder = builder::<(), u32>();
+        let a = builder.add(|_: &()| {}, [], [], &[]);
+        let b = builder.add(|_: &()| { wait(&sinking); }, [], [], &[]);
+        builder.add(|_: &()| finished.store(true, Ordering::SeqCst), [], [], &[b]);
+
+        let mut exec = builder.build();
+        exec.checkpoint(|checkpoint| {
+            //b completes and c executes while the sink persists the checkpoint of a
+            if checkpoint.is_completed(a) && !sinking.swap(true, Ordering::SeqCst) && !wait(&finished) {
+                stalled.store(true, Ordering::SeqCst);
+            }
+
+            *saved.lock().unwrap() = Some(checkpoint.clone());
+        });
+
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        pool.install(|| exec.run(&()));
+
+        assert!(!stalled.load(Ordering::SeqCst), "a slow sink must not stall tasks completing meanwhile");
+        assert_eq!(saved.lock().unwrap().take().unwrap().len(), 3, "the latest checkpoint must be persisted");
+    }
+
+    #[test]
+    fn run_shared_after_resume() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let executed = AtomicUsize::new(0);
+        let count = |_: &()| { executed.fetch_add(1, Ordering::Relaxed); };
+
+        let mut builder = builder::<(), u32>();
+        let a = builder.add_shared(count, [], [0], &[]);
+        builder.add_shared(count, [0], [], &[a]);
+
+        let mut exec = builder.build();
+        let saved = Arc::new(Mutex::new(None));
+        let sink = saved.clone();
+        exec.checkpoint(move |checkpoint| *sink.lock().unwrap() = Some(checkpoint.clone()));
+
+        exec.run(&());
+        let checkpoint = saved.lock().unwrap().take().unwrap();
+        assert_eq!(checkpoint.len(), 2);
+
+        exec.resume_from_checkpoint(&checkpoint, &()).unwrap();
+        assert_eq!(executed.load(Ordering::Relaxed), 2, "completed tasks must not execute again");
+
+        exec.run_shared(&());
+        assert_eq!(executed.load(Ordering::Relaxed), 4, "shared runs start from scratch");
+    }
+
+    #[test]
+    fn non_idempotent() {
+        use std::panic::{self, AssertUnwindSafe};
+        use std::sync::Mutex;
+
+        let log = Mutex::new(Vec::new());
+        let saved = Mutex::new(None);
+
+        let build = |fail: bool| {
+            let (log, saved) = (&log, &saved);
+            let push = move |name: &'static str| move |_: &()| log.lock().unwrap().push(name);
+
+            let mut builder = builder::<(), u32>();
+            let ids = builder.extend(vec![
+                TaskSpec::new("charge", move |_: &()| {
+                    log.lock().unwrap().push("charge");
+                    assert!(!fail, "crashed");
+                }).non_idempotent(),
+                TaskSpec::new("render", move |_: &()| {
+                    assert!(!fail, "crashed");
+                    log.lock().unwrap().push("render");
+                }),
+                TaskSpec::new("receipt", push("receipt")).after(["charge"]),
+                TaskSpec::new("archive", push("archive")).after(["receipt"]),
+            ]).unwrap();
+
+            let mut exec = builder.build();
+            exec.set_parallelism(Parallelism::Sequential);
+            exec.checkpoint(move |checkpoint| *saved.lock().unwrap() = Some(checkpoint.clone()));
+            (exec, ids)
+        };
+
+        let (mut crashing, ids) = build(true);
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| crashing.run(&()))).is_err());
+
+        let checkpoint = saved.lock().unwrap().take().unwrap();
+        assert!(checkpoint.is_interrupted(ids["charge"]));
+        assert!(!checkpoint.is_interrupted(ids["render"]), "only non-idempotent tasks are recorded when they start");
+
+        let (mut restarted, ids) = build(false);
+        let stopped = restarted.resume_from_checkpoint(&checkpoint, &()).unwrap();
+        assert_eq!(stopped, [ids["charge"], ids["receipt"], ids["archive"]]);
+        assert_eq!(*log.lock().unwrap(), ["charge", "render"], "interrupted non-idempotent tasks must not execute again");
+
+        let resumed = saved.lock().unwrap().take().unwrap();
+        assert_eq!(resumed.interrupted().collect::<Vec<_>>(), [0]);
+    }
+
+    #[test]
+    fn compensate() {
+        use std::panic::{self, AssertUnwindSafe};
+        use std::sync::Mutex;
+
+        let log = Mutex::new(Vec::new());
+        let push = |name: &'static str| { let log = &log; move |_: &()| log.lock().unwrap().push(name) };
+
+        let build = |fail: bool| {
+            let mut builder = builder::<(), u32>();
+            builder.extend(vec![
+                TaskSpec::new("reserve", push("reserve")).compensate(push("release")),
+                TaskSpec::new("charge", push("charge")).compensate(push("refund")).after(["reserve"]),
+                TaskSpec::new("notify", push("notify")).after(["reserve"]),
+                TaskSpec::new("ship", move |_: &()| assert!(!fail, "out of stock")).after(["charge"]),
+            ]).unwrap();
+
+            builder.build()
+        };
+
+        let mut exec = build(false);
+        exec.run(&());
+        assert_eq!(*log.lock().unwrap(), ["reserve", "charge", "notify"], "successful runs aren't compensated");
+
+        for parallelism in [Parallelism::Sequential, Parallelism::Full] {
+            log.lock().unwrap().clear();
+
+            let mut failing = build(true);
+            failing.set_parallelism(parallelism);
+            assert!(panic::catch_unwind(AssertUnwindSafe(|| failing.run(&()))).is_err());
+
+            let log = log.lock().unwrap();
+            let compensations: Vec<_> = log.iter().filter(|name| ["release", "refund"].contains(name)).collect();
+            assert_eq!(compensations, [&"refund", &"release"], "dependants are compensated before their dependencies");
+            assert!(log[0] == "reserve" && log.contains(&"charge"));
+        }
+
+        let fail = AtomicBool::new(true);
+        let mut builder = builder::<(), u32>();
+        builder.extend(vec![
+            TaskSpec::new("reserve", push("reserve")).compensate(push("release")),
+            TaskSpec::new("charge", |_: &()| assert!(!fail.load(Ordering::Relaxed), "declined")).after(["reserve"]),
+            TaskSpec::new("ship", push("ship")).after(["charge"]),
+        ]).unwrap();
+
+        let mut exec = builder.build();
+        for parallelism in [Parallelism::Sequential, Parallelism::Full] {
+            log.lock().unwrap().clear();
+            fail.store(true, Ordering::Relaxed);
+            exec.set_parallelism(parallelism);
+            assert!(panic::catch_unwind(AssertUnwindSafe(|| exec.run(&()))).is_err());
+            assert_eq!(*log.lock().unwrap(), ["reserve", "release"], "tasks after the failed one don't execute");
+
+            fail.store(false, Ordering::Relaxed);
+            exec.run(&());
+            assert_eq!(*log.lock().unwrap(), ["reserve", "release", "reserve", "ship"], "the executor runs again after a failed run");
+        }
+    }
+
+    #[test]
+    fn hooks() {
+        use std::sync::Mutex;
+
+        let log = Mutex::new(Vec::new());
+        let push = |name: &'static str| { let log = &log; move |_: &u32| log.lock().unwrap().push(name.to_string()) };
+
+        let mut builder = builder::<u32, u32>();
+        builder.extend(vec![
+            TaskSpec::new("a", push("a")).writes([0]),
+            TaskSpec::new("b", push("b")).writes([0]),
+        ]).unwrap();
+
+        let mut exec = builder.build();
+        let log = &log;
+        exec.before_run(move |frame: &u32, exec: &InterlockExecutor<'_, u32, u32>| log.lock().unwrap().push(format!("acquire {} of {}", frame, exec.len())));
+        exec.after_run(move |frame: &u32, _: &InterlockExecutor<'_, u32, u32>| log.lock().unwrap().push(format!("submit {}", frame)));
+
+        exec.run(&1);
+        exec.set_parallelism(Parallelism::Sequential);
+        exec.run(&2);
+
+        let log = log.lock().unwrap();
+        assert_eq!(log[0], "acquire 1 of 2");
+        assert_eq!(log[3], "submit 1");
+        assert_eq!(log[4..], ["acquire 2 of 2", "a", "b", "submit 2"]);
+    }
+
+    #[test]
+    fn run_with_extra() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        struct Frame {
+            delta: u32,
+            events: Vec<&'static str>
+        }
+
+        let build = || {
+            let mut graph = builder::<Mutex<Vec<String>>, u32>();
+            let input = graph.add_with_extra(|log: &Mutex<Vec<String>>, frame: &Frame| {
+                log.lock().unwrap().extend(frame.events.iter().map(|event| event.to_string()));
+            }, [], [0], &[]);
+
+            let step = graph.add_with_context(|log: &Mutex<Vec<String>>, context: &TaskContext<u32>| {
+                assert!(context.extra::<u64>().is_none(), "extra data of another type");
+                let delta = context.extra::<Frame>().map_or(0, |frame| frame.delta);
+                log.lock().unwrap().push(format!("step {}", delta));
+            }, [0], [], &[input]);
+            graph.label(step, "step").unwrap();
+
+            graph.extend(vec![TaskSpec::with_extra("count", |log: &Mutex<Vec<String>>, frame: &Frame| {
+                log.lock().unwrap().push(format!("{} events", frame.events.len()));
+            }).reads([0]).after(["step"])]).unwrap();
+
+            graph.build()
+        };
+
+        let log = Mutex::new(Vec::new());
+        let mut exec = build();
+        exec.run_with_extra(&log, &Frame { delta: 16, events: vec!["jump", "fire"] });
+        exec.run_with_extra(&log, &Frame { delta: 17, events: vec![] });
+        assert_eq!(*log.lock().unwrap(), ["jump", "fire", "step 16", "2 events", "step 17", "0 events"]);
+
+        let plain = panic::catch_unwind(AssertUnwindSafe(|| build().run(&log)));
+        assert!(plain.is_err(), "tasks taking extra data can't run without it");
+    }
+
+    #[test]
+    fn configure() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        struct Solver {
+            iterations: usize
+        }
+
+        let mut graph = builder::<Mutex<Vec<usize>>, u32>();
+        let solve = graph.add_with_params(Solver { iterations: 4 }, |log: &Mutex<Vec<usize>>, solver: &Solver| {
+            log.lock().unwrap().push(solver.iterations);
+        }, [], [0], &[]);
+        graph.label(solve, "solve").unwrap();
+
+        graph.extend(vec![TaskSpec::with_params("scale", 2usize, |log: &Mutex<Vec<usize>>, scale: &usize| {
+            let mut log = log.lock().unwrap();
+            let last = log[log.len() - 1];
+            log.push(last * scale);
+        }).reads([0]).after(["solve"])]).unwrap();
+
+        let mut exec = graph.build();
+        let log = Mutex::new(Vec::new());
+        exec.run(&log);
+
+        exec.configure(solve, Solver { iterations: 8 });
+        exec.configure(exec.task_by_label("scale").unwrap(), 3usize);
+        exec.run(&log);
+        assert_eq!(*log.lock().unwrap(), [4, 8, 8, 24]);
+        assert_eq!(exec.params::<Solver>(solve).map(|solver| solver.iterations), Some(8));
+        assert!(exec.params::<usize>(solve).is_none());
+
+        let other = panic::catch_unwind(AssertUnwindSafe(|| exec.configure(solve, 16usize)));
+        assert!(other.is_err(), "parameters keep their type");
+    }
+
+    #[test]
+    fn warm_up() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let executed = AtomicUsize::new(0);
+        let hooked = AtomicUsize::new(0);
+        let count = |_: &()| { executed.fetch_add(1, Ordering::Relaxed); };
+
+        let mut graph = builder::<(), u32>();
+        let a = graph.add(count, [], [0], &[]);
+        let b = graph.add(count, [0], [1], &[a]);
+        graph.add(count, [0, 1], [], &[b]);
+        graph.add(count, [], [0], &[]);
+
+        let mut exec = graph.build();
+        exec.before_run(|_, _| { hooked.fetch_add(1, Ordering::Relaxed); });
+
+        for (parallelism, seeding) in [(Parallelism::Threads(2), Seeding::Lazy), (Parallelism::Full, Seeding::Ordered), (Parallelism::Sequential, Seeding::Lazy)] {
+            exec.set_parallelism(parallelism);
+            exec.set_seeding(seeding);
+            exec.warm_up(&());
+            assert_eq!((executed.load(Ordering::Relaxed), hooked.load(Ordering::Relaxed)), (0, 0), "warm ups execute nothing");
+        }
+
+        exec.run(&());
+        assert_eq!(executed.load(Ordering::Relaxed), 4, "dry runs leave the counters ready for a run");
+        assert_eq!(hooked.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn deferred() {
+        enum Command {
+            Spawn(u32),
+            Despawn(u32)
+        }
+
+        let commands = CommandBuffer::new();
+
+        let mut builder = builder::<Vec<u32>, u32>();
+        builder.extend(vec![
+            TaskSpec::new("spawn", |world: &Vec<u32>| commands.push(Command::Spawn(world.len() as u32))),
+            TaskSpec::new("despawn", |world: &Vec<u32>| commands.extend(world.iter().filter(|&&id| id % 2 == 1).map(|&id| Command::Despawn(id)))).after(["spawn"]),
+        ]).unwrap();
+
+        let mut exec = builder.build();
+        let mut world = vec![0, 1, 2];
+
+        let apply = |world: &mut Vec<u32>, command: Command| match command {
+            Command::Spawn(id) => world.push(id),
+            Command::Despawn(id) => world.retain(|&other| other != id)
+        };
+
+        exec.run_deferred(&mut world, &commands, apply);
+        assert_eq!(world, [0, 2, 3]);
+        assert!(commands.is_empty());
+
+        exec.run_deferred(&mut world, &commands, apply);
+        assert_eq!(world, [0, 2]);
+    }
+
+    #[test]
+    fn flush() {
+        use std::sync::{Mutex, RwLock};
+        use std::sync::atomic::AtomicBool;
+
+        let commands = CommandBuffer::new();
+        let seen = Mutex::new(Vec::new());
+        let flushing = AtomicBool::new(false);
+
+        let observe = |_: &RwLock<Vec<u32>>| assert!(!flushing.load(Ordering::SeqCst), "tasks must not execute during a flush");
+
+        let mut builder = builder::<RwLock<Vec<u32>>, u32>();
+        builder.extend(vec![
+            TaskSpec::new("spawn", |world: &RwLock<Vec<u32>>| commands.push(world.read().unwrap().len() as u32)).writes([0]),
+            TaskSpec::flush("flush", &commands, |world: &RwLock<Vec<u32>>, id| {
+                flushing.store(true, Ordering::SeqCst);
+                world.write().unwrap().push(id);
+                flushing.store(false, Ordering::SeqCst);
+            }).after(["spawn"]),
+            TaskSpec::new("count", |world: &RwLock<Vec<u32>>| seen.lock().unwrap().push(world.read().unwrap().len())).after(["flush"]),
+            TaskSpec::new("physics", observe).reads([1]),
+        ]).unwrap();
+
+        let mut exec = builder.build();
+        let world = RwLock::new(Vec::new());
+        exec.run(&world);
+        exec.run(&world);
+
+        assert_eq!(*world.read().unwrap(), [0, 1]);
+        assert_eq!(*seen.lock().unwrap(), [1, 2], "dependants of a flush observe its commands in the same run");
+        assert!(commands.is_empty());
+    }
+
+    #[test]
+    fn flush_concurrent() {
+        use std::sync::atomic::AtomicBool;
+
+        let commands = CommandBuffer::new();
+        let flushing = AtomicBool::new(false);
+
+        let observe = |_: &()| {
+            assert!(!flushing.load(Ordering::SeqCst), "tasks must not execute during a flush");
+            thread::sleep(Duration::from_millis(2));
+            assert!(!flushing.load(Ordering::SeqCst), "tasks must not execute during a flush");
+        };
+
+        let mut builder = builder::<(), &str>();
+        builder.policy("log", resource::ConflictPolicy::Concurrent);
+        builder.capacity("db", 2);
+        builder.extend(vec![
+            TaskSpec::flush("flush", &commands, |_: &(), ()| {
+                flushing.store(true, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(2));
+                flushing.store(false, Ordering::SeqCst);
+            }),
+            TaskSpec::new("append", observe).writes(["log"]),
+            TaskSpec::new("query", observe).writes(["db"]),
+        ]).unwrap();
+
+        let mut exec = builder.build();
+        assert_eq!(exec.tasks[0].lockable_deps().len(), 2, "a flush conflicts with concurrent and capacity resources");
+
+        for _ in 0..8 {
+            commands.push(());
+            exec.run(&());
+        }
+
+        assert!(commands.is_empty());
+    }
+
+    #[test]
+    fn version() {
+        use std::sync::Mutex;
+
+        let build = |changed: bool| {
+            let closure = |_: &()| {};
+            let mut builder = builder::<(), &str>();
+            let mut specs = vec![
+                TaskSpec::new("input", closure).writes(["keys"]).memoize(|_| 1),
+                TaskSpec::new("move", closure).reads(["keys"]).writes([if changed { "velocity" } else { "position" }]).after(["input"]),
+                TaskSpec::new("draw", closure).reads(["position"]),
+            ];
+
+            if changed {
+                specs.pop();
+                specs.push(TaskSpec::new("audio", closure));
+            }
+
+            builder.extend(specs).unwrap();
+            builder.build()
+        };
+
+        let saved = Mutex::new(None);
+        let mut exec = build(false);
+        exec.checkpoint(|checkpoint| *saved.lock().unwrap() = Some(checkpoint.clone()));
+        exec.run(&());
+
+        assert_eq!(exec.version(), build(false).version());
+        assert_eq!(exec.version().fingerprint(), build(false).version().fingerprint());
+        assert!(build(false).restore_memo_cache(&exec.memo_cache()).is_ok());
+
+        let checkpoint = saved.lock().unwrap().take().unwrap();
+        let mismatch = build(true).resume_from_checkpoint(&checkpoint, &()).unwrap_err();
+        assert_eq!(mismatch, VersionMismatch { added: vec!["audio".to_string()], removed: vec!["draw".to_string()], changed: vec!["move".to_string()] });
+        assert_eq!(mismatch.to_string(), "the graph changed since the artifact was persisted\n  + 'audio'\n  - 'draw'\n  ~ 'move'");
+
+        let mut changed = build(true);
+        assert!(changed.restore_memo_cache(&exec.memo_cache()).is_err());
+        assert!(changed.restore_memo_cache(&MemoCache::new()).is_ok(), "caches without a version aren't checked");
+
+        let plan = exec.plan().unwrap();
+        assert_eq!(plan.version(), &exec.version());
+        assert_ne!(plan.version().fingerprint(), changed.version().fingerprint());
+    }
+
+    #[test]
+    fn fingerprint() {
+        let build = |order: bool| {
+            let closure = |_: &()| {};
+            let mut builder = builder::<(), &str>();
+            let a = builder.add(closure, ["input"], ["position", "velocity"], &[]);
+            let b = builder.add(closure, ["position"], [], &[a]);
+            builder.add(closure, [], [], &[a, b]);
+            builder.label(a, "a").unwrap();
+
+            let mut exec = builder.build();
+            if order {
+                exec.set_priority(Priority::Fanout);
+            }
+
+            exec
+        };
+
+        let fingerprint = build(false).fingerprint();
+        assert_eq!(build(true).fingerprint(), fingerprint, "settings of the executor aren't part of the structure");
+        assert_eq!(build(false).fingerprint(), fingerprint, "equal graphs have equal fingerprints");
+
+        let mut builder = builder::<(), &str>();
+        builder.add(|_: &()| {}, ["input"], ["position"], &[]);
+        assert_ne!(builder.build().fingerprint(), fingerprint);
+    }
+
+    #[test]
+    fn named() {
+        use std::sync::Mutex;
+
+        #[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+        enum Pass { Input, Physics, Render, Audio }
+
+        let log = Mutex::new(Vec::new());
+        let task = |pass: Pass| {
+            let log = &log;
+            move |_: &()| log.lock().unwrap().push(pass)
+        };
+
+        let mut builder = named_builder::<(), u32, Pass>();
+        let audio = builder.add(task(Pass::Audio), [], [], &[]);
+        builder.label(audio, Pass::Audio).unwrap();
+
+        let ids = builder.extend(vec![
+            TaskSpec::new(Pass::Render, task(Pass::Render)).reads([0]).after([Pass::Physics]),
+            TaskSpec::new(Pass::Physics, task(Pass::Physics)).writes([0]).after([Pass::Input]),
+            TaskSpec::new(Pass::Input, task(Pass::Input)).after([Pass::Audio]),
+        ]).unwrap();
+
+        assert_eq!(builder.label(audio, Pass::Input), Err(BuildError::DuplicateLabel(Pass::Input)));
+        assert_eq!(builder.extend(vec![TaskSpec::new(Pass::Audio, task(Pass::Audio))]), Err(BuildError::DuplicateLabel(Pass::Audio)));
+
+        let mut exec = builder.build();
+        assert_eq!(exec.label(ids[&Pass::Physics]), Some(&Pass::Physics));
+        assert_eq!(exec.label(audio), Some(&Pass::Audio));
+
+        exec.set_parallelism(Parallelism::Sequential);
+        exec.run(&());
+        assert_eq!(*log.lock().unwrap(), [Pass::Audio, Pass::Input, Pass::Physics, Pass::Render]);
+        assert!(exec.diff(&exec).is_empty());
+    }
+
+    #[test]
+    fn iter() {
+        let closure = |_: &()| {};
+        let mut builder = builder::<(), &str>();
+
+        let input = builder.add(closure, [], ["input"], &[]);
+        let physics = builder.add(closure, ["input"], ["world"], &[input]);
+        let render = builder.add(closure, ["world", "ui"], [], &[input, physics]);
+        builder.read_all(render);
+        builder.realtime(render);
+        builder.label(physics, "physics").unwrap();
+
+        let exec = builder.build();
+        assert_eq!(exec.iter().len(), 3);
+
+        let tasks: Vec<_> = exec.iter().collect();
+        assert_eq!(tasks.iter().map(TaskInfo::id).collect::<Vec<_>>(), [input, physics, render]);
+        assert_eq!(tasks[1].label().map(String::as_str), Some("physics"));
+        assert_eq!(tasks[0].label(), None);
+
+        assert_eq!(tasks[1].accesses(), &[(&"input", Access::Read), (&"world", Access::Write)]);
+        assert_eq!(tasks[2].all(), Some(Access::Read));
+        assert!(tasks[2].is_realtime() && !tasks[1].is_realtime());
+
+        assert_eq!(tasks[2].dependencies(), &[input, physics]);
+        assert_eq!(tasks[0].dependants().len(), 2);
+        assert!(tasks[2].dependants().is_empty());
+
+        let labeled = (&exec).into_iter().filter_map(|task| task.label().cloned()).collect::<Vec<_>>();
+        assert_eq!(labeled, ["physics"]);
+    }
+
+    #[test]
+    fn task_by_label() {
+        let closure = |_: &()| {};
+        let mut builder = builder::<(), &str>();
+
+        let ids = builder.extend(vec![
+            TaskSpec::new("input", closure).writes(["input"]),
+            TaskSpec::new("physics", closure).reads(["input"]).after(["input"]),
+        ]).unwrap();
+
+        let exec = builder.build();
+        let label = String::from("physics");
+
+        assert_eq!(exec.task_by_label("input"), Some(ids["input"]));
+        assert_eq!(exec.task_by_label(&label), Some(ids["physics"]));
+        assert_eq!(exec.task_by_label("render"), None);
+
+        let physics = exec.task(ids["physics"]);
+        assert_eq!(physics.label().map(String::as_str), Some("physics"));
+        assert_eq!(physics.dependencies(), &[ids["input"]]);
+        assert_eq!(physics.accesses(), &[(&"input", Access::Read)]);
+        assert_eq!(exec.task(ids["input"]).dependants(), &[ids["physics"]]);
+    }
+
+    #[test]
+    fn markers() {
+        use crate::tasks::{marker, noop};
+        use std::sync::Mutex;
+
+        let log = Mutex::new(Vec::new());
+        let task = |name: &'static str| {
+            let log = &log;
+            move |_: &()| log.lock().unwrap().push(name)
+        };
+
+        let mut builder = builder::<(), &str>();
+        let shadows = builder.add(task("shadows"), [], ["shadows"], &[]);
+        let lighting = builder.add(task("lighting"), [], ["lighting"], &[]);
+        let done = builder.add_marker(marker("geometry done"), [shadows, lighting]).unwrap();
+        builder.add(task("post"), ["shadows", "lighting"], [], [done]);
+        builder.add(noop(), [], [], [done]);
+
+        assert!(matches!(builder.add_marker(marker("geometry done"), [done]), Err(BuildError::DuplicateLabel(label)) if label == "geometry done"));
+
+        let ids = builder.extend(vec![TaskSpec::marker(marker("frame done")).after(["geometry done"])]).unwrap();
+
+        let mut exec = builder.build();
+        exec.run(&());
+        assert_eq!(log.lock().unwrap().last(), Some(&"post"));
+
+        let info = exec.task(done);
+        assert_eq!(info.label().map(String::as_str), Some("geometry done"));
+        assert_eq!(info.dependencies(), &[shadows, lighting]);
+        assert!(info.accesses().is_empty() && info.all().is_none());
+        assert_eq!(exec.task(ids["frame done"]).dependencies(), &[done]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn initial_counts() {
+        use self::run::{Boxed, Plain};
+        use self::task::{Task, TaskOptions};
+        use std::panic;
+
+        let id = |id: usize| TaskId::branded(0, id);
+        let task = |task: usize, unlock: Vec<TaskId>, initial: usize| {
+            Task::<(), u32>::new(id(task), None, Boxed::new(Plain(|_: &()| {})), TaskOptions::default(), Vec::new(), unlock, initial)
+        };
+
+        context::check_initial_counts(&[task(0, vec![id(1)], 0), task(1, Vec::new(), 1)]);
+
+        //1 would start right away and again once 0 unlocks it
+        let desynced = [task(0, vec![id(1)], 0), task(1, Vec::new(), 0)];
+        let message = panic::catch_unwind(panic::AssertUnwindSafe(|| context::check_initial_counts(&desynced))).unwrap_err();
+        assert_eq!(message.downcast_ref::<String>().map(String::as_str), Some("initial counts out of sync with the graph: 1 starts at 0 instead of 1 and runs twice"));
+
+        //1 waits for an unlock 0 doesn't send
+        let stuck = [task(0, Vec::new(), 0), task(1, Vec::new(), 1)];
+        let message = panic::catch_unwind(panic::AssertUnwindSafe(|| context::check_initial_counts(&stuck))).unwrap_err();
+        assert_eq!(message.downcast_ref::<String>().map(String::as_str), Some("initial counts out of sync with the graph: 1 starts at 1 instead of 0 and is stuck"));
+    }
+
+    #[test]
+    fn task_store() {
+        use self::context::TaskStore;
+
+        let chunks: &[Vec<usize>] = &[vec![0, 1, 2], vec![3, 4, 5], vec![6]];
+        assert_eq!(TaskStore::<usize>::len(chunks), 7);
+        assert!((0..7).all(|id| *TaskStore::<usize>::get(chunks, id) == id));
+
+        let uneven: &[Vec<usize>] = &[vec![0], vec![1, 2]];
+        assert!(panic::catch_unwind(|| TaskStore::<usize>::len(uneven)).is_err());
+        let empty: &[Vec<usize>] = &[vec![], vec![0]];
+        assert!(panic::catch_unwind(|| TaskStore::<usize>::len(empty)).is_err());
+        assert_eq!(TaskStore::<usize>::len(&[][..] as &[Vec<usize>]), 0);
+
+        let array = [0, 1];
+        assert_eq!(TaskStore::len(&array), 2);
+        assert_eq!(*TaskStore::get(&array, 1), 1);
+    }
+
+    #[test]
+    fn independent_sets() {
+        let closure = |_: &()| {};
+        let mut builder = builder::<(), &str>();
+
+        let input = builder.add(closure, [], ["input"], &[]);
+        let audio = builder.add(closure, [], ["audio"], &[]);
+        let physics = builder.add(closure, ["input"], [], &[]);
+        let mixer = builder.add(closure, [], [], [audio]);
+        let output = builder.add(closure, [], [], [mixer]);
+        let ui = builder.add(closure, [], ["ui"], &[]);
+
+        let exec = builder.build();
+        let sets = exec.independent_sets();
+        assert_eq!(sets, vec![vec![input, audio, ui], vec![physics, mixer], vec![output]]);
+        assert!(sets.iter().all(|set| exec.are_independent(set)));
+
+        assert!(!exec.are_independent(&[input, physics]), "conflicting tasks");
+        assert!(!exec.are_independent(&[output, ui, audio]), "output waits for audio through mixer");
+        assert!(exec.are_independent(&[physics, output]));
+        assert!(exec.are_independent(&[]));
+    }
+
+    #[test]
+    fn app() {
+        use std::sync::Mutex;
+
+        let stage = |name: &'static str| {
+            let mut builder = builder::<Mutex<Vec<&str>>, u32>();
+            builder.add(move |log: &Mutex<Vec<&str>>| log.lock().unwrap().push(name), [], [], &[]);
+            builder.build()
+        };
+
+        let mut app = App::new(Mutex::new(Vec::new()));
+        app.add_stage("shutdown", Schedule::Shutdown, stage("shutdown"));
+        app.add_stage("sim", Schedule::Fixed { step: Duration::from_millis(10), max_steps: None }, stage("sim"));
+        app.add_stage("frame", Schedule::Frame, stage("frame"));
+        app.add_stage("startup", Schedule::Startup, stage("startup"));
+
+        app.update(Duration::from_millis(25));
+        app.update(Duration::from_millis(4));
+        app.shutdown();
+        app.shutdown();
+
+        assert!(app.is_stopped());
+        assert_eq!(app.stats("sim").map(|stats| stats.runs), Some(2));
+        assert_eq!(app.stats("frame").map(|stats| stats.runs), Some(2));
+        assert_eq!(app.stats("missing"), None);
+        assert_eq!(app.stages().into_iter().map(|(name, _, _)| name).collect::<Vec<_>>(), ["shutdown", "sim", "frame", "startup"]);
+        assert_eq!(app.into_data().into_inner().unwrap(), ["startup", "sim", "sim", "frame", "frame", "shutdown"]);
+    }
+
+    #[test]
+    fn timed_stages() {
+        use std::sync::Mutex;
+        use std::time::UNIX_EPOCH;
+
+        let stage = |name: &'static str| {
+            let mut builder = builder::<Mutex<Vec<&str>>, u32>();
+            builder.add(move |log: &Mutex<Vec<&str>>| log.lock().unwrap().push(name), [], [], &[]);
+            builder.build()
+        };
+
+        let hour = Duration::from_secs(60 * 60);
+        let start = UNIX_EPOCH + hour * 24 * 10 + hour * 23;
+        let now = Arc::new(Mutex::new(start));
+
+        let mut app = App::new(Mutex::new(Vec::new()));
+        let clock = now.clone();
+        app.set_clock(move || *clock.lock().unwrap());
+        app.add_stage("autosave", Schedule::Every(Duration::from_secs(60)), stage("autosave"));
+        app.add_stage("telemetry", Schedule::Daily { at: hour / 2 }, stage("telemetry"));
+        app.add_stage("frame", Schedule::Frame, stage("frame"));
+        assert_eq!(app.next_due(), None);
+
+        let mut advance = |by: Duration| -> Vec<&str> {
+            *now.lock().unwrap() += by;
+            app.update(Duration::from_millis(16));
+            app.data_mut().get_mut().unwrap().drain(..).filter(|&name| name != "frame").collect()
+        };
+
+        assert!(advance(Duration::default()).is_empty(), "timers start with the first update");
+        assert!(advance(Duration::from_secs(30)).is_empty());
+        assert_eq!(advance(Duration::from_secs(31)), ["autosave"]);
+        assert_eq!(advance(Duration::from_secs(300)), ["autosave"], "missed intervals are skipped");
+        assert!(advance(Duration::from_secs(59)).is_empty());
+        assert_eq!(advance(hour * 2), ["autosave", "telemetry"]);
+        assert!(advance(Duration::from_secs(1)).is_empty());
+
+        let next = start + hour * 2 + Duration::from_secs(480);
+        assert_eq!(app.next_due(), Some(next), "the autosave comes before the next day");
+        assert_eq!(app.stats("telemetry").map(|stats| stats.runs), Some(1));
+
+        let zero = panic::catch_unwind(AssertUnwindSafe(|| app.add_stage("spin", Schedule::Every(Duration::default()), stage("spin"))));
+        assert!(zero.is_err());
+    }
+
+    #[test]
+    fn linked_stages() {
+        use std::sync::Mutex;
+
+        let log = |name| move |log: &Mutex<Vec<&str>>| log.lock().unwrap().push(name);
+        let stage = |names: &[&'static str]| {
+            let mut builder = builder::<Mutex<Vec<&str>>, u32>();
+            let mut previous = None;
+
+            for &name in names {
+                let id = builder.add(log(name), [], [], previous);
+                builder.label(id, name).unwrap();
+                previous = Some(id);
+            }
+
+            builder.build()
+        };
+
+        let mut app = App::new(Mutex::new(Vec::new()));
+        app.add_stage("sim", Schedule::Frame, stage(&["input", "extract", "cleanup"]));
+        app.add_stage("present", Schedule::Frame, stage(&["present"]));
+        app.add_stage("render", Schedule::Frame, stage(&["prepare", "draw"]));
+        app.link("render", "draw", "sim", Some("extract"));
+        app.link("present", "present", "sim", None);
+
+        for _ in 0..3 {
+            app.update(Duration::from_millis(16));
+
+            let log = app.data_mut().get_mut().unwrap().drain(..).collect::<Vec<_>>();
+            let at = |name| log.iter().position(|other| *other == name).unwrap();
+            assert_eq!(log.len(), 6);
+            assert!(at("extract") < at("draw"));
+            assert!(at("cleanup") < at("present"));
+        }
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| app.link("sim", "input", "render", None)));
+        assert!(result.is_err(), "render waits, so it can't be waited for");
+    }
+
+    #[test]
+    fn pipeline() {
+        use std::sync::Mutex;
+        use std::sync::atomic::AtomicU32;
+
+        let rendered = Mutex::new(Vec::new());
+
+        let mut sim = builder::<AtomicU32, u32>();
+        sim.add(|step: &AtomicU32| { step.fetch_add(1, Ordering::Relaxed); }, [], [], &[]);
+
+        let mut render = builder::<u32, u32>();
+        render.add(|step: &u32| rendered.lock().unwrap().push(*step), [], [], &[]);
+
+        let mut pipeline = Pipeline::new(sim.build(), render.build(), 0, |step: &AtomicU32, extracted: &mut u32| *extracted = step.load(Ordering::Relaxed));
+        let step = AtomicU32::new(0);
+
+        for _ in 0..3 {
+            pipeline.frame(&step);
+        }
+
+        //every frame renders the step simulated by the frame before
+        assert_eq!(*rendered.lock().unwrap(), [0, 1, 2]);
+        assert_eq!(step.load(Ordering::Relaxed), 3);
+
+        pipeline.flush(&step);
+        assert_eq!(*pipeline.extracted(), 3);
+        assert_eq!(pipeline.frames(), 3);
+        assert_eq!(*rendered.lock().unwrap(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn stream() {
+        use std::sync::Mutex;
+        use std::sync::atomic::{AtomicBool, AtomicU32};
+
+        let rendered = Mutex::new(Vec::new());
+        let stalled = AtomicBool::new(false);
+
+        let mut sim = builder::<AtomicU32, u32>();
+        sim.add(|step: &AtomicU32| { step.fetch_add(1, Ordering::Relaxed); }, [], [], &[]);
+
+        let mut sink = builder::<u32, u32>();
+        sink.add(|step: &u32| {
+            while stalled.load(Ordering::Acquire) {
+                thread::yield_now();
+            }
+
+            thread::sleep(Duration::from_micros(200));
+            rendered.lock().unwrap().push(*step);
+        }, [], [], &[]);
+
+        //the stalled sink mustn't hold a pool thread the simulation needs
+        let mut sink = sink.build();
+        sink.set_parallelism(Parallelism::Sequential);
+
+        let mut pipeline = Pipeline::new(sim.build(), sink, 0, |step: &AtomicU32, extracted: &mut u32| *extracted = step.load(Ordering::Relaxed));
+        let step = AtomicU32::new(0);
+
+        let stats = pipeline.stream(&step, 2, Backpressure::Block, |step| step.load(Ordering::Relaxed) < 20);
+        assert_eq!((stats.frames, stats.rendered, stats.rejected), (20, 20, 0));
+        assert!(stats.max_queued <= 2, "{:?}", stats);
+        assert_eq!(*rendered.lock().unwrap(), (0..20).collect::<Vec<_>>(), "copies render in order");
+        assert_eq!(*pipeline.extracted(), 19);
+
+        //the sink stalls until the simulation is done, so all but the copies it took or queued are rejected
+        stalled.store(true, Ordering::Release);
+        let rejected = Mutex::new(Vec::new());
+        let reject = Backpressure::Reject(Box::new(|step| rejected.lock().unwrap().push(step)));
+
+        let stats = pipeline.stream(&step, 2, reject, |step| {
+            let more = step.load(Ordering::Relaxed) < 30;
+            stalled.store(more, Ordering::Release);
+            more
+        });
+
+        let rejected = rejected.into_inner().unwrap();
+        assert_eq!(stats.frames, 10);
+        assert_eq!(stats.rendered + stats.rejected, 10);
+        assert!(stats.rejected >= 7 && rejected.len() as u64 == stats.rejected, "{:?}", stats);
+        assert_eq!(stats.max_queued, 2);
+        assert_eq!(pipeline.frames(), 30);
+
+        let mut all = rendered.lock().unwrap().clone();
+        all.extend(rejected);
+        all.sort_unstable();
+        assert_eq!(all, (0..30).collect::<Vec<_>>(), "every copy is either rendered or rejected");
+
+        let zero = panic::catch_unwind(AssertUnwindSafe(|| pipeline.stream(&step, 0, Backpressure::Block, |_| false)));
+        assert!(zero.is_err());
+    }
+
+    #[test]
+    fn fixed_timestep() {
+        use std::sync::Mutex;
+
+        let ticks = Mutex::new(Vec::new());
+        let mut builder = builder::<(), u32>();
+        builder.add_with_context(|_: &(), context: &TaskContext<u32>| ticks.lock().unwrap().push(context.tick().unwrap()), [], [], &[]);
+        let mut exec = builder.build();
+
+        let step = Duration::from_millis(10);
+        let mut timestep = FixedTimestep::new(step).max_steps(3);
+
+        assert_eq!(timestep.run(Duration::from_millis(4), &mut exec, &()), 0);
+        assert_eq!(timestep.run(Duration::from_millis(21), &mut exec, &()), 2);
+        assert_eq!(timestep.accumulated(), Duration::from_millis(5));
+        assert_eq!(exec.tick(), None);
+
+        let tick = |index, alpha| Tick { index, count: 2, step, alpha };
+        assert_eq!(ticks.lock().unwrap().drain(..).collect::<Vec<_>>(), [tick(0, 0.5), tick(1, 0.5)]);
+
+        //a long frame drops the steps above the limit
+        assert_eq!(timestep.run(Duration::from_millis(100), &mut exec, &()), 3);
+        assert_eq!(timestep.accumulated(), Duration::from_millis(5));
+        assert_eq!(timestep.advance(Duration::from_millis(5)).len(), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn fixed_timestep_panic() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let mut builder = builder::<(), u32>();
+        builder.add_with_context(|_: &(), context: &TaskContext<u32>| assert_ne!(context.tick().unwrap().index, 1, "crashed"), [], [], &[]);
+        let mut exec = builder.build();
+
+        let mut timestep = FixedTimestep::new(Duration::from_millis(10));
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| timestep.run(Duration::from_millis(30), &mut exec, &()))).is_err());
+        assert_eq!(exec.tick(), None, "a panicking step must not leave its tick behind");
+    }
+
+    #[test]
+    fn split_impact() {
+        let closure = |_: &()| {};
+        let mut builder = builder::<(), &str>();
+
+        let a = builder.add(closure, [], ["entities"], &[]);
+        let b = builder.add(closure, [], ["entities"], &[]);
+        let c = builder.add(closure, [], ["entities"], &[]);
+        let d = builder.add(closure, [], ["entities"], &[]);
+        builder.add(closure, ["entities"], [], &[]);
+        builder.add(closure, [], [], [a, d]);
+
+        let ms = |_| Duration::from_millis(1);
+        let impact = builder.split_impact(&"entities", &[vec![a, b], vec![c, d]], 4, ms);
+
+        assert_eq!(impact.before, GraphStats { dependencies: 2, conflicts: 10, critical_path: Duration::from_millis(2), makespan: Duration::from_millis(5) });
+        assert_eq!(impact.after.conflicts, 6, "the reader still conflicts with every part");
+        assert_eq!(impact.after.makespan, Duration::from_millis(3));
+        assert_eq!(impact.after.critical_path, impact.before.critical_path);
+        assert_eq!(impact.saved(), Duration::from_millis(2));
+
+        builder.fairness(Fairness::Fifo);
+        let fifo = builder.graph_stats(1, ms);
+        assert_eq!((fifo.dependencies, fifo.conflicts), (12, 0));
+        assert_eq!(fifo.makespan, Duration::from_millis(6));
+
+        //nothing was split, d still waits for every earlier writer
+        let exec = builder.build();
+        assert_eq!(exec.tasks[d.id()].initial_count(), 3);
+    }
+
+    #[test]
+    fn fanout() {
+        use std::sync::Mutex;
+
+        let log = Mutex::new(Vec::new());
+        let task = |name: &'static str| {
+            let log = &log;
+            move |_: &()| log.lock().unwrap().push(name)
+        };
+
+        let mut builder = builder::<(), u32>();
+        builder.add(task("leaf"), [], [], &[]);
+        let hub = builder.add(task("hub"), [], [], &[]);
+        builder.add(task("side"), [], [], &[hub]);
+        let inner = builder.add(task("inner"), [], [], &[hub]);
+        builder.add(task("outer"), [], [], &[inner]);
+
+        let mut exec = builder.build();
+        exec.set_parallelism(Parallelism::Sequential);
+
+        exec.run(&());
+        assert_eq!(log.lock().unwrap().drain(..).collect::<Vec<_>>(), ["leaf", "hub", "side", "inner", "outer"]);
+
+        exec.set_priority(Priority::Fanout);
+        exec.run(&());
+        assert_eq!(log.lock().unwrap().drain(..).collect::<Vec<_>>(), ["hub", "inner", "outer", "side", "leaf"]);
+
+        exec.set_priority(Priority::Declaration);
+        exec.run(&());
+        assert_eq!(log.lock().unwrap().drain(..).collect::<Vec<_>>(), ["leaf", "hub", "side", "inner", "outer"]);
+    }
+
+    #[test]
+    fn fairness() {
+        use self::resource::Fairness;
+        use std::sync::Mutex;
+
+        let closure = |_: &()| std::thread::sleep(Duration::from_millis(1));
+        let reader = TimelineReader::new();
+
+        let mut builder = builder::<(), u32>();
+        builder.fairness(Fairness::Fifo);
+        builder.add(reader.wrap("r1", closure), [0], [], &[]);
+        builder.add(reader.wrap("r2", closure), [0], [], &[]);
+        builder.add(reader.wrap("w", closure), [], [0], &[]);
+        builder.add(reader.wrap("r3", closure), [0], [], &[]);
+        builder.add(reader.wrap("other", closure), [1], [], &[]);
+
+        let mut exec = builder.build();
+        exec.run(&());
+
+        let analyzer = reader.analyze();
+        dep(&analyzer, "r1", "w");
+        dep(&analyzer, "r2", "w");
+        dep(&analyzer, "w", "r3");
+        assert!(exec.tasks.iter().all(|task| task.lockable_deps().is_empty()), "conflicts must become dependencies");
+
+        let log = Mutex::new(Vec::new());
+        let task = |name: &'static str| {
+            let log = &log;
+            move |_: &()| log.lock().unwrap().push(name)
+        };
+
+        let mut builder = InterlockBuilder::<(), u32>::new();
+        builder.fairness(Fairness::WriterPriority);
+        builder.add(task("r1"), [0], [], &[]);
+        builder.add(task("r2"), [0], [], &[]);
+        builder.add(task("w"), [], [0], &[]);
+
+        let mut exec = builder.build();
+        exec.set_parallelism(Parallelism::Sequential);
+        exec.run(&());
+        assert_eq!(*log.lock().unwrap(), ["w", "r1", "r2"]);
+    }
+
+    #[test]
+    fn verify_timeline() {
+        let closure = |_: &()| {};
+
+        let reader = TimelineReader::new();
+        let mut graph = named_builder::<(), u32, &str>();
+
+        let mut add = |name, reads: &[u32], writes: &[u32], deps: &[TaskId]| {
+            let task = graph.add(reader.wrap(name, closure), reads.iter().copied(), writes.iter().copied(), deps);
+            graph.label(task, name).unwrap();
+            task
+        };
+
+        let input = add("input", &[], &[0], &[]);
+        let physics = add("physics", &[0], &[1], &[input]);
+        add("ai", &[0, 1], &[2], &[input]);
+        add("render", &[1, 2], &[], &[physics]);
+
+        let mut exec = graph.build();
+        exec.run(&());
+        exec.run(&());
+
+        assert_eq!(exec.verify_timeline(&reader.analyze()), Ok(()));
+
+        let ms = Duration::from_millis;
+        let timeline = TimelineAnalyzer::from_spans(vec![
+            ("input", ms(0), ms(10)),
+            ("physics", ms(5), ms(20)),
+            ("ai", ms(15), ms(25)),
+            ("render", ms(30), ms(40)),
+            ("unknown", ms(0), ms(40))
+        ]);
+
+        let violations = exec.verify_timeline(&timeline).unwrap_err();
+        assert_eq!(violations, [
+            Violation::Dependency { before: &"input", after: &"physics", repeat: 0 },
+            Violation::Conflict { a: &"physics", b: &"ai", repeat: 0 }
+        ]);
+        assert_eq!(violations[0].to_string(), "'physics' started before 'input' ended in execution 0");
+
+        //physics skipped in the first run, in the second one render overlaps ai
+        let timeline = TimelineAnalyzer::from_spans(vec![
+            ("input", ms(0), ms(10)),
+            ("ai", ms(10), ms(20)),
+            ("render", ms(20), ms(30)),
+            ("input", ms(100), ms(110)),
+            ("physics", ms(110), ms(120)),
+            ("ai", ms(120), ms(130)),
+            ("render", ms(125), ms(140))
+        ]);
+
+        assert_eq!(exec.verify_timeline(&timeline), Err(vec![Violation::Conflict { a: &"ai", b: &"render", repeat: 1 }]));
+    }
+}
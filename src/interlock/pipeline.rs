@@ -0,0 +1,250 @@
+use super::InterlockExecutor;
+use crate::Executable;
+use std::collections::VecDeque;
+use std::hash::Hash;
+use std::panic;
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Copies the state the render graph needs from the simulated data, see `Pipeline`.
+type Extract<'a, T, E> = dyn FnMut(&T, &mut E) + Send + 'a;
+
+/// What `Pipeline::stream` does with a copy once the render graph fell behind by the bound.
+pub enum Backpressure<'a, E> {
+    /// The simulation waits until the render graph took a copy, so it slows down to the pace of the render graph.
+    Block,
+    /// Hands the copy to the callback instead of queuing it, e.g. to count and drop it or to spill it to disk.
+    Reject(Box<dyn FnMut(E) + 'a>)
+}
+
+/// Counts of a `Pipeline::stream` call.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct StreamStats {
+    /// Frames simulated, each of them extracted a copy.
+    pub frames: u64,
+    pub rendered: u64,
+    /// Copies handed to the callback of `Backpressure::Reject`.
+    pub rejected: u64,
+    /// Most copies queued at the same time.
+    pub max_queued: usize,
+    /// Time the simulation waited for the render graph.
+    pub blocked: Duration
+}
+
+/// Copies extracted but not rendered yet, and rendered ones to extract into again.
+struct Queue<E> {
+    state: Mutex<QueueState<E>>,
+    pushed: Condvar,
+    popped: Condvar
+}
+
+struct QueueState<E> {
+    items: VecDeque<E>,
+    spare: Vec<E>,
+    //set when either side stops, so the other one doesn't wait for it forever
+    closed: bool
+}
+
+impl<E> Queue<E> {
+
+    fn lock(&self) -> MutexGuard<'_, QueueState<E>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Queues `item` unless `bound` items are queued already, then applies `backpressure`. Returns false once closed.
+    fn push(&self, item: E, bound: usize, backpressure: &mut Backpressure<'_, E>, stats: &mut StreamStats) -> bool {
+        let mut state = self.lock();
+
+        if state.items.len() >= bound {
+            match backpressure {
+                Backpressure::Block => {
+                    let start = Instant::now();
+                    while state.items.len() >= bound && !state.closed {
+                        state = self.popped.wait(state).unwrap_or_else(PoisonError::into_inner);
+                    }
+
+                    stats.blocked += start.elapsed();
+                },
+
+                Backpressure::Reject(reject) => {
+                    drop(state);
+                    reject(item);
+                    stats.rejected += 1;
+                    return true;
+                }
+            }
+        }
+
+        if state.closed {
+            return false;
+        }
+
+        state.items.push_back(item);
+        stats.max_queued = stats.max_queued.max(state.items.len());
+        self.pushed.notify_one();
+        true
+    }
+
+    /// Takes the oldest item, waits for one if none is queued. Returns `None` once closed and empty.
+    fn pop(&self) -> Option<E> {
+        let mut state = self.lock();
+
+        loop {
+            match state.items.pop_front() {
+                Some(item) => {
+                    self.popped.notify_one();
+                    return Some(item);
+                },
+
+                None if state.closed => return None,
+                None => state = self.pushed.wait(state).unwrap_or_else(PoisonError::into_inner)
+            }
+        }
+    }
+}
+
+/// Closes the queue when a side of the stream stops, whether it returned or panicked.
+struct Close<'a, E>(&'a Queue<E>);
+
+impl<E> Drop for Close<'_, E> {
+
+    fn drop(&mut self) {
+        self.0.lock().closed = true;
+        self.0.pushed.notify_all();
+        self.0.popped.notify_all();
+    }
+}
+
+/**
+ Pipelined simulation and rendering: every frame first copies what the render graph needs out of the data with
+ `extract`, then simulates the next step and renders the copy at the same time. Rendering lags a frame behind,
+ in exchange neither graph waits for the other, and the render graph never sees the data while it changes.
+
+ The copy phase runs alone, on the calling thread, so `extract` has exclusive access to the copy and shared access
+ to the data without any synchronization. Keep it to copying, everything else belongs to one of the graphs.
+*/
+pub struct Pipeline<'task, T, E, R, N = String> {
+    sim: InterlockExecutor<'task, T, R, N>,
+    render: InterlockExecutor<'task, E, R, N>,
+    extract: Box<Extract<'task, T, E>>,
+    extracted: E,
+    frames: u64
+}
+
+impl<'task, T: Sync, E: Sync, R: Eq + Hash + Send + Sync, N: Send + Sync> Pipeline<'task, T, E, R, N> {
+
+    /// Pipelines `sim` and `render`, `extracted` is the copy `extract` overwrites every frame.
+    pub fn new(sim: InterlockExecutor<'task, T, R, N>, render: InterlockExecutor<'task, E, R, N>, extracted: E,
+               extract: impl FnMut(&T, &mut E) + Send + 'task) -> Self {
+        Self { sim, render, extract: Box::new(extract), extracted, frames: 0 }
+    }
+
+    /// Copies the current state of `data`, then runs the simulation over `data` and the render graph over the copy in parallel.
+    pub fn frame(&mut self, data: &T) {
+        (self.extract)(data, &mut self.extracted);
+
+        let (sim, render, extracted) = (&mut self.sim, &mut self.render, &self.extracted);
+        rayon::join(|| sim.run(data), || render.run(extracted));
+
+        self.frames += 1;
+    }
+
+    /// Copies and renders the current state of `data` without simulating, e.g. to show the last step before shutting down.
+    pub fn flush(&mut self, data: &T) {
+        (self.extract)(data, &mut self.extracted);
+        self.render.run(&self.extracted);
+    }
+
+    /**
+     Runs frames as long as `more` returns true, without waiting for the render graph: the simulation extracts a copy
+     and steps on the calling thread, while another thread renders the copies in order, e.g. an ingestion graph feeding
+     a slow sink. Once `bound` copies wait to be rendered, `backpressure` decides what happens to the next one, so the
+     queue doesn't grow without limit when the render graph stalls. Copies are cloned from the last rendered one and
+     reused once rendered. Returns after every queued copy was rendered.
+
+     Panics if `bound` is 0, or once both sides stopped if one of the graphs panicked.
+    */
+    pub fn stream(&mut self, data: &T, bound: usize, mut backpressure: Backpressure<'_, E>, mut more: impl FnMut(&T) -> bool) -> StreamStats
+        where E: Clone + Send {
+        assert!(bound > 0, "can't stream with a bound of 0");
+
+        let queue = Queue { state: Mutex::new(QueueState { items: VecDeque::new(), spare: Vec::new(), closed: false }), pushed: Condvar::new(), popped: Condvar::new() };
+        let mut stats = StreamStats::default();
+
+        let (sim, render, extract, template) = (&mut self.sim, &mut self.render, &mut self.extract, &self.extracted);
+        let (last, rendered) = thread::scope(|scope| {
+            let queue = &queue;
+            let renderer = scope.spawn(move || {
+                let _close = Close(queue);
+                let (mut last, mut rendered) = (None, 0);
+
+                while let Some(item) = queue.pop() {
+                    render.run(&item);
+                    rendered += 1;
+
+                    if let Some(previous) = last.replace(item) {
+                        queue.lock().spare.push(previous);
+                    }
+                }
+
+                (last, rendered)
+            });
+
+            let close = Close(queue);
+            while more(data) {
+                let mut copy = queue.lock().spare.pop().unwrap_or_else(|| template.clone());
+                extract(data, &mut copy);
+
+                //the render graph panicked
+                if !queue.push(copy, bound, &mut backpressure, &mut stats) {
+                    break;
+                }
+
+                sim.run(data);
+                stats.frames += 1;
+            }
+
+            drop(close);
+            renderer.join().unwrap_or_else(|payload| panic::resume_unwind(payload))
+        });
+
+        if let Some(last) = last {
+            self.extracted = last;
+        }
+
+        stats.rendered = rendered;
+        self.frames += stats.frames;
+        stats
+    }
+
+    /// Returns the copy rendered last.
+    pub fn extracted(&self) -> &E {
+        &self.extracted
+    }
+
+    /// Number of frames run so far, flushes don't count.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn sim(&self) -> &InterlockExecutor<'task, T, R, N> {
+        &self.sim
+    }
+
+    pub fn sim_mut(&mut self) -> &mut InterlockExecutor<'task, T, R, N> {
+        &mut self.sim
+    }
+
+    pub fn render(&self) -> &InterlockExecutor<'task, E, R, N> {
+        &self.render
+    }
+
+    pub fn render_mut(&mut self) -> &mut InterlockExecutor<'task, E, R, N> {
+        &mut self.render
+    }
+
+    pub fn into_inner(self) -> (InterlockExecutor<'task, T, R, N>, InterlockExecutor<'task, E, R, N>, E) {
+        (self.sim, self.render, self.extracted)
+    }
+}
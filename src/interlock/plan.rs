@@ -0,0 +1,265 @@
+use crate::Executable;
+use super::InterlockExecutor;
+use super::error::BuildError;
+use super::resource::{Access, Accesses, ConflictPolicy, Fairness, Parent, Policies, Resolve, ResourceTable, Resources};
+use super::run::{Boxed, Changes, ContextExecutable, Plain};
+use super::semaphore::Semaphore;
+use super::task::{Compensation, Task, TaskId, TaskOptions};
+use super::version::GraphVersion;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+/**
+ Structure of a built graph: lock and unlock lists, initial counts, labels and accesses.
+ Taken from an executor with `InterlockExecutor::plan` and turned back into an executor with `hydrate`,
+ which skips deriving conflicts again. Serializable with the `serde` feature.
+*/
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Plan<R> {
+    tasks: Vec<PlanTask<R>>,
+    policies: Vec<(R, ConflictPolicy)>,
+    capacities: Vec<usize>,
+    fairness: Fairness,
+    version: GraphVersion
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct PlanTask<R> {
+    label: String,
+    initial: usize,
+    lock: Vec<u32>,
+    unlock: Vec<u32>,
+    accesses: Vec<(R, Access)>,
+    all: Option<Access>,
+    realtime: bool,
+    blocking: bool,
+    remote: bool,
+    idempotent: bool,
+    permits: Vec<usize>
+}
+
+/**
+ Task bodies by label for `Plan::hydrate`, together with everything else of a graph that is code
+ rather than structure: the resource hierarchy and resolvers.
+*/
+pub struct TaskRegistry<'task, T, R> {
+    tasks: HashMap<String, Boxed<'task, T, R>>,
+    resolvers: HashMap<String, Arc<Resolve<'task, T, R>>>,
+    compensations: HashMap<String, Arc<Compensation<'task, T>>>,
+    parent: Option<Arc<Parent<'task, R>>>
+}
+
+impl<'task, T, R> Default for TaskRegistry<'task, T, R> {
+
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'task, T, R> TaskRegistry<'task, T, R> {
+
+    pub fn new() -> Self {
+        Self { tasks: HashMap::new(), resolvers: HashMap::new(), compensations: HashMap::new(), parent: None }
+    }
+
+    pub fn insert(&mut self, label: impl Into<String>, task: impl Executable<T> + Send + 'task) where T: 'task, R: 'task {
+        self.tasks.insert(label.into(), Boxed::new(Plain(task)));
+    }
+
+    pub fn insert_with_context(&mut self, label: impl Into<String>, task: impl ContextExecutable<T, R> + Send + 'task) {
+        self.tasks.insert(label.into(), Boxed::new(task));
+    }
+
+    /// See `InterlockBuilder::resolve`.
+    pub fn resolve(&mut self, label: impl Into<String>, resolve: impl Fn(&T, &mut Accesses<R>) + Send + Sync + 'task) {
+        self.resolvers.insert(label.into(), Arc::new(resolve));
+    }
+
+    /// See `InterlockBuilder::compensate`.
+    pub fn compensate(&mut self, label: impl Into<String>, compensation: impl Executable<T> + Send + 'task) {
+        self.compensations.insert(label.into(), Arc::new(Mutex::new(Box::new(compensation))));
+    }
+
+    pub(crate) fn contains(&self, label: &str) -> bool {
+        self.tasks.contains_key(label)
+    }
+
+    /// See `InterlockBuilder::hierarchy`, has to match the hierarchy the plan was built with.
+    pub fn hierarchy(&mut self, parent: impl Fn(&R) -> Option<R> + Send + Sync + 'task) {
+        self.parent = Some(Arc::new(parent));
+    }
+}
+
+impl<R: Eq + Hash> Plan<R> {
+
+    pub(crate) fn new<T>(executor: &InterlockExecutor<'_, T, R>) -> Result<Self, BuildError> where R: Clone {
+        let table = executor.resources.table();
+        let accesses = table.accesses(executor.tasks.len());
+        let indices = |ids: &[TaskId]| ids.iter().map(|id| id.id() as u32).collect();
+
+        let tasks = executor.tasks.iter().zip(accesses).map(|(task, accesses)| {
+            let label = task.label().ok_or(BuildError::UnlabeledTask(task.id()))?;
+
+            Ok(PlanTask {
+                label: label.to_string(),
+                initial: task.initial_count(),
+                lock: indices(task.static_locks()),
+                unlock: indices(task.static_unlocks()),
+                accesses: accesses.into_iter().map(|(resource, access)| (resource.clone(), access)).collect(),
+                all: table.all(task.id()),
+                realtime: task.is_realtime(),
+                blocking: task.is_blocking(),
+                remote: task.is_remote(),
+                idempotent: task.is_idempotent(),
+                permits: task.permits().to_vec()
+            })
+        }).collect::<Result<_, _>>()?;
+
+        let policies = executor.resources.policies().iter().map(|(resource, policy)| (resource.clone(), *policy)).collect();
+        let capacities = executor.semaphores.iter().map(Semaphore::permits).collect();
+        Ok(Self { tasks, policies, capacities, fairness: executor.fairness, version: GraphVersion::new(executor) })
+    }
+
+    /// Returns the structure of the graph the plan was taken from, e.g. to compare it with a graph built from code.
+    pub fn version(&self) -> &GraphVersion {
+        &self.version
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Returns the labels of the tasks that aren't remote.
+    pub(crate) fn local_labels(&self) -> impl Iterator<Item=&str> {
+        self.tasks.iter().filter(|task| !task.remote).map(|task| task.label.as_str())
+    }
+
+    /**
+     Builds the planned graph with the bodies of `registry`, every planned task needs a body.
+     Returns an error if the hydrated graph doesn't match the version the plan was taken from, e.g. an edited plan.
+    */
+    pub fn hydrate<'task, T: Sync>(self, mut registry: TaskRegistry<'task, T, R>) -> Result<InterlockExecutor<'task, T, R>, BuildError> {
+        if let Some(task) = self.tasks.iter().find(|task| !registry.tasks.contains_key(&task.label)) {
+            return Err(BuildError::MissingTask(task.label.clone()));
+        }
+
+        self.validate()?;
+
+        let brand = TaskId::next_brand();
+        let ids = |indices: Vec<u32>| indices.into_iter().map(|idx| TaskId::branded(brand, idx as usize)).collect::<Vec<_>>();
+
+        let parent = registry.parent;
+        let mut table = ResourceTable::new();
+        let mut tasks = Vec::with_capacity(self.tasks.len());
+        let mut resolvers = Vec::new();
+
+        for (idx, task) in self.tasks.into_iter().enumerate() {
+            let id = TaskId::branded(brand, idx);
+
+            for (resource, access) in task.accesses {
+                table.insert(parent.as_deref(), resource, access, id);
+            }
+
+            if let Some(access) = task.all {
+                table.insert_all(access, id);
+            }
+
+            if let Some(resolve) = registry.resolvers.remove(&task.label) {
+                resolvers.push((id, resolve));
+            }
+
+            let compensation = registry.compensations.remove(&task.label);
+            let body = registry.tasks.remove(&task.label).expect("task was checked");
+            let options = TaskOptions {
+                realtime: task.realtime,
+                blocking: task.blocking,
+                remote: task.remote,
+                idempotent: task.idempotent,
+                compensation,
+                ..TaskOptions::default()
+            };
+
+            let mut hydrated = Task::new(id, Some(task.label), body, options, ids(task.lock), ids(task.unlock), task.initial);
+            hydrated.set_permits(task.permits);
+            tasks.push(hydrated);
+        }
+
+        let changes = Changes::new(tasks.len(), &table);
+        let policies: Policies<R> = self.policies.into_iter().collect();
+
+        let mut resources = Resources::new(parent, policies, table);
+        for (id, resolve) in resolvers {
+            resources.add_resolver(id, resolve);
+        }
+
+        let mut executor = InterlockExecutor::new(tasks, resources, changes, &self.capacities);
+        executor.set_fairness(self.fairness);
+
+        self.version.check(&executor.version()).map_err(BuildError::VersionMismatch)?;
+        Ok(executor)
+    }
+
+    //deserialized plans aren't trusted, an index out of range would panic and a wrong count hang the runs
+    fn validate(&self) -> Result<(), BuildError> {
+        let mut expected = vec![0isize; self.tasks.len()];
+        for task in &self.tasks {
+            let tasks = task.lock.iter().chain(&task.unlock).all(|&idx| (idx as usize) < self.tasks.len());
+            let semaphores = task.permits.iter().all(|&idx| idx < self.capacities.len());
+            if !tasks || !semaphores {
+                return Err(BuildError::CorruptPlan(task.label.clone()));
+            }
+
+            task.unlock.iter().for_each(|&idx| expected[idx as usize] += 1);
+            task.lock.iter().for_each(|&idx| expected[idx as usize] -= 1);
+        }
+
+        match self.tasks.iter().zip(expected).find(|(task, expected)| task.initial as isize != *expected) {
+            Some((task, _)) => Err(BuildError::CorruptPlan(task.label.clone())),
+            None => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interlock::builder;
+
+    #[test]
+    fn corrupt() {
+        let mut graph = builder::<(), &str>();
+        let a = graph.add(|_: &()| {}, [], ["log"], &[]);
+        let b = graph.add(|_: &()| {}, ["log"], [], &[]);
+        graph.label(a, "a").unwrap();
+        graph.label(b, "b").unwrap();
+        let plan = graph.build().plan().unwrap();
+
+        let registry = || {
+            let mut registry = TaskRegistry::new();
+            registry.insert("a", |_: &()| {});
+            registry.insert("b", |_: &()| {});
+            registry
+        };
+
+        let mut out_of_range = plan.clone();
+        out_of_range.tasks[0].unlock.push(2);
+        assert_eq!(out_of_range.hydrate(registry()).unwrap_err(), BuildError::CorruptPlan("a".to_string()));
+
+        let mut missing_semaphore = plan.clone();
+        missing_semaphore.tasks[1].permits.push(0);
+        assert_eq!(missing_semaphore.hydrate(registry()).unwrap_err(), BuildError::CorruptPlan("b".to_string()));
+
+        let mut out_of_sync = plan.clone();
+        out_of_sync.tasks[1].initial += 1;
+        assert_eq!(out_of_sync.hydrate(registry()).unwrap_err(), BuildError::CorruptPlan("b".to_string()));
+
+        assert!(plan.hydrate(registry()).is_ok());
+    }
+}
@@ -0,0 +1,291 @@
+use rayon::{ThreadPool, ThreadPoolBuilder};
+#[cfg(feature = "affinity")]
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+/// Threads an executor runs on, see `InterlockExecutor::pool_info`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct PoolInfo {
+    /// Worker threads of the pool a run started from the calling thread executes on.
+    pub threads: usize,
+    /// Threads reserved for realtime tasks, see `InterlockExecutor::reserve_realtime`.
+    pub realtime: usize,
+    /// Threads of the pools tasks are distributed over, see `InterlockExecutor::distribute`.
+    pub nodes: usize,
+    /**
+     Threads dedicated to single tasks, see `InterlockExecutor::dedicate`. They mostly wait for IO, so they don't
+     count towards oversubscription.
+    */
+    pub dedicated: usize,
+    /// Extra threads started for blocking tasks so far, see `InterlockBuilder::mark_blocking`. Don't count either.
+    pub blocking: usize,
+    /// Hardware threads of the machine.
+    pub available: usize
+}
+
+impl PoolInfo {
+
+    fn current(pools: &Pools) -> Self {
+        Self {
+            threads: rayon::current_num_threads(),
+            realtime: pools.realtime.as_deref().map_or(0, ThreadPool::current_num_threads),
+            nodes: pools.nodes.iter().map(|pool| pool.current_num_threads()).sum(),
+            dedicated: pools.dedicated.iter().flatten().count(),
+            blocking: pools.blocking.load(Ordering::Relaxed),
+            available: thread::available_parallelism().map_or(1, |threads| threads.get())
+        }
+    }
+
+    /**
+     Returns true if the executor has more threads than the machine, e.g. because the realtime pool comes on top
+     of a pool already using every core. Tasks then compete for cores and get descheduled in the middle of a run.
+    */
+    pub fn is_oversubscribed(&self) -> bool {
+        self.threads + self.realtime + self.nodes > self.available
+    }
+}
+
+/**
+ How many threads runs of an executor use, see `InterlockExecutor::set_parallelism`.
+ Every mode gives the same guarantees, only the amount of tasks running at the same time differs.
+*/
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum Parallelism {
+    /// Runs on the pool of the calling thread.
+    #[default]
+    Full,
+    /// Runs on at most the given number of threads, see `InterlockExecutor::run_with_max_threads`.
+    Threads(usize),
+    /// Runs every task on the calling thread, one after another, including realtime, distributed and dedicated tasks.
+    Sequential
+}
+
+/**
+ How runs on a pool hand out the tasks that are ready at run start, see `InterlockExecutor::set_seeding`.
+ Sequential runs start tasks in the order `Lazy` does on a single thread either way.
+*/
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum Seeding {
+    /**
+     Ready tasks are taken one after another, in priority and declaration order, as threads become free, and tasks
+     they unlock start right away on the same thread. A fast task may unlock its dependants before later ready tasks
+     even started, so the order tasks start in depends on timing. Doesn't allocate.
+    */
+    #[default]
+    Lazy,
+    /**
+     Every ready task is taken and queued in priority and declaration order before any task executes, and tasks start
+     in the order they become ready: on a single thread all ready tasks start before the ones they unlock, and every
+     run starts its tasks in the same order. Meant for reproducible warm-ups and benchmarks, allocates a job per task.
+    */
+    Ordered
+}
+
+/// Pools tasks execute on instead of the pool the run started on.
+#[derive(Default)]
+pub struct Pools {
+    realtime: Option<Arc<ThreadPool>>,
+    nodes: Vec<Arc<ThreadPool>>,
+    //index of the node pool of every task
+    placement: Vec<Option<usize>>,
+    //thread of every dedicated task, indexed by task
+    dedicated: Vec<Option<Arc<ThreadPool>>>,
+    //extra threads of blocking tasks that don't execute one right now, and how many were started
+    idle: Mutex<Vec<ThreadPool>>,
+    blocking: AtomicUsize,
+    //pool capping the parallelism of runs, kept as long as the cap doesn't change
+    capped: Mutex<Option<Arc<ThreadPool>>>
+}
+
+impl Pools {
+
+    pub fn reserve_realtime(&mut self, pool: Arc<ThreadPool>) {
+        self.realtime = Some(pool);
+    }
+
+    pub fn distribute(&mut self, nodes: Vec<Arc<ThreadPool>>, placement: Vec<Option<usize>>) {
+        self.nodes = nodes;
+        self.placement = placement;
+    }
+
+    pub fn dedicate(&mut self, id: usize, tasks: usize) {
+        self.dedicated.resize(tasks, None);
+
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(1)
+            .thread_name(move |_| format!("interlock-dedicated-{}", id))
+            .build()
+            .expect("failed to create a dedicated thread");
+
+        self.dedicated[id] = Some(Arc::new(pool));
+    }
+
+    pub fn is_dedicated(&self, id: usize) -> bool {
+        matches!(self.dedicated.get(id), Some(Some(_)))
+    }
+
+    pub fn info(&self) -> PoolInfo {
+        PoolInfo::current(self)
+    }
+
+    /// Returns the pool task `id` has to execute on, if any.
+    pub fn get(&self, id: usize, realtime: bool) -> Option<&ThreadPool> {
+        if let Some(Some(pool)) = self.dedicated.get(id) {
+            return Some(pool);
+        }
+
+        match (&self.realtime, self.placement.get(id).copied().flatten()) {
+            (Some(pool), _) if realtime => Some(pool),
+            (_, Some(node)) => Some(&self.nodes[node]),
+            _ => None
+        }
+    }
+
+    pub fn duplicate(&self) -> Self {
+        Self {
+            realtime: self.realtime.clone(),
+            nodes: self.nodes.clone(),
+            placement: self.placement.clone(),
+            dedicated: self.dedicated.clone(),
+            idle: Mutex::default(),
+            blocking: AtomicUsize::new(0),
+            capped: Mutex::default()
+        }
+    }
+
+    /// Starts the threads of every pool runs with `parallelism` use, they otherwise start with the first run on them.
+    pub fn warm_up(&self, parallelism: Parallelism) {
+        match parallelism {
+            Parallelism::Threads(threads) if threads < rayon::current_num_threads() => { self.capped(threads).broadcast(|_| ()); },
+            Parallelism::Sequential => (),
+            _ => { rayon::broadcast(|_| ()); }
+        }
+
+        self.realtime.iter().chain(&self.nodes).chain(self.dedicated.iter().flatten()).for_each(|pool| { pool.broadcast(|_| ()); });
+    }
+
+    /**
+     Executes `op` on an extra thread, e.g. a blocking task, while the calling worker keeps executing other jobs of its
+     pool. Takes an idle extra thread or starts a new one, and keeps it for the next blocking task once `op` returned.
+    */
+    pub fn expand<O: Send>(&self, op: impl FnOnce() -> O + Send) -> O {
+        let idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner).pop();
+        let pool = idle.unwrap_or_else(|| {
+            let idx = self.blocking.fetch_add(1, Ordering::Relaxed);
+            ThreadPoolBuilder::new()
+                .num_threads(1)
+                .thread_name(move |_| format!("interlock-blocking-{}", idx))
+                .build()
+                .expect("failed to create an extra thread for a blocking task")
+        });
+
+        //returned even if op panics
+        let pool = Idle(self, Some(pool));
+        pool.1.as_ref().unwrap().install(op)
+    }
+
+    pub fn capped(&self, threads: usize) -> Arc<ThreadPool> {
+        let mut capped = self.capped.lock().unwrap();
+        match &*capped {
+            Some(pool) if pool.current_num_threads() == threads => pool.clone(),
+            _ => {
+                let pool = ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .thread_name(|idx| format!("interlock-capped-{}", idx))
+                    .build()
+                    .expect("failed to create the capped thread pool");
+
+                capped.insert(Arc::new(pool)).clone()
+            }
+        }
+    }
+}
+
+/// Puts an extra thread back into the idle ones once its blocking task returned.
+struct Idle<'a>(&'a Pools, Option<ThreadPool>);
+
+impl Drop for Idle<'_> {
+
+    fn drop(&mut self) {
+        if let Some(pool) = self.1.take() {
+            self.0.idle.lock().unwrap_or_else(PoisonError::into_inner).push(pool);
+        }
+    }
+}
+
+/**
+ Creates a pool with one thread per core of `cores`, each pinned to its core, e.g. one pool per socket
+ to use with `InterlockExecutor::distribute`. Pinning is only supported on Linux, elsewhere the threads aren't pinned.
+
+ Returns an error if `cores` is empty, if a core isn't available to the process, e.g. because of a cgroup limit,
+ or if the pool can't be created. A thread that fails to pin itself anyway, e.g. because the cores of the process
+ changed since, stays unpinned.
+*/
+#[cfg(feature = "affinity")]
+pub fn pinned_pool(cores: &[usize]) -> io::Result<ThreadPool> {
+    if cores.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "a pinned pool needs at least one core"));
+    }
+
+    let cores = cores.to_vec();
+    affinity::check(&cores)?;
+
+    ThreadPoolBuilder::new()
+        .num_threads(cores.len())
+        .thread_name(|idx| format!("interlock-pinned-{}", idx))
+        .start_handler(move |idx| { affinity::pin(cores[idx]); })
+        .build()
+        .map_err(io::Error::other)
+}
+
+#[cfg(all(feature = "affinity", target_os = "linux"))]
+mod affinity {
+    use std::io;
+
+    //matches the kernel's cpu_set_t of 1024 cores
+    const WORDS: usize = 1024 / 64;
+
+    extern "C" {
+        fn sched_getaffinity(pid: i32, size: usize, mask: *mut u64) -> i32;
+        fn sched_setaffinity(pid: i32, size: usize, mask: *const u64) -> i32;
+    }
+
+    /// Returns an error for the first of `cores` the calling thread may not run on.
+    pub fn check(cores: &[usize]) -> io::Result<()> {
+        let mut mask = [0u64; WORDS];
+
+        //SAFETY: the mask outlives the call and its size is passed along, pid 0 is the calling thread
+        if unsafe { sched_getaffinity(0, std::mem::size_of_val(&mask), mask.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        match cores.iter().find(|&&core| core >= WORDS * 64 || mask[core / 64] & (1 << (core % 64)) == 0) {
+            Some(core) => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("core {} isn't available to the process", core))),
+            None => Ok(())
+        }
+    }
+
+    /// Pins the calling thread to `core`, returns false if it stays unpinned.
+    pub fn pin(core: usize) -> bool {
+        let mut mask = [0u64; WORDS];
+        mask[core / 64] |= 1 << (core % 64);
+
+        //SAFETY: as above, `check` made sure the core is in range
+        unsafe { sched_setaffinity(0, std::mem::size_of_val(&mask), mask.as_ptr()) == 0 }
+    }
+}
+
+#[cfg(all(feature = "affinity", not(target_os = "linux")))]
+mod affinity {
+    use std::io;
+
+    pub fn check(_: &[usize]) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn pin(_: usize) -> bool {
+        false
+    }
+}
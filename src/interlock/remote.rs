@@ -0,0 +1,354 @@
+use super::InterlockExecutor;
+use super::context::Slot;
+use super::error::BuildError;
+use super::plan::{Plan, TaskRegistry};
+use super::run::TaskContext;
+use super::task::Task;
+use std::any::Any;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::hash::Hash;
+use std::io::{self, BufRead, BufReader, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Request to execute a remote task on a worker, see `InterlockBuilder::remote`.
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RemoteTask {
+    /// Label of the task, workers find its body by label.
+    pub label: String,
+    /// Number of the run the task belongs to, counting from 1, e.g. for workers to reload the run data.
+    pub run: u64
+}
+
+impl RemoteTask {
+
+    fn encode(&self) -> String {
+        format!("{} {}\n", self.run, self.label)
+    }
+
+    fn decode(line: &str) -> Option<Self> {
+        let (run, label) = line.trim_end_matches(&['\r', '\n'][..]).split_once(' ')?;
+        Some(Self { label: label.to_string(), run: run.parse().ok()? })
+    }
+}
+
+/// Failed execution of a remote task.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct RemoteError {
+    pub task: String,
+    pub message: String
+}
+
+impl RemoteError {
+
+    pub fn new(task: &RemoteTask, message: impl Into<String>) -> Self {
+        Self { task: task.label.clone(), message: message.into() }
+    }
+}
+
+impl Display for RemoteError {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "remote task '{}' failed: {}", self.task, self.message)
+    }
+}
+
+impl Error for RemoteError {}
+
+/**
+ Sends remote tasks to workers, see `InterlockExecutor::set_transport`.
+ Implemented for closures, e.g. one posting the request to a render farm and polling until the job is done.
+*/
+pub trait Transport: Send + Sync {
+    /// Executes `task` on a worker and blocks until it finished.
+    fn execute(&self, task: &RemoteTask) -> Result<(), RemoteError>;
+}
+
+impl<F: Fn(&RemoteTask) -> Result<(), RemoteError> + Send + Sync> Transport for F {
+
+    fn execute(&self, task: &RemoteTask) -> Result<(), RemoteError> {
+        (self)(task)
+    }
+}
+
+/// Transport of an executor and the labels its remote tasks are sent by, see `InterlockExecutor::set_transport`.
+#[derive(Clone)]
+pub(crate) struct Remote<'a> {
+    pub transport: Arc<dyn Transport + 'a>,
+    /// Labels of remote tasks by index.
+    pub labels: Vec<Option<String>>
+}
+
+/**
+ Worker side of remote tasks: executes them by label with the bodies of a `TaskRegistry`.
+ Built from the plan of the graph, so bodies get the same `TaskContext` as in a local run.
+ The registry only needs bodies of remote tasks.
+*/
+pub struct RemoteExecutor<'task, T, R> {
+    executor: InterlockExecutor<'task, T, R>,
+    labels: HashMap<String, usize>
+}
+
+impl<'task, T: Sync + 'task, R: Eq + Hash + 'task> RemoteExecutor<'task, T, R> {
+
+    pub fn new(plan: Plan<R>, mut registry: TaskRegistry<'task, T, R>) -> Result<Self, BuildError> {
+        let local: Vec<_> = plan.local_labels().filter(|label| !registry.contains(label)).map(str::to_string).collect();
+        for label in local {
+            registry.insert(label, |_: &T| panic!("local tasks can't execute on a worker"));
+        }
+
+        let executor = plan.hydrate(registry)?;
+        let labels = executor.tasks.iter()
+            .filter(|task| task.is_remote())
+            .filter_map(|task| task.label().map(|label| (label.to_string(), task.id().id())))
+            .collect();
+
+        Ok(Self { executor, labels })
+    }
+
+    /// Executes a single remote task, returns an error if it isn't a remote task of the graph or if it panicked.
+    pub fn execute(&mut self, task: &RemoteTask, data: &T) -> Result<(), RemoteError> {
+        let id = *self.labels.get(&task.label).ok_or_else(|| RemoteError::new(task, "no remote task has this label"))?;
+        let executor = &self.executor;
+        let local = &executor.tasks[id];
+
+        local.reset(0);
+        let mut borrow = local.take().expect("remote task is already executing");
+
+        let since = executor.changes.start(id);
+        let context = TaskContext::new(local.id(), since, &executor.changes, executor.resources.table(), executor.resources.parent(), None, executor.tick, &executor.scratch)
+            .with_params(local.params());
+
+        panic::catch_unwind(AssertUnwindSafe(|| <Task<T, R> as Slot<T, R>>::execute(&mut borrow, data, &context)))
+            .map_err(|payload| RemoteError::new(task, message(payload.as_ref())))?;
+
+        if !context.is_unchanged() {
+            executor.changes.finish(id);
+        }
+
+        Ok(())
+    }
+
+    /**
+     Executes the requests of a `ProcessPool` read from `input` until it is closed and answers them on `output`,
+     e.g. `worker.serve(io::stdin().lock(), io::stdout(), &data)` in the worker process.
+    */
+    pub fn serve(&mut self, input: impl BufRead, mut output: impl Write, data: &T) -> io::Result<()> {
+        for line in input.lines() {
+            let result = match RemoteTask::decode(&line?) {
+                Some(task) => self.execute(&task, data),
+                None => Err(RemoteError { task: String::new(), message: "malformed request".to_string() })
+            };
+
+            //answers are single lines as well
+            match result {
+                Ok(()) => writeln!(output, "ok")?,
+                Err(err) => writeln!(output, "err {}", err.message.replace('\n', " "))?
+            }
+
+            output.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => payload.downcast_ref::<String>().cloned().unwrap_or_else(|| "task panicked".to_string())
+    }
+}
+
+struct Worker {
+    child: Child,
+    input: Option<ChildStdin>,
+    output: BufReader<ChildStdout>
+}
+
+impl Worker {
+
+    fn execute(&mut self, task: &RemoteTask) -> io::Result<Result<(), String>> {
+        let input = self.input.as_mut().unwrap();
+        input.write_all(task.encode().as_bytes())?;
+        input.flush()?;
+
+        let mut answer = String::new();
+        if self.output.read_line(&mut answer)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "worker exited"));
+        }
+
+        match answer.trim_end() {
+            "ok" => Ok(Ok(())),
+            answer => Ok(Err(answer.strip_prefix("err ").unwrap_or(answer).to_string()))
+        }
+    }
+}
+
+impl Drop for Worker {
+
+    fn drop(&mut self) {
+        //closing the input ends `serve`
+        drop(self.input.take());
+        let _ = self.child.wait();
+    }
+}
+
+#[derive(Default)]
+struct Workers {
+    idle: Vec<Worker>,
+    started: usize
+}
+
+/**
+ Reference transport executing remote tasks in worker processes, each started with `command` and answering
+ requests on its standard output with `RemoteExecutor::serve`. Up to `workers` processes are started on demand
+ and reused by the following tasks and runs; a worker that fails to answer is killed and replaced.
+*/
+pub struct ProcessPool {
+    command: Box<dyn Fn() -> Command + Send + Sync>,
+    workers: usize,
+    state: Mutex<Workers>,
+    idle: Condvar
+}
+
+impl ProcessPool {
+
+    /// Panics if `workers` is 0.
+    pub fn new(workers: usize, command: impl Fn() -> Command + Send + Sync + 'static) -> Self {
+        assert!(workers > 0, "a process pool needs at least one worker");
+        Self { command: Box::new(command), workers, state: Mutex::new(Workers::default()), idle: Condvar::new() }
+    }
+
+    fn start(&self) -> io::Result<Worker> {
+        let mut child = (self.command)().stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let input = child.stdin.take();
+        let output = BufReader::new(child.stdout.take().unwrap());
+        Ok(Worker { child, input, output })
+    }
+
+    /// Takes an idle worker or starts a new one, waits for a worker to become idle if all of them are busy.
+    fn acquire(&self) -> io::Result<Worker> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(worker) = state.idle.pop() {
+                return Ok(worker);
+            }
+
+            if state.started < self.workers {
+                state.started += 1;
+                drop(state);
+
+                return self.start().inspect_err(|_| self.retire());
+            }
+
+            state = self.idle.wait(state).unwrap();
+        }
+    }
+
+    fn release(&self, worker: Worker) {
+        self.state.lock().unwrap().idle.push(worker);
+        self.idle.notify_one();
+    }
+
+    fn retire(&self) {
+        self.state.lock().unwrap().started -= 1;
+        self.idle.notify_one();
+    }
+}
+
+impl Transport for ProcessPool {
+
+    fn execute(&self, task: &RemoteTask) -> Result<(), RemoteError> {
+        if task.label.contains('\n') {
+            return Err(RemoteError::new(task, "labels of remote tasks can't contain line breaks"));
+        }
+
+        let mut worker = self.acquire().map_err(|err| RemoteError::new(task, err.to_string()))?;
+        match worker.execute(task) {
+            Ok(result) => {
+                self.release(worker);
+                result.map_err(|message| RemoteError::new(task, message))
+            },
+
+            Err(err) => {
+                let _ = worker.child.kill();
+                drop(worker);
+                self.retire();
+
+                Err(RemoteError::new(task, err.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interlock::builder::TaskSpec;
+    use crate::interlock::builder;
+    use crate::Executable;
+    use std::sync::Arc;
+
+    #[test]
+    fn remote() {
+        let log = Mutex::new(Vec::new());
+        let push = |label: &'static str| { let log = &log; move |_: &()| log.lock().unwrap().push(label) };
+
+        let mut builder = builder::<(), u32>();
+        builder.extend(vec![
+            TaskSpec::new("a", push("a")).writes([0]),
+            TaskSpec::new("b", push("local b")).reads([0]).writes([1]).remote(),
+            TaskSpec::new("c", push("c")).reads([1]),
+        ]).unwrap();
+
+        let mut exec = builder.build();
+
+        let mut registry = TaskRegistry::new();
+        registry.insert("b", push("b"));
+        let worker = Arc::new(Mutex::new(RemoteExecutor::new(exec.plan().unwrap(), registry).unwrap()));
+
+        let remote = worker.clone();
+        let transport = move |task: &RemoteTask| remote.lock().unwrap().execute(task, &());
+        exec.set_transport(Some(Arc::new(transport)));
+
+        exec.run(&());
+        assert_eq!(*log.lock().unwrap(), ["a", "b", "c"]);
+
+        let unknown = RemoteTask { label: "a".to_string(), run: 1 };
+        assert!(worker.lock().unwrap().execute(&unknown, &()).is_err(), "local tasks must not execute on workers");
+
+        exec.set_transport(None);
+        exec.run(&());
+        assert_eq!(log.lock().unwrap()[3..], ["a", "local b", "c"]);
+    }
+
+    #[test]
+    fn process_pool() {
+        let shell = |script: &'static str| move || {
+            let mut command = Command::new("sh");
+            command.args(["-c", script]);
+            command
+        };
+
+        let task = RemoteTask { label: "bake lightmaps".to_string(), run: 1 };
+        let pool = ProcessPool::new(2, shell("while read run label; do echo ok; done"));
+        for _ in 0..4 {
+            assert_eq!(pool.execute(&task), Ok(()));
+        }
+
+        assert_eq!(pool.state.lock().unwrap().started, 1);
+
+        let failing = ProcessPool::new(1, shell("while read run label; do echo \"err no $label\"; done"));
+        assert_eq!(failing.execute(&task), Err(RemoteError::new(&task, "no bake lightmaps")));
+
+        let exiting = ProcessPool::new(1, shell("exit 0"));
+        assert!(exiting.execute(&task).is_err());
+        assert_eq!(exiting.state.lock().unwrap().started, 0, "workers that exited must be replaced");
+    }
+}
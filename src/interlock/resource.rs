@@ -0,0 +1,735 @@
+use super::task::TaskId;
+use rayon::prelude::*;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::sync::Arc;
+
+/// Kind of access a task declares on a resource.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Access {
+    Read,
+    Write
+}
+
+/**
+ Decides which accesses to a resource conflict with each other.
+ For hierarchical resources, the policy of a resource applies to accesses of the resource itself and to accesses
+ of its descendants meeting there, e.g. writing a concurrent child still conflicts with writing its parent.
+*/
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConflictPolicy {
+    /// Every access conflicts with every other access, even two reads.
+    Exclusive,
+    /// A write conflicts with every other access, reads do not conflict with each other.
+    #[default]
+    ReadWrite,
+    /// No accesses conflict, e.g. for append-only resources or atomics. Tasks writing every resource still do.
+    Concurrent
+}
+
+impl ConflictPolicy {
+
+    /// Returns true if two accesses meeting at a resource with this policy conflict.
+    pub fn conflicts(self, a: Access, b: Access) -> bool {
+        match self {
+            ConflictPolicy::Exclusive => true,
+            ConflictPolicy::ReadWrite => a == Access::Write || b == Access::Write,
+            ConflictPolicy::Concurrent => false
+        }
+    }
+
+    /**
+     Returns the policy an access to every resource meets a resource with this policy with: writing every resource
+     conflicts with every access whatever the policy, e.g. a flush, reading every resource follows the policy.
+    */
+    fn wildcard(self, access: Access) -> Self {
+        match access {
+            Access::Write => ConflictPolicy::Exclusive,
+            Access::Read => self
+        }
+    }
+}
+
+/**
+ Decides which of several conflicting tasks starts first, see `InterlockBuilder::fairness`.
+ Only static accesses are taken into account, tasks conflicting over resolved resources start in any order.
+*/
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Fairness {
+    /// Conflicting tasks start in whatever order they become ready.
+    #[default]
+    Unordered,
+    /**
+     Writers that are ready start before readers that are ready at the same time. Readers that already started
+     still finish first, writers don't interrupt them.
+    */
+    WriterPriority,
+    /**
+     Conflicting tasks start in the order they were added: a task waits for every conflicting task added before it,
+     so neither readers nor writers are deferred by tasks added after them. Makes the order of every run the same.
+    */
+    Fifo
+}
+
+/**
+ Parent function for `/`-separated resource paths: `"world/chunks/7"` is nested in `"world/chunks"`,
+ which in turn is nested in `"world"`.
+*/
+pub fn path_parent<'a>(path: &&'a str) -> Option<&'a str> {
+    path.rfind('/').map(|idx| &path[..idx])
+}
+
+/// Maps a resource to the resource it is nested in.
+pub(crate) type Parent<'a, R> = dyn Fn(&R) -> Option<R> + Send + Sync + 'a;
+
+/// Conflict policies of resources, resources without an entry use the default policy.
+pub(crate) type Policies<R> = HashMap<R, ConflictPolicy>;
+
+fn policy<P: Borrow<Q> + Eq + Hash, Q: Eq + Hash + ?Sized>(policies: &HashMap<P, ConflictPolicy>, resource: &Q) -> ConflictPolicy {
+    policies.get(resource).copied().unwrap_or_default()
+}
+
+/// Resolves resources accessed by a task in the current run.
+pub(crate) type Resolve<'a, T, R> = dyn Fn(&T, &mut Accesses<R>) + Send + Sync + 'a;
+
+/// Resources accessed by a task during a single run, filled by a resolver at run start.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Accesses<R> {
+    reads: Vec<R>,
+    writes: Vec<R>
+}
+
+impl<R> Accesses<R> {
+
+    pub fn new() -> Self {
+        Self { reads: Vec::new(), writes: Vec::new() }
+    }
+
+    pub fn read(&mut self, resource: R) {
+        self.reads.push(resource);
+    }
+
+    pub fn write(&mut self, resource: R) {
+        self.writes.push(resource);
+    }
+
+    pub fn reads(&self) -> &[R] {
+        self.reads.as_slice()
+    }
+
+    pub fn writes(&self) -> &[R] {
+        self.writes.as_slice()
+    }
+
+    pub fn clear(&mut self) {
+        self.reads.clear();
+        self.writes.clear();
+    }
+}
+
+impl<R> Default for Accesses<R> {
+
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+ Tasks accessing a single resource.
+ Accesses declared on the resource itself are _direct_, accesses declared on any of its children are _nested_.
+ Two accesses meeting at a resource conflict if at least one of them is direct and the resource's policy says so.
+*/
+#[derive(Clone, Default)]
+struct Accessors {
+    direct: Vec<(TaskId, Access)>,
+    nested: Vec<(TaskId, Access)>
+}
+
+impl Accessors {
+
+    fn push(&mut self, task: TaskId, access: Access, nested: bool) {
+        match nested {
+            false => self.direct.push((task, access)),
+            true => self.nested.push((task, access))
+        }
+    }
+
+    /// Calls `f` for every accessor conflicting with the given access.
+    fn conflicting(&self, access: Access, nested: bool, policy: ConflictPolicy, f: &mut impl FnMut(TaskId)) {
+        let nested = match nested {
+            false => self.nested.as_slice(),
+            true => &[]
+        };
+
+        self.direct.iter()
+            .chain(nested.iter())
+            .filter(|(_, other)| policy.conflicts(access, *other))
+            .for_each(|(task, _)| f(*task));
+    }
+}
+
+/// Access of a task to a resource entry of a table.
+#[derive(Clone, Copy)]
+struct Member {
+    task: TaskId,
+    entry: usize,
+    access: Access,
+    nested: bool
+}
+
+/**
+ Collects resource accesses of all tasks and derives which tasks cannot run at the same time.
+
+ If a parent function is set, an access to a resource is also registered as a nested access on all of its ancestors,
+ so that writing a parent conflicts with every access to its children and vice versa.
+
+ Tasks accessing _all_ resources meet every direct access of every resource and each other.
+*/
+#[derive(Clone)]
+pub(crate) struct ResourceTable<K> {
+    index: HashMap<K, usize>,
+    entries: Vec<Accessors>,
+    members: Vec<Member>,
+    all: Vec<(TaskId, Access)>
+}
+
+impl<K: Eq + Hash> ResourceTable<K> {
+
+    pub fn new() -> Self {
+        Self { index: HashMap::new(), entries: Vec::new(), members: Vec::new(), all: Vec::new() }
+    }
+
+    pub fn insert_all(&mut self, access: Access, task: TaskId) {
+        self.all.push((task, access));
+    }
+
+    fn push(&mut self, resource: K, access: Access, task: TaskId, nested: bool) {
+        let entries = &mut self.entries;
+        let entry = *self.index.entry(resource).or_insert_with(|| {
+            entries.push(Accessors::default());
+            entries.len() - 1
+        });
+
+        self.entries[entry].push(task, access, nested);
+        self.members.push(Member { task, entry, access, nested });
+    }
+
+    pub fn entry<Q: Eq + Hash + ?Sized>(&self, resource: &Q) -> Option<usize> where K: Borrow<Q> {
+        self.index.get(resource).copied()
+    }
+
+    /// Returns the policy of every entry.
+    fn policies<P: Borrow<Q> + Eq + Hash, Q: Eq + Hash>(&self, policies: &HashMap<P, ConflictPolicy>) -> Vec<ConflictPolicy> where K: Borrow<Q> {
+        let mut result = vec![ConflictPolicy::default(); self.entries.len()];
+        for (resource, entry) in self.index.iter() {
+            result[*entry] = policy(policies, resource.borrow());
+        }
+
+        result
+    }
+
+    /**
+     Returns the tasks conflicting with each of the first `tasks` tasks, sorted by id and without duplicates.
+     Every task's conflicts are derived independently from the resources it accesses, in parallel.
+    */
+    pub fn conflicts<P: Borrow<Q> + Eq + Hash, Q: Eq + Hash>(&self, tasks: usize, policies: &HashMap<P, ConflictPolicy>) -> Vec<Vec<TaskId>> where K: Borrow<Q> {
+        let policies = self.policies(policies);
+
+        //group members by task, they usually are already
+        let mut members = self.members.clone();
+        members.sort_by_key(|member| member.task.id());
+
+        let mut starts = vec![0usize; tasks + 1];
+        members.iter().for_each(|member| starts[member.task.id() + 1] += 1);
+        (0..tasks).for_each(|idx| starts[idx + 1] += starts[idx]);
+
+        let mut wildcards = vec![None; tasks];
+        self.all.iter().for_each(|(task, access)| wildcards[task.id()] = Some(*access));
+
+        //only capture what is Sync regardless of K
+        let (entries, all, policies, members) = (&self.entries, &self.all, &policies, &members);
+
+        (0..tasks).into_par_iter().map(|task| {
+            let mut locks = Vec::new();
+            let mut push = |other: TaskId| locks.push(other);
+
+            for member in members[starts[task]..starts[task + 1]].iter() {
+                let policy = policies[member.entry];
+                entries[member.entry].conflicting(member.access, member.nested, policy, &mut push);
+
+                //every access is direct on exactly one resource, so wildcards only need to meet direct accesses
+                if !member.nested {
+                    conflicting_all(all, member.access, policy, &mut push);
+                }
+            }
+
+            if let Some(access) = wildcards[task] {
+                for (entry, accessors) in entries.iter().enumerate() {
+                    accessors.conflicting(access, true, policies[entry].wildcard(access), &mut push);
+                }
+
+                conflicting_all(all, access, ConflictPolicy::ReadWrite, &mut push);
+            }
+
+            locks.retain(|other| other.id() != task);
+            locks.sort_unstable_by_key(|other| other.id());
+            locks.dedup();
+            locks
+        }).collect()
+    }
+}
+
+impl<K> ResourceTable<K> {
+
+    /// Returns the resources each of the first `tasks` tasks accesses directly, in declaration order.
+    pub fn accesses(&self, tasks: usize) -> Vec<Vec<(&K, Access)>> {
+        let mut keys = Vec::with_capacity(self.entries.len());
+        keys.resize_with(self.entries.len(), || None);
+        self.index.iter().for_each(|(key, entry)| keys[*entry] = Some(key));
+
+        let mut accesses = vec![Vec::new(); tasks];
+        for member in self.members.iter().filter(|member| !member.nested) {
+            accesses[member.task.id()].push((keys[member.entry].expect("entry without key"), member.access));
+        }
+
+        accesses
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the entries each of the first `tasks` tasks writes and whether they are nested, and whether it writes all resources.
+    pub fn writes(&self, tasks: usize) -> (Vec<Vec<(usize, bool)>>, Vec<bool>) {
+        let mut writes = vec![Vec::new(); tasks];
+        for member in self.members.iter().filter(|member| member.access == Access::Write) {
+            writes[member.task.id()].push((member.entry, member.nested));
+        }
+
+        let mut all = vec![false; tasks];
+        for (task, _) in self.all.iter().filter(|(_, access)| *access == Access::Write) {
+            all[task.id()] = true;
+        }
+
+        (writes, all)
+    }
+
+    /**
+     Groups the first `tasks` tasks by the resources they access directly: tasks sharing a resource are in
+     the same group, also through other tasks. Returns the group of every task, `None` for tasks without resources.
+    */
+    pub fn groups(&self, tasks: usize) -> Vec<Option<usize>> {
+        fn find(parents: &mut [usize], mut idx: usize) -> usize {
+            while parents[idx] != idx {
+                parents[idx] = parents[parents[idx]];
+                idx = parents[idx];
+            }
+
+            idx
+        }
+
+        let mut parents: Vec<_> = (0..tasks).collect();
+        let mut owners = vec![None; self.entries.len()];
+
+        for member in self.members.iter().filter(|member| !member.nested) {
+            let task = find(&mut parents, member.task.id());
+            match owners[member.entry] {
+                Some(owner) => {
+                    let owner = find(&mut parents, owner);
+                    parents[task] = owner;
+                },
+
+                None => owners[member.entry] = Some(task)
+            }
+        }
+
+        let mut grouped = vec![false; tasks];
+        self.members.iter().filter(|member| !member.nested).for_each(|member| grouped[member.task.id()] = true);
+
+        (0..tasks).map(|task| match grouped[task] {
+            true => Some(find(&mut parents, task)),
+            false => None
+        }).collect()
+    }
+
+    /// Returns the access of `task` to all resources, if any.
+    pub fn all(&self, task: TaskId) -> Option<Access> {
+        self.all.iter().find(|(other, _)| *other == task).map(|(_, access)| *access)
+    }
+}
+
+fn conflicting_all(all: &[(TaskId, Access)], access: Access, policy: ConflictPolicy, f: &mut impl FnMut(TaskId)) {
+    all.iter()
+        .filter(|(_, other)| policy.wildcard(*other).conflicts(access, *other))
+        .for_each(|(task, _)| f(*task));
+}
+
+impl<R: Eq + Hash> ResourceTable<R> {
+
+    pub fn insert(&mut self, parent: Option<&Parent<R>>, resource: R, access: Access, task: TaskId) {
+        ancestors(parent, &resource, |ancestor| self.push(ancestor, access, task, true));
+        self.push(resource, access, task, false);
+    }
+}
+
+impl<'r, R: Eq + Hash> ResourceTable<Key<'r, R>> {
+
+    fn insert_ref(&mut self, parent: Option<&Parent<R>>, resource: &'r R, access: Access, task: TaskId) {
+        ancestors(parent, resource, |ancestor| self.push(Key::Owned(ancestor), access, task, true));
+        self.push(Key::Borrowed(resource), access, task, false);
+    }
+}
+
+impl<'r, R: Eq + Hash> ResourceTable<Part<'r, R>> {
+
+    /**
+     Registers an access to `part` of `resource`, or to the resource itself if `part` is `None`.
+     Parts are nested in the resource like children, so they only conflict with each other within the same part.
+    */
+    pub fn insert_part(&mut self, parent: Option<&Parent<R>>, resource: &'r R, part: Option<usize>, access: Access, task: TaskId) {
+        ancestors(parent, resource, |ancestor| self.push(Part { resource: Key::Owned(ancestor), part: None }, access, task, true));
+
+        if part.is_some() {
+            self.push(Part { resource: Key::Borrowed(resource), part: None }, access, task, true);
+        }
+
+        self.push(Part { resource: Key::Borrowed(resource), part }, access, task, false);
+    }
+}
+
+/// Number of ancestors above which a hierarchy is taken to be cyclic, see `InterlockBuilder::hierarchy`.
+pub const MAX_DEPTH: usize = 1024;
+
+pub(crate) fn ancestors<R>(parent: Option<&Parent<R>>, resource: &R, mut f: impl FnMut(R)) {
+    if let Some(parent) = parent {
+        let mut ancestor = parent(resource);
+        for _ in 0..MAX_DEPTH {
+            match ancestor {
+                Some(current) => {
+                    ancestor = parent(&current);
+                    f(current);
+                },
+                None => return
+            }
+        }
+
+        if ancestor.is_some() {
+            panic!("can't resolve the ancestors of a resource, the hierarchy is cyclic or deeper than {} levels", MAX_DEPTH);
+        }
+    }
+}
+
+/// Resource key that is either borrowed from a resolver or derived as an ancestor of a borrowed key.
+enum Key<'r, R> {
+    Borrowed(&'r R),
+    Owned(R)
+}
+
+impl<'r, R> Borrow<R> for Key<'r, R> {
+
+    fn borrow(&self) -> &R {
+        match self {
+            Key::Borrowed(resource) => resource,
+            Key::Owned(resource) => resource
+        }
+    }
+}
+
+impl<'r, R: PartialEq> PartialEq for Key<'r, R> {
+
+    fn eq(&self, other: &Self) -> bool {
+        Borrow::<R>::borrow(self) == Borrow::<R>::borrow(other)
+    }
+}
+
+impl<'r, R: Eq> Eq for Key<'r, R> {}
+
+impl<'r, R: Hash> Hash for Key<'r, R> {
+
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Borrow::<R>::borrow(self).hash(state)
+    }
+}
+
+/**
+ Resource key of a what-if analysis, see `InterlockBuilder::split_impact`. Borrows as the resource it is
+ a part of, so parts take the conflict policy of their resource.
+*/
+#[derive(Eq, PartialEq, Hash)]
+pub(crate) struct Part<'r, R> {
+    resource: Key<'r, R>,
+    part: Option<usize>
+}
+
+impl<'r, R> Borrow<R> for Part<'r, R> {
+
+    fn borrow(&self) -> &R {
+        self.resource.borrow()
+    }
+}
+
+struct Resolver<'a, T, R> {
+    task: TaskId,
+    resolve: Arc<Resolve<'a, T, R>>,
+    accesses: Accesses<R>
+}
+
+/**
+ Resources of a built graph: the static accesses declared in the builder plus resolvers of tasks
+ whose resources are only known at run start.
+
+ Resolvers are evaluated before every run. Conflicts involving resolved resources are only recomputed
+ when a resolver returns different resources than in the previous run, static conflicts are never recomputed.
+*/
+pub(crate) struct Resources<'a, T, R> {
+    parent: Option<Arc<Parent<'a, R>>>,
+    policies: Policies<R>,
+    table: ResourceTable<R>,
+    resolvers: Vec<Resolver<'a, T, R>>,
+    scratch: Accesses<R>,
+    resolved: bool
+}
+
+impl<'a, T, R: Eq + Hash> Resources<'a, T, R> {
+
+    pub fn new(parent: Option<Arc<Parent<'a, R>>>, policies: Policies<R>, table: ResourceTable<R>) -> Self {
+        Self { parent, policies, table, resolvers: Vec::new(), scratch: Accesses::new(), resolved: false }
+    }
+
+    pub fn add_resolver(&mut self, task: TaskId, resolve: Arc<Resolve<'a, T, R>>) {
+        self.resolvers.push(Resolver { task, resolve, accesses: Accesses::new() });
+    }
+
+    /// Returns a copy sharing the parent function and the resolvers.
+    pub fn duplicate(&self) -> Self where R: Clone {
+        Self {
+            parent: self.parent.clone(),
+            policies: self.policies.clone(),
+            table: self.table.clone(),
+            resolvers: self.resolvers.iter().map(|resolver| Resolver {
+                task: resolver.task,
+                resolve: resolver.resolve.clone(),
+                accesses: resolver.accesses.clone()
+            }).collect(),
+            scratch: Accesses::new(),
+            resolved: self.resolved
+        }
+    }
+
+    pub fn parent(&self) -> Option<&Parent<'a, R>> {
+        self.parent.as_deref()
+    }
+
+    pub fn policies(&self) -> &Policies<R> {
+        &self.policies
+    }
+
+    pub fn has_resolvers(&self) -> bool {
+        !self.resolvers.is_empty()
+    }
+
+    pub fn table(&self) -> &ResourceTable<R> {
+        &self.table
+    }
+
+    /// Resolves resources for the current run, returns true if any resolver changed its resources since the previous run.
+    pub fn update(&mut self, data: &T) -> bool {
+        let mut changed = !self.resolved && !self.resolvers.is_empty();
+
+        for resolver in self.resolvers.iter_mut() {
+            self.scratch.clear();
+            (resolver.resolve)(data, &mut self.scratch);
+
+            if self.scratch != resolver.accesses {
+                mem::swap(&mut self.scratch, &mut resolver.accesses);
+                changed = true;
+            }
+        }
+
+        self.resolved = true;
+        changed
+    }
+
+    /// Calls `f(current, next)` for every ordered pair of tasks that conflict because of a resolved resource.
+    pub fn conflicts(&self, mut f: impl FnMut(TaskId, TaskId)) {
+        let parent = self.parent.as_deref();
+        let mut dynamic = ResourceTable::new();
+
+        for resolver in self.resolvers.iter() {
+            for read in resolver.accesses.reads.iter() {
+                dynamic.insert_ref(parent, read, Access::Read, resolver.task);
+            }
+
+            for write in resolver.accesses.writes.iter() {
+                dynamic.insert_ref(parent, write, Access::Write, resolver.task);
+            }
+        }
+
+        //resolved resources also meet static accesses of the same resource
+        let policies = dynamic.policies(&self.policies);
+        let mut statics = vec![None; dynamic.entries.len()];
+        for (resource, entry) in dynamic.index.iter() {
+            statics[*entry] = self.table.index.get(Borrow::<R>::borrow(resource)).copied();
+        }
+
+        for member in dynamic.members.iter() {
+            let policy = policies[member.entry];
+            let mut push = |other: TaskId| {
+                if other != member.task {
+                    f(member.task, other);
+                    f(other, member.task);
+                }
+            };
+
+            dynamic.entries[member.entry].conflicting(member.access, member.nested, policy, &mut push);
+
+            if let Some(entry) = statics[member.entry] {
+                self.table.entries[entry].conflicting(member.access, member.nested, policy, &mut push);
+            }
+
+            if !member.nested {
+                conflicting_all(&self.table.all, member.access, policy, &mut push);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn conflicts(table: &ResourceTable<&'static str>) -> HashSet<(usize, usize)> {
+        conflicts_with_policies(table, &Policies::new())
+    }
+
+    fn conflicts_with_policies(table: &ResourceTable<&'static str>, policies: &Policies<&'static str>) -> HashSet<(usize, usize)> {
+        //every task that accesses anything
+        let tasks = table.members.iter().map(|member| member.task).chain(table.all.iter().map(|(task, _)| *task));
+        let tasks = tasks.map(|task| task.id() + 1).max().unwrap_or(0);
+
+        let mut set = HashSet::new();
+        for (task, locks) in table.conflicts::<_, &str>(tasks, policies).into_iter().enumerate() {
+            locks.iter().for_each(|other| { set.insert((other.id(), task)); });
+        }
+
+        set
+    }
+
+    #[test]
+    fn parent() {
+        assert_eq!(path_parent(&"world/chunks/7"), Some("world/chunks"));
+        assert_eq!(path_parent(&"world/chunks"), Some("world"));
+        assert_eq!(path_parent(&"world"), None);
+    }
+
+    #[test]
+    fn flat() {
+        let mut table = ResourceTable::new();
+
+        table.insert(None, "world/chunks", Access::Write, TaskId::branded(0, 0));
+        table.insert(None, "world/chunks/7", Access::Write, TaskId::branded(0, 1));
+        table.insert(None, "world/chunks/7", Access::Read, TaskId::branded(0, 2));
+
+        assert_eq!(conflicts(&table), [(1, 2), (2, 1)].iter().copied().collect());
+    }
+
+    #[test]
+    fn hierarchical() {
+        let parent: &Parent<&str> = &path_parent;
+        let mut table = ResourceTable::new();
+
+        table.insert(Some(parent), "world/chunks", Access::Write, TaskId::branded(0, 0));
+        table.insert(Some(parent), "world/chunks/7", Access::Read, TaskId::branded(0, 1));
+        table.insert(Some(parent), "world/chunks/8", Access::Write, TaskId::branded(0, 2));
+        table.insert(Some(parent), "world", Access::Read, TaskId::branded(0, 3));
+        table.insert(Some(parent), "world/entities", Access::Read, TaskId::branded(0, 4));
+
+        let set = conflicts(&table);
+
+        assert!(set.contains(&(0, 1)), "parent write must conflict with child read");
+        assert!(set.contains(&(0, 2)), "parent write must conflict with child write");
+        assert!(set.contains(&(0, 3)), "child write must conflict with parent read");
+        assert!(set.contains(&(2, 3)), "child write must conflict with ancestor read");
+        assert!(!set.contains(&(1, 2)), "siblings must not conflict");
+        assert!(!set.contains(&(1, 3)), "reads must not conflict");
+        assert!(!set.contains(&(0, 4)), "unrelated subtrees must not conflict");
+    }
+
+    #[test]
+    fn all() {
+        let mut table = ResourceTable::new();
+
+        table.insert(None, "a", Access::Read, TaskId::branded(0, 0));
+        table.insert(None, "b", Access::Write, TaskId::branded(0, 1));
+        table.insert_all(Access::Read, TaskId::branded(0, 2));
+        table.insert_all(Access::Read, TaskId::branded(0, 3));
+        table.insert_all(Access::Write, TaskId::branded(0, 4));
+
+        let set = conflicts(&table);
+
+        assert!(set.contains(&(1, 2)), "reading everything must conflict with writers");
+        assert!(!set.contains(&(0, 2)), "reading everything must not conflict with readers");
+        assert!(!set.contains(&(2, 3)), "reading everything twice must not conflict");
+        assert!(set.contains(&(0, 4)), "writing everything must conflict with readers");
+        assert!(set.contains(&(1, 4)), "writing everything must conflict with writers");
+        assert!(set.contains(&(2, 4)), "writing everything must conflict with reading everything");
+    }
+
+    #[test]
+    fn resolve() {
+        let parent: Arc<Parent<&str>> = Arc::new(path_parent);
+        let mut table = ResourceTable::new();
+        table.insert(Some(parent.as_ref()), "world/chunks", Access::Write, TaskId::branded(0, 0));
+
+        let mut resources = Resources::new(Some(parent), Policies::new(), table);
+        resources.add_resolver(TaskId::branded(0, 1), Arc::new(|data: &Vec<&'static str>, accesses: &mut Accesses<&str>| {
+            data.iter().for_each(|chunk| accesses.read(*chunk));
+        }));
+
+        let conflicts = |resources: &Resources<Vec<&str>, &str>| {
+            let mut set = HashSet::new();
+            resources.conflicts(|a, b| { set.insert((a.id(), b.id())); });
+            set
+        };
+
+        assert!(resources.update(&vec!["world/chunks/7"]), "first run must resolve");
+        assert!(conflicts(&resources).contains(&(0, 1)), "resolved child must conflict with static parent writer");
+
+        assert!(!resources.update(&vec!["world/chunks/7"]), "unchanged resources must not be recomputed");
+
+        assert!(resources.update(&vec!["world/entities/7"]), "changed resources must be recomputed");
+        assert!(conflicts(&resources).is_empty(), "resolved resource in another subtree must not conflict");
+    }
+
+    #[test]
+    fn policy() {
+        let parent: &Parent<&str> = &path_parent;
+        let mut table = ResourceTable::new();
+        let mut policies = Policies::new();
+
+        policies.insert("log", ConflictPolicy::Concurrent);
+        policies.insert("device", ConflictPolicy::Exclusive);
+
+        table.insert(Some(parent), "log", Access::Write, TaskId::branded(0, 0));
+        table.insert(Some(parent), "log", Access::Read, TaskId::branded(0, 1));
+        table.insert(Some(parent), "device", Access::Read, TaskId::branded(0, 2));
+        table.insert(Some(parent), "device/queue", Access::Read, TaskId::branded(0, 3));
+        table.insert(Some(parent), "log/errors", Access::Write, TaskId::branded(0, 4));
+        table.insert_all(Access::Read, TaskId::branded(0, 5));
+
+        let set = conflicts_with_policies(&table, &policies);
+
+        assert!(!set.contains(&(0, 1)), "concurrent resource must not conflict");
+        assert!(!set.contains(&(0, 4)), "concurrent resource must not conflict with its children");
+        assert!(!set.contains(&(0, 5)), "concurrent resource must not conflict with reading everything");
+        assert!(set.contains(&(2, 3)), "exclusive resource must conflict on reads of its children");
+        assert!(set.contains(&(2, 5)), "exclusive resource must conflict with reading everything");
+        assert!(!set.contains(&(3, 5)), "children of an exclusive resource keep their own policy");
+    }
+}
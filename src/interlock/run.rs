@@ -0,0 +1,314 @@
+use crate::Executable;
+use super::resource::{self, Parent, ResourceTable};
+use super::scratch::Scratch;
+use super::task::{Params, TaskId};
+use super::timestep::Tick;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/**
+ Task that also receives the context of its run, e.g. to check which resources changed since it last ran.
+ Closures taking the run data and a `&TaskContext` implement it, see `InterlockBuilder::add_with_context`.
+*/
+pub trait ContextExecutable<T, R> {
+    fn run(&mut self, data: &T, context: &TaskContext<'_, R>);
+}
+
+impl<T, R, F: FnMut(&T, &TaskContext<'_, R>)> ContextExecutable<T, R> for F {
+
+    fn run(&mut self, data: &T, context: &TaskContext<'_, R>) {
+        (self)(data, context)
+    }
+}
+
+/// Body of a task as stored by the executor.
+pub(crate) type Body<'a, T, R> = dyn ContextExecutable<T, R> + Send + 'a;
+
+/// Body of a task as stored by the executor, tasks added already boxed keep their box instead of a `Body` around it.
+pub(crate) enum Boxed<'a, T, R> {
+    Body(Box<Body<'a, T, R>>),
+    Plain(Box<dyn Executable<T> + Send + 'a>)
+}
+
+impl<'a, T, R> Boxed<'a, T, R> {
+
+    pub fn new(body: impl ContextExecutable<T, R> + Send + 'a) -> Self {
+        Boxed::Body(Box::new(body))
+    }
+}
+
+impl<'a, T, R> ContextExecutable<T, R> for Boxed<'a, T, R> {
+
+    fn run(&mut self, data: &T, context: &TaskContext<'_, R>) {
+        match self {
+            Boxed::Body(body) => body.run(data, context),
+            Boxed::Plain(task) => task.run(data)
+        }
+    }
+}
+
+/// Task that doesn't need its context.
+pub(crate) struct Plain<E>(pub E);
+
+impl<T, R, E: Executable<T>> ContextExecutable<T, R> for Plain<E> {
+
+    fn run(&mut self, data: &T, _: &TaskContext<'_, R>) {
+        self.0.run(data)
+    }
+}
+
+/// Task that receives the extra data of the run, see `InterlockBuilder::add_with_extra`.
+pub(crate) struct Extra<F, E>(pub F, pub PhantomData<fn(&E)>);
+
+impl<T, R, E: 'static, F: FnMut(&T, &E)> ContextExecutable<T, R> for Extra<F, E> {
+
+    fn run(&mut self, data: &T, context: &TaskContext<'_, R>) {
+        let extra = context.extra().expect("can't run a task taking extra data without it, see InterlockExecutor::run_with_extra");
+        (self.0)(data, extra)
+    }
+}
+
+/// Task that receives its parameters, see `InterlockBuilder::add_with_params`.
+pub(crate) struct Configured<F, P>(pub F, pub PhantomData<fn(&P)>);
+
+impl<T, R, P: 'static, F: FnMut(&T, &P)> ContextExecutable<T, R> for Configured<F, P> {
+
+    fn run(&mut self, data: &T, context: &TaskContext<'_, R>) {
+        (self.0)(data, context.params().expect("parameters are only replaced by ones of the same type"))
+    }
+}
+
+/// Context of a single task execution.
+pub struct TaskContext<'r, R> {
+    task: TaskId,
+    since: u64,
+    changes: &'r Changes,
+    table: &'r ResourceTable<R>,
+    parent: Option<&'r Parent<'r, R>>,
+    deadline: Option<Instant>,
+    tick: Option<Tick>,
+    scratch: &'r Scratch,
+    extra: Option<&'r (dyn Any + Sync)>,
+    params: Option<&'r Params>,
+    unchanged: Cell<bool>,
+    suspended: Cell<bool>,
+    snapshot: RefCell<Option<Vec<u8>>>,
+    #[cfg(feature = "inspector")]
+    allocated: Cell<Option<u64>>
+}
+
+impl<'r, R> TaskContext<'r, R> {
+
+    pub(crate) fn with_extra(mut self, extra: Option<&'r (dyn Any + Sync)>) -> Self {
+        self.extra = extra;
+        self
+    }
+
+    pub(crate) fn with_params(mut self, params: Option<&'r Params>) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Returns the extra data of this run if it is an `E`, see `InterlockExecutor::run_with_extra`.
+    pub fn extra<E: 'static>(&self) -> Option<&E> {
+        let extra: &dyn Any = self.extra?;
+        extra.downcast_ref()
+    }
+
+    /// Returns the parameters of the task if they are a `P`, see `InterlockBuilder::add_with_params`.
+    pub fn params<P: 'static>(&self) -> Option<&P> {
+        self.params?.downcast_ref()
+    }
+}
+
+impl<'r, R: Eq + Hash> TaskContext<'r, R> {
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(task: TaskId, since: u64, changes: &'r Changes, table: &'r ResourceTable<R>, parent: Option<&'r Parent<'r, R>>,
+                      deadline: Option<Instant>, tick: Option<Tick>, scratch: &'r Scratch) -> Self {
+        Self {
+            task, since, changes, table, parent, deadline, tick, scratch,
+            extra: None,
+            params: None,
+            unchanged: Cell::new(false),
+            suspended: Cell::new(false),
+            snapshot: RefCell::new(None),
+            #[cfg(feature = "inspector")]
+            allocated: Cell::new(None)
+        }
+    }
+
+    pub fn task(&self) -> TaskId {
+        self.task
+    }
+
+    /// Returns the position of this run within a fixed timestep update, `None` outside of one, see `FixedTimestep`.
+    pub fn tick(&self) -> Option<Tick> {
+        self.tick
+    }
+
+    /**
+     Returns memory for temporary allocations of this execution, freed when the next run starts, e.g. to collect
+     the entities to update without allocating every frame. See `InterlockExecutor::scratch_stats` for its usage.
+    */
+    pub fn scratch(&self) -> &Scratch {
+        self.scratch
+    }
+
+    /// Declares that this run of the task left everything it writes as it was, so readers don't see a change.
+    pub fn unchanged(&self) {
+        self.unchanged.set(true);
+    }
+
+    pub(crate) fn is_unchanged(&self) -> bool {
+        self.unchanged.get()
+    }
+
+    /**
+     Returns true once this execution used up its time slice, see `InterlockExecutor::set_time_slice`.
+     Long tasks should then save their progress, call `suspend` and return. Always false in sequential runs.
+    */
+    pub fn should_yield(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /**
+     Declares that the task returned before finishing its work. The worker executes other pending tasks first,
+     then the task again with a fresh time slice. Its locks stay held and its dependants wait in the meantime,
+     so the task has to keep its progress itself.
+    */
+    pub fn suspend(&self) {
+        self.suspended.set(true);
+    }
+
+    pub(crate) fn is_suspended(&self) -> bool {
+        self.suspended.get()
+    }
+
+    /**
+     Saves a snapshot of the task's state in the checkpoint once the task completed, see `InterlockExecutor::checkpoint`.
+     Restore it from `Checkpoint::snapshot` before resuming, completed tasks don't execute again.
+    */
+    pub fn save(&self, snapshot: Vec<u8>) {
+        *self.snapshot.borrow_mut() = Some(snapshot);
+    }
+
+    pub(crate) fn take_snapshot(&self) -> Option<Vec<u8>> {
+        self.snapshot.borrow_mut().take()
+    }
+
+    /**
+     Reports `bytes` allocated by this execution, e.g. from an arena the allocation counter doesn't see.
+     Added to the bytes measured by the counter, see `InterlockExecutor::set_allocation_counter`.
+    */
+    #[cfg(feature = "inspector")]
+    pub fn allocated(&self, bytes: u64) {
+        self.allocated.set(Some(self.allocated.get().unwrap_or_default() + bytes));
+    }
+
+    #[cfg(feature = "inspector")]
+    pub(crate) fn take_allocated(&self) -> Option<u64> {
+        self.allocated.take()
+    }
+
+    /**
+     Returns true if a task writing `resource`, one of its ancestors or one of its descendants finished
+     since this task last started. Always true on the first run of the task.
+     Only static accesses are tracked, writes of resolved resources don't count as changes.
+    */
+    pub fn changed(&self, resource: &R) -> bool {
+        if self.since == 0 {
+            return true;
+        }
+
+        let (changes, table, since) = (self.changes, self.table, self.since);
+
+        //descendants are covered by the nested versions, ancestors only count if they were written directly
+        let mut result = changes.all.load(Ordering::Acquire) > since
+            || table.entry(resource).is_some_and(|entry| changes.version(&changes.versions, entry) > since);
+
+        resource::ancestors(self.parent, resource, |ancestor| {
+            result |= table.entry(&ancestor).is_some_and(|entry| changes.version(&changes.direct, entry) > since);
+        });
+
+        result
+    }
+}
+
+/**
+ Versions of resources for change detection.
+ Every task start and every finished writer takes a tick of a shared clock, a resource changed since
+ a task started if a writer of it finished at a later tick. Writers bump all entries they access,
+ nested ones included, so writing a child changes its ancestors as well. Direct writes are also
+ tracked separately, since writing a parent changes all of its children.
+*/
+pub(crate) struct Changes {
+    clock: AtomicU64,
+    versions: Vec<AtomicU64>,
+    direct: Vec<AtomicU64>,
+    all: AtomicU64,
+    started: Vec<AtomicU64>,
+    writes: Vec<Vec<(usize, bool)>>,
+    write_all: Vec<bool>
+}
+
+impl Changes {
+
+    pub fn new<R>(tasks: usize, table: &ResourceTable<R>) -> Self {
+        let (writes, write_all) = table.writes(tasks);
+        Self::with_writes(table.len(), writes, write_all)
+    }
+
+    fn with_writes(entries: usize, writes: Vec<Vec<(usize, bool)>>, write_all: Vec<bool>) -> Self {
+        Self {
+            clock: AtomicU64::new(0),
+            versions: (0..entries).map(|_| AtomicU64::new(0)).collect(),
+            direct: (0..entries).map(|_| AtomicU64::new(0)).collect(),
+            all: AtomicU64::new(0),
+            started: (0..writes.len()).map(|_| AtomicU64::new(0)).collect(),
+            writes,
+            write_all
+        }
+    }
+
+    /// Returns the same tracking without any recorded runs.
+    pub fn duplicate(&self) -> Self {
+        Self::with_writes(self.versions.len(), self.writes.clone(), self.write_all.clone())
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::AcqRel) + 1
+    }
+
+    fn version(&self, versions: &[AtomicU64], entry: usize) -> u64 {
+        versions[entry].load(Ordering::Acquire)
+    }
+
+    /// Records the start of `task` and returns the tick of its previous start, 0 if it never ran.
+    pub fn start(&self, task: usize) -> u64 {
+        self.started[task].swap(self.tick(), Ordering::AcqRel)
+    }
+
+    /// Records that `task` finished, bumping the versions of everything it writes.
+    pub fn finish(&self, task: usize) {
+        if self.writes[task].is_empty() && !self.write_all[task] {
+            return;
+        }
+
+        let tick = self.tick();
+        for (entry, nested) in self.writes[task].iter() {
+            self.versions[*entry].fetch_max(tick, Ordering::AcqRel);
+            if !nested {
+                self.direct[*entry].fetch_max(tick, Ordering::AcqRel);
+            }
+        }
+
+        if self.write_all[task] {
+            self.all.fetch_max(tick, Ordering::AcqRel);
+        }
+    }
+}
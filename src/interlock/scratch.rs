@@ -0,0 +1,169 @@
+use std::alloc::{self, Layout};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::{mem, slice};
+
+//alignment of the buffer, larger alignments are padded within it
+const ALIGN: usize = 16;
+
+/**
+ Bump allocator owned by an executor for temporary allocations of its tasks, e.g. per-frame lists, see
+ `TaskContext::scratch`. Allocations live until the task returns and are freed all at once when the next run starts,
+ their destructors never run. Tasks executing in parallel allocate from the same buffer without locking.
+ Allocations that don't fit fall back to the global allocator, and the buffer grows to the peak before the next run,
+ so runs stop allocating once they reached their peak usage.
+*/
+pub struct Scratch {
+    buffer: NonNull<u8>,
+    capacity: usize,
+    //end of the last allocation in the buffer, may exceed the capacity once it ran out
+    offset: AtomicUsize,
+    overflow: Mutex<Vec<(NonNull<u8>, Layout)>>,
+    overflowed: AtomicUsize,
+    peak: usize
+}
+
+//SAFETY: the buffer is only written through the disjoint allocations handed out, like a Vec of bytes
+unsafe impl Send for Scratch {}
+unsafe impl Sync for Scratch {}
+
+/// Scratch memory used by the runs of an executor, see `InterlockExecutor::scratch_stats`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct ScratchStats {
+    /// Bytes allocated in the last run, including padding, or in the run in progress.
+    pub used: usize,
+    /// Most bytes allocated in a single run.
+    pub peak: usize,
+    /// Bytes runs can allocate before they fall back to the global allocator.
+    pub capacity: usize,
+    /// Allocations of the last run that didn't fit and used the global allocator.
+    pub overflowed: usize
+}
+
+impl Scratch {
+
+    pub(crate) fn new(capacity: usize) -> Self {
+        let buffer = match capacity {
+            0 => NonNull::dangling(),
+            _ => NonNull::new(unsafe { alloc::alloc(buffer_layout(capacity)) }).unwrap_or_else(|| alloc::handle_alloc_error(buffer_layout(capacity)))
+        };
+
+        Self {
+            buffer,
+            capacity,
+            offset: AtomicUsize::new(0),
+            overflow: Mutex::new(Vec::new()),
+            overflowed: AtomicUsize::new(0),
+            peak: 0
+        }
+    }
+
+    /// Moves `value` into the scratch memory, it isn't dropped.
+    #[allow(clippy::mut_from_ref)] //every call returns a different allocation
+    pub fn alloc<V>(&self, value: V) -> &mut V {
+        let ptr = self.allocate(Layout::new::<V>()).cast::<V>();
+
+        //SAFETY: the allocation fits a V and isn't handed out again before the scratch resets, which takes &mut self
+        unsafe {
+            ptr.as_ptr().write(value);
+            &mut *ptr.as_ptr()
+        }
+    }
+
+    /// Copies `values` into the scratch memory.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_copy<V: Copy>(&self, values: &[V]) -> &mut [V] {
+        self.alloc_slice_with(values.len(), |idx| values[idx])
+    }
+
+    /// Allocates `len` values created by `value` from their index, they aren't dropped.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_with<V>(&self, len: usize, mut value: impl FnMut(usize) -> V) -> &mut [V] {
+        let layout = Layout::array::<V>(len).expect("can't allocate a scratch slice larger than isize::MAX bytes");
+        let ptr = self.allocate(layout).cast::<V>();
+
+        //SAFETY: as for alloc, every value is written before the slice is created
+        unsafe {
+            (0..len).for_each(|idx| ptr.as_ptr().add(idx).write(value(idx)));
+            slice::from_raw_parts_mut(ptr.as_ptr(), len)
+        }
+    }
+
+    fn allocate(&self, layout: Layout) -> NonNull<u8> {
+        if layout.size() == 0 {
+            //SAFETY: alignments are never 0
+            return unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+        }
+
+        let base = self.buffer.as_ptr() as usize;
+        let mut offset = self.offset.load(Ordering::Relaxed);
+
+        loop {
+            let start = (base + offset).next_multiple_of(layout.align()) - base;
+            let end = start + layout.size();
+
+            match self.offset.compare_exchange_weak(offset, end, Ordering::Relaxed, Ordering::Relaxed) {
+                //SAFETY: within the buffer, no other allocation overlaps start..end
+                Ok(_) if end <= self.capacity => return unsafe { NonNull::new_unchecked(self.buffer.as_ptr().add(start)) },
+                //counted in the offset all the same, so the buffer grows to fit it next time
+                Ok(_) => return self.allocate_overflow(layout),
+                Err(current) => offset = current
+            }
+        }
+    }
+
+    #[cold]
+    fn allocate_overflow(&self, layout: Layout) -> NonNull<u8> {
+        let ptr = NonNull::new(unsafe { alloc::alloc(layout) }).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        self.overflow.lock().unwrap_or_else(PoisonError::into_inner).push((ptr, layout));
+        self.overflowed.fetch_add(1, Ordering::Relaxed);
+        ptr
+    }
+
+    pub fn stats(&self) -> ScratchStats {
+        let used = self.offset.load(Ordering::Relaxed);
+        ScratchStats { used, peak: self.peak.max(used), capacity: self.capacity, overflowed: self.overflowed.load(Ordering::Relaxed) }
+    }
+
+    /// Grows the buffer to at least `capacity` bytes, e.g. to the peak of a previous session, before the next run.
+    pub(crate) fn reserve(&mut self, capacity: usize) {
+        if capacity > self.capacity {
+            let peak = self.peak.max(*self.offset.get_mut());
+            *self = Self::new(capacity);
+            self.peak = peak;
+        }
+    }
+
+    /// Frees every allocation, and grows the buffer if the last run didn't fit.
+    pub(crate) fn reset(&mut self) {
+        free(self.overflow.get_mut().unwrap_or_else(PoisonError::into_inner));
+        *self.overflowed.get_mut() = 0;
+
+        let used = mem::take(self.offset.get_mut());
+        self.peak = self.peak.max(used);
+
+        if used > self.capacity {
+            self.reserve(used.next_power_of_two());
+        }
+    }
+}
+
+impl Drop for Scratch {
+
+    fn drop(&mut self) {
+        if self.capacity > 0 {
+            unsafe { alloc::dealloc(self.buffer.as_ptr(), buffer_layout(self.capacity)) };
+        }
+
+        free(self.overflow.get_mut().unwrap_or_else(PoisonError::into_inner));
+    }
+}
+
+fn buffer_layout(capacity: usize) -> Layout {
+    Layout::from_size_align(capacity, ALIGN).expect("can't reserve more than isize::MAX bytes of scratch memory")
+}
+
+fn free(overflow: &mut Vec<(NonNull<u8>, Layout)>) {
+    overflow.drain(..).for_each(|(ptr, layout)| unsafe { alloc::dealloc(ptr.as_ptr(), layout) });
+}
@@ -0,0 +1,132 @@
+use super::spawn::Job;
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+struct State {
+    available: usize,
+    //jobs of spawned runs waiting for a permit, a released permit is handed to the first one
+    parked: VecDeque<Job>
+}
+
+/// Permits of a resource with a capacity, see `InterlockBuilder::capacity`.
+pub struct Semaphore {
+    permits: usize,
+    state: Mutex<State>,
+    released: Condvar
+}
+
+/// Lock of an exhausted semaphore, the permit can be awaited without blocking by parking a job.
+pub struct Exhausted<'a> {
+    state: MutexGuard<'a, State>
+}
+
+impl Exhausted<'_> {
+
+    /// Runs `job` once a permit was released, the job then owns that permit.
+    pub fn park(mut self, job: Job) {
+        self.state.parked.push_back(job);
+    }
+}
+
+impl Semaphore {
+
+    pub fn new(permits: usize) -> Self {
+        Self { permits, state: Mutex::new(State { available: permits, parked: VecDeque::new() }), released: Condvar::new() }
+    }
+
+    pub fn permits(&self) -> usize {
+        self.permits
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Blocks until a permit is available and takes it.
+    pub fn acquire(&self) {
+        let mut state = self.state();
+        while state.available == 0 {
+            state = self.released.wait(state).unwrap();
+        }
+
+        state.available -= 1;
+    }
+
+    /// Takes a permit if one is available, the semaphore stays locked otherwise until the caller parked or gave up.
+    pub fn try_acquire(&self) -> Result<(), Exhausted<'_>> {
+        let mut state = self.state();
+        match state.available {
+            0 => Err(Exhausted { state }),
+            _ => {
+                state.available -= 1;
+                Ok(())
+            }
+        }
+    }
+
+    /// Releases a permit, returns the parked job it was handed to, if any.
+    #[must_use]
+    pub fn release(&self) -> Option<Job> {
+        let mut state = self.state();
+        let parked = state.parked.pop_front();
+
+        if parked.is_none() {
+            state.available += 1;
+            self.released.notify_one();
+        }
+
+        parked
+    }
+}
+
+/**
+ Permits a task holds during an execution, taken in ascending order of the semaphores so tasks holding some
+ of their permits never wait for each other in a cycle. Releases everything it holds when dropped.
+*/
+pub struct Permits<'a> {
+    semaphores: &'a [Semaphore],
+    ids: &'a [usize],
+    held: usize
+}
+
+impl<'a> Permits<'a> {
+
+    pub fn new(semaphores: &'a [Semaphore], ids: &'a [usize]) -> Self {
+        Self { semaphores, ids, held: 0 }
+    }
+
+    /// Returns the semaphore of the next permit to take, if any.
+    pub fn next(&self) -> Option<&'a Semaphore> {
+        self.ids.get(self.held).map(|&id| &self.semaphores[id])
+    }
+
+    /// Records that the permit of `next` was taken.
+    pub fn taken(&mut self) {
+        self.held += 1;
+    }
+
+    /// Blocks until every permit is taken.
+    pub fn acquire(&mut self) {
+        while let Some(semaphore) = self.next() {
+            semaphore.acquire();
+            self.taken();
+        }
+    }
+
+    /// Releases every permit held and runs the parked jobs they were handed to.
+    pub fn release(&mut self) {
+        while self.held > 0 {
+            self.held -= 1;
+            if let Some(job) = self.semaphores[self.ids[self.held]].release() {
+                job();
+            }
+        }
+    }
+}
+
+impl Drop for Permits<'_> {
+
+    fn drop(&mut self) {
+        self.release();
+    }
+}
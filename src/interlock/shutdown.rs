@@ -0,0 +1,155 @@
+use super::task::TaskId;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Shutdown state of an executor, tracked once a handle was taken, see `InterlockExecutor::shutdown_handle`.
+pub(crate) struct Drain {
+    requested: AtomicBool,
+    //runs in progress
+    runs: AtomicUsize,
+    ids: Vec<TaskId>,
+    //executions in progress of every task, several shared runs may execute a task at the same time
+    running: Vec<AtomicUsize>,
+    skipped: Vec<AtomicBool>,
+    lock: Mutex<()>,
+    ended: Condvar
+}
+
+impl Drain {
+
+    pub fn new(ids: Vec<TaskId>) -> Self {
+        Self {
+            requested: AtomicBool::new(false),
+            runs: AtomicUsize::new(0),
+            running: ids.iter().map(|_| AtomicUsize::new(0)).collect(),
+            skipped: ids.iter().map(|_| AtomicBool::new(false)).collect(),
+            ids,
+            lock: Mutex::new(()),
+            ended: Condvar::new()
+        }
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    /// Counts a run as in progress until the returned guard drops.
+    pub fn begin(&self) -> Run<'_> {
+        self.runs.fetch_add(1, Ordering::SeqCst);
+        Run(self)
+    }
+
+    /// Counts an execution of `task` as running until the returned guard drops, or skips it if a shutdown was requested.
+    pub fn start(&self, task: usize) -> Option<Running<'_>> {
+        if self.is_requested() {
+            self.skipped[task].store(true, Ordering::Relaxed);
+            return None;
+        }
+
+        self.running[task].fetch_add(1, Ordering::Relaxed);
+        Some(Running { drain: self, task })
+    }
+}
+
+/// Run in progress, see `Drain::begin`.
+pub(crate) struct Run<'a>(&'a Drain);
+
+impl Drop for Run<'_> {
+
+    fn drop(&mut self) {
+        self.0.runs.fetch_sub(1, Ordering::SeqCst);
+
+        //under the lock, so a shutdown checking the runs before waiting doesn't miss the end
+        let _lock = self.0.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        self.0.ended.notify_all();
+    }
+}
+
+/// Execution of a task in progress, see `Drain::start`.
+pub(crate) struct Running<'a> {
+    drain: &'a Drain,
+    task: usize
+}
+
+impl Drop for Running<'_> {
+
+    fn drop(&mut self) {
+        self.drain.running[self.task].fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/**
+ Shuts an executor down from another thread, e.g. when a server stops in the middle of a run.
+ Created by `InterlockExecutor::shutdown_handle`, clones shut down the same executor.
+*/
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    drain: Arc<Drain>
+}
+
+impl ShutdownHandle {
+
+    pub(crate) fn new(drain: Arc<Drain>) -> Self {
+        Self { drain }
+    }
+
+    /**
+     Stops starting tasks and waits up to `timeout` for the runs in progress to return, then reports the tasks
+     it abandoned. Tasks that already started keep running, tasks that didn't start are skipped along with their
+     dependants, and so is every task of the runs started afterwards. Tasks of other graphs waiting for a skipped
+     task panic, see `App::link`. Shutting down again waits again and reports everything abandoned so far.
+    */
+    pub fn shutdown(&self, timeout: Duration) -> ShutdownReport {
+        let drain = &self.drain;
+        drain.requested.store(true, Ordering::SeqCst);
+
+        let deadline = Instant::now() + timeout;
+        let mut lock = drain.lock.lock().unwrap_or_else(PoisonError::into_inner);
+
+        while drain.runs.load(Ordering::SeqCst) > 0 {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left == Duration::default() {
+                break;
+            }
+
+            lock = drain.ended.wait_timeout(lock, left).unwrap_or_else(PoisonError::into_inner).0;
+        }
+
+        drop(lock);
+
+        let tasks = |abandoned: &dyn Fn(usize) -> bool| (0..drain.ids.len()).filter(|&id| abandoned(id)).map(|id| drain.ids[id]).collect();
+
+        ShutdownReport {
+            drained: drain.runs.load(Ordering::SeqCst) == 0,
+            running: tasks(&|id| drain.running[id].load(Ordering::Relaxed) > 0),
+            skipped: tasks(&|id| drain.skipped[id].load(Ordering::Relaxed))
+        }
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.drain.is_requested()
+    }
+}
+
+/// Tasks a shutdown abandoned, see `ShutdownHandle::shutdown`.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ShutdownReport {
+    /// Whether every run in progress returned within the timeout.
+    pub drained: bool,
+    /// Tasks still running when the timeout elapsed, in declaration order.
+    pub running: Vec<TaskId>,
+    /// Tasks reached after the shutdown that didn't start, in declaration order.
+    pub skipped: Vec<TaskId>
+}
+
+impl ShutdownReport {
+
+    /// Returns the running and the skipped tasks, in declaration order.
+    pub fn abandoned(&self) -> Vec<TaskId> {
+        let mut abandoned: Vec<_> = self.running.iter().chain(self.skipped.iter()).copied().collect();
+        abandoned.sort_by_key(TaskId::id);
+        abandoned.dedup();
+        abandoned
+    }
+}
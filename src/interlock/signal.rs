@@ -0,0 +1,46 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::thread;
+
+const PENDING: u8 = 0;
+const SET: u8 = 1;
+const ABANDONED: u8 = 2;
+
+/**
+ Completion of a task or of a whole run that a task of another graph waits for, see `App::link`.
+ Clones share the same state, it is reset before every update of the app.
+*/
+#[derive(Clone, Default)]
+pub(crate) struct Signal(Arc<AtomicU8>);
+
+impl Signal {
+
+    pub fn reset(&self) {
+        self.0.store(PENDING, Ordering::Relaxed);
+    }
+
+    /// Releases the waiting tasks, they see everything written before.
+    pub fn set(&self) {
+        self.0.store(SET, Ordering::Release);
+    }
+
+    /// Makes the waiting tasks panic, unless the signal was set already. Called when the awaited run panicked.
+    pub fn abandon(&self) {
+        let _ = self.0.compare_exchange(PENDING, ABANDONED, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    /// Blocks until the signal is set, executing other work of the rayon pool meanwhile, e.g. the tasks of the awaited graph.
+    pub fn wait(&self) {
+        loop {
+            match self.0.load(Ordering::Acquire) {
+                SET => return,
+                ABANDONED => panic!("the task or run this task waits for panicked"),
+                _ => {}
+            }
+
+            if rayon::yield_now() != Some(rayon::Yield::Executed) {
+                thread::yield_now();
+            }
+        }
+    }
+}
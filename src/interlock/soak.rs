@@ -0,0 +1,288 @@
+use super::InterlockExecutor;
+use super::task::TaskId;
+use std::hash::Hash;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+/**
+ Soak test of a graph: runs it continuously for a duration, aggregates the timings of every task over time windows
+ and flags tasks whose duration or queue latency drifts from the first window, e.g. from a leak or a growing cache.
+ The queue latency of a task is the time from the end of its last dependency, or the start of the run, until it started.
+*/
+#[derive(Clone, Copy, Debug)]
+pub struct Soak {
+    duration: Duration,
+    window: Duration,
+    threshold: f64,
+    min_drift: Duration
+}
+
+impl Soak {
+
+    /// Soak test of `duration` with windows of a minute, a relative threshold of 0.5 and a minimal drift of 100 µs.
+    pub fn new(duration: Duration) -> Self {
+        Self { duration, window: Duration::from_secs(60), threshold: 0.5, min_drift: Duration::from_micros(100) }
+    }
+
+    /// Panics if `window` is zero.
+    pub fn window(mut self, window: Duration) -> Self {
+        assert!(window > Duration::default(), "soak windows can't be empty");
+        self.window = window;
+        self
+    }
+
+    /// Flags a mean that differs from the first window by more than `threshold` times the mean of the first window.
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Ignores drifts up to `min_drift`, so short tasks don't get flagged for noise.
+    pub fn min_drift(mut self, min_drift: Duration) -> Self {
+        self.min_drift = min_drift;
+        self
+    }
+
+    /**
+     Calls `frame` until the soak test is over, e.g. `|exec| exec.run(&world)` after advancing the world.
+     Calls that didn't run the graph aren't sampled.
+    */
+    pub fn run<'task, T: Sync, R: Eq + Hash, N: Clone>(&self, executor: &mut InterlockExecutor<'task, T, R, N>,
+                                                       mut frame: impl FnMut(&mut InterlockExecutor<'task, T, R, N>)) -> SoakReport<N> {
+        let ids: Vec<_> = executor.tasks.iter().map(|task| task.id()).collect();
+        let labels = executor.tasks.iter().map(|task| task.label().cloned()).collect();
+
+        let mut dependencies = vec![Vec::new(); ids.len()];
+        for (id, task) in executor.tasks.iter().enumerate() {
+            task.dependants().iter().for_each(|dependant| dependencies[dependant.id()].push(id));
+        }
+
+        let mut windows: Vec<SoakWindow> = Vec::new();
+        let mut sums = vec![Sums::default(); ids.len()];
+        let mut runs = 0;
+
+        let start = Instant::now();
+        while start.elapsed() < self.duration {
+            let before = executor.runs.load(Ordering::Relaxed);
+            frame(executor);
+            if executor.runs.load(Ordering::Relaxed) == before {
+                continue;
+            }
+
+            //windows that passed without a run stay empty
+            let index = (start.elapsed().as_nanos() / self.window.as_nanos()) as usize;
+            while windows.len() < index {
+                windows.push(SoakWindow::close(self.window * windows.len() as u32, &mut sums));
+            }
+
+            let timings: Vec<_> = (0..ids.len()).map(|id| executor.stats.get(id).last).collect();
+            for (id, &timing) in timings.iter().enumerate() {
+                if let Some((start, end)) = timing {
+                    let ready = dependencies[id].iter().filter_map(|&dependency| timings[dependency]).map(|(_, end)| end).max();
+                    sums[id].add(end - start, start.saturating_sub(ready.unwrap_or_default()));
+                }
+            }
+
+            runs += 1;
+        }
+
+        if sums.iter().any(|sums| sums.runs > 0) {
+            windows.push(SoakWindow::close(self.window * windows.len() as u32, &mut sums));
+        }
+
+        let drifts = self.drifts(&ids, &windows);
+        SoakReport { runs, labels, windows, drifts }
+    }
+
+    fn drifts(&self, ids: &[TaskId], windows: &[SoakWindow]) -> Vec<Drift> {
+        let mut drifts = Vec::new();
+
+        for (id, &task) in ids.iter().enumerate() {
+            let mut sampled = windows.iter().enumerate().filter(|(_, window)| window.tasks[id].runs > 0);
+            let baseline = match sampled.next() {
+                Some((_, window)) => window.tasks[id],
+                None => continue
+            };
+
+            let mut flagged = [false; 2];
+            for (index, window) in sampled {
+                let metrics = [
+                    (Metric::Duration, baseline.duration, window.tasks[id].duration),
+                    (Metric::Latency, baseline.latency, window.tasks[id].latency)
+                ];
+
+                for (flagged, (metric, baseline, value)) in flagged.iter_mut().zip(metrics) {
+                    //only the first window a metric drifted in is reported
+                    if !*flagged && self.drifted(baseline, value) {
+                        *flagged = true;
+                        drifts.push(Drift { task, window: index, metric, baseline, value });
+                    }
+                }
+            }
+        }
+
+        drifts
+    }
+
+    fn drifted(&self, baseline: Duration, value: Duration) -> bool {
+        let drift = value.abs_diff(baseline);
+        drift > self.min_drift && drift.as_secs_f64() > baseline.as_secs_f64() * self.threshold
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Sums {
+    runs: u64,
+    duration: Duration,
+    latency: Duration,
+    max: Duration
+}
+
+impl Sums {
+
+    fn add(&mut self, duration: Duration, latency: Duration) {
+        self.runs += 1;
+        self.duration += duration;
+        self.latency += latency;
+        self.max = self.max.max(duration);
+    }
+
+    fn take(&mut self) -> WindowStats {
+        let sums = std::mem::take(self);
+        let mean = |total: Duration| match sums.runs {
+            0 => Duration::default(),
+            runs => Duration::from_nanos((total.as_nanos() / runs as u128) as u64)
+        };
+
+        WindowStats { runs: sums.runs, duration: mean(sums.duration), latency: mean(sums.latency), max: sums.max }
+    }
+}
+
+/// Timings of a task in a window of a soak test.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct WindowStats {
+    /// Number of executions in the window.
+    pub runs: u64,
+    /// Mean duration of the executions.
+    pub duration: Duration,
+    /// Mean queue latency of the executions.
+    pub latency: Duration,
+    pub max: Duration
+}
+
+/// Time window of a soak test.
+#[derive(Clone, Debug)]
+pub struct SoakWindow {
+    /// Start of the window, relative to the start of the soak test.
+    pub start: Duration,
+    /// Timings of the tasks by index.
+    pub tasks: Vec<WindowStats>
+}
+
+impl SoakWindow {
+
+    fn close(start: Duration, sums: &mut [Sums]) -> Self {
+        Self { start, tasks: sums.iter_mut().map(Sums::take).collect() }
+    }
+}
+
+/// Timing a drift is detected in.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Metric {
+    Duration,
+    Latency
+}
+
+/// Mean of a task that drifted from the first window it executed in.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Drift {
+    pub task: TaskId,
+    /// Index of the first window the mean drifted in.
+    pub window: usize,
+    pub metric: Metric,
+    pub baseline: Duration,
+    pub value: Duration
+}
+
+/// Result of `Soak::run`, `N` is the type of the labels.
+#[derive(Clone, Debug)]
+pub struct SoakReport<N = String> {
+    runs: u64,
+    labels: Vec<Option<N>>,
+    windows: Vec<SoakWindow>,
+    drifts: Vec<Drift>
+}
+
+impl<N> SoakReport<N> {
+
+    /// Returns the number of sampled runs.
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    pub fn windows(&self) -> &[SoakWindow] {
+        &self.windows
+    }
+
+    pub fn drifts(&self) -> &[Drift] {
+        &self.drifts
+    }
+
+    pub fn is_stable(&self) -> bool {
+        self.drifts.is_empty()
+    }
+
+    pub fn label(&self, task: TaskId) -> Option<&N> {
+        self.labels[task.id()].as_ref()
+    }
+
+    /// Returns the timings of `task` over all windows.
+    pub fn task(&self, task: TaskId) -> impl Iterator<Item=WindowStats> + '_ {
+        self.windows.iter().map(move |window| window.tasks[task.id()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interlock::builder::TaskSpec;
+    use crate::interlock::builder;
+    use crate::Executable;
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn drift() {
+        let degraded = AtomicBool::new(false);
+        let leak = |_: &()| {
+            let millis = if degraded.load(Ordering::Relaxed) { 6 } else { 1 };
+            std::thread::sleep(Duration::from_millis(millis));
+        };
+
+        let mut builder = builder::<(), &str>();
+        let ids = builder.extend(vec![
+            TaskSpec::new("steady", |_: &()| {}),
+            TaskSpec::new("leak", leak),
+            TaskSpec::new("after", |_: &()| {}).after(["leak"]),
+        ]).unwrap();
+
+        let mut exec = builder.build();
+        let start = Instant::now();
+        let report = Soak::new(Duration::from_millis(300))
+            .window(Duration::from_millis(100))
+            .min_drift(Duration::from_millis(2))
+            .run(&mut exec, |exec| {
+                degraded.store(start.elapsed() >= Duration::from_millis(150), Ordering::Relaxed);
+                exec.run(&());
+            });
+
+        assert!(report.runs() > 0);
+        assert!(report.windows().len() >= 3);
+        assert_eq!(report.label(ids["leak"]).map(String::as_str), Some("leak"));
+        assert!(report.task(ids["after"]).all(|stats| stats.latency < Duration::from_millis(2)));
+
+        let drifts = report.drifts();
+        assert!(!report.is_stable());
+        assert!(drifts.iter().all(|drift| drift.task == ids["leak"] && drift.metric == Metric::Duration), "{:?}", drifts);
+        assert!(drifts[0].window >= 1 && drifts[0].value > drifts[0].baseline);
+    }
+}
@@ -0,0 +1,94 @@
+use std::any::Any;
+use std::panic;
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Task execution handed to a `Spawn`.
+pub type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/**
+ Runs jobs on threads of another runtime, see `InterlockExecutor::run_spawned`.
+ Implemented for closures taking a `Job`, e.g. `|job| { tokio::task::spawn_blocking(job); }`.
+*/
+pub trait Spawn {
+    fn spawn(&self, job: Job);
+}
+
+impl<F: Fn(Job)> Spawn for F {
+
+    fn spawn(&self, job: Job) {
+        (self)(job)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    pending: usize,
+    panic: Option<Box<dyn Any + Send>>
+}
+
+/// Counts the jobs of a run that didn't finish yet and keeps the first panic of a job.
+#[derive(Default)]
+pub struct Latch {
+    state: Mutex<State>,
+    done: Condvar
+}
+
+impl Latch {
+
+    pub fn start(self: &Arc<Self>) -> Pending {
+        self.state.lock().unwrap().pending += 1;
+        Pending { latch: self.clone() }
+    }
+
+    pub fn panic(&self, payload: Box<dyn Any + Send>) {
+        self.state.lock().unwrap().panic.get_or_insert(payload);
+    }
+
+    pub fn is_panicking(&self) -> bool {
+        self.state.lock().unwrap().panic.is_some()
+    }
+
+    pub fn wait(&self) {
+        let mut state = self.state.lock().unwrap();
+        while state.pending > 0 {
+            state = self.done.wait(state).unwrap();
+        }
+    }
+
+    /// Resumes the first panic of a job, if any.
+    pub fn resume(&self) {
+        if let Some(payload) = self.state.lock().unwrap().panic.take() {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+/// A job that didn't finish yet, finishes when dropped, also if the runtime drops the job without running it.
+pub struct Pending {
+    latch: Arc<Latch>
+}
+
+impl Pending {
+
+    /// Fails the run, the job was dropped without running its task.
+    pub fn abandon(&self) {
+        self.latch.panic(Box::new("a task was dropped by the spawner without running"));
+    }
+
+    /// Fails the run with the panic of the job.
+    pub fn panic(&self, payload: Box<dyn Any + Send>) {
+        self.latch.panic(payload);
+    }
+}
+
+impl Drop for Pending {
+
+    fn drop(&mut self) {
+        let mut state = self.latch.state.lock().unwrap();
+        state.pending -= 1;
+
+        if state.pending == 0 {
+            self.latch.done.notify_all();
+        }
+    }
+}
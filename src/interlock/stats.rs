@@ -0,0 +1,314 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Timings of a task, recorded by executors built with the `inspector` feature.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct TaskStats {
+    /// Number of executions, memoized tasks that were skipped don't count.
+    pub runs: u64,
+    pub total: Duration,
+    pub max: Duration,
+    /// Start and end of the execution in the last run, relative to the start of that run.
+    pub last: Option<(Duration, Duration)>,
+    /// Index of the rayon thread the task last executed on.
+    pub thread: Option<usize>,
+    /// Bytes allocated by the execution in the last run, if an allocation counter or the task reported them.
+    pub allocated: Option<u64>,
+    /// Most bytes allocated by a single execution.
+    pub max_allocated: u64
+}
+
+impl TaskStats {
+
+    pub fn mean(&self) -> Duration {
+        match self.runs {
+            0 => Duration::default(),
+            runs => Duration::from_nanos((self.total.as_nanos() / runs as u128) as u64)
+        }
+    }
+}
+
+/**
+ Counter operations of the last run, recorded by executors built with the `inspector` feature, e.g. to relate
+ the scheduling overhead to the shape of the graph. Shared runs add to the counts of the last run.
+*/
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct LockStats {
+    /// Locks of conflicting tasks taken when a task started.
+    pub locks: u64,
+    /// Unlocks of dependants and conflicting tasks when a task finished.
+    pub unlocks: u64,
+    /// Attempts to take a task that was still locked or already taken.
+    pub failed_takes: u64
+}
+
+/**
+ Which runs an executor built with the `inspector` feature records into `TaskStats`, see `InterlockExecutor::set_sampling`.
+ Runs that aren't sampled don't touch the stats of their tasks, `LockStats` count every run.
+*/
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum Sampling {
+    #[default]
+    All,
+    /// Records the first run and every `n`th after it.
+    Every(u64),
+    /**
+     Records runs that took at least the given duration, e.g. frames over budget. Tasks are timed in every run,
+     but only slow runs count towards the totals and replace the timeline of the last recorded run.
+    */
+    Slower(Duration)
+}
+
+//nanos since the origin, start is 0 if the task didn't execute
+#[derive(Default)]
+struct Span {
+    start: AtomicU64,
+    end: AtomicU64,
+    //thread index + 1, 0 outside of a rayon pool
+    thread: AtomicU64,
+    //bytes + 1, 0 if none were reported
+    allocated: AtomicU64
+}
+
+impl Span {
+
+    fn take(&self, into: &Span) -> Option<(u64, u64)> {
+        let start = self.start.swap(0, Ordering::Relaxed);
+        into.start.store(start, Ordering::Relaxed);
+        into.end.store(self.end.load(Ordering::Relaxed), Ordering::Relaxed);
+        into.thread.store(self.thread.load(Ordering::Relaxed), Ordering::Relaxed);
+        into.allocated.store(self.allocated.swap(0, Ordering::Relaxed), Ordering::Relaxed);
+
+        match start {
+            0 => None,
+            start => Some((into.end.load(Ordering::Relaxed) - start, into.allocated.load(Ordering::Relaxed)))
+        }
+    }
+}
+
+#[derive(Default)]
+struct Record {
+    runs: AtomicU64,
+    total: AtomicU64,
+    max: AtomicU64,
+    max_allocated: AtomicU64,
+    //execution in the last recorded run
+    last: Span,
+    //execution in the run in progress, until it turns out to be slow enough to record
+    pending: Span
+}
+
+impl Record {
+
+    fn add(&self, duration: u64, allocated: u64) {
+        self.runs.fetch_add(1, Ordering::Relaxed);
+        self.total.fetch_add(duration, Ordering::Relaxed);
+        self.max.fetch_max(duration, Ordering::Relaxed);
+
+        if let Some(allocated) = allocated.checked_sub(1) {
+            self.max_allocated.fetch_max(allocated, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Timings of all tasks, every task writes its own record so tasks don't contend.
+pub struct Stats {
+    origin: Instant,
+    //start of the last recorded run
+    run: AtomicU64,
+    records: Vec<Record>,
+    sampling: Sampling,
+    sampled: bool,
+    //runs since the sampling changed
+    count: u64,
+    //whether the run in progress was recorded as slow already, shared runs may record it again
+    kept: AtomicBool,
+    //nanos the last run took, and whether it was recorded
+    elapsed: AtomicU64,
+    recorded: AtomicBool,
+    locks: AtomicU64,
+    unlocks: AtomicU64,
+    failed_takes: AtomicU64
+}
+
+impl Stats {
+
+    pub fn new(tasks: usize) -> Self {
+        Self {
+            origin: Instant::now(),
+            run: AtomicU64::new(0),
+            records: (0..tasks).map(|_| Record::default()).collect(),
+            sampling: Sampling::All,
+            sampled: true,
+            count: 0,
+            kept: AtomicBool::new(false),
+            elapsed: AtomicU64::new(0),
+            recorded: AtomicBool::new(false),
+            locks: AtomicU64::new(0),
+            unlocks: AtomicU64::new(0),
+            failed_takes: AtomicU64::new(0)
+        }
+    }
+
+    fn nanos(&self, instant: Instant) -> u64 {
+        //offset by one so a start of 0 means the task didn't execute
+        instant.duration_since(self.origin).as_nanos() as u64 + 1
+    }
+
+    /// Panics on `Sampling::Every(0)`.
+    pub fn set_sampling(&mut self, sampling: Sampling) {
+        assert_ne!(sampling, Sampling::Every(0), "can't sample every 0th run");
+        self.sampling = sampling;
+        self.count = 0;
+    }
+
+    pub fn sampling(&self) -> Sampling {
+        self.sampling
+    }
+
+    /// Returns whether tasks of the run in progress are timed, and their allocations measured.
+    pub fn is_sampled(&self) -> bool {
+        self.sampled
+    }
+
+    /// Starts a new run, timelines of the previous run are cleared if the new one is recorded.
+    pub fn begin(&mut self) {
+        self.sampled = match self.sampling {
+            Sampling::Every(n) => self.count.is_multiple_of(n),
+            _ => true
+        };
+
+        self.count += 1;
+        *self.kept.get_mut() = false;
+
+        match self.sampling {
+            Sampling::Slower(_) => self.records.iter().for_each(|record| record.pending.start.store(0, Ordering::Relaxed)),
+            _ if self.sampled => {
+                *self.run.get_mut() = self.nanos(Instant::now());
+                self.records.iter().for_each(|record| {
+                    record.last.start.store(0, Ordering::Relaxed);
+                    record.last.allocated.store(0, Ordering::Relaxed);
+                });
+            },
+            _ => ()
+        }
+
+        [&self.locks, &self.unlocks, &self.failed_takes].iter().for_each(|counter| counter.store(0, Ordering::Relaxed));
+    }
+
+    /// Ends a run that started at `start`, `Sampling::Slower` records it if it took long enough.
+    pub fn finish(&self, start: Instant) {
+        let elapsed = start.elapsed();
+        let (recorded, threshold) = match self.sampling {
+            Sampling::Slower(threshold) => (elapsed >= threshold, Some(threshold)),
+            _ => (self.sampled, None)
+        };
+
+        self.elapsed.store(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        self.recorded.store(recorded, Ordering::Relaxed);
+
+        if threshold.is_none() || !recorded {
+            return;
+        }
+
+        //a shared run after a slow run adds to it
+        let first = !self.kept.swap(true, Ordering::Relaxed);
+        if first {
+            self.run.store(self.nanos(start), Ordering::Relaxed);
+        }
+
+        for record in &self.records {
+            match record.pending.start.load(Ordering::Relaxed) {
+                0 if first => record.last.start.store(0, Ordering::Relaxed),
+                0 => (),
+                _ => if let Some((duration, allocated)) = record.pending.take(&record.last) {
+                    record.add(duration, allocated);
+                }
+            }
+        }
+    }
+
+    /// Returns how long the last run took if it was recorded, shared runs included.
+    pub fn recorded_run(&self) -> Option<Duration> {
+        match self.recorded.load(Ordering::Relaxed) {
+            true => Some(Duration::from_nanos(self.elapsed.load(Ordering::Relaxed))),
+            false => None
+        }
+    }
+
+    /// Returns when the last recorded run started, if any.
+    pub fn started(&self) -> Option<Instant> {
+        match self.run.load(Ordering::Relaxed) {
+            0 => None,
+            run => Some(self.origin + Duration::from_nanos(run - 1))
+        }
+    }
+
+    pub fn record(&self, id: usize, start: Instant, end: Instant, allocated: Option<u64>) {
+        if !self.sampled {
+            return;
+        }
+
+        let record = &self.records[id];
+        let span = match self.sampling {
+            Sampling::Slower(_) => &record.pending,
+            _ => &record.last
+        };
+
+        let thread = rayon::current_thread_index().map_or(0, |thread| thread as u64 + 1);
+        span.start.store(self.nanos(start), Ordering::Relaxed);
+        span.end.store(self.nanos(end), Ordering::Relaxed);
+        span.thread.store(thread, Ordering::Relaxed);
+        if let Some(allocated) = allocated {
+            span.allocated.store(allocated.saturating_add(1), Ordering::Relaxed);
+        }
+
+        if !matches!(self.sampling, Sampling::Slower(_)) {
+            record.add(end.duration_since(start).as_nanos() as u64, allocated.map_or(0, |allocated| allocated.saturating_add(1)));
+        }
+    }
+
+    pub fn count_locks(&self, locks: usize) {
+        if locks > 0 {
+            self.locks.fetch_add(locks as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn count_unlocks(&self, unlocks: usize) {
+        if unlocks > 0 {
+            self.unlocks.fetch_add(unlocks as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn count_failed_take(&self) {
+        self.failed_takes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn locks(&self) -> LockStats {
+        LockStats {
+            locks: self.locks.load(Ordering::Relaxed),
+            unlocks: self.unlocks.load(Ordering::Relaxed),
+            failed_takes: self.failed_takes.load(Ordering::Relaxed)
+        }
+    }
+
+    pub fn get(&self, id: usize) -> TaskStats {
+        let record = &self.records[id];
+        let (start, end) = (record.last.start.load(Ordering::Relaxed), record.last.end.load(Ordering::Relaxed));
+        let run = self.run.load(Ordering::Relaxed);
+        let since_run = |nanos: u64| Duration::from_nanos(nanos.saturating_sub(run));
+
+        TaskStats {
+            runs: record.runs.load(Ordering::Relaxed),
+            total: Duration::from_nanos(record.total.load(Ordering::Relaxed)),
+            max: Duration::from_nanos(record.max.load(Ordering::Relaxed)),
+            last: if start == 0 { None } else { Some((since_run(start), since_run(end))) },
+            thread: match record.last.thread.load(Ordering::Relaxed) {
+                0 => None,
+                thread => Some(thread as usize - 1)
+            },
+            allocated: record.last.allocated.load(Ordering::Relaxed).checked_sub(1),
+            max_allocated: record.max_allocated.load(Ordering::Relaxed)
+        }
+    }
+}
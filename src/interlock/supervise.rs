@@ -0,0 +1,114 @@
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+/**
+ What happens when a supervised task panics, see `InterlockBuilder::supervise`. Failures count per task and reset
+ when the task completes, so `n` is the number of runs in a row the task may fail before the policy gives up on it.
+*/
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Supervision {
+    /// The panic is caught and the task executes again in the next run, with a fresh body if it has a factory.
+    Restart,
+    /// Restarts the task until it failed `n` runs in a row, then disables it: later runs skip it until it is enabled again.
+    Disable(u32),
+    /// Restarts the task until it failed `n` runs in a row, then lets the panic fail the run like an unsupervised task.
+    Escalate(u32)
+}
+
+impl Supervision {
+
+    /// Panics if the policy gives up before the first failure.
+    pub(crate) fn checked(self) -> Self {
+        if let Supervision::Disable(0) | Supervision::Escalate(0) = self {
+            panic!("a supervised task can't give up before it failed once");
+        }
+
+        self
+    }
+}
+
+/// Failure counters of a supervised task, see `InterlockExecutor::supervision`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct SupervisionStats {
+    /// Failures over all runs of this executor.
+    pub failures: u64,
+    /// Failures since the task last completed.
+    pub consecutive: u32,
+    /// Failures the task was restarted after.
+    pub restarts: u64,
+    pub disabled: bool
+}
+
+/// Supervision state of a task, updated by the runs executing it.
+pub(crate) struct Supervisor {
+    supervision: Supervision,
+    failures: AtomicU64,
+    consecutive: AtomicU32,
+    restarts: AtomicU64,
+    disabled: AtomicBool,
+    //the body is replaced before the next run, if the task has a factory
+    restart: AtomicBool
+}
+
+impl Supervisor {
+
+    pub fn new(supervision: Supervision) -> Self {
+        Self {
+            supervision,
+            failures: AtomicU64::new(0),
+            consecutive: AtomicU32::new(0),
+            restarts: AtomicU64::new(0),
+            disabled: AtomicBool::new(false),
+            restart: AtomicBool::new(false)
+        }
+    }
+
+    pub fn supervision(&self) -> Supervision {
+        self.supervision
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled.load(Ordering::Relaxed)
+    }
+
+    pub fn complete(&self) {
+        self.consecutive.store(0, Ordering::Relaxed);
+    }
+
+    /// Records a failure, returns the panic back if the policy escalates it.
+    pub fn fail(&self, payload: Box<dyn Any + Send>) -> Result<(), Box<dyn Any + Send>> {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        let consecutive = self.consecutive.fetch_add(1, Ordering::Relaxed).saturating_add(1);
+
+        match self.supervision {
+            Supervision::Disable(n) if consecutive >= n => self.disabled.store(true, Ordering::Relaxed),
+            Supervision::Escalate(n) if consecutive >= n => return Err(payload),
+            _ => {
+                self.restarts.fetch_add(1, Ordering::Relaxed);
+                self.restart.store(true, Ordering::Relaxed);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether the task failed since the last call and should get a fresh body.
+    pub fn take_restart(&self) -> bool {
+        self.restart.swap(false, Ordering::Relaxed)
+    }
+
+    /// Enables the task again and forgets its consecutive failures, the totals stay.
+    pub fn enable(&self) {
+        self.consecutive.store(0, Ordering::Relaxed);
+        self.disabled.store(false, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> SupervisionStats {
+        SupervisionStats {
+            failures: self.failures.load(Ordering::Relaxed),
+            consecutive: self.consecutive.load(Ordering::Relaxed),
+            restarts: self.restarts.load(Ordering::Relaxed),
+            disabled: self.is_disabled()
+        }
+    }
+}
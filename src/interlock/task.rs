@@ -1,67 +1,366 @@
-use crate::Executable;
 use super::cell::{CountCell, CountRef};
+use super::context::Slot;
+use super::memo::Memo;
+use super::run::{Boxed, ContextExecutable, TaskContext};
+use super::signal::Signal;
+use super::supervise::Supervisor;
+use crate::Executable;
+use std::any::Any;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Formatter};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Creates a fresh instance of a task, used to duplicate executors.
+pub(crate) type Factory<'a, T, R> = dyn Fn() -> Boxed<'a, T, R> + Send + Sync + 'a;
+
+/// Task body that can run for several runs at the same time.
+pub(crate) type SharedFn<'a, T> = dyn Fn(&T) + Send + Sync + 'a;
+
+/// Undoes the effects of a task, shared with the duplicates of the task.
+pub(crate) type Compensation<'a, T> = Mutex<Box<dyn Executable<T> + Send + 'a>>;
+
+/// Copies a resource before a task executes, returns what puts the copy back, see `InterlockBuilder::transactional`.
+pub(crate) type Snapshot<'a, T> = dyn Fn(&T) -> Box<dyn FnOnce(&T) + 'a> + Send + Sync + 'a;
+
+/// Parameters of a task, see `InterlockBuilder::add_with_params`.
+pub(crate) type Params = dyn Any + Send + Sync;
+
+/**
+ Order in which the executor starts tasks that are ready at the same time, realtime tasks always come first.
+ See `InterlockExecutor::set_priority`.
+*/
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum Priority {
+    /// Tasks start in the order they were added.
+    #[default]
+    Declaration,
+    /**
+     Tasks that many tasks wait for, directly or transitively, start first, so a task blocking a large part
+     of the graph doesn't wait behind a leaf task. Ties keep the declaration order.
+    */
+    Fanout
+}
+
+/**
+ Options of a task, set with `TaskSpec` or `InterlockBuilder` and moved into the built task as a whole,
+ so an option is declared once instead of being copied field by field from the spec to the task.
+*/
+pub struct TaskOptions<'a, T, R> {
+    pub factory: Option<Arc<Factory<'a, T, R>>>,
+    pub shared: Option<Arc<SharedFn<'a, T>>>,
+    pub memo: Option<Memo<'a, T>>,
+    pub realtime: bool,
+    pub blocking: bool,
+    pub remote: bool,
+    pub idempotent: bool,
+    pub compensation: Option<Arc<Compensation<'a, T>>>,
+    pub supervisor: Option<Supervisor>,
+    pub snapshots: Vec<Arc<Snapshot<'a, T>>>,
+    pub params: Option<Arc<Params>>
+}
+
+impl<'a, T, R> Default for TaskOptions<'a, T, R> {
+
+    fn default() -> Self {
+        Self {
+            factory: None,
+            shared: None,
+            memo: None,
+            realtime: false,
+            blocking: false,
+            remote: false,
+            idempotent: true,
+            compensation: None,
+            supervisor: None,
+            snapshots: Vec::new(),
+            params: None
+        }
+    }
+}
+
+impl<'a, T, R> TaskOptions<'a, T, R> {
 
-#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
-pub struct TaskId(usize);
+    /// Returns the same options for a copy of the task, which memoizes and fails on its own.
+    fn duplicate(&self) -> Self {
+        Self {
+            factory: self.factory.clone(),
+            shared: self.shared.clone(),
+            memo: self.memo.as_ref().map(Memo::duplicate),
+            realtime: self.realtime,
+            blocking: self.blocking,
+            remote: self.remote,
+            idempotent: self.idempotent,
+            compensation: self.compensation.clone(),
+            supervisor: self.supervisor.as_ref().map(|supervisor| Supervisor::new(supervisor.supervision())),
+            snapshots: self.snapshots.clone(),
+            params: self.params.clone()
+        }
+    }
+}
+
+static NEXT_BRAND: AtomicU32 = AtomicU32::new(1);
+
+/**
+ Handle of a task within a graph.
+ Every builder brands the handles it returns, so using a handle with a different builder is detected
+ instead of silently referring to an unrelated task.
+*/
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+pub struct TaskId {
+    brand: u32,
+    index: u32
+}
 
 impl TaskId {
 
-    pub(crate) fn new(id: usize) -> Self {
-        Self(id)
+    pub(crate) fn branded(brand: u32, id: usize) -> Self {
+        let index = u32::try_from(id).expect("failed to add the task: too many tasks");
+        Self { brand, index }
+    }
+
+    /// Returns a brand no other builder uses, panics once all of them were handed out rather than reusing one.
+    pub(crate) fn next_brand() -> u32 {
+        NEXT_BRAND.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |brand| brand.checked_add(1))
+            .expect("can't create another builder, all task id brands are used")
+    }
+
+    pub(crate) fn brand(&self) -> u32 {
+        self.brand
     }
 
     pub fn id(&self) -> usize {
-        self.0
+        self.index as usize
     }
 }
 
-pub struct Task<'a, T> {
+impl Debug for TaskId {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "TaskId({})", self.index)
+    }
+}
+
+/**
+ Task of a built graph, `Send` and `Sync` because its body is `Send` and only borrowed through its `CountCell`.
+ Labels are of type `N`, see `InterlockBuilder::named`.
+*/
+pub struct Task<'a, T, R, N = String> {
     id: TaskId,
-    task: CountCell<Box<dyn Executable<T> + Send + 'a>>,
+    label: Option<N>,
+    task: CountCell<Boxed<'a, T, R>>,
+    options: TaskOptions<'a, T, R>,
+    permits: Vec<usize>,
+    waits: Vec<Signal>,
+    signals: Vec<Signal>,
     lock: Vec<TaskId>,
     unlock: Vec<TaskId>,
-    initial: usize
+    initial: usize,
+    static_lock: usize,
+    static_unlock: usize
 }
 
-pub struct TaskRef<'r, 'task, T> {
-    task: &'r Task<'task, T>,
-    borrow: CountRef<'r, Box<dyn Executable<T> + Send + 'task>>
+pub struct TaskRef<'r, 'task, T, R> {
+    borrow: CountRef<'r, Boxed<'task, T, R>>
 }
 
-impl<'r, 'task, T> TaskRef<'r, 'task, T> {
+/// Counter of a shared task within a single shared run.
+pub struct SharedSlot<'r, 'task, T> {
+    counter: CountCell<()>,
+    task: &'r SharedFn<'task, T>
+}
 
-    pub fn task(&self) -> &Task<'task, T> {
-        self.task
-    }
+impl<'r, 'task, T> SharedSlot<'r, 'task, T> {
 
-    pub fn execute(&mut self, data: &T) {
-        self.borrow.run(data);
+    pub fn new(task: &'r SharedFn<'task, T>) -> Self {
+        Self { counter: CountCell::new(()), task }
     }
 }
 
-impl<'task, T> Task<'task, T> {
-    pub fn new(id: TaskId, task: Box<dyn Executable<T> + Send + 'task>, lock: Vec<TaskId>, unlock: Vec<TaskId>, initial: usize) -> Self {
-        Self { id, task: CountCell::new(task), lock, unlock, initial }
-    }
+impl<'r, 'task: 'r, T: 'r, R: 'r, N: Sync + 'r> Slot<'r, T, R> for Task<'task, T, R, N> {
+    type Borrow = TaskRef<'r, 'task, T, R>;
 
-    pub fn id(&self) -> TaskId {
-        self.id
+    fn reset(&self, count: usize) {
+        self.task.reset(count);
     }
 
-    pub fn init(&self) {
-        self.task.reset(self.initial);
+    fn complete(&self) {
+        self.task.complete();
     }
 
-    pub fn lock(&self) {
+    fn lock(&self) {
         self.task.lock()
     }
 
-    pub fn unlock(&self) -> bool {
+    fn unlock(&self) -> bool {
         self.task.unlock()
     }
 
-    pub fn take(&self) -> Option<TaskRef<'_, 'task, T>> {
-        self.task.take().map(|borrow| TaskRef { task: self, borrow })
+    fn take(&'r self) -> Option<Self::Borrow> {
+        self.task.take().map(|borrow| TaskRef { borrow })
+    }
+
+    fn execute(borrow: &mut Self::Borrow, data: &T, context: &TaskContext<'_, R>) {
+        borrow.borrow.run(data, context);
+    }
+}
+
+impl<'r, 'task, T, R> Slot<'r, T, R> for SharedSlot<'r, 'task, T> {
+    type Borrow = (CountRef<'r, ()>, &'r SharedFn<'task, T>);
+
+    fn reset(&self, count: usize) {
+        self.counter.reset(count);
+    }
+
+    fn complete(&self) {
+        self.counter.complete();
+    }
+
+    fn lock(&self) {
+        self.counter.lock()
+    }
+
+    fn unlock(&self) -> bool {
+        self.counter.unlock()
+    }
+
+    fn take(&'r self) -> Option<Self::Borrow> {
+        self.counter.take().map(|borrow| (borrow, self.task))
+    }
+
+    fn execute((_, task): &mut Self::Borrow, data: &T, _: &TaskContext<'_, R>) {
+        task(data);
+    }
+}
+
+impl<'task, T, R, N> Task<'task, T, R, N> {
+    pub fn new(id: TaskId, label: Option<N>, task: Boxed<'task, T, R>, options: TaskOptions<'task, T, R>, lock: Vec<TaskId>, unlock: Vec<TaskId>, initial: usize) -> Self {
+        let (static_lock, static_unlock) = (lock.len(), unlock.len());
+        Self { id, label, task: CountCell::new(task), options, permits: Vec::new(), waits: Vec::new(), signals: Vec::new(), lock, unlock, initial, static_lock, static_unlock }
+    }
+
+    pub fn shared(&self) -> Option<&SharedFn<'task, T>> {
+        self.options.shared.as_deref()
+    }
+
+    pub fn memo(&self) -> Option<&Memo<'task, T>> {
+        self.options.memo.as_ref()
+    }
+
+    pub fn is_realtime(&self) -> bool {
+        self.options.realtime
+    }
+
+    pub fn is_blocking(&self) -> bool {
+        self.options.blocking
+    }
+
+    pub fn is_remote(&self) -> bool {
+        self.options.remote
+    }
+
+    /// Returns whether the task can execute again after it was interrupted, see `InterlockBuilder::non_idempotent`.
+    pub fn is_idempotent(&self) -> bool {
+        self.options.idempotent
+    }
+
+    /// Returns what undoes the task when a later task of the run fails, see `InterlockBuilder::compensate`.
+    pub fn compensation(&self) -> Option<&Compensation<'task, T>> {
+        self.options.compensation.as_deref()
+    }
+
+    /// Returns the failure state of the task, see `InterlockBuilder::supervise`.
+    pub fn supervisor(&self) -> Option<&Supervisor> {
+        self.options.supervisor.as_ref()
+    }
+
+    /// Returns what copies the resources the task restores when it panics, see `InterlockBuilder::transactional`.
+    pub fn snapshots(&self) -> &[Arc<Snapshot<'task, T>>] {
+        &self.options.snapshots
+    }
+
+    pub fn set_params(&mut self, params: Option<Arc<Params>>) {
+        self.options.params = params;
+    }
+
+    /// Returns the parameters the task was added with or configured, see `InterlockBuilder::add_with_params`.
+    pub fn params(&self) -> Option<&Params> {
+        self.options.params.as_deref()
+    }
+
+    /// Replaces the body of a task that failed since the last run with a fresh one from its factory, if it has one.
+    pub fn restart(&mut self) {
+        let restart = self.options.supervisor.as_ref().is_some_and(Supervisor::take_restart);
+        if let Some(factory) = self.options.factory.as_ref().filter(|_| restart) {
+            self.task = CountCell::new(factory());
+        }
+    }
+
+    /// Sets the semaphores of the resources with a capacity the task accesses.
+    pub fn set_permits(&mut self, mut permits: Vec<usize>) {
+        permits.sort_unstable();
+        permits.dedup();
+        self.permits = permits;
+    }
+
+    /// Returns the semaphores the task takes a permit of before it executes, in ascending order.
+    pub fn permits(&self) -> &[usize] {
+        &self.permits
+    }
+
+    /// Makes the task wait for `signal` before it executes, see `App::link`.
+    pub fn add_wait(&mut self, signal: Signal) {
+        self.waits.push(signal);
+    }
+
+    pub fn waits(&self) -> &[Signal] {
+        &self.waits
+    }
+
+    /// Sets `signal` whenever the task completed or was skipped.
+    pub fn add_signal(&mut self, signal: Signal) {
+        self.signals.push(signal);
+    }
+
+    pub fn signals(&self) -> &[Signal] {
+        &self.signals
+    }
+
+    /// Sorts the dependants and the locks by `rank`, so tasks with a lower rank are unlocked and started first.
+    pub fn prioritize(&mut self, rank: &[usize]) {
+        let dependants = self.static_unlock - self.static_lock;
+        let (dependants, locks) = self.unlock[..self.static_unlock].split_at_mut(dependants);
+
+        dependants.sort_by_key(|task| rank[task.id()]);
+        locks.sort_by_key(|task| rank[task.id()]);
+    }
+
+    /// Creates the same task with a fresh instance from its factory, returns `None` if it has none.
+    pub fn duplicate(&self) -> Option<Self> where N: Clone {
+        self.options.factory.as_ref().map(|factory| Self {
+            id: self.id,
+            label: self.label.clone(),
+            task: CountCell::new(factory()),
+            options: self.options.duplicate(),
+            permits: self.permits.clone(),
+            //links belong to the graphs of an app, not to their copies
+            waits: Vec::new(),
+            signals: Vec::new(),
+            lock: self.lock.clone(),
+            unlock: self.unlock.clone(),
+            initial: self.initial,
+            static_lock: self.static_lock,
+            static_unlock: self.static_unlock
+        })
+    }
+
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    pub fn label(&self) -> Option<&N> {
+        self.label.as_ref()
     }
 
     pub fn initial_count(&self) -> usize {
@@ -75,4 +374,32 @@ impl<'task, T> Task<'task, T> {
     pub fn unlockable_deps(&self) -> &[TaskId] {
         self.unlock.as_slice()
     }
+
+    /// Returns the locks derived when the graph was built, without the ones of resolved resources.
+    pub fn static_locks(&self) -> &[TaskId] {
+        &self.lock[..self.static_lock]
+    }
+
+    pub fn static_unlocks(&self) -> &[TaskId] {
+        &self.unlock[..self.static_unlock]
+    }
+
+    /// Returns the tasks depending on this task, they come first in the unlock list.
+    pub fn dependants(&self) -> &[TaskId] {
+        &self.unlock[..self.static_unlock - self.static_lock]
+    }
+
+    /// Removes locks added for resolved resources of a previous run.
+    pub fn clear_dynamic_locks(&mut self) {
+        self.lock.truncate(self.static_lock);
+        self.unlock.truncate(self.static_unlock);
+    }
+
+    /// Adds a lock for a resolved resource, it is locked and unlocked like locks of static resources.
+    pub fn add_dynamic_lock(&mut self, id: TaskId) {
+        if !self.lock.contains(&id) {
+            self.lock.push(id);
+            self.unlock.push(id);
+        }
+    }
 }
@@ -0,0 +1,124 @@
+use super::InterlockExecutor;
+use std::hash::Hash;
+use std::time::Duration;
+
+/**
+ Position of a run within an update of a fixed timestep, see `FixedTimestep`.
+ Tasks read it with `TaskContext::tick`, e.g. to interpolate rendered state between the last two simulation steps.
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tick {
+    /// Index of the run within the update, counting from 0.
+    pub index: u32,
+    /// Number of runs of the update.
+    pub count: u32,
+    /// Time a run advances by, the step of a fixed timestep or the elapsed time of a frame of an `App`.
+    pub step: Duration,
+    /// Time left over after the update as a fraction of the step, in `0.0..1.0`.
+    pub alpha: f64
+}
+
+/**
+ Fixed timestep accumulator, the classic game loop: elapsed time accumulates, and every step of it runs
+ the simulation once, so it advances at the same rate whatever the frame rate is.
+ Use it standalone with `run`, or through a fixed stage of an `App`.
+*/
+#[derive(Clone, Copy, Debug)]
+pub struct FixedTimestep {
+    step: Duration,
+    max_steps: u32,
+    accumulated: Duration
+}
+
+impl FixedTimestep {
+
+    /// Panics if `step` is zero.
+    pub fn new(step: Duration) -> Self {
+        assert!(step > Duration::default(), "a fixed timestep needs a step longer than zero");
+        Self { step, max_steps: u32::MAX, accumulated: Duration::default() }
+    }
+
+    /**
+     Runs at most `max_steps` steps per update and drops the time of the others, so a simulation that is slower than
+     real time falls behind instead of taking longer with every update. Unlimited by default. Panics if `max_steps` is 0.
+    */
+    pub fn max_steps(mut self, max_steps: u32) -> Self {
+        assert!(max_steps > 0, "a fixed timestep needs at least one step per update");
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// Time accumulated but not simulated yet, less than a step after every update.
+    pub fn accumulated(&self) -> Duration {
+        self.accumulated
+    }
+
+    /// Accumulated time as a fraction of the step, to interpolate between the last two steps.
+    pub fn alpha(&self) -> f64 {
+        self.accumulated.as_secs_f64() / self.step.as_secs_f64()
+    }
+
+    /// Adds `elapsed` to the accumulated time and returns the steps it completed.
+    pub fn advance(&mut self, elapsed: Duration) -> Ticks {
+        self.accumulated += elapsed;
+
+        let steps = self.accumulated.as_nanos() / self.step.as_nanos();
+        let count = steps.min(self.max_steps as u128) as u32;
+
+        //dropped steps don't leave more than a step behind either
+        self.accumulated = match steps > count as u128 {
+            true => Duration::from_nanos((self.accumulated.as_nanos() % self.step.as_nanos()) as u64),
+            false => self.accumulated - self.step * count
+        };
+
+        Ticks { index: 0, count, step: self.step, alpha: self.alpha() }
+    }
+
+    /**
+     Advances by `elapsed` and runs `executor` once per completed step, its tasks see the `Tick`. Returns the number of runs.
+     The executor has no tick afterwards, also if a step panics.
+    */
+    pub fn run<'task, T: Sync, R: Eq + Hash + Sync, N: Sync>(&mut self, elapsed: Duration, executor: &mut InterlockExecutor<'task, T, R, N>, data: &T) -> u32 {
+        let ticks = self.advance(elapsed);
+        let count = ticks.count;
+
+        for tick in ticks {
+            executor.run_tick(data, tick);
+        }
+
+        count
+    }
+}
+
+/// Steps completed by an update of a `FixedTimestep`.
+#[derive(Clone, Debug)]
+pub struct Ticks {
+    index: u32,
+    count: u32,
+    step: Duration,
+    alpha: f64
+}
+
+impl Iterator for Ticks {
+    type Item = Tick;
+
+    fn next(&mut self) -> Option<Tick> {
+        if self.index == self.count {
+            return None;
+        }
+
+        self.index += 1;
+        Some(Tick { index: self.index - 1, count: self.count, step: self.step, alpha: self.alpha })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.count - self.index) as usize;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Ticks {}
@@ -0,0 +1,107 @@
+use super::InterlockExecutor;
+use super::task::TaskId;
+use crate::test::analysis::{TimelineAnalyzer, TimelineTask, Timestamp};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::hash::Hash;
+
+/// Pair of tasks a timeline ran in an order the graph doesn't allow, see `InterlockExecutor::verify_timeline`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Violation<'a, N> {
+    /// `after` depends on `before`, but didn't start after it ended.
+    Dependency { before: &'a N, after: &'a N, repeat: usize },
+    /// The tasks conflict, but overlapped.
+    Conflict { a: &'a N, b: &'a N, repeat: usize }
+}
+
+impl<'a, N: Display> Display for Violation<'a, N> {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Dependency { before, after, repeat } => write!(f, "'{}' started before '{}' ended in execution {}", after, before, repeat),
+            Violation::Conflict { a, b, repeat } => write!(f, "'{}' and '{}' overlapped in execution {}", a, b, repeat)
+        }
+    }
+}
+
+impl<'task, T: Sync, R: Eq + Hash, N: Eq + Hash> InterlockExecutor<'task, T, R, N> {
+
+    /**
+     Checks `timeline` against the graph: every task has to start after the tasks it depends on ended, and tasks with
+     conflicting accesses must not overlap. Tasks are matched by label, e.g. with a timeline of `TimelineReader`
+     tasks wrapped under their labels, and only executions of the same run are checked against each other.
+
+     A timeline of several runs is split where a label repeats, at the last point before it where no task was
+     running, so the runs mustn't overlap, e.g. runs of `run_shared` on several threads can't be told apart, and a
+     task of a run mustn't start before a gap in the previous one. Unlabeled tasks, tasks missing from the timeline
+     and accesses resolved at run time aren't checked. Returns every violation, in task order.
+    */
+    pub fn verify_timeline<'a, D: Timestamp>(&'a self, timeline: &'a TimelineAnalyzer<N, D>) -> Result<(), Vec<Violation<'a, N>>> {
+        let runs = runs(timeline);
+        let count = runs.last().map_or(0, |run| run + 1);
+        let executions: HashMap<(&N, usize), &TimelineTask<N, D>> = timeline.iter().zip(runs).map(|(task, run)| ((task.name(), run), task)).collect();
+        let execution = |task: TaskId, run| self.tasks[task.id()].label().and_then(|label| executions.get(&(label, run)).copied());
+
+        let mut violations = Vec::new();
+        for task in &self.tasks {
+            let label = match task.label() {
+                Some(label) => label,
+                None => continue
+            };
+
+            //every pair once, dependencies are checked on their own
+            let conflicts: Vec<_> = task.static_locks().iter().filter(|other| other.id() > task.id().id() && !task.dependants().contains(other)).collect();
+            for run in 0..count {
+                let own = match executions.get(&(label, run)) {
+                    Some(own) => own,
+                    None => continue
+                };
+
+                for after in task.dependants().iter().filter_map(|dependant| execution(*dependant, run)) {
+                    if !own.ends_before(after) {
+                        violations.push(Violation::Dependency { before: label, after: after.name(), repeat: run });
+                    }
+                }
+
+                for other in conflicts.iter().filter_map(|other| execution(**other, run)) {
+                    if !own.ends_before(other) && !own.starts_after(other) {
+                        violations.push(Violation::Conflict { a: label, b: other.name(), repeat: run });
+                    }
+                }
+            }
+        }
+
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations)
+        }
+    }
+}
+
+//run of every task of the timeline, see `verify_timeline`
+fn runs<N: Eq + Hash, D: Timestamp>(timeline: &TimelineAnalyzer<N, D>) -> Vec<usize> {
+    let tasks: Vec<_> = timeline.iter().collect();
+    let mut runs = Vec::with_capacity(tasks.len());
+    let mut seen = HashSet::new();
+    let (mut run, mut first, mut idle, mut ended) = (0, 0, 0, D::default());
+
+    for (idx, task) in tasks.iter().enumerate() {
+        if task.start() >= ended {
+            idle = idx;
+        }
+
+        if seen.contains(task.name()) {
+            //without a gap in the run the repeat starts the next one itself
+            first = if idle > first { idle } else { idx };
+            run += 1;
+            runs[first..].iter_mut().for_each(|task_run| *task_run = run);
+            seen = tasks[first..idx].iter().map(|task| task.name()).collect();
+        }
+
+        seen.insert(task.name());
+        runs.push(run);
+        ended = ended.max(task.end());
+    }
+
+    runs
+}
@@ -0,0 +1,200 @@
+use super::InterlockExecutor;
+use super::resource::Access;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/**
+ Structure of a graph at the time an artifact was persisted: a hash per task of its label, position, dependants
+ and static accesses. Plans, memo caches and checkpoints carry the version of their graph, so a stale artifact
+ is refused with the tasks that changed instead of being applied to a different graph. Serializable with the
+ `serde` feature.
+
+ Hashes don't depend on the process or platform, but they are only stable for a given build: labels are hashed
+ as displayed and resources through their `Hash` impls, which std doesn't promise to keep between releases,
+ so a new compiler or a changed `Display` impl may turn persisted artifacts stale.
+*/
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphVersion {
+    //task name, labels or #index for unlabeled tasks, and its hash
+    tasks: Vec<(String, u64)>
+}
+
+impl GraphVersion {
+
+    pub(crate) fn new<T, R: Eq + Hash, N: Display>(executor: &InterlockExecutor<'_, T, R, N>) -> Self {
+        let table = executor.resources.table();
+        let accesses = table.accesses(executor.tasks.len());
+
+        let tasks = executor.tasks.iter().zip(accesses).enumerate().map(|(id, (task, accesses))| {
+            let name = task.label().map_or_else(|| format!("#{}", id), N::to_string);
+
+            //accesses are hashed one by one and sorted, their order isn't part of the structure
+            let mut accesses: Vec<_> = accesses.into_iter().map(|(resource, access)| {
+                let mut hasher = Fnv::default();
+                resource.hash(&mut hasher);
+                hasher.write_u8(access_code(Some(access)));
+                hasher.finish()
+            }).collect();
+            accesses.sort_unstable();
+
+            let mut dependants: Vec<_> = task.dependants().iter().map(|dependant| dependant.id() as u64).collect();
+            dependants.sort_unstable();
+
+            let mut hasher = Fnv::default();
+            hasher.write(name.as_bytes());
+            hasher.write_u64(id as u64);
+            hasher.write_u8(access_code(table.all(task.id())));
+            [dependants, accesses].iter().for_each(|list| {
+                hasher.write_u64(list.len() as u64);
+                list.iter().for_each(|&value| hasher.write_u64(value));
+            });
+
+            (name, hasher.finish())
+        }).collect();
+
+        Self { tasks }
+    }
+
+    /// Returns a hash of the whole structure, equal for graphs built the same way by the same build, see `GraphVersion`.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = Fnv::default();
+        hasher.write_u64(self.tasks.len() as u64);
+        self.tasks.iter().for_each(|(_, hash)| hasher.write_u64(*hash));
+        hasher.finish()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Returns an error listing the tasks that differ if `current` isn't the same version.
+    pub fn check(&self, current: &GraphVersion) -> Result<(), VersionMismatch> {
+        if self == current {
+            return Ok(());
+        }
+
+        let find = |version: &GraphVersion, name: &str| version.tasks.iter().find(|(other, _)| other == name).map(|(_, hash)| *hash);
+        let names = |version: &GraphVersion, other: &GraphVersion| version.tasks.iter()
+            .filter(|(name, _)| find(other, name).is_none())
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        let changed = current.tasks.iter()
+            .filter(|(name, hash)| find(self, name).is_some_and(|other| other != *hash))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        Err(VersionMismatch { added: names(current, self), removed: names(self, current), changed })
+    }
+}
+
+/// Difference between the graph an artifact was persisted from and the current one, by task label.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct VersionMismatch {
+    /// Tasks only the current graph has.
+    pub added: Vec<String>,
+    /// Tasks only the persisted graph had.
+    pub removed: Vec<String>,
+    /// Tasks whose position, dependants or accesses changed.
+    pub changed: Vec<String>
+}
+
+impl Display for VersionMismatch {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "the graph changed since the artifact was persisted")?;
+
+        let groups = [("+", &self.added), ("-", &self.removed), ("~", &self.changed)];
+        for (sign, names) in groups.iter() {
+            for name in names.iter() {
+                write!(f, "\n  {} '{}'", sign, name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for VersionMismatch {}
+
+fn access_code(access: Option<Access>) -> u8 {
+    match access {
+        None => 0,
+        Some(Access::Read) => 1,
+        Some(Access::Write) => 2
+    }
+}
+
+/// FNV-1a, unlike the std hashers its output is specified, so only the hashed values can change between releases.
+struct Fnv(u64);
+
+impl Default for Fnv {
+
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv {
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    //fixed width and byte order, so hashes match across platforms
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, value: u128) {
+        self.write(&value.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv() {
+        //reference values of FNV-1a
+        let hash = |bytes: &[u8]| {
+            let mut hasher = Fnv::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+
+        assert_eq!(hash(b""), 0xcbf29ce484222325);
+        assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash(b"foobar"), 0x85944171f73967e8);
+
+        let mut usize = Fnv::default();
+        usize.write_usize(7);
+        assert_eq!(usize.finish(), hash(&7u64.to_le_bytes()), "widths don't depend on the platform");
+    }
+}
@@ -1,8 +1,26 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
 pub mod seq;
+#[cfg(feature = "std")]
 pub mod par;
+#[cfg(feature = "std")]
 pub mod interlock;
+#[cfg(feature = "std")]
+pub mod tasks;
+#[cfg(feature = "std")]
+pub mod resources;
+#[cfg(feature = "std")]
 pub mod test;
 
+/// Fixed-size graphs only, without the `std` feature, see `StaticGraph`.
+#[cfg(not(feature = "std"))]
+pub mod interlock {
+    #[path = "fixed.rs"]
+    mod fixed;
+
+    pub use self::fixed::{StaticGraph, StaticInterlock, StaticSchedule};
+}
+
 /**
  This trait is the heard of that library.
  It represents a single indivisible unit of work that requires reference to `T` to run.
@@ -17,10 +35,36 @@ impl<T, F: FnMut(&T)> Executable<T> for F {
     }
 }
 
+#[cfg(feature = "std")]
+impl<'a, T> Executable<T> for Box<dyn Executable<T> + Send + 'a> {
+    fn run(&mut self, data: &T) {
+        (**self).run(data)
+    }
+}
+
+/**
+ Task whose work can be divided, e.g. a filter over the rows of an image.
+ The work of a run is split in half whenever an idle worker steals a part of it, so a single large task
+ spreads over all threads that have nothing else to do. Run it through `tasks::Splitting`.
+*/
+pub trait SplittableExecutable<T> {
+    type Work: Send;
+
+    /// Returns all the work of a run.
+    fn work(&mut self, data: &T) -> Self::Work;
+
+    /// Splits `work` in two halves, or returns it unchanged if it is too small to be split.
+    fn split(&self, work: Self::Work) -> (Self::Work, Option<Self::Work>);
+
+    /// Executes a part of the work, parts are executed on several threads at the same time.
+    fn execute(&self, data: &T, work: Self::Work);
+}
+
 pub fn seq<T, Q1: Executable<T>, Q2: Executable<T>>(first: Q1, second: Q2) -> seq::Seq<Q1, Q2> {
     seq::Seq::new(first, second)
 }
 
+#[cfg(feature = "std")]
 pub fn par<T: Sync, Q1: Executable<T> + Send, Q2: Executable<T>+ Send>(first: Q1, second: Q2) -> par::Par<Q1, Q2> {
     par::Par::new(first, second)
 }
@@ -36,6 +80,33 @@ macro_rules! par {
     };
 }
 
+/**
+ Executes all tasks in parallel like `par!`, but joins them as a balanced tree: neighbours are paired up level by level,
+ so every task is at most log2(n) joins deep instead of the chain `par!` builds. Tasks may have different types.
+*/
+#[macro_export]
+macro_rules! par_all {
+    (@pair [$($done:expr),*] $a:expr, $b:expr, $($rest:expr),+) => {
+        $crate::par_all!(@pair [$($done,)* $crate::par($a, $b)] $($rest),+)
+    };
+
+    (@pair [$($done:expr),*] $a:expr, $b:expr) => {
+        $crate::par_all!($($done,)* $crate::par($a, $b))
+    };
+
+    (@pair [$($done:expr),*] $a:expr) => {
+        $crate::par_all!($($done,)* $a)
+    };
+
+    ($e:expr) => {
+        $e
+    };
+
+    ($($es:expr),+ $(,)?) => {
+        $crate::par_all!(@pair [] $($es),+)
+    };
+}
+
 #[macro_export]
 macro_rules! seq {
     ($e1:expr, $e2:expr) => {
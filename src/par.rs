@@ -5,11 +5,38 @@ use rayon::join;
  Executes two tasks in parallel, that is, one task _may_ begin at the same time as another, but there is no guarantee.
  You cannot use thread-local data as the tasks may be executed on a different thread to caller.
 
- Uses `rayon::join` internally.
+ Uses `rayon::join` internally: one side executes on the calling worker right away, the other one is offered
+ to idle workers and executes on the caller afterwards unless it was stolen. By default the head executes on
+ the caller, see `Placement` to change it.
 */
 pub struct Par<Q1, Q2> {
     head: Q1,
-    tail: Q2
+    tail: Q2,
+    placement: Placement
+}
+
+/**
+ Side of a `Par` executing on the calling worker. The other side waits until an idle worker steals it, so keeping
+ the expensive side on the caller and offering the cheap one finishes sooner when no worker is idle.
+*/
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum Placement {
+    #[default]
+    Head,
+    Tail,
+    /// The side with the larger weight executes on the caller, the head on ties.
+    Weighted { head: u32, tail: u32 }
+}
+
+impl Placement {
+
+    fn head_on_caller(self) -> bool {
+        match self {
+            Placement::Head => true,
+            Placement::Tail => false,
+            Placement::Weighted { head, tail } => head >= tail
+        }
+    }
 }
 
 impl<T: Send + Sync, Q1: Executable<T> + Send, Q2: Executable<T> + Send> Executable<T> for Par<Q1, Q2> {
@@ -21,13 +48,111 @@ impl<T: Send + Sync, Q1: Executable<T> + Send, Q2: Executable<T> + Send> Executa
         let head = move || head.run(data);
         let tail = move || tail.run(data);
 
-        join(head, tail);
+        //join executes its first closure on the caller
+        if self.placement.head_on_caller() {
+            join(head, tail);
+        } else {
+            join(tail, head);
+        }
     }
 }
 
 impl<Q1, Q2> Par<Q1, Q2> {
 
     pub fn new(head: Q1, tail: Q2) -> Self {
-        Self { head, tail }
+        Self { head, tail, placement: Placement::Head }
+    }
+
+    /// Changes the side executing on the calling worker.
+    pub fn placement(mut self, placement: Placement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// Executes the side with the larger estimated cost on the calling worker, see `Placement::Weighted`.
+    pub fn weighted(self, head: u32, tail: u32) -> Self {
+        self.placement(Placement::Weighted { head, tail })
+    }
+}
+
+/**
+ Executes all tasks of an array in parallel, joined as a balanced tree so idle workers steal large halves first.
+ See the `par_all!` macro for tasks of different types.
+*/
+pub struct ParArray<E, const N: usize> {
+    tasks: [E; N]
+}
+
+impl<E, const N: usize> ParArray<E, N> {
+
+    pub fn new(tasks: [E; N]) -> Self {
+        Self { tasks }
+    }
+}
+
+impl<T: Sync, E: Executable<T> + Send, const N: usize> Executable<T> for ParArray<E, N> {
+
+    fn run(&mut self, data: &T) {
+        run_all(&mut self.tasks, data);
+    }
+}
+
+fn run_all<T: Sync, E: Executable<T> + Send>(tasks: &mut [E], data: &T) {
+    match tasks {
+        [] => {},
+        [task] => task.run(data),
+        _ => {
+            let (head, tail) = tasks.split_at_mut(tasks.len() / 2);
+            join(|| run_all(head, data), || run_all(tail, data));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rayon::ThreadPoolBuilder;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn placement() {
+        let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+
+        let record = |placement: Placement| pool.install(|| {
+            let threads = Mutex::new([None; 2]);
+            let side = |idx: usize| {
+                let threads = &threads;
+                move |_: &()| threads.lock().unwrap()[idx] = rayon::current_thread_index()
+            };
+
+            Par::new(side(0), side(1)).placement(placement).run(&());
+            let caller = rayon::current_thread_index();
+            let threads = threads.into_inner().unwrap();
+            (threads[0] == caller, threads[1] == caller)
+        });
+
+        assert!(record(Placement::Head).0);
+        assert!(record(Placement::Tail).1);
+        assert!(record(Placement::Weighted { head: 1, tail: 10 }).1, "the heavier side stays on the caller");
+        assert!(record(Placement::Weighted { head: 10, tail: 1 }).0);
+    }
+
+    #[test]
+    fn all() {
+        let sum = AtomicUsize::new(0);
+        let add = |value: usize| {
+            let sum = &sum;
+            move |_: &()| { sum.fetch_add(value, Ordering::Relaxed); }
+        };
+
+        ParArray::new([1, 2, 4, 8, 16].map(add)).run(&());
+        assert_eq!(sum.swap(0, Ordering::Relaxed), 31);
+
+        ParArray::<fn(&()), 0>::new([]).run(&());
+
+        let mut tree = crate::par_all!(add(1), add(2), add(4), |_: &()| {}, add(8), add(16), add(32));
+        tree.run(&());
+        assert_eq!(sum.load(Ordering::Relaxed), 63);
     }
 }
@@ -0,0 +1,298 @@
+use crate::Executable;
+use crate::interlock::TaskId;
+use crate::interlock::builder::InterlockBuilder;
+use std::any::{self, Any, TypeId};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+/**
+ Values of different types, one per type, for tasks that mutate them, e.g. the physics and render state of a game.
+ Tasks added with `InterlockBuilder::add_with_resources` declare which types they read and write with an `Access`,
+ the executor schedules them by these declarations like any other resource, and the tasks borrow exactly the
+ declared values through `Borrows`. Tasks that never conflict never contend for a value, a borrow that does means
+ the task borrowed the value twice itself and panics instead of blocking.
+*/
+#[derive(Default)]
+pub struct Resources {
+    values: HashMap<TypeId, Value>
+}
+
+struct Value {
+    name: &'static str,
+    value: RwLock<Box<dyn Any + Send + Sync>>
+}
+
+impl Resources {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts `value`, returns the previous value of its type.
+    pub fn insert<R: Send + Sync + 'static>(&mut self, value: R) -> Option<R> {
+        let value = Value { name: any::type_name::<R>(), value: RwLock::new(Box::new(value)) };
+        self.values.insert(TypeId::of::<R>(), value).map(Value::into_inner)
+    }
+
+    pub fn remove<R: 'static>(&mut self) -> Option<R> {
+        self.values.remove(&TypeId::of::<R>()).map(Value::into_inner)
+    }
+
+    pub fn contains<R: 'static>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<R>())
+    }
+
+    /// Returns the value of type `R` between runs, e.g. to read the results of the last one.
+    pub fn get_mut<R: 'static>(&mut self) -> Option<&mut R> {
+        let value = self.values.get_mut(&TypeId::of::<R>())?.value.get_mut().unwrap_or_else(|e| e.into_inner());
+        value.downcast_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    fn value<R: 'static>(&self) -> &Value {
+        self.values.get(&TypeId::of::<R>()).unwrap_or_else(|| panic!("can't borrow resource {}, it wasn't inserted", any::type_name::<R>()))
+    }
+}
+
+impl Value {
+
+    fn into_inner<R: 'static>(self) -> R {
+        let value = self.value.into_inner().unwrap_or_else(|e| e.into_inner());
+        *value.downcast().expect("values are stored under their type")
+    }
+}
+
+impl Debug for Resources {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.values.values().map(|value| value.name)).finish()
+    }
+}
+
+/// Types of resources a task reads and writes, see `InterlockBuilder::add_with_resources`.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Access {
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>
+}
+
+impl Access {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read<R: 'static>(mut self) -> Self {
+        self.reads.push(TypeId::of::<R>());
+        self
+    }
+
+    /// Declares a write of `R`, which allows reading it as well.
+    pub fn write<R: 'static>(mut self) -> Self {
+        self.writes.push(TypeId::of::<R>());
+        self
+    }
+
+    pub fn reads(&self) -> &[TypeId] {
+        &self.reads
+    }
+
+    pub fn writes(&self) -> &[TypeId] {
+        &self.writes
+    }
+}
+
+/// Resources a task declared, see `Access`.
+pub struct Borrows<'a> {
+    resources: &'a Resources,
+    access: &'a Access
+}
+
+impl<'a> Borrows<'a> {
+
+    /**
+     Borrows the value of type `R`, panics if the task didn't declare reading or writing it.
+     A value a task panicked while writing is borrowed as the task left it, see `write`.
+    */
+    pub fn read<R: 'static>(&self) -> Res<'a, R> {
+        let id = TypeId::of::<R>();
+        if !self.access.reads.contains(&id) && !self.access.writes.contains(&id) {
+            panic!("can't read resource {}, the task didn't declare it", any::type_name::<R>());
+        }
+
+        let guard = match self.resources.value::<R>().value.try_read() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("can't read resource {}, the task writes it already", any::type_name::<R>())
+        };
+
+        Res { guard, value: PhantomData }
+    }
+
+    /**
+     Borrows the value of type `R` mutably, panics if the task didn't declare writing it.
+     If a task panics while it writes the value, the value keeps what the task wrote so far: borrows ignore
+     the poisoned lock on purpose, since the panic already fails the run or goes to the supervision of the task,
+     and a later run shouldn't fail for it again. Make the task `InterlockBuilder::transactional` over `TypeId::of::<R>()`
+     to put the value back instead.
+    */
+    pub fn write<R: 'static>(&self) -> ResMut<'a, R> {
+        if !self.access.writes.contains(&TypeId::of::<R>()) {
+            panic!("can't write resource {}, the task didn't declare it", any::type_name::<R>());
+        }
+
+        let guard = match self.resources.value::<R>().value.try_write() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("can't write resource {}, the task borrows it already", any::type_name::<R>())
+        };
+
+        ResMut { guard, value: PhantomData }
+    }
+}
+
+/// Borrowed resource, see `Borrows::read`.
+pub struct Res<'a, R> {
+    guard: RwLockReadGuard<'a, Box<dyn Any + Send + Sync>>,
+    value: PhantomData<&'a R>
+}
+
+impl<'a, R: 'static> Deref for Res<'a, R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        self.guard.downcast_ref().expect("values are stored under their type")
+    }
+}
+
+/// Mutably borrowed resource, see `Borrows::write`.
+pub struct ResMut<'a, R> {
+    guard: RwLockWriteGuard<'a, Box<dyn Any + Send + Sync>>,
+    value: PhantomData<&'a mut R>
+}
+
+impl<'a, R: 'static> Deref for ResMut<'a, R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        self.guard.downcast_ref().expect("values are stored under their type")
+    }
+}
+
+impl<'a, R: 'static> DerefMut for ResMut<'a, R> {
+
+    fn deref_mut(&mut self) -> &mut R {
+        self.guard.downcast_mut().expect("values are stored under their type")
+    }
+}
+
+/// Task borrowing the resources it declared, see `InterlockBuilder::add_with_resources`.
+struct Accessing<F> {
+    access: Access,
+    task: F
+}
+
+impl<F: FnMut(&Borrows<'_>)> Executable<Resources> for Accessing<F> {
+
+    fn run(&mut self, data: &Resources) {
+        (self.task)(&Borrows { resources: data, access: &self.access })
+    }
+}
+
+impl<'task, N: Eq + Hash + Clone> InterlockBuilder<'task, Resources, TypeId, N> {
+
+    /**
+     Adds a task reading and writing the resources declared by `access`, it conflicts with the other tasks
+     accessing them like with any other reads and writes. Panics when the task borrows one it didn't declare.
+    */
+    pub fn add_with_resources<D: Borrow<TaskId>>(&mut self,
+                                                 access: Access,
+                                                 task: impl FnMut(&Borrows<'_>) + Send + 'task,
+                                                 deps: impl IntoIterator<Item=D>) -> TaskId {
+        let (reads, writes) = (access.reads.clone(), access.writes.clone());
+        self.add(Accessing { access, task }, reads, writes, deps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interlock::builder;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(Vec<f32>);
+
+    struct Velocity(Vec<f32>);
+
+    #[test]
+    fn resources() {
+        let mut resources = Resources::new();
+        resources.insert(Position(vec![0.0, 1.0]));
+        resources.insert(Velocity(vec![1.0, -1.0]));
+        resources.insert(0usize);
+
+        let mut graph = builder();
+        let integrate = graph.add_with_resources(Access::new().read::<Velocity>().write::<Position>(), |res: &Borrows<'_>| {
+            let velocity = res.read::<Velocity>();
+            for (position, velocity) in res.write::<Position>().0.iter_mut().zip(&velocity.0) {
+                *position += velocity;
+            }
+        }, None::<TaskId>);
+
+        graph.add_with_resources(Access::new().read::<Position>().write::<usize>(), |res: &Borrows<'_>| {
+            *res.write::<usize>() += res.read::<Position>().0.len();
+        }, [integrate]);
+
+        let mut exec = graph.build();
+        exec.run(&resources);
+        exec.run(&resources);
+
+        assert_eq!(resources.get_mut::<Position>(), Some(&mut Position(vec![2.0, -1.0])));
+        assert_eq!(resources.remove::<usize>(), Some(4));
+        assert!(!resources.contains::<usize>());
+        assert_eq!(resources.insert(Position(vec![])), Some(Position(vec![2.0, -1.0])));
+        assert_eq!(resources.len(), 2);
+    }
+
+    #[test]
+    fn resources_poisoned() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let mut resources = Resources::new();
+        resources.insert(Position(vec![]));
+
+        let mut task = Accessing { access: Access::new().write::<Position>(), task: |res: &Borrows<'_>| {
+            let mut position = res.write::<Position>();
+            position.0.push(1.0);
+            assert!(position.0.len() < 2, "crashed");
+        }};
+
+        task.run(&resources);
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| task.run(&resources))).is_err());
+
+        let access = Access::new().read::<Position>();
+        (Accessing { access, task: |res: &Borrows<'_>| assert_eq!(res.read::<Position>().0, [1.0, 1.0]) }).run(&resources);
+    }
+
+    #[test]
+    #[should_panic(expected = "didn't declare it")]
+    fn resources_undeclared() {
+        let mut resources = Resources::new();
+        resources.insert(Position(vec![]));
+
+        let access = Access::new().read::<Position>();
+        (Accessing { access, task: |res: &Borrows<'_>| res.write::<Position>().0.push(1.0) }).run(&resources);
+    }
+}
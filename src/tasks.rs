@@ -0,0 +1,715 @@
+use crate::{Executable, SplittableExecutable};
+use rayon::iter::ParallelIterator;
+use std::cell::RefCell;
+use std::hint;
+use std::io::Read;
+use std::ops::Range;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/**
+ Runs a task that is also reachable from outside the graph, e.g. a stateful system that UI or inspection
+ code pokes between runs. The task is locked for the duration of every run.
+*/
+pub struct Shared<E> {
+    task: Arc<Mutex<E>>
+}
+
+impl<E> Shared<E> {
+
+    pub fn new(task: Arc<Mutex<E>>) -> Self {
+        Self { task }
+    }
+
+    /// Returns the shared task, e.g. to keep a handle for outside code.
+    pub fn get(&self) -> &Arc<Mutex<E>> {
+        &self.task
+    }
+}
+
+impl<T, E: Executable<T>> Executable<T> for Shared<E> {
+
+    fn run(&mut self, data: &T) {
+        self.task.lock().expect("shared task was poisoned").run(data)
+    }
+}
+
+/**
+ Single threaded version of `Shared`, for `Rc<RefCell<E>>` tasks.
+ It is not `Send`, so it can only run in sequential chains, not in parallel ones or in interlock graphs.
+*/
+pub struct SharedLocal<E> {
+    task: Rc<RefCell<E>>
+}
+
+impl<E> SharedLocal<E> {
+
+    pub fn new(task: Rc<RefCell<E>>) -> Self {
+        Self { task }
+    }
+
+    pub fn get(&self) -> &Rc<RefCell<E>> {
+        &self.task
+    }
+}
+
+impl<T, E: Executable<T>> Executable<T> for SharedLocal<E> {
+
+    fn run(&mut self, data: &T) {
+        self.task.borrow_mut().run(data)
+    }
+}
+
+/**
+ Task with private state: the state is created by `init` on the first run and then passed to `task`
+ alongside the run data on every run. Keeping the state out of the closure keeps the closure itself
+ stateless, so the same closure can back several tasks or replace an older version of the task.
+*/
+pub struct Local<S, I, F> {
+    state: Option<S>,
+    init: I,
+    task: F
+}
+
+impl<S, I: Fn() -> S, F> Local<S, I, F> {
+
+    pub fn new(init: I, task: F) -> Self {
+        Self { state: None, init, task }
+    }
+
+    /// Returns the state, if the task already ran.
+    pub fn state(&self) -> Option<&S> {
+        self.state.as_ref()
+    }
+
+    /// Replaces the task and keeps the state, e.g. to swap in a reloaded version of the task.
+    pub fn replace<G>(self, task: G) -> Local<S, I, G> {
+        Local { state: self.state, init: self.init, task }
+    }
+
+    /// Creates the same task without state, e.g. for every copy of a duplicated executor.
+    pub fn fresh(&self) -> Self where I: Clone, F: Clone {
+        Self::new(self.init.clone(), self.task.clone())
+    }
+}
+
+impl<T, S, I: Fn() -> S, F: FnMut(&mut S, &T)> Executable<T> for Local<S, I, F> {
+
+    fn run(&mut self, data: &T) {
+        let state = self.state.get_or_insert_with(&self.init);
+        (self.task)(state, data)
+    }
+}
+
+/// Runs a `SplittableExecutable` task, splitting its work among idle workers of the current pool.
+pub struct Splitting<E> {
+    task: E
+}
+
+impl<E> Splitting<E> {
+
+    pub fn new(task: E) -> Self {
+        Self { task }
+    }
+
+    pub fn get(&self) -> &E {
+        &self.task
+    }
+}
+
+impl<T: Sync, E: SplittableExecutable<T> + Sync> Executable<T> for Splitting<E> {
+
+    fn run(&mut self, data: &T) {
+        let work = self.task.work(data);
+        let task = &self.task;
+
+        rayon::iter::split(work, |work| task.split(work)).for_each(|work| task.execute(data, work))
+    }
+}
+
+/**
+ Splittable task over the indices `0..len(data)`, e.g. rows of an image.
+ Ranges are split until they are shorter than twice the minimum length, see `min_len`.
+*/
+pub struct SplitRange<L, F> {
+    len: L,
+    body: F,
+    min: usize
+}
+
+impl<L, F> SplitRange<L, F> {
+
+    pub fn new(len: L, body: F) -> Self {
+        Self { len, body, min: 1 }
+    }
+
+    /// Keeps ranges at least `min` long, so the cost of a split doesn't outweigh the work of a range.
+    pub fn min_len(mut self, min: usize) -> Self {
+        self.min = min.max(1);
+        self
+    }
+}
+
+impl<T, L: FnMut(&T) -> usize, F: Fn(&T, Range<usize>)> SplittableExecutable<T> for SplitRange<L, F> {
+    type Work = Range<usize>;
+
+    fn work(&mut self, data: &T) -> Range<usize> {
+        0..(self.len)(data)
+    }
+
+    fn split(&self, work: Range<usize>) -> (Range<usize>, Option<Range<usize>>) {
+        if work.len() < self.min * 2 {
+            return (work, None);
+        }
+
+        let mid = work.start + work.len() / 2;
+        (work.start..mid, Some(mid..work.end))
+    }
+
+    fn execute(&self, data: &T, work: Range<usize>) {
+        (self.body)(data, work)
+    }
+}
+
+/// Exit status and standard output of the last execution of a `CommandTask`.
+#[derive(Clone, Debug)]
+pub struct CommandOutput {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    /// The process was killed through `CommandHandle::kill`.
+    pub killed: bool
+}
+
+impl CommandOutput {
+
+    pub fn success(&self) -> bool {
+        !self.killed && self.status.success()
+    }
+}
+
+#[derive(Default)]
+struct CommandState {
+    child: Option<Child>,
+    killed: bool,
+    output: Option<CommandOutput>
+}
+
+/// Handle of a `CommandTask` reachable from outside the graph, e.g. to cancel a run or to report the output.
+#[derive(Clone, Default)]
+pub struct CommandHandle {
+    state: Arc<Mutex<CommandState>>
+}
+
+impl CommandHandle {
+
+    fn state(&self) -> MutexGuard<'_, CommandState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Kills the process of the current execution, returns false if no process is running.
+    pub fn kill(&self) -> bool {
+        let mut state = self.state();
+        let killed = state.child.as_mut().is_some_and(|child| child.kill().is_ok());
+
+        state.killed |= killed;
+        killed
+    }
+
+    /// Returns the output of the last finished execution.
+    pub fn output(&self) -> Option<CommandOutput> {
+        self.state().output.clone()
+    }
+}
+
+/**
+ Runs an external process, e.g. a code generator of a build pipeline. `command` creates the command for every
+ run, its standard output is captured and its exit status recorded, see `CommandHandle::output`.
+ Panics if the process can't be started, and once `checked` also if it fails or is killed.
+*/
+pub struct CommandTask<F> {
+    command: F,
+    checked: bool,
+    handle: CommandHandle
+}
+
+impl<F> CommandTask<F> {
+
+    pub fn new(command: F) -> Self {
+        Self { command, checked: false, handle: CommandHandle::default() }
+    }
+
+    /// Fails the run if the process exits unsuccessfully, so dependants never see outputs of a failed process.
+    pub fn checked(mut self) -> Self {
+        self.checked = true;
+        self
+    }
+
+    pub fn handle(&self) -> &CommandHandle {
+        &self.handle
+    }
+}
+
+impl<T, F: FnMut(&T) -> Command> Executable<T> for CommandTask<F> {
+
+    fn run(&mut self, data: &T) {
+        let mut child = (self.command)(data)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap_or_else(|err| panic!("failed to start the command: {}", err));
+
+        //the child is reachable for kill while its output is read
+        let mut pipe = child.stdout.take().unwrap();
+        {
+            let mut state = self.handle.state();
+            state.child = Some(child);
+            state.killed = false;
+        }
+
+        let mut stdout = Vec::new();
+        pipe.read_to_end(&mut stdout).expect("failed to read the output of the command");
+
+        let mut state = self.handle.state();
+        let status = state.child.take().unwrap().wait().expect("failed to wait for the command");
+        let output = CommandOutput { status, stdout, killed: state.killed };
+
+        let success = output.success();
+        state.output = Some(output);
+        drop(state);
+
+        if self.checked && !success {
+            panic!("command failed: {}", status);
+        }
+    }
+}
+
+/// Task doing nothing, e.g. a join point other tasks depend on instead of depending on a whole group of tasks.
+#[derive(Clone, Copy, Eq, PartialEq, Default, Debug)]
+pub struct Noop;
+
+impl<T> Executable<T> for Noop {
+
+    fn run(&mut self, _: &T) {}
+}
+
+/**
+ Task blocking its thread for at least a duration on every run, e.g. dummy work in tests.
+ The thread is free for the OS while it sleeps, which may wake it up late, see `Busy` to finish on time.
+*/
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Sleep {
+    duration: Duration
+}
+
+impl Sleep {
+
+    pub fn new(duration: Duration) -> Self {
+        Self { duration }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+impl<T> Executable<T> for Sleep {
+
+    fn run(&mut self, _: &T) {
+        //some platform timers wake up slightly early, sleep for the rest
+        let end = Instant::now() + self.duration;
+        let mut now = Instant::now();
+
+        while now < end {
+            thread::sleep(end - now);
+            now = Instant::now();
+        }
+    }
+}
+
+/**
+ Task spinning on its thread for a duration on every run, e.g. to simulate work in benchmarks.
+ Keeps the thread busy like real work does, so it finishes close to the duration even with coarse OS timers.
+*/
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Busy {
+    duration: Duration
+}
+
+impl Busy {
+
+    pub fn new(duration: Duration) -> Self {
+        Self { duration }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+impl<T> Executable<T> for Busy {
+
+    fn run(&mut self, _: &T) {
+        let start = Instant::now();
+
+        while start.elapsed() < self.duration {
+            hint::spin_loop();
+        }
+    }
+}
+
+/**
+ Task doing nothing that names a point of the graph, e.g. the end of a phase. Added with
+ `InterlockBuilder::add_marker` it is labeled by its name, so it shows up in graph exports and timelines,
+ and accesses no resources, so it only delays its dependants until its dependencies finished.
+*/
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Marker<N = String> {
+    name: N
+}
+
+impl<N> Marker<N> {
+
+    pub fn new(name: N) -> Self {
+        Self { name }
+    }
+
+    pub fn name(&self) -> &N {
+        &self.name
+    }
+
+    pub fn into_name(self) -> N {
+        self.name
+    }
+}
+
+impl<T, N> Executable<T> for Marker<N> {
+
+    fn run(&mut self, _: &T) {}
+}
+
+/**
+ Tuple data that gives out its component `I`, so a task can be restricted to a single component,
+ see `Component` and `PerComponent`. Implemented for tuples of up to 6 components.
+*/
+pub trait Project<const I: usize> {
+    type Output;
+
+    fn project(&self) -> &Self::Output;
+}
+
+/// Runs a task over component `I` of tuple data, e.g. a task of `B` as a task of `(A, B, C)`.
+pub struct Component<E, const I: usize> {
+    task: E
+}
+
+impl<E, const I: usize> Component<E, I> {
+
+    pub fn new(task: E) -> Self {
+        Self { task }
+    }
+
+    pub fn get(&self) -> &E {
+        &self.task
+    }
+}
+
+impl<T: Project<I>, E: Executable<T::Output>, const I: usize> Executable<T> for Component<E, I> {
+
+    fn run(&mut self, data: &T) {
+        self.task.run(data.project())
+    }
+}
+
+/**
+ Runs a tuple of tasks in parallel over tuple data, the task at each position receiving only the component at
+ the same position. Each task can only reach its own component, so the tasks never need to be declared as
+ disjoint, e.g. `per_component((physics, audio))` for data `(World, Mixer)`.
+*/
+pub struct PerComponent<Q> {
+    tasks: Q
+}
+
+impl<Q> PerComponent<Q> {
+
+    pub fn new(tasks: Q) -> Self {
+        Self { tasks }
+    }
+
+    pub fn get(&self) -> &Q {
+        &self.tasks
+    }
+}
+
+macro_rules! tuple {
+    (@project [$($all:ident),+]) => {};
+    (@project [$($all:ident),+] ($idx:tt, $name:ident) $($rest:tt)*) => {
+        impl<$($all),+> Project<$idx> for ($($all,)+) {
+            type Output = $name;
+
+            fn project(&self) -> &$name {
+                &self.$idx
+            }
+        }
+
+        tuple!(@project [$($all),+] $($rest)*);
+    };
+    ($(($idx:tt, $name:ident, $task:ident)),+) => {
+        tuple!(@project [$($name),+] $(($idx, $name))+);
+
+        impl<$($name: Sync,)+ $($task: Executable<$name> + Send),+> Executable<($($name,)+)> for PerComponent<($($task,)+)> {
+
+            fn run(&mut self, data: &($($name,)+)) {
+                rayon::scope(|scope| {
+                    $(
+                        let (task, component) = (&mut self.tasks.$idx, &data.$idx);
+                        scope.spawn(move |_| task.run(component));
+                    )+
+                });
+            }
+        }
+    };
+}
+
+tuple!((0, A, EA));
+tuple!((0, A, EA), (1, B, EB));
+tuple!((0, A, EA), (1, B, EB), (2, C, EC));
+tuple!((0, A, EA), (1, B, EB), (2, C, EC), (3, D, ED));
+tuple!((0, A, EA), (1, B, EB), (2, C, EC), (3, D, ED), (4, E, EE));
+tuple!((0, A, EA), (1, B, EB), (2, C, EC), (3, D, ED), (4, E, EE), (5, F, EF));
+
+pub fn local<S, I: Fn() -> S, F>(init: I, task: F) -> Local<S, I, F> {
+    Local::new(init, task)
+}
+
+pub fn shared<E>(task: &Arc<Mutex<E>>) -> Shared<E> {
+    Shared::new(task.clone())
+}
+
+pub fn shared_local<E>(task: &Rc<RefCell<E>>) -> SharedLocal<E> {
+    SharedLocal::new(task.clone())
+}
+
+pub fn splitting<E>(task: E) -> Splitting<E> {
+    Splitting::new(task)
+}
+
+/// Splittable task over the indices `0..len(data)`, see `SplitRange`.
+pub fn split_range<L, F>(len: L, body: F) -> Splitting<SplitRange<L, F>> {
+    Splitting::new(SplitRange::new(len, body))
+}
+
+pub fn command<F>(command: F) -> CommandTask<F> {
+    CommandTask::new(command)
+}
+
+pub fn noop() -> Noop {
+    Noop
+}
+
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep::new(duration)
+}
+
+pub fn busy(duration: Duration) -> Busy {
+    Busy::new(duration)
+}
+
+pub fn marker<N>(name: N) -> Marker<N> {
+    Marker::new(name)
+}
+
+/// Runs `task` over component `I` of tuple data, e.g. `component::<1, _>(task)`.
+pub fn component<const I: usize, E>(task: E) -> Component<E, I> {
+    Component::new(task)
+}
+
+/// Runs a tuple of tasks in parallel, each over the component of the data at its position, see `PerComponent`.
+pub fn per_component<Q>(tasks: Q) -> PerComponent<Q> {
+    PerComponent::new(tasks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interlock;
+    use crate::seq;
+
+    struct Counter(usize);
+
+    impl Executable<usize> for Counter {
+
+        fn run(&mut self, data: &usize) {
+            self.0 += data;
+        }
+    }
+
+    #[test]
+    fn shared_system() {
+        let counter = Arc::new(Mutex::new(Counter(0)));
+
+        let mut builder = interlock::builder::<usize, u32>();
+        builder.add(shared(&counter), [], [0], &[]);
+        builder.add(shared(&counter), [], [0], &[]);
+
+        let mut exec = builder.build();
+        exec.run(&2);
+
+        counter.lock().unwrap().0 *= 10;
+        exec.run(&1);
+
+        assert_eq!(counter.lock().unwrap().0, 42);
+    }
+
+    #[test]
+    fn local_state() {
+        let template = local(|| 0, |runs: &mut usize, log: &Mutex<Vec<usize>>| {
+            *runs += 1;
+            log.lock().unwrap().push(*runs);
+        });
+
+        let mut builder = interlock::builder::<Mutex<Vec<usize>>, u32>();
+        builder.add_factory(move || template.fresh(), [], [0], &[]);
+
+        let mut exec = builder.build();
+        let mut copy = exec.duplicate().unwrap();
+        let (log, copy_log) = (Mutex::new(Vec::new()), Mutex::new(Vec::new()));
+
+        exec.run(&log);
+        exec.run(&log);
+        copy.run(&copy_log);
+
+        assert_eq!(log.into_inner().unwrap(), vec![1, 2]);
+        assert_eq!(copy_log.into_inner().unwrap(), vec![1], "copies must not share state");
+
+        let mut task = local(|| 10, |sum: &mut usize, data: &usize| *sum += data);
+        assert!(task.state().is_none(), "state must be created lazily");
+
+        task.run(&1);
+        let mut task = task.replace(|sum: &mut usize, data: &usize| *sum *= data);
+        task.run(&4);
+
+        assert_eq!(task.state(), Some(&44));
+    }
+
+    #[test]
+    fn shared_local_system() {
+        let counter = Rc::new(RefCell::new(Counter(0)));
+        let mut chain = seq(shared_local(&counter), shared_local(&counter));
+
+        chain.run(&3);
+        assert_eq!(counter.borrow().0, 6);
+    }
+
+    #[test]
+    fn split_system() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Image {
+            rows: Vec<AtomicUsize>,
+            parts: AtomicUsize
+        }
+
+        let filter = split_range(|image: &Image| image.rows.len(), |image: &Image, rows: Range<usize>| {
+            rows.for_each(|row| { image.rows[row].fetch_add(row, Ordering::Relaxed); });
+            image.parts.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let mut builder = interlock::builder::<Image, u32>();
+        builder.add(filter, [], [0], &[]);
+
+        let mut exec = builder.build();
+        let image = Image { rows: (0..1000).map(|_| AtomicUsize::new(0)).collect(), parts: AtomicUsize::new(0) };
+
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        pool.install(|| exec.run(&image));
+
+        assert!(image.rows.iter().enumerate().all(|(row, value)| value.load(Ordering::Relaxed) == row), "every row must be filtered once");
+        assert!(image.parts.load(Ordering::Relaxed) > 1, "idle workers must split the work");
+
+        let task = SplitRange::new(|_: &()| 10, |_: &(), _: Range<usize>| {}).min_len(4);
+        assert_eq!(SplittableExecutable::<()>::split(&task, 0..10), (0..5, Some(5..10)));
+        assert_eq!(SplittableExecutable::<()>::split(&task, 0..7), (0..7, None));
+    }
+
+    #[test]
+    fn components() {
+        let mut doubling = per_component((
+            |a: &Mutex<u32>| *a.lock().unwrap() *= 2,
+            |_: &String| {},
+            component::<1, _>(|b: &Mutex<i64>| *b.lock().unwrap() -= 1)
+        ));
+
+        let data = (Mutex::new(21), String::from("unchanged"), (Mutex::new(0u8), Mutex::new(1)));
+        doubling.run(&data);
+
+        assert_eq!(*data.0.lock().unwrap(), 42);
+        assert_eq!(*data.2.1.lock().unwrap(), 0);
+        assert_eq!(*data.2.0.lock().unwrap(), 0, "components other than the declared one stay untouched");
+
+        let mut builder = interlock::builder::<(Mutex<u32>, u32), u32>();
+        builder.add(component::<0, _>(|a: &Mutex<u32>| *a.lock().unwrap() += 1), [], [0], &[]);
+        let data = (Mutex::new(0), 0);
+        builder.build().run(&data);
+        assert_eq!(*data.0.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn timed() {
+        let duration = Duration::from_millis(5);
+
+        for mut task in [Box::new(sleep(duration)) as Box<dyn Executable<()> + Send>, Box::new(busy(duration))] {
+            let start = Instant::now();
+            task.run(&());
+            assert!(start.elapsed() >= duration);
+        }
+
+        let mut builder = interlock::builder::<(), u32>();
+        builder.add(busy(duration), [], [0], &[]);
+        builder.add(sleep(duration), [], [0], &[]);
+
+        let start = Instant::now();
+        builder.build().run(&());
+        assert!(start.elapsed() >= duration * 2, "both tasks write the same resource");
+    }
+
+    #[test]
+    fn command_system() {
+        use std::panic::{self, AssertUnwindSafe};
+        use std::time::{Duration, Instant};
+
+        let shell = |script: &'static str| move |_: &()| {
+            let mut command = Command::new("sh");
+            command.args(["-c", script]);
+            command
+        };
+
+        let mut echo = command(shell("echo generated"));
+        echo.run(&());
+
+        let output = echo.handle().output().unwrap();
+        assert!(output.success());
+        assert_eq!(output.stdout, b"generated\n");
+
+        let mut failing = command(shell("exit 3")).checked();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| failing.run(&())));
+        assert!(result.is_err(), "checked commands must fail the run");
+        assert_eq!(failing.handle().output().unwrap().status.code(), Some(3));
+
+        let mut sleeping = command(shell("exec sleep 10"));
+        let handle = sleeping.handle().clone();
+        assert!(!handle.kill(), "nothing is running yet");
+
+        let start = Instant::now();
+        std::thread::scope(|scope| {
+            scope.spawn(|| sleeping.run(&()));
+            while !handle.kill() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        });
+
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(handle.output().unwrap().killed);
+    }
+}
@@ -0,0 +1,199 @@
+use super::TimelineEvent;
+use std::collections::HashMap;
+use std::collections::hash_map;
+use std::hash::Hash;
+use std::iter::FromIterator;
+use std::time::{Duration, Instant};
+
+/// Durations of the tasks of a name, see `TimelineAccumulator::stats`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct NameStats {
+    pub count: u64,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    /// Duration of the task that ended last.
+    pub last: Duration
+}
+
+impl NameStats {
+
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::default(),
+            count => Duration::from_nanos((self.total.as_nanos() / count as u128) as u64)
+        }
+    }
+
+    fn add(&mut self, duration: Duration) {
+        self.min = if self.count == 0 { duration } else { self.min.min(duration) };
+        self.max = self.max.max(duration);
+        self.total += duration;
+        self.last = duration;
+        self.count += 1;
+    }
+}
+
+/**
+ Statistics of a timeline updated event by event, e.g. for a server recording for days where keeping every task like
+ `TimelineAnalyzer` does isn't an option. Memory grows with the number of names only. Events that don't pair up,
+ e.g. the end of a task that started before the accumulator, are counted rather than failing like in an analyzer.
+*/
+#[derive(Clone, Debug)]
+pub struct TimelineAccumulator<N> {
+    pending: HashMap<N, Instant>,
+    names: HashMap<N, NameStats>,
+    busy: Duration,
+    //earliest and latest event
+    first: Option<Instant>,
+    last: Option<Instant>,
+    max_concurrency: usize,
+    unmatched: u64
+}
+
+impl<N: Eq + Hash> TimelineAccumulator<N> {
+
+    pub fn new() -> Self {
+        Self {
+            pending: HashMap::new(),
+            names: HashMap::new(),
+            busy: Duration::default(),
+            first: None,
+            last: None,
+            max_concurrency: 0,
+            unmatched: 0
+        }
+    }
+
+    pub fn push(&mut self, event: TimelineEvent<N>) {
+        let time = event.time();
+        self.first = Some(self.first.map_or(time, |first| first.min(time)));
+        self.last = Some(self.last.map_or(time, |last| last.max(time)));
+
+        match event {
+            TimelineEvent::Start(name, start) => {
+                //the earlier start never ends as far as the accumulator knows
+                if self.pending.insert(name, start).is_some() {
+                    self.unmatched += 1;
+                }
+
+                self.max_concurrency = self.max_concurrency.max(self.pending.len());
+            },
+
+            TimelineEvent::End(name, end) => match self.pending.remove(&name) {
+                Some(start) => {
+                    let duration = end.saturating_duration_since(start);
+                    self.busy += duration;
+                    self.names.entry(name).or_default().add(duration);
+                },
+
+                None => self.unmatched += 1
+            }
+        }
+    }
+
+    /// Returns the durations of the tasks named `name` that ended so far.
+    pub fn stats(&self, name: &N) -> Option<NameStats> {
+        self.names.get(name).copied()
+    }
+
+    /// Iterates over the names of the tasks that ended so far with their durations, in no particular order.
+    pub fn iter(&self) -> hash_map::Iter<'_, N, NameStats> {
+        self.names.iter()
+    }
+
+    /// Returns how many tasks started but didn't end yet.
+    pub fn concurrency(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns the most tasks that ran at the same time.
+    pub fn max_concurrency(&self) -> usize {
+        self.max_concurrency
+    }
+
+    /// Returns how many tasks ran at the same time on average, the total duration of the tasks over the time recorded.
+    pub fn mean_concurrency(&self) -> f64 {
+        match self.elapsed().as_secs_f64() {
+            elapsed if elapsed > 0.0 => self.busy.as_secs_f64() / elapsed,
+            _ => 0.0
+        }
+    }
+
+    /// Returns the time from the earliest to the latest event.
+    pub fn elapsed(&self) -> Duration {
+        self.first.zip(self.last).map_or(Duration::default(), |(first, last)| last - first)
+    }
+
+    /// Returns the total duration of all tasks that ended, like `TimelineAnalyzer::serial_len`.
+    pub fn busy(&self) -> Duration {
+        self.busy
+    }
+
+    /// Returns how many events didn't pair up: ends without a start and starts replaced by another start of their name.
+    pub fn unmatched(&self) -> u64 {
+        self.unmatched
+    }
+}
+
+impl<N: Eq + Hash> Default for TimelineAccumulator<N> {
+
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: Eq + Hash> Extend<TimelineEvent<N>> for TimelineAccumulator<N> {
+
+    fn extend<T: IntoIterator<Item=TimelineEvent<N>>>(&mut self, iter: T) {
+        iter.into_iter().for_each(|event| self.push(event));
+    }
+}
+
+impl<N: Eq + Hash> FromIterator<TimelineEvent<N>> for TimelineAccumulator<N> {
+
+    fn from_iter<T: IntoIterator<Item=TimelineEvent<N>>>(iter: T) -> Self {
+        let mut accumulator = Self::new();
+        accumulator.extend(iter);
+        accumulator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::TimelineEvent::{End, Start};
+    use std::ops::Add;
+
+    #[test]
+    fn accumulate() {
+        let now = Instant::now();
+        let instant = |t| now.add(Duration::from_millis(t));
+
+        let mut accumulator: TimelineAccumulator<_> = vec![
+            End     ("x", instant(0)),
+            Start   ("a", instant(0)),
+            Start   ("b", instant(0)),
+            End     ("b", instant(5)),
+            Start   ("c", instant(5)),
+            End     ("a", instant(10)),
+        ].into_iter().collect();
+
+        assert_eq!(accumulator.concurrency(), 1);
+        assert_eq!(accumulator.max_concurrency(), 2);
+        assert_eq!(accumulator.unmatched(), 1, "x ended before the accumulator saw it start");
+
+        accumulator.extend(vec![End("c", instant(15)), Start("a", instant(20)), End("a", instant(40))]);
+
+        let a = accumulator.stats(&"a").unwrap();
+        assert_eq!((a.count, a.min, a.max, a.last), (2, Duration::from_millis(10), Duration::from_millis(20), Duration::from_millis(20)));
+        assert_eq!(a.mean(), Duration::from_millis(15));
+        assert_eq!(accumulator.stats(&"x"), None);
+        assert_eq!(accumulator.iter().count(), 3);
+
+        assert_eq!(accumulator.concurrency(), 0);
+        assert_eq!(accumulator.busy(), Duration::from_millis(45));
+        assert_eq!(accumulator.elapsed(), Duration::from_millis(40));
+        assert!((accumulator.mean_concurrency() - 45.0 / 40.0).abs() < 1e-9);
+    }
+}
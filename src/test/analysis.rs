@@ -1,8 +1,45 @@
+use rayon::prelude::*;
 use std::time::Duration;
 use std::iter::FromIterator;
-use std::hash::Hash;
-use std::collections::HashMap;
-use super::TimelineEvent;
+use std::hash::{BuildHasher, Hash};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::collections::hash_map::RandomState;
+use std::fmt::{Debug, Display, Formatter};
+use std::fmt;
+use std::ops::{Add, Sub};
+use super::{EventTime, TimelineEvent};
+
+/**
+ Time on a timeline, a `Duration` for timelines recorded from `Instant`s or plain integer ticks,
+ e.g. nanoseconds of GPU timestamp queries or of an imported kernel trace.
+*/
+pub trait Timestamp: Copy + Ord + Default + Add<Output=Self> + Sub<Output=Self> {
+    /// Converts the time to a float, only ratios of converted times are used.
+    fn as_f64(self) -> f64;
+}
+
+impl Timestamp for Duration {
+
+    fn as_f64(self) -> f64 {
+        self.as_secs_f64()
+    }
+}
+
+macro_rules! integer_timestamp {
+    ($($t:ty),+) => {
+        $(
+            impl Timestamp for $t {
+
+                fn as_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )+
+    };
+}
+
+integer_timestamp!(u32, u64, u128, i64);
 
 #[derive(Eq, PartialEq, Copy, Clone, Hash, Debug)]
 pub enum TimelineOrder {
@@ -12,38 +49,61 @@ pub enum TimelineOrder {
 }
 
 #[derive(Eq, PartialEq, Clone, Hash, Debug)]
-pub struct TimelineTask<N> {
+pub struct TimelineTask<N, D = Duration> {
     name: N,
-    start: Duration,
-    length: Duration
+    start: D,
+    length: D,
+    thread: Option<usize>
 }
 
-impl<N> TimelineTask<N> {
+impl<N, D: Timestamp> TimelineTask<N, D> {
 
     pub fn new(name: N,
-               start: Duration,
-               length: Duration) -> Self {
-        Self { name, start, length }
+               start: D,
+               length: D) -> Self {
+        Self { name, start, length, thread: None }
+    }
+
+    /// Sets the index of the thread the task executed on, see `TimelineAnalyzer::recorded_threads`.
+    pub fn with_thread(mut self, thread: usize) -> Self {
+        self.thread = Some(thread);
+        self
     }
 
     pub fn name(&self) -> &N {
         &self.name
     }
 
-    pub fn start(&self) -> Duration {
+    pub fn start(&self) -> D {
         self.start
     }
 
-    pub fn end(&self) -> Duration {
+    pub fn end(&self) -> D {
         self.start + self.length
     }
 
-    pub fn len(&self) -> Duration {
+    pub fn len(&self) -> D {
         self.length
     }
 
+    pub fn thread(&self) -> Option<usize> {
+        self.thread
+    }
+
+    /// Returns where `task` is relative to this task, `Parallel` if they overlap at all.
     pub fn order_to(&self, task: &Self) -> TimelineOrder {
-        if task.start() < self.end() && self.start() < task.end() {
+        self.order_to_with_epsilon(task, D::default())
+    }
+
+    /**
+     Returns where `task` is relative to this task like `order_to`, but a task starting at most `epsilon` before
+     the other one ended doesn't make them `Parallel`, e.g. to allow for the resolution of the clock or for
+     timestamps taken on different threads.
+    */
+    pub fn order_to_with_epsilon(&self, task: &Self, epsilon: D) -> TimelineOrder {
+        //differences instead of adding epsilon, which could overflow integer timestamps
+        let overlaps = |a: &Self, b: &Self| a.start() < b.end() && b.end() - a.start() > epsilon;
+        if overlaps(task, self) && overlaps(self, task) {
             TimelineOrder::Parallel
         } else if task.start() > self.start() {
             TimelineOrder::After
@@ -51,16 +111,43 @@ impl<N> TimelineTask<N> {
             TimelineOrder::Before
         }
     }
+
+    /// Returns true if this task ended before `task` started, or exactly when it started.
+    pub fn ends_before(&self, task: &Self) -> bool {
+        self.end() <= task.start()
+    }
+
+    /// Returns true if this task started after `task` ended, or exactly when it ended.
+    pub fn starts_after(&self, task: &Self) -> bool {
+        task.ends_before(self)
+    }
 }
 
 #[derive(Clone, Debug)]
-pub struct TimelineAnalyzer<N> {
-    tasks: Vec<TimelineTask<N>>
+pub struct TimelineAnalyzer<N, D = Duration> {
+    tasks: Vec<TimelineTask<N, D>>
 }
 
-impl<N: PartialEq> TimelineAnalyzer<N> {
+impl<N, D: Timestamp> TimelineAnalyzer<N, D> {
+
+    /**
+     Creates the timeline of imported spans, each given by name, start and end time.
+     Times are shifted so the earliest span starts at zero, like timelines recorded with a `TimelineReader`,
+     spans ending before they start are imported with a zero duration.
+    */
+    pub fn from_spans(spans: impl IntoIterator<Item=(N, D, D)>) -> Self {
+        let spans: Vec<_> = spans.into_iter().collect();
+        let min = spans.iter().map(|(_, start, _)| *start).min().unwrap_or_default();
 
-    pub fn single<'a>(&'a self, name: &'a N) -> Option<&'a TimelineTask<N>> {
+        let mut tasks: Vec<_> = spans.into_iter().map(|(name, start, end)| TimelineTask::new(name, start - min, end.max(start) - start)).collect();
+        tasks.sort_by_key(TimelineTask::start);
+        Self { tasks }
+    }
+}
+
+impl<N: PartialEq, D: Timestamp> TimelineAnalyzer<N, D> {
+
+    pub fn single<'a>(&'a self, name: &'a N) -> Option<&'a TimelineTask<N, D>> {
         let mut iter = self.get(name);
         match iter.next() {
             Some(value) =>
@@ -73,11 +160,11 @@ impl<N: PartialEq> TimelineAnalyzer<N> {
         }
     }
 
-    pub fn first<'a>(&'a self, name: &'a N) -> Option<&'a TimelineTask<N>> {
+    pub fn first<'a>(&'a self, name: &'a N) -> Option<&'a TimelineTask<N, D>> {
         self.get(name).next()
     }
 
-    pub fn last<'a>(&'a self, name: &'a N) -> Option<&'a TimelineTask<N>> {
+    pub fn last<'a>(&'a self, name: &'a N) -> Option<&'a TimelineTask<N, D>> {
         self.get(name).last()
     }
 
@@ -89,57 +176,227 @@ impl<N: PartialEq> TimelineAnalyzer<N> {
         self.get(name).next().is_some()
     }
 
-    pub fn get<'a>(&'a self, name: &'a N) -> impl Iterator<Item=&TimelineTask<N>> + 'a {
+    pub fn get<'a>(&'a self, name: &'a N) -> impl Iterator<Item=&'a TimelineTask<N, D>> + 'a {
         self.iter().filter(move |t| &t.name == name)
     }
 
-    pub fn iter(&self) -> impl Iterator<Item=&TimelineTask<N>> + '_ {
+    pub fn iter(&self) -> impl Iterator<Item=&TimelineTask<N, D>> + '_ {
         self.tasks.iter()
     }
 
-    pub fn len(&self) -> Duration {
+    pub fn len(&self) -> D {
         self.iter()
             .map(|t| t.end())
             .max()
-            .unwrap_or(Duration::from_millis(0))
+            .unwrap_or_default()
     }
 
-    pub fn serial_len(&self) -> Duration {
+    pub fn serial_len(&self) -> D {
         self.iter()
             .map(|t| t.len())
-            .sum()
+            .fold(D::default(), |sum, len| sum + len)
     }
 
     pub fn efficiency(&self) -> f64 {
-        self.serial_len().as_secs_f64() / self.len().as_secs_f64()
+        self.serial_len().as_f64() / self.len().as_f64()
     }
 
+    /**
+     Returns the fewest threads that could have executed the timeline, the most tasks running at any one time.
+     Tasks ending exactly when others start can share a thread, and so can tasks of no length at the same time,
+     but one within another task needs a thread of its own. See `recorded_threads` for the threads actually used.
+    */
     pub fn threads(&self) -> usize {
-        let mut counter = Vec::new();
+        self.lanes().into_iter().max().map_or(0, |lane| lane + 1)
+    }
 
-        fn find_slot(counter: &Vec<Duration>, start: Duration) -> Option<usize> {
-            for (idx, end) in counter.iter().enumerate() {
-                if &start >= end {
-                    return Some(idx);
-                }
+    /// Returns how many threads the tasks executed on, `None` unless every task has its thread, see `TimelineTask::with_thread`.
+    pub fn recorded_threads(&self) -> Option<usize> {
+        self.iter()
+            .map(TimelineTask::thread)
+            .collect::<Option<HashSet<_>>>()
+            .map(|threads| threads.len())
+    }
+
+    /**
+     Returns the lane of every task, like the threads of `threads`. Tasks take the lowest lane free at their start,
+     in order of their start and, at the same start, of their end, so tasks of no length take a lane before the
+     tasks starting with them. That colors the overlaps with as few lanes as possible.
+    */
+    pub(crate) fn lanes(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.tasks.len()).collect();
+        order.sort_by_key(|&task| (self.tasks[task].start(), self.tasks[task].end()));
+
+        let mut lanes = vec![0; self.tasks.len()];
+        let mut busy: BinaryHeap<Reverse<(D, usize)>> = BinaryHeap::new();
+        let mut free: BinaryHeap<Reverse<usize>> = BinaryHeap::new();
+
+        for task in order {
+            let (start, end) = (self.tasks[task].start(), self.tasks[task].end());
+            while let Some(&Reverse((_, lane))) = busy.peek().filter(|Reverse((end, _))| *end <= start) {
+                busy.pop();
+                free.push(Reverse(lane));
             }
 
-            None
+            //no lane free, every lane in use is busy
+            let lane = free.pop().map_or(busy.len(), |Reverse(lane)| lane);
+            busy.push(Reverse((end, lane)));
+            lanes[task] = lane;
         }
 
+        lanes
+    }
+
+    /// Returns the time all tasks named `name` took together, zero if none executed.
+    pub fn total(&self, name: &N) -> D {
+        self.get(name).fold(D::default(), |sum, task| sum + task.len())
+    }
+
+    /**
+     Returns the Pearson correlation, from -1 to 1, between the time `a` and `b` took in each of `runs`, e.g. the
+     timelines of many frames taken with `InterlockExecutor::timeline`. Runs missing either task are left out.
+     Returns `None` with fewer than two runs left, or if either time never changes.
+    */
+    pub fn correlation(runs: &[Self], a: &N, b: &N) -> Option<f64> {
+        pearson(runs.iter().filter(|run| run.has(a) && run.has(b)).map(|run| (run.total(a).as_f64(), run.total(b).as_f64())))
+    }
+
+    /**
+     Returns the correlation between the time `name` took and the length of the run in each of `runs`, like
+     `correlation`: close to 1 if the task drives the variance of the runs, close to 0 if its variance doesn't matter.
+    */
+    pub fn makespan_correlation(runs: &[Self], name: &N) -> Option<f64> {
+        pearson(runs.iter().filter(|run| run.has(name)).map(|run| (run.total(name).as_f64(), run.len().as_f64())))
+    }
+}
+
+fn pearson(samples: impl Iterator<Item=(f64, f64)>) -> Option<f64> {
+    let samples: Vec<_> = samples.collect();
+    if samples.len() < 2 {
+        return None;
+    }
+
+    let n = samples.len() as f64;
+    let (mean_x, mean_y) = samples.iter().fold((0.0, 0.0), |(x, y), (sx, sy)| (x + sx / n, y + sy / n));
+    let (cov, var_x, var_y) = samples.iter().fold((0.0, 0.0, 0.0), |(cov, var_x, var_y), (x, y)| {
+        let (dx, dy) = (x - mean_x, y - mean_y);
+        (cov + dx * dy, var_x + dx * dx, var_y + dy * dy)
+    });
+
+    match var_x * var_y {
+        product if product > 0.0 => Some((cov / product.sqrt()).clamp(-1.0, 1.0)),
+        _ => None
+    }
+}
+
+/**
+ Slowest tasks of a timeline, see `TimelineAnalyzer::top`. Displays as a few lines ready to log,
+ with names in their `Display` form and times in their `Debug` form, e.g. `1.5ms` for a `Duration`.
+*/
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TopReport<'a, N, D = Duration> {
+    /// Longest single tasks, longest first.
+    pub longest: Vec<&'a TimelineTask<N, D>>,
+    /// Names with the most time over all their tasks, with that time and the number of tasks, most time first.
+    pub cumulative: Vec<(&'a N, D, usize)>
+}
+
+/// Task that took much longer than usual, see `TimelineAnalyzer::outliers`.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Outlier<'a, N, D = Duration> {
+    pub task: &'a TimelineTask<N, D>,
+    /// Index of the task among the tasks of its name, e.g. the frame it executed in if it executes once per frame.
+    pub repeat: usize,
+    /// Median duration of the tasks of its name.
+    pub median: D,
+    /// Tasks that executed at the same time, in order of their start.
+    pub concurrent: Vec<&'a TimelineTask<N, D>>
+}
+
+impl<N: Eq + Hash, D: Timestamp> TimelineAnalyzer<N, D> {
+
+    /**
+     Returns the tasks that took more than `k` times the median duration of their name, in order of their start,
+     with the tasks they overlapped, which are often the ones they compete with for a lock, cache or core.
+
+     Panics if `k` isn't positive.
+    */
+    pub fn outliers(&self, k: f64) -> Vec<Outlier<'_, N, D>> {
+        assert!(k > 0.0, "can't find outliers over {} times the median", k);
+
+        let mut durations: HashMap<&N, Vec<D>> = HashMap::new();
+        self.iter().for_each(|task| durations.entry(task.name()).or_default().push(task.len()));
+
+        let medians: HashMap<_, _> = durations.into_iter()
+            .map(|(name, mut durations)| {
+                durations.sort_unstable();
+                (name, durations[durations.len() / 2])
+            })
+            .collect();
+
+        let mut repeats: HashMap<&N, usize> = HashMap::new();
+        self.iter()
+            .filter_map(|task| {
+                let repeat = repeats.entry(task.name()).or_default();
+                *repeat += 1;
+
+                let median = medians[task.name()];
+                (task.len().as_f64() > k * median.as_f64()).then(|| (task, *repeat - 1, median))
+            })
+            .map(|(task, repeat, median)| {
+                let concurrent = self.iter()
+                    .take_while(|other| other.start() < task.end())
+                    .filter(|&other| !std::ptr::eq(other, task) && task.order_to(other) == TimelineOrder::Parallel)
+                    .collect();
+
+                Outlier { task, repeat, median, concurrent }
+            })
+            .collect()
+    }
+
+    /// Returns the `n` longest tasks and the `n` names that took the most time over all their repeats.
+    pub fn top(&self, n: usize) -> TopReport<'_, N, D> {
+        let mut longest: Vec<_> = self.iter().collect();
+        longest.sort_by_key(|task| Reverse(task.len()));
+        longest.truncate(n);
+
+        //tasks are sorted, the first task of a name starts first
+        let mut totals: HashMap<&N, (D, usize, D)> = HashMap::new();
         for task in self.iter() {
-            match find_slot(&counter, task.start()) {
-                Some(id) => counter[id] = task.end(),
-                None => counter.push(task.end())
-            }
+            let (total, count, _) = totals.entry(task.name()).or_insert((D::default(), 0, task.start()));
+            *total = *total + task.len();
+            *count += 1;
+        }
+
+        //ties in order of the first task of the name
+        let mut cumulative: Vec<_> = totals.into_iter().collect();
+        cumulative.sort_by_key(|&(_, (total, _, first))| (Reverse(total), first));
+        let cumulative = cumulative.into_iter().take(n).map(|(name, (total, count, _))| (name, total, count)).collect();
+
+        TopReport { longest, cumulative }
+    }
+}
+
+impl<'a, N: Display, D: Timestamp + Debug> Display for TopReport<'a, N, D> {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "longest tasks:")?;
+        for (idx, task) in self.longest.iter().enumerate() {
+            writeln!(f, "  {}. '{}' {:?} at {:?}", idx + 1, task.name(), task.len(), task.start())?;
+        }
+
+        writeln!(f, "most time in total:")?;
+        for (idx, (name, total, count)) in self.cumulative.iter().enumerate() {
+            let plural = if *count == 1 { "" } else { "s" };
+            writeln!(f, "  {}. '{}' {:?} over {} task{}", idx + 1, name, total, count, plural)?;
         }
 
-        counter.len()
+        Ok(())
     }
 }
 
-impl<N> FromIterator<TimelineTask<N>> for TimelineAnalyzer<N> {
-    fn from_iter<T: IntoIterator<Item=TimelineTask<N>>>(iter: T) -> Self {
+impl<N, D: Timestamp> FromIterator<TimelineTask<N, D>> for TimelineAnalyzer<N, D> {
+    fn from_iter<T: IntoIterator<Item=TimelineTask<N, D>>>(iter: T) -> Self {
         let mut tasks: Vec<_> = iter.into_iter().collect();
         tasks.sort_by_key(|t| t.start());
 
@@ -147,41 +404,78 @@ impl<N> FromIterator<TimelineTask<N>> for TimelineAnalyzer<N> {
     }
 }
 
-impl<N: Eq + Hash> FromIterator<TimelineEvent<N>> for TimelineAnalyzer<N> {
-    fn from_iter<T: IntoIterator<Item=TimelineEvent<N>>>(iter: T) -> Self {
-        let events: Vec<TimelineEvent<N>> = iter.into_iter().collect();
-        match events.iter().min_by_key(|e| e.time()) {
-            Some(start) => {
-                let min = start.time();
-                let mut pending = HashMap::new();
-                let mut tasks = Vec::new();
-
-                for event in events {
-                    match event {
-                        TimelineEvent::Start(name, time) => {
-                            assert!(pending.insert(name, time).is_none(), "task analysis: start with duplicate name")
-                        },
-
-                        TimelineEvent::End(name, end) => {
-                            let start = pending.remove(&name).expect("task analysis: unmatched end");
-                            let start = start - min;
-                            let end = end - min;
-
-                            tasks.push(TimelineTask::new(name, start, end - start))
-                        }
-                    }
-                }
+//events above which pairing and sorting run on the rayon pool
+const PARALLEL_EVENTS: usize = 1 << 14;
 
-                if !pending.is_empty() {
-                    panic!("task analysis: unmatched start")
-                }
+impl<N: Eq + Hash, I: EventTime> FromIterator<TimelineEvent<N, I>> for TimelineAnalyzer<N, I::Elapsed> {
+
+    /// Pairs the start and end events of every task, tasks starting at the same time keep the order of their starts.
+    fn from_iter<T: IntoIterator<Item=TimelineEvent<N, I>>>(iter: T) -> Self {
+        let events: Vec<TimelineEvent<N, I>> = iter.into_iter().collect();
+        let min = match events.iter().map(TimelineEvent::time).min() {
+            Some(min) => min,
+            None => return Self { tasks: Vec::new() }
+        };
+
+        let mut tasks = pair(events.into_iter().enumerate(), min);
+        tasks.sort_unstable_by_key(|(idx, task)| (task.start(), *idx));
+        Self { tasks: tasks.into_iter().map(|(_, task)| task).collect() }
+    }
+}
 
-                tasks.into_iter().collect()
+impl<N: Eq + Hash + Send + Sync, D: Timestamp + Send> TimelineAnalyzer<N, D> {
+
+    /**
+     Pairs the events like collecting them does, with the same order of the tasks. Large timelines are split by task
+     name so every thread pairs the events of a share of the names, which keeps the events of a name in order,
+     and the tasks are sorted in parallel.
+    */
+    pub fn par_from_events<I: EventTime<Elapsed=D> + Send + Sync>(events: Vec<TimelineEvent<N, I>>) -> Self {
+        if events.len() < PARALLEL_EVENTS {
+            return events.into_iter().collect();
+        }
+
+        let min = events.par_iter().map(TimelineEvent::time).min().expect("events aren't empty");
+
+        let hasher = RandomState::new();
+        let shards = rayon::current_num_threads() * 4;
+        let indices: Vec<_> = events.par_iter().map(|event| (hasher.hash_one(event.name()) % shards as u64) as usize).collect();
+
+        let mut sharded: Vec<Vec<_>> = (0..shards).map(|_| Vec::with_capacity(events.len() / shards)).collect();
+        for (event, shard) in events.into_iter().enumerate().zip(indices) {
+            sharded[shard].push(event);
+        }
+
+        let mut tasks: Vec<_> = sharded.into_par_iter().flat_map_iter(|events| pair(events, min)).collect();
+        tasks.par_sort_unstable_by_key(|(idx, task)| (task.start(), *idx));
+        Self { tasks: tasks.into_par_iter().map(|(_, task)| task).collect() }
+    }
+}
+
+/// Turns the events into tasks starting relative to `min` with the index of their start, in the order their ends come in.
+fn pair<N: Eq + Hash, I: EventTime>(events: impl IntoIterator<Item=(usize, TimelineEvent<N, I>)>, min: I) -> Vec<(usize, TimelineTask<N, I::Elapsed>)> {
+    let events = events.into_iter();
+    let mut pending = HashMap::new();
+    let mut tasks = Vec::with_capacity(events.size_hint().0 / 2);
+
+    for (idx, event) in events {
+        match event {
+            TimelineEvent::Start(name, time) => {
+                assert!(pending.insert(name, (idx, time)).is_none(), "task analysis: start with duplicate name")
             },
 
-            None => Vec::<TimelineTask<_>>::new().into_iter().collect()
+            TimelineEvent::End(name, end) => {
+                let (idx, start) = pending.remove(&name).expect("task analysis: unmatched end");
+                tasks.push((idx, TimelineTask::new(name, start.since(min), end.since(start))))
+            }
         }
     }
+
+    if !pending.is_empty() {
+        panic!("task analysis: unmatched start")
+    }
+
+    tasks
 }
 
 #[cfg(test)]
@@ -313,4 +607,181 @@ mod tests {
         assert_eq!(a.last(&"a"), task("a", 30, 40).as_ref());
         assert_eq!(a.last(&"b"), task("b", 40, 40).as_ref());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn analyzer_spans() {
+        //gpu timestamps in nanoseconds, far from zero
+        let a = TimelineAnalyzer::from_spans(vec![
+            ("shadows", 1_000_000u64, 1_004_000),
+            ("gbuffer", 1_002_000, 1_006_000),
+            ("lighting", 1_006_000, 1_008_000),
+        ]);
+
+        assert_eq!(a.first(&"gbuffer"), Some(&TimelineTask::new("gbuffer", 2_000, 4_000)));
+        assert_eq!(a.first(&"shadows").unwrap().order_to(a.first(&"gbuffer").unwrap()), TimelineOrder::Parallel);
+        assert_eq!(a.first(&"gbuffer").unwrap().order_to(a.first(&"lighting").unwrap()), TimelineOrder::After);
+
+        assert_eq!(a.len(), 8_000);
+        assert_eq!(a.serial_len(), 10_000);
+        assert_eq!(a.threads(), 2);
+        assert!((a.efficiency() - 1.25).abs() < 1e-9);
+
+        //clock skew between queues
+        let skewed = TimelineAnalyzer::from_spans(vec![("present", 10u64, 4)]);
+        assert_eq!(skewed.first(&"present"), Some(&TimelineTask::new("present", 0, 0)));
+    }
+
+    #[test]
+    fn analyzer_threads() {
+        let same = TimelineAnalyzer::from_spans(vec![("a", 0u64, 10), ("b", 0, 10), ("c", 0, 10)]);
+        assert_eq!(same.threads(), 3);
+
+        //tasks of no length starting with another one go first, else they'd take a third thread
+        let zero = TimelineAnalyzer::from_spans(vec![("a", 0u64, 10), ("b", 5, 8), ("x", 5, 5), ("y", 5, 5)]);
+        assert_eq!(zero.threads(), 2);
+        assert_eq!(zero.lanes(), [0, 1, 1, 1]);
+
+        let inside = TimelineAnalyzer::from_spans(vec![("a", 0u64, 10), ("x", 5, 5)]);
+        assert_eq!(inside.threads(), 2);
+
+        let boundary = TimelineAnalyzer::from_spans(vec![("a", 0u64, 10), ("x", 10, 10), ("b", 10, 20), ("y", 20, 20)]);
+        assert_eq!(boundary.threads(), 1);
+
+        let points = TimelineAnalyzer::from_spans(vec![("x", 0u64, 0), ("y", 0, 0), ("z", 0, 0)]);
+        assert_eq!(points.threads(), 1);
+
+        let empty: TimelineAnalyzer<&str, u64> = Vec::<TimelineTask<_, _>>::new().into_iter().collect();
+        assert_eq!((empty.threads(), empty.recorded_threads()), (0, Some(0)));
+
+        let recorded: TimelineAnalyzer<_, u64> = vec![
+            TimelineTask::new("a", 0, 10).with_thread(0),
+            TimelineTask::new("b", 10, 10).with_thread(3),
+            TimelineTask::new("c", 20, 10).with_thread(0)
+        ].into_iter().collect();
+        assert_eq!((recorded.threads(), recorded.recorded_threads()), (1, Some(2)));
+        assert_eq!(boundary.recorded_threads(), None);
+    }
+
+    #[test]
+    fn analyzer_construct_parallel() {
+        use TimelineEvent::{Start, End};
+
+        let now = Instant::now();
+        let instant = |t| now.add(Duration::from_micros(t));
+
+        //runs of 100 tasks starting two at a time and overlapping the next, large enough to pair in parallel
+        let mut events: Vec<_> = (0..PARALLEL_EVENTS as u64)
+            .flat_map(|idx| [Start(idx % 100, instant(idx / 2 * 10)), End(idx % 100, instant(idx / 2 * 10 + 15))])
+            .collect();
+
+        events.sort_by_key(TimelineEvent::time);
+        let sequential: TimelineAnalyzer<_> = events.iter().copied().collect();
+        let analyzer = TimelineAnalyzer::par_from_events(events);
+
+        assert_eq!(analyzer.iter().count(), PARALLEL_EVENTS);
+        assert!(analyzer.iter().eq(sequential.iter()), "ties are ordered the same way");
+        assert_eq!(analyzer.iter().take(4).map(TimelineTask::name).collect::<Vec<_>>(), [&0, &1, &2, &3]);
+        assert_eq!(analyzer.count(&7), PARALLEL_EVENTS / 100 + usize::from(PARALLEL_EVENTS % 100 > 7));
+        assert!(analyzer.iter().zip(analyzer.iter().skip(1)).all(|(a, b)| a.start() <= b.start()), "tasks are sorted by start");
+        assert_eq!(analyzer.first(&7).map(TimelineTask::start), Some(Duration::from_micros(30)));
+        assert_eq!(analyzer.last(&0).map(TimelineTask::len), Some(Duration::from_micros(15)));
+        assert_eq!(analyzer.len(), Duration::from_micros((PARALLEL_EVENTS as u64 - 1) / 2 * 10 + 15));
+    }
+
+    #[test]
+    fn analyzer_top() {
+        let a = construct_analyzer();
+        let top = a.top(2);
+
+        let longest: Vec<_> = top.longest.iter().map(|task| (*task.name(), task.len())).collect();
+        assert_eq!(longest, [("f", Duration::from_millis(15)), ("a", Duration::from_millis(10))]);
+        assert_eq!(top.cumulative, [(&"a", Duration::from_millis(20), 2), (&"f", Duration::from_millis(15), 1)]);
+
+        assert_eq!(top.to_string(), "\
+longest tasks:
+  1. 'f' 15ms at 15ms
+  2. 'a' 10ms at 0ns
+most time in total:
+  1. 'a' 20ms over 2 tasks
+  2. 'f' 15ms over 1 task
+");
+
+        assert!(a.top(0).longest.is_empty());
+        assert_eq!(a.top(100).cumulative.len(), 7);
+    }
+
+    #[test]
+    fn analyzer_outliers() {
+        let ms = Duration::from_millis;
+        let frame = |idx: u64, physics: u64| vec![
+            ("physics", ms(idx * 100), ms(idx * 100 + physics)),
+            ("audio", ms(idx * 100), ms(idx * 100 + 5)),
+        ];
+
+        let mut spans: Vec<_> = (0..5).flat_map(|idx| frame(idx, if idx == 3 { 40 } else { 10 })).collect();
+        spans.push(("stream", ms(320), ms(330)));
+
+        let a = TimelineAnalyzer::from_spans(spans);
+        let outliers = a.outliers(2.0);
+
+        assert_eq!(outliers.len(), 1);
+        assert_eq!((*outliers[0].task.name(), outliers[0].repeat, outliers[0].median), ("physics", 3, ms(10)));
+
+        let concurrent: Vec<_> = outliers[0].concurrent.iter().map(|task| *task.name()).collect();
+        assert_eq!(concurrent, ["audio", "stream"]);
+
+        assert!(a.outliers(4.0).is_empty());
+    }
+
+    #[test]
+    fn analyzer_correlation() {
+        let ms = Duration::from_millis;
+
+        //physics makes the frame slow, audio varies on its own next to it
+        let runs: Vec<_> = [(10, 3), (30, 5), (20, 2), (40, 4), (15, 6)].iter()
+            .map(|&(physics, audio)| TimelineAnalyzer::from_spans(vec![
+                ("physics", ms(0), ms(physics)),
+                ("audio", ms(0), ms(audio)),
+                ("render", ms(physics), ms(physics + 5))
+            ]))
+            .collect();
+
+        let physics = TimelineAnalyzer::makespan_correlation(&runs, &"physics").unwrap();
+        assert!((physics - 1.0).abs() < 1e-9, "{}", physics);
+
+        let audio = TimelineAnalyzer::makespan_correlation(&runs, &"audio").unwrap();
+        assert!(audio.abs() < 0.5, "{}", audio);
+
+        let pair = TimelineAnalyzer::correlation(&runs, &"physics", &"audio").unwrap();
+        assert!((pair - audio).abs() < 1e-9, "the makespan follows physics");
+        assert_eq!(TimelineAnalyzer::correlation(&runs, &"physics", &"render"), None, "render always takes 5ms");
+        assert_eq!(TimelineAnalyzer::correlation(&runs[..1], &"physics", &"audio"), None);
+        assert_eq!(runs[0].total(&"missing"), Duration::default());
+    }
+
+    #[test]
+    fn task_order_epsilon() {
+        let ns = Duration::from_nanos;
+        let a = TimelineTask::new((), ns(0), ns(100));
+        let b = TimelineTask::new((), ns(99), ns(100));
+        let c = TimelineTask::new((), ns(10), ns(20));
+
+        assert_eq!(a.order_to(&b), TimelineOrder::Parallel);
+        assert_eq!(a.order_to_with_epsilon(&b, ns(1)), TimelineOrder::After);
+        assert_eq!(b.order_to_with_epsilon(&a, ns(1)), TimelineOrder::Before);
+        assert_eq!(a.order_to_with_epsilon(&b, ns(0)), a.order_to(&b));
+
+        //c starts long before a ends
+        assert_eq!(a.order_to_with_epsilon(&c, ns(29)), TimelineOrder::Parallel);
+
+        assert!(!a.ends_before(&b) && !b.starts_after(&a));
+        let d = TimelineTask::new((), ns(100), ns(1));
+        assert!(a.ends_before(&d) && d.starts_after(&a));
+        assert!(!d.ends_before(&a) && !a.starts_after(&d));
+
+        let max = TimelineTask::new((), u64::MAX - 10, 10);
+        let e = TimelineTask::new((), u64::MAX - 5, 5);
+        assert_eq!(max.order_to_with_epsilon(&e, 5), TimelineOrder::After);
+        assert_eq!(max.order_to_with_epsilon(&e, 4), TimelineOrder::Parallel);
+    }
+}
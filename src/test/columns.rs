@@ -0,0 +1,105 @@
+use super::analysis::{TimelineAnalyzer, TimelineTask, Timestamp};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Duration;
+
+/**
+ Timeline as columns of equal length, one row per task in order of their start, e.g. to load into a data frame
+ without going through CSV. Times are converted with `Timestamp::as_f64`, seconds for `Duration` timelines.
+ Serializable with the `serde` feature.
+*/
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimelineColumns<N> {
+    pub name: Vec<N>,
+    pub start: Vec<f64>,
+    pub end: Vec<f64>,
+    pub length: Vec<f64>,
+    /// Index of the task among the tasks of its name.
+    pub repeat: Vec<usize>,
+    /// Lane the task is drawn in, see `TimelineAnalyzer::threads`.
+    pub lane: Vec<usize>
+}
+
+impl<N> TimelineColumns<N> {
+
+    /// Returns the number of rows.
+    pub fn len(&self) -> usize {
+        self.name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.name.is_empty()
+    }
+}
+
+impl<N: Clone + Eq + Hash, D: Timestamp> TimelineAnalyzer<N, D> {
+
+    pub fn to_columns(&self) -> TimelineColumns<N> {
+        let len = self.iter().count();
+        let mut columns = TimelineColumns {
+            name: Vec::with_capacity(len),
+            start: Vec::with_capacity(len),
+            end: Vec::with_capacity(len),
+            length: Vec::with_capacity(len),
+            repeat: Vec::with_capacity(len),
+            lane: self.lanes()
+        };
+
+        let mut repeats: HashMap<&N, usize> = HashMap::new();
+        for task in self.iter() {
+            let repeat = repeats.entry(task.name()).or_default();
+
+            columns.name.push(task.name().clone());
+            columns.start.push(task.start().as_f64());
+            columns.end.push(task.end().as_f64());
+            columns.length.push(task.len().as_f64());
+            columns.repeat.push(*repeat);
+            *repeat += 1;
+        }
+
+        columns
+    }
+}
+
+impl<N> From<TimelineColumns<N>> for TimelineAnalyzer<N> {
+
+    /// Reads a `Duration` timeline back, e.g. one processed in a notebook.
+    fn from(columns: TimelineColumns<N>) -> Self {
+        columns.name.into_iter()
+            .zip(columns.start)
+            .zip(columns.end)
+            .map(|((name, start), end)| TimelineTask::new(name, Duration::from_secs_f64(start), Duration::from_secs_f64(end - start)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns() {
+        let ms = Duration::from_millis;
+        let timeline = TimelineAnalyzer::from_spans(vec![
+            ("a", ms(10), ms(20)),
+            ("b", ms(15), ms(25)),
+            ("a", ms(20), ms(30))
+        ]);
+
+        let columns = timeline.to_columns();
+        assert_eq!(columns.len(), 3);
+        assert_eq!(columns.name, ["a", "b", "a"]);
+        assert_eq!(columns.start, [0.0, 0.005, 0.01]);
+        assert_eq!(columns.length, [0.01; 3]);
+        assert_eq!(columns.repeat, [0, 0, 1]);
+        assert_eq!(columns.lane, [0, 1, 0]);
+
+        let back = TimelineAnalyzer::from(columns);
+        assert_eq!(back.count(&"a"), 2);
+        assert_eq!(back.threads(), 2);
+
+        let ticks = TimelineAnalyzer::from_spans(vec![("gpu", 100u64, 160)]).to_columns();
+        assert_eq!((ticks.start[0], ticks.end[0]), (0.0, 60.0));
+    }
+}
@@ -1,24 +1,33 @@
+pub mod accumulator;
 pub mod analysis;
+#[cfg(feature = "columnar")]
+pub mod columns;
 
 use crate::Executable;
-use self::analysis::TimelineAnalyzer;
+use self::accumulator::TimelineAccumulator;
+use self::analysis::{TimelineAnalyzer, Timestamp};
+use std::collections::{HashMap, VecDeque};
 use std::sync::mpsc::{Sender, Receiver, channel};
-use std::time::Instant;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 use std::hash::Hash;
+use std::vec;
 
-pub struct WrappedTask<N, F> {
-    sender: Sender<TimelineEvent<N>>,
+pub struct WrappedTask<N, F, I: EventTime = Instant> {
+    sender: Sink<N, I>,
+    clock: I::Clock,
     name: N,
     func: F
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
-pub enum TimelineEvent<N> {
-    Start(N, Instant),
-    End(N, Instant)
+pub enum TimelineEvent<N, I = Instant> {
+    Start(N, I),
+    End(N, I)
 }
 
-impl<N> TimelineEvent<N> {
+impl<N, I: Copy> TimelineEvent<N, I> {
 
     pub fn name(&self) -> &N {
         match self {
@@ -27,7 +36,7 @@ impl<N> TimelineEvent<N> {
         }
     }
 
-    pub fn time(&self) -> Instant {
+    pub fn time(&self) -> I {
         match self {
             TimelineEvent::Start(_, time) => *time,
             TimelineEvent::End(_, time) => *time
@@ -35,53 +44,287 @@ impl<N> TimelineEvent<N> {
     }
 }
 
-pub struct TimelineReader<N> {
-    sender: Sender<TimelineEvent<N>>,
-    receiver: Receiver<TimelineEvent<N>>
+/**
+ Time of recorded events, an `Instant` or the tick of a logical clock, see `TimelineReader::logical`.
+ Analyzed timelines hold the time elapsed since their first event.
+*/
+pub trait EventTime: Copy + Ord {
+    type Elapsed: Timestamp;
+    /// State of the clock shared by the tasks of a reader.
+    type Clock: Clone;
+
+    fn now(clock: &Self::Clock) -> Self;
+
+    /// Returns the time elapsed since `earlier`, zero if it is later.
+    fn since(self, earlier: Self) -> Self::Elapsed;
+}
+
+impl EventTime for Instant {
+    type Elapsed = Duration;
+    type Clock = ();
+
+    fn now(_: &()) -> Self {
+        Instant::now()
+    }
+
+    fn since(self, earlier: Self) -> Duration {
+        self.saturating_duration_since(earlier)
+    }
+}
+
+impl EventTime for u64 {
+    type Elapsed = u64;
+    type Clock = Arc<AtomicU64>;
+
+    fn now(ticks: &Arc<AtomicU64>) -> Self {
+        //a single counter orders its ticks like the tasks taking them whatever the ordering
+        ticks.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn since(self, earlier: Self) -> u64 {
+        self.saturating_sub(earlier)
+    }
+}
+
+/**
+ Records the events of wrapped tasks. A reader created with `new` keeps every event until it is collected,
+ a `bounded` one only the latest, so it can stay on for a whole session, e.g. for an in-game profiler.
+*/
+pub struct TimelineReader<N, I: EventTime = Instant> {
+    sender: Sink<N, I>,
+    clock: I::Clock,
+    receiver: Option<Receiver<TimelineEvent<N, I>>>
 }
 
-pub struct TimelineIterator<N> {
-    receiver: Receiver<TimelineEvent<N>>
+enum Sink<N, I: EventTime> {
+    Channel(Sender<TimelineEvent<N, I>>),
+    Ring(Arc<Mutex<Ring<N, I>>>)
+}
+
+/// Latest events of a bounded reader, oldest first.
+struct Ring<N, I: EventTime> {
+    events: VecDeque<TimelineEvent<N, I>>,
+    capacity: usize,
+    max_age: Option<I::Elapsed>,
+    dropped: u64
+}
+
+pub struct TimelineIterator<N, I = Instant> {
+    events: Events<N, I>
+}
+
+enum Events<N, I> {
+    Channel(Receiver<TimelineEvent<N, I>>),
+    Ring(vec::IntoIter<TimelineEvent<N, I>>)
 }
 
 impl<N: Clone> TimelineReader<N> {
 
     pub fn new() -> Self {
         let (sender, receiver) = channel();
-        Self { sender, receiver }
+        Self { sender: Sink::Channel(sender), clock: (), receiver: Some(receiver) }
     }
 
-    pub fn wrap<'a, T: Sync, F: Executable<T>>(&self, name: N, func: F) -> WrappedTask<N, F> {
-        WrappedTask { sender: self.sender.clone(), name, func }
+    /**
+     Creates a reader keeping the latest `capacity` events, older ones are dropped and counted, see `dropped`.
+     Memory for all of them is allocated up front, recording doesn't allocate.
+
+     Panics if `capacity` is 0.
+    */
+    pub fn bounded(capacity: usize) -> Self {
+        assert!(capacity > 0, "can't record a timeline into a buffer of 0 events");
+
+        let ring = Ring { events: VecDeque::with_capacity(capacity), capacity, max_age: None, dropped: 0 };
+        Self { sender: Sink::Ring(Arc::new(Mutex::new(ring))), clock: (), receiver: None }
     }
 
-    pub fn collect(self) -> TimelineIterator<N> {
-        TimelineIterator { receiver: self.receiver }
+    /**
+     Turns the reader into one recording every event with the next tick of a counter instead of the time, e.g. for
+     tests that assert on the order of tasks and mustn't depend on the resolution of the clock. In the analyzed
+     timeline a task ends before another starts exactly if its end tick is lower, no two events share a tick and
+     no task has zero length. Ages given to `max_age` are counted in ticks as well.
+
+     Panics if events were recorded already or their age was limited, the reader starts over with the first tick.
+    */
+    pub fn logical(self) -> TimelineReader<N, u64> {
+        let clock = Arc::new(AtomicU64::new(0));
+        match self.sender {
+            Sink::Channel(_) => {
+                assert!(self.receiver.is_some_and(|receiver| receiver.try_recv().is_err()), "can't make a reader logical after it recorded events");
+
+                let (sender, receiver) = channel();
+                TimelineReader { sender: Sink::Channel(sender), clock, receiver: Some(receiver) }
+            },
+
+            Sink::Ring(ring) => {
+                let ring = ring.lock().unwrap_or_else(PoisonError::into_inner);
+                assert!(ring.events.is_empty() && ring.dropped == 0, "can't make a reader logical after it recorded events");
+                assert!(ring.max_age.is_none(), "can't make a reader logical after limiting the age of its events, ages are counted in ticks");
+
+                let ring = Ring { events: VecDeque::with_capacity(ring.capacity), capacity: ring.capacity, max_age: None, dropped: 0 };
+                TimelineReader { sender: Sink::Ring(Arc::new(Mutex::new(ring))), clock, receiver: None }
+            }
+        }
     }
 }
 
-impl<N: Clone + Eq + Hash> TimelineReader<N> {
-    pub fn analyze(self) -> TimelineAnalyzer<N> {
-        self.collect().collect()
+impl<N: Clone, I: EventTime> TimelineReader<N, I> {
+
+    /// Drops events older than `age` relative to the latest one as well, e.g. to keep the last seconds of a session.
+    pub fn max_age(self, age: I::Elapsed) -> Self {
+        match &self.sender {
+            Sink::Ring(ring) => ring.lock().unwrap_or_else(PoisonError::into_inner).max_age = Some(age),
+            Sink::Channel(_) => panic!("can't limit the age of events of an unbounded reader, see TimelineReader::bounded")
+        }
+
+        self
+    }
+
+    pub fn wrap<T: Sync, F: Executable<T>>(&self, name: N, func: F) -> WrappedTask<N, F, I> {
+        WrappedTask { sender: self.sender.clone(), clock: self.clock.clone(), name, func }
+    }
+
+    /// Returns how many events a bounded reader dropped so far, always 0 for unbounded ones.
+    pub fn dropped(&self) -> u64 {
+        match &self.sender {
+            Sink::Ring(ring) => ring.lock().unwrap_or_else(PoisonError::into_inner).dropped,
+            Sink::Channel(_) => 0
+        }
+    }
+
+    /// Returns the events a bounded reader keeps right now, oldest first, while tasks keep recording.
+    pub fn snapshot(&self) -> Vec<TimelineEvent<N, I>> {
+        match &self.sender {
+            Sink::Ring(ring) => ring.lock().unwrap_or_else(PoisonError::into_inner).events.iter().cloned().collect(),
+            Sink::Channel(_) => panic!("can't take a snapshot of an unbounded reader, collect it instead")
+        }
+    }
+
+    pub fn collect(self) -> TimelineIterator<N, I> {
+        let events = match (self.sender, self.receiver) {
+            (_, Some(receiver)) => Events::Channel(receiver),
+            (Sink::Ring(ring), None) => Events::Ring(ring.lock().unwrap_or_else(PoisonError::into_inner).events.drain(..).collect::<Vec<_>>().into_iter()),
+            (Sink::Channel(_), None) => unreachable!("unbounded readers keep their receiver")
+        };
+
+        TimelineIterator { events }
     }
 }
 
-impl<N: Clone, T: Sync, F: Executable<T>> Executable<T> for WrappedTask<N, F> {
+impl<N, I: EventTime> Clone for Sink<N, I> {
+
+    fn clone(&self) -> Self {
+        match self {
+            Sink::Channel(sender) => Sink::Channel(sender.clone()),
+            Sink::Ring(ring) => Sink::Ring(ring.clone())
+        }
+    }
+}
+
+impl<N, I: EventTime> Sink<N, I> {
+
+    fn send(&self, event: TimelineEvent<N, I>) {
+        match self {
+            Sink::Channel(sender) => { let _ = sender.send(event); },
+            Sink::Ring(ring) => ring.lock().unwrap_or_else(PoisonError::into_inner).push(event)
+        }
+    }
+}
+
+impl<N, I: EventTime> Ring<N, I> {
+
+    fn push(&mut self, event: TimelineEvent<N, I>) {
+        if let Some(age) = self.max_age {
+            while self.events.front().is_some_and(|oldest| event.time().since(oldest.time()) > age) {
+                self.events.pop_front();
+                self.dropped += 1;
+            }
+        }
+
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+
+        self.events.push_back(event);
+    }
+}
+
+impl<N: Clone> Default for TimelineReader<N> {
+
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: Eq + Hash> TimelineReader<N> {
+
+    /// Moves the events recorded so far into `accumulator`, e.g. once per frame, the reader keeps recording.
+    pub fn accumulate(&self, accumulator: &mut TimelineAccumulator<N>) {
+        match (&self.sender, &self.receiver) {
+            (_, Some(receiver)) => accumulator.extend(receiver.try_iter()),
+            (Sink::Ring(ring), None) => accumulator.extend(ring.lock().unwrap_or_else(PoisonError::into_inner).events.drain(..)),
+            (Sink::Channel(_), None) => unreachable!("unbounded readers keep their receiver")
+        }
+    }
+}
+
+impl<N: Clone + Eq + Hash, I: EventTime> TimelineReader<N, I> {
+
+    /// Analyzes the recorded tasks, a bounded reader leaves out the ones it dropped the start or didn't see the end of.
+    pub fn analyze(self) -> TimelineAnalyzer<N, I::Elapsed> {
+        match self.receiver {
+            Some(_) => self.collect().collect(),
+            None => complete(self.collect().collect()).into_iter().collect()
+        }
+    }
+}
+
+impl<N: Clone + Eq + Hash + Send + Sync, I: EventTime + Send + Sync> TimelineReader<N, I> where I::Elapsed: Send {
+
+    /// Analyzes the recorded tasks like `analyze`, pairing the events of large timelines on the rayon pool.
+    pub fn par_analyze(self) -> TimelineAnalyzer<N, I::Elapsed> {
+        match self.receiver {
+            Some(_) => TimelineAnalyzer::par_from_events(self.collect().collect()),
+            None => TimelineAnalyzer::par_from_events(complete(self.collect().collect()))
+        }
+    }
+}
+
+/// Keeps the events that pair up into a start and an end.
+fn complete<N: Clone + Eq + Hash, I>(events: Vec<TimelineEvent<N, I>>) -> Vec<TimelineEvent<N, I>> {
+    let mut keep = vec![false; events.len()];
+    let mut pending = HashMap::new();
+
+    for (idx, event) in events.iter().enumerate() {
+        match event {
+            TimelineEvent::Start(name, _) => { pending.insert(name.clone(), idx); },
+            TimelineEvent::End(name, _) => if let Some(start) = pending.remove(name) {
+                keep[start] = true;
+                keep[idx] = true;
+            }
+        }
+    }
+
+    events.into_iter().zip(keep).filter(|(_, keep)| *keep).map(|(event, _)| event).collect()
+}
+
+impl<N: Clone, T: Sync, F: Executable<T>, I: EventTime> Executable<T> for WrappedTask<N, F, I> {
 
     fn run(&mut self, data: &T) {
-        let _ = self.sender.send(TimelineEvent::Start(self.name.clone(), Instant::now()));
+        self.sender.send(TimelineEvent::Start(self.name.clone(), I::now(&self.clock)));
         self.func.run(data);
-        let _ = self.sender.send(TimelineEvent::End(self.name.clone(), Instant::now()));
+        self.sender.send(TimelineEvent::End(self.name.clone(), I::now(&self.clock)));
     }
 }
 
-impl<N> Iterator for TimelineIterator<N> {
-    type Item = TimelineEvent<N>;
+impl<N, I> Iterator for TimelineIterator<N, I> {
+    type Item = TimelineEvent<N, I>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.receiver.try_recv() {
-            Ok(e) => Some(e),
-            Err(_) => None
+        match &mut self.events {
+            Events::Channel(receiver) => receiver.try_recv().ok(),
+            Events::Ring(events) => events.next()
         }
     }
 }
@@ -134,4 +377,90 @@ mod tests {
         end(iter.next(), "e");
         assert_eq!(iter.next(), None, "expected end of iterator")
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn bounded() {
+        use std::time::Duration;
+
+        let reader = TimelineReader::bounded(5);
+        let closure = |_: &()| {};
+
+        for name in ["a", "b", "c", "d"] {
+            reader.wrap(name, closure).run(&());
+        }
+
+        assert_eq!(reader.dropped(), 3);
+        let names: Vec<_> = reader.snapshot().iter().map(|event| *event.name()).collect();
+        assert_eq!(names, ["b", "c", "c", "d", "d"]);
+
+        //the end of b lost its start
+        let timeline = reader.analyze();
+        assert!(!timeline.has(&"b") && timeline.has(&"c") && timeline.has(&"d"));
+
+        let reader = TimelineReader::bounded(100).max_age(Duration::from_millis(20));
+        reader.wrap("old", closure).run(&());
+        std::thread::sleep(Duration::from_millis(30));
+        reader.wrap("new", closure).run(&());
+
+        assert_eq!(reader.dropped(), 2);
+        let names: Vec<_> = reader.collect().map(|event| *event.name()).collect();
+        assert_eq!(names, ["new", "new"]);
+    }
+
+    #[test]
+    fn accumulate() {
+        use crate::test::accumulator::TimelineAccumulator;
+
+        let closure = |_: &()| {};
+        let mut accumulator = TimelineAccumulator::new();
+
+        for reader in [TimelineReader::new(), TimelineReader::bounded(4)] {
+            reader.wrap("a", closure).run(&());
+            reader.accumulate(&mut accumulator);
+            reader.wrap("a", closure).run(&());
+            reader.wrap("b", closure).run(&());
+            reader.accumulate(&mut accumulator);
+
+            assert_eq!(reader.collect().count(), 0, "accumulated events are taken from the reader");
+        }
+
+        assert_eq!(accumulator.stats(&"a").map(|stats| stats.count), Some(4));
+        assert_eq!(accumulator.stats(&"b").map(|stats| stats.count), Some(2));
+        assert_eq!((accumulator.max_concurrency(), accumulator.unmatched()), (1, 0));
+    }
+
+    #[test]
+    fn logical() {
+        use crate::test::analysis::{TimelineOrder, TimelineTask};
+
+        let reader = TimelineReader::new().logical();
+        let closure = |_: &()| {};
+
+        let mut inner = reader.wrap("inner", closure);
+        reader.wrap("outer", move |data: &()| inner.run(data)).run(&());
+        reader.wrap("after", closure).run(&());
+
+        let timeline = reader.analyze();
+        assert_eq!(timeline.single(&"outer"), Some(&TimelineTask::new("outer", 0, 3)));
+        assert_eq!(timeline.single(&"inner"), Some(&TimelineTask::new("inner", 1, 1)));
+        assert_eq!(timeline.single(&"after"), Some(&TimelineTask::new("after", 4, 1)));
+
+        let (outer, inner, after) = (timeline.single(&"outer").unwrap(), timeline.single(&"inner").unwrap(), timeline.single(&"after").unwrap());
+        assert_eq!(outer.order_to(inner), TimelineOrder::Parallel);
+        assert!(inner.ends_before(after) && outer.ends_before(after));
+
+        let reader = TimelineReader::bounded(3).logical();
+        reader.wrap("a", closure).run(&());
+        reader.wrap("b", closure).run(&());
+        assert_eq!(reader.dropped(), 1);
+        assert_eq!(reader.analyze().single(&"b"), Some(&TimelineTask::new("b", 0, 1)), "the timeline starts at the first event kept");
+
+        //ages in ticks
+        let reader = TimelineReader::bounded(10).logical().max_age(2);
+        for name in ["a", "b"] {
+            reader.wrap(name, closure).run(&());
+        }
+        let names: Vec<_> = reader.snapshot().iter().map(|event| (*event.name(), event.time())).collect();
+        assert_eq!(names, [("a", 1), ("b", 2), ("b", 3)]);
+    }
+}
@@ -0,0 +1,108 @@
+//! Runs of a built graph allocate nothing, counted by a global allocator, which is why this is a test binary of its own.
+//! It runs without the test harness, which allocates on its own threads while tests run.
+
+use calcite::Executable;
+use calcite::interlock::builder;
+use calcite::interlock::builder::TaskSpec;
+use calcite::interlock::{Dispatch, Parallelism, Priority, TaskContext};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+struct Counting;
+
+static COUNTING: AtomicBool = AtomicBool::new(false);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if COUNTING.load(Ordering::Relaxed) {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if COUNTING.load(Ordering::Relaxed) {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Returns the number of allocations `f` made on any thread.
+fn count(f: impl FnOnce()) -> usize {
+    ALLOCATIONS.store(0, Ordering::Relaxed);
+    COUNTING.store(true, Ordering::SeqCst);
+    f();
+    COUNTING.store(false, Ordering::SeqCst);
+    ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+fn main() {
+    let executed = AtomicUsize::new(0);
+    let closure = |_: &()| { executed.fetch_add(1, Ordering::Relaxed); };
+
+    let labels: Vec<String> = (0..64).map(|idx| format!("t{}", idx)).collect();
+    let specs = labels.iter().enumerate().map(|(idx, label)| {
+        let spec = TaskSpec::new(label.clone(), closure).reads([idx as u32 % 5]).writes([5 + idx as u32 % 7]);
+        match idx {
+            0..=7 => spec,
+            _ => spec.after([labels[idx - 8].clone()])
+        }
+    });
+
+    let mut graph = builder::<(), u32>();
+    for idx in 0..8 {
+        graph.add_with_context(|_: &(), context: &TaskContext<u32>| {
+            let ids = context.scratch().alloc_slice_with(256, |id| id as u64);
+            assert_eq!(ids.iter().sum::<u64>(), 255 * 128);
+        }, [], [idx], &[]);
+    }
+
+    let mut scratch = graph.build();
+
+    let mut builder = builder::<(), u32>();
+    builder.extend(specs).unwrap();
+
+    let mut exec = builder.build();
+    exec.set_priority(Priority::Fanout);
+
+    //runs injected into a pool from outside allocate in rayon itself, so the graph runs from within one
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    pool.broadcast(|_| {});
+
+    pool.install(|| {
+        for parallelism in [Parallelism::Full, Parallelism::Sequential] {
+            exec.set_parallelism(parallelism);
+            exec.run(&());
+
+            let allocations = count(|| (0..100).for_each(|_| exec.run(&())));
+            assert_eq!(allocations, 0, "{:?} runs allocated", parallelism);
+        }
+
+        let bodies = Dispatch::new((0..64).map(|_| closure));
+        exec.run_dispatched(&(), &bodies);
+
+        let allocations = count(|| (0..100).for_each(|_| exec.run_dispatched(&(), &bodies)));
+        assert_eq!(allocations, 0, "dispatched runs allocated");
+
+        //the first run measures the peak of the scratch memory, the second grows it
+        scratch.run(&());
+        scratch.run(&());
+
+        let allocations = count(|| (0..100).for_each(|_| scratch.run(&())));
+        assert_eq!(allocations, 0, "runs allocating scratch memory allocated");
+    });
+
+    assert_eq!(executed.load(Ordering::Relaxed), 64 * 303);
+}